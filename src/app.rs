@@ -1,86 +1,100 @@
 use crate::client::QBitClient;
-use crate::config::AppConfig;
-use crate::server::http::run_http_server;
+use crate::config::{AppConfig, QBitInstance};
+use crate::humanize::Humanizer;
+use crate::reload::Reloader;
+use crate::secret::Secret;
+use crate::server::audit::AuditLog;
+use crate::server::auth::{Grant, HttpAuth, SHARED_TOKEN_IDENTITY, Scope};
+use crate::server::http::{HttpAddress, HttpListener, serve_http};
+use crate::server::instances::Instances;
+use crate::server::journal::Journal;
+use crate::server::logging::{self, McpLogLayer, Redacting};
 use crate::server::mcp::McpServer;
-use std::collections::HashMap;
-use tracing::{error, info};
+use crate::server::oauth::{JwksSource, OAuthSettings, OAuthVerifier};
+use crate::server::policy::ToolPolicy;
+use crate::server::tls::TlsFiles;
+use crate::server::tools::TOOLSETS;
+use anyhow::Context;
+use std::collections::{HashMap, HashSet};
+use time::OffsetDateTime;
+use time::format_description::well_known::Rfc3339;
+use tracing::{error, info, warn};
 use tracing_appender::non_blocking::WorkerGuard;
 use tracing_appender::rolling::{RollingFileAppender, Rotation};
-use tracing_subscriber::{EnvFilter, Layer, layer::SubscriberExt, util::SubscriberInitExt};
+use tracing_subscriber::{EnvFilter, Layer, layer::SubscriberExt, reload, util::SubscriberInitExt};
+
+/// Changes the level of the console and file logs, e.g. after a configuration reload.
+pub type LogLevelSetter = Box<dyn Fn(&str) + Send + Sync>;
 
 pub async fn run_app(
     config: AppConfig,
     mut shutdown_rx: Option<tokio::sync::oneshot::Receiver<()>>,
 ) -> anyhow::Result<()> {
     // Keep guard alive for the duration of the program
-    let _guard = init_logging(&config);
+    let (_guard, set_log_level) = init_logging(&config);
 
     info!(
         "Starting qBittorrent MCP Server in {} mode (lazy: {})",
         config.server_mode, config.lazy_mode
     );
 
-    let instances = config.get_instances();
-    let mut clients = HashMap::new();
-
-    for inst in instances {
-        if inst.host.trim().is_empty() {
-            continue;
-        }
-        let base_url = if inst.host.starts_with("http://") || inst.host.starts_with("https://") {
-            if let Some(port) = inst.port {
-                format!("{}:{}", inst.host, port)
-            } else {
-                inst.host.clone()
-            }
-        } else {
-            let port = inst.port.unwrap_or(80);
-            format!("http://{}:{}", inst.host, port)
-        };
-
-        let no_verify_ssl =
-            inst.no_verify_ssl.unwrap_or(config.no_verify_ssl) && base_url.starts_with("https://");
-
-        info!("Initializing client '{}' at {}", inst.name, base_url);
-
-        let client = if let (Some(u), Some(p)) = (&inst.username, &inst.password) {
-            QBitClient::new(base_url, u, p, no_verify_ssl)
-        } else {
-            QBitClient::new_no_auth(base_url, no_verify_ssl)
-        };
+    register_secrets(&config);
+    let instances = build_instances(&config, None);
+    if instances.is_empty() {
+        anyhow::bail!("No qBittorrent instances configured");
+    }
 
-        if inst.username.is_some() {
-            if let Err(e) = client.login().await {
-                error!(
-                    "Failed to login to qBittorrent instance '{}': {}",
-                    inst.name, e
-                );
-            } else {
-                info!(
-                    "Logged in to qBittorrent instance '{}' successfully",
-                    inst.name
-                );
+    let mut server = McpServer::new(HashMap::new(), config.lazy_mode)
+        .with_instances(instances)
+        .with_max_in_flight(config.max_concurrent_requests)
+        .with_require_confirmation(config.require_confirmation);
+    if let Some(toolsets) = &config.toolsets {
+        for name in toolsets {
+            if !TOOLSETS.iter().any(|(known, _)| known == name) {
+                warn!("Ignoring unknown toolset '{}' in configuration", name);
             }
         }
-        clients.insert(inst.name, client);
+        server = server.with_default_toolsets(toolsets.iter().cloned());
     }
-
-    if clients.is_empty() {
-        anyhow::bail!("No qBittorrent instances configured");
+    if config.audit_log_enable {
+        let dir = config.audit_log_dir.as_deref().unwrap_or(&config.log_dir);
+        let audit = AuditLog::new(dir, &config.audit_log_filename, log_rotation(&config))?;
+        info!("Writing audit log to {}/{}", dir, config.audit_log_filename);
+        server = server.with_audit_log(audit);
+    }
+    if let Some(file) = &config.undo_journal_file {
+        server = server.with_journal(Journal::open(file, config.undo_journal_size)?);
+    } else {
+        server = server.with_journal(Journal::in_memory(config.undo_journal_size));
     }
+    server = server.with_humanize(config.humanize, Humanizer::new(&config.humanize_time_zone)?);
 
-    let server = McpServer::new(clients.clone(), config.lazy_mode);
+    // Instances that cannot be logged in to yet are retried by the event loop
+    server.login_all().await;
 
-    // Spawn background polling task for notifications
+    // Spawn background polling task for notifications and instance health
     server.start_event_loop(config.polling_interval_ms);
 
+    // Pick up changes to the configuration file, and reload on SIGHUP
+    tokio::spawn(Reloader::new(server.clone(), config.clone(), set_log_level).run());
+
     let server_clone = server.clone();
     let mut server_handle = match config.server_mode.as_str() {
         "http" => {
-            // Hardcoded port 3000 for now as it was in the original main.rs
+            let address = http_address(&config)?;
+            let listener = HttpListener::bind(&address).await?;
+            info!("Starting HTTP MCP Server on {}", address);
+            let auth = http_auth(&config)?;
+            if !auth.is_enabled() {
+                warn!("No HTTP tokens configured; the HTTP endpoint does not authenticate clients");
+            }
+            if let Some(oauth) = auth.oauth() {
+                info!("Accepting OAuth tokens from {}", oauth.settings().issuer);
+            }
             tokio::spawn(async move {
-                let _ =
-                    run_http_server(server_clone, "0.0.0.0", 3000, config.http_auth_token).await;
+                if let Err(e) = serve_http(server_clone, listener, auth).await {
+                    error!("HTTP server failed: {:#}", e);
+                }
             })
         }
         _ => tokio::spawn(async move {
@@ -110,23 +124,145 @@ pub async fn run_app(
     Ok(())
 }
 
-fn init_logging(config: &AppConfig) -> Option<WorkerGuard> {
-    let filter_layer =
-        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(&config.log_level));
+/// Where the HTTP server listens, from `http_unix_socket`, or `http_host`, `http_port` and
+/// the TLS certificate and key.
+pub fn http_address(config: &AppConfig) -> anyhow::Result<HttpAddress> {
+    if let Some(path) = &config.http_unix_socket {
+        return Ok(HttpAddress::Unix(path.into()));
+    }
+    let tls = match (&config.http_tls_cert, &config.http_tls_key) {
+        (Some(cert), Some(key)) => Some(TlsFiles {
+            cert: cert.into(),
+            key: key.into(),
+        }),
+        (None, None) => None,
+        _ => anyhow::bail!("http_tls_cert and http_tls_key must be set together"),
+    };
+    Ok(HttpAddress::Tcp {
+        host: config.http_host.clone(),
+        port: config.http_port,
+        tls,
+    })
+}
+
+/// The tokens the HTTP transport accepts: the shared `http_auth_token`, which may do
+/// everything, and the named `http_tokens` with their scopes, instances and expiry.
+pub fn http_auth(config: &AppConfig) -> anyhow::Result<HttpAuth> {
+    let mut auth = HttpAuth::default().with_query_token(config.http_allow_query_token);
+    if let Some(token) = &config.http_auth_token {
+        auth = auth.with_token(token.clone(), Grant::unrestricted(SHARED_TOKEN_IDENTITY));
+    }
+    let mut names = HashSet::from([SHARED_TOKEN_IDENTITY]);
+    let instances: Vec<String> = config.get_instances().into_iter().map(|i| i.name).collect();
+    for token in config.http_tokens.iter().flatten() {
+        if !names.insert(token.name.as_str()) {
+            anyhow::bail!("HTTP token name '{}' is used more than once", token.name);
+        }
+        let secret = token
+            .token
+            .clone()
+            .filter(|t| !t.is_empty())
+            .ok_or_else(|| anyhow::anyhow!("HTTP token '{}' has no token", token.name))?;
+        if token.scopes.is_empty() {
+            warn!(
+                "HTTP token '{}' has no scopes and cannot do anything",
+                token.name
+            );
+        }
+        for instance in token.instances.iter().flatten() {
+            if !instances.contains(instance) {
+                warn!(
+                    "HTTP token '{}' names unknown instance '{}'",
+                    token.name, instance
+                );
+            }
+        }
+        let expires = token
+            .expires
+            .as_deref()
+            .map(|expires| OffsetDateTime::parse(expires, &Rfc3339))
+            .transpose()
+            .with_context(|| {
+                format!(
+                    "HTTP token '{}': expires must be an RFC 3339 timestamp",
+                    token.name
+                )
+            })?;
+        let grant = Grant {
+            name: token.name.clone(),
+            scopes: token.scopes.iter().copied().collect(),
+            instances: token
+                .instances
+                .as_ref()
+                .map(|i| i.iter().cloned().collect()),
+            expires,
+        };
+        if grant.is_expired() {
+            warn!("HTTP token '{}' has expired", token.name);
+        }
+        auth = auth.with_token(secret, grant);
+    }
+    if let Some(settings) = oauth_settings(config)? {
+        auth = auth.with_oauth(OAuthVerifier::new(settings)?);
+    }
+    Ok(auth)
+}
+
+/// The OAuth resource server settings, if `oauth_issuer` is set.
+pub fn oauth_settings(config: &AppConfig) -> anyhow::Result<Option<OAuthSettings>> {
+    let Some(issuer) = config.oauth_issuer.clone() else {
+        return Ok(None);
+    };
+    let resource = config.oauth_resource.clone().ok_or_else(|| {
+        anyhow::anyhow!("oauth_issuer requires oauth_resource, the URL of this server")
+    })?;
+    let jwks = match (&config.oauth_jwks_url, &config.oauth_jwks_file) {
+        (Some(url), None) => JwksSource::Url(url.clone()),
+        (None, Some(file)) => JwksSource::File(file.into()),
+        _ => {
+            anyhow::bail!("oauth_issuer requires exactly one of oauth_jwks_url and oauth_jwks_file")
+        }
+    };
+    Ok(Some(OAuthSettings {
+        issuer,
+        audience: config
+            .oauth_audience
+            .clone()
+            .unwrap_or_else(|| resource.clone()),
+        resource,
+        jwks,
+        scopes: vec![
+            (config.oauth_read_scope.clone(), Scope::Read),
+            (config.oauth_write_scope.clone(), Scope::Write),
+            (config.oauth_admin_scope.clone(), Scope::Admin),
+        ],
+    }))
+}
+
+fn log_rotation(config: &AppConfig) -> Rotation {
+    match config.log_rotate.to_lowercase().as_str() {
+        "hourly" => Rotation::HOURLY,
+        "never" => Rotation::NEVER,
+        _ => Rotation::DAILY,
+    }
+}
 
+fn init_logging(config: &AppConfig) -> (Option<WorkerGuard>, LogLevelSetter) {
+    // RUST_LOG wins over the configuration, also when that is reloaded
+    let from_env = EnvFilter::try_from_default_env().ok();
+    let filter_layer = from_env
+        .clone()
+        .unwrap_or_else(|| EnvFilter::new(&config.log_level));
+
+    let (stdout_filter, stdout_handle) = reload::Layer::new(filter_layer.clone());
     let stdout_layer = tracing_subscriber::fmt::layer()
-        .with_writer(std::io::stderr)
-        .with_filter(filter_layer.clone());
+        .with_writer(Redacting(std::io::stderr))
+        .with_filter(stdout_filter);
+    let (file_filter, file_handle) = reload::Layer::new(filter_layer);
 
     let (file_layer, guard) = if config.log_file_enable {
-        let rotation = match config.log_rotate.to_lowercase().as_str() {
-            "hourly" => Rotation::HOURLY,
-            "never" => Rotation::NEVER,
-            _ => Rotation::DAILY,
-        };
-
         let file_appender = RollingFileAppender::builder()
-            .rotation(rotation)
+            .rotation(log_rotation(config))
             .filename_prefix(&config.log_filename)
             .build(&config.log_dir)
             .expect("Failed to create log file appender");
@@ -136,9 +272,9 @@ fn init_logging(config: &AppConfig) -> Option<WorkerGuard> {
         (
             Some(
                 tracing_subscriber::fmt::layer()
-                    .with_writer(non_blocking)
+                    .with_writer(Redacting(non_blocking))
                     .with_ansi(false)
-                    .with_filter(filter_layer),
+                    .with_filter(file_filter),
             ),
             Some(guard),
         )
@@ -146,7 +282,9 @@ fn init_logging(config: &AppConfig) -> Option<WorkerGuard> {
         (None, None)
     };
 
-    let registry = tracing_subscriber::registry().with(stdout_layer);
+    let registry = tracing_subscriber::registry()
+        .with(stdout_layer)
+        .with(McpLogLayer::filtered());
 
     if let Some(layer) = file_layer {
         let _ = registry.with(layer).try_init();
@@ -154,5 +292,132 @@ fn init_logging(config: &AppConfig) -> Option<WorkerGuard> {
         let _ = registry.try_init();
     }
 
-    guard
+    let set_log_level: LogLevelSetter = Box::new(move |level| {
+        if from_env.is_some() {
+            return;
+        }
+        let _ = stdout_handle.modify(|filter| *filter = EnvFilter::new(level));
+        let _ = file_handle.modify(|filter| *filter = EnvFilter::new(level));
+    });
+    (guard, set_log_level)
+}
+
+/// Registers the credentials in `config` so that they never reach clients through
+/// notifications/message.
+pub(crate) fn register_secrets(config: &AppConfig) {
+    for secret in config
+        .get_instances()
+        .into_iter()
+        .filter_map(|i| i.password)
+        .chain(config.http_auth_token.clone())
+        .chain(
+            config
+                .http_tokens
+                .iter()
+                .flatten()
+                .filter_map(|t| t.token.clone()),
+        )
+    {
+        logging::register_secret(secret.expose());
+    }
+}
+
+/// The address of an instance's Web UI.
+fn base_url(inst: &QBitInstance) -> String {
+    if inst.host.starts_with("http://") || inst.host.starts_with("https://") {
+        if let Some(port) = inst.port {
+            format!("{}:{}", inst.host, port)
+        } else {
+            inst.host.clone()
+        }
+    } else {
+        let port = inst.port.unwrap_or(80);
+        format!("http://{}:{}", inst.host, port)
+    }
+}
+
+/// Everything that goes into an instance's client; a client is replaced when this changes.
+pub(crate) fn connection(
+    inst: &QBitInstance,
+    config: &AppConfig,
+) -> (String, Option<String>, Option<Secret>, bool) {
+    let base_url = base_url(inst);
+    let no_verify_ssl =
+        inst.no_verify_ssl.unwrap_or(config.no_verify_ssl) && base_url.starts_with("https://");
+    (
+        base_url,
+        inst.username.clone(),
+        inst.password.clone(),
+        no_verify_ssl,
+    )
+}
+
+fn build_client(inst: &QBitInstance, config: &AppConfig) -> QBitClient {
+    let (base_url, username, password, no_verify_ssl) = connection(inst, config);
+    info!("Initializing client '{}' at {}", inst.name, base_url);
+    if let (Some(u), Some(p)) = (username, password) {
+        QBitClient::new(base_url, u, p.expose(), no_verify_ssl)
+    } else {
+        QBitClient::new_no_auth(base_url, no_verify_ssl)
+    }
+}
+
+/// The instances configured in `config`, with their tool policies. Clients in `reuse` are
+/// kept instead of creating new ones, so that they keep their session.
+pub fn build_instances(
+    config: &AppConfig,
+    reuse: Option<&HashMap<String, QBitClient>>,
+) -> Instances {
+    let instances: Vec<QBitInstance> = config
+        .get_instances()
+        .into_iter()
+        .filter(|inst| !inst.host.trim().is_empty())
+        .collect();
+    let clients = instances
+        .iter()
+        .map(|inst| {
+            let client = reuse
+                .and_then(|clients| clients.get(&inst.name).cloned())
+                .unwrap_or_else(|| build_client(inst, config));
+            (inst.name.clone(), client)
+        })
+        .collect();
+
+    let mut result = Instances::new(clients).with_policy(tool_policy(
+        config.read_only,
+        config.allowed_tools.as_ref(),
+        config.denied_tools.as_ref(),
+    ));
+    for inst in &instances {
+        if inst.read_only.is_some() || inst.allowed_tools.is_some() || inst.denied_tools.is_some() {
+            // An instance can be made read-only, but not writable under a read-only server
+            let policy = tool_policy(
+                config.read_only || inst.read_only.unwrap_or(false),
+                inst.allowed_tools
+                    .as_ref()
+                    .or(config.allowed_tools.as_ref()),
+                inst.denied_tools.as_ref().or(config.denied_tools.as_ref()),
+            );
+            result = result.with_instance_policy(inst.name.clone(), policy);
+        }
+    }
+    result
+}
+
+fn tool_policy(
+    read_only: bool,
+    allowed_tools: Option<&Vec<String>>,
+    denied_tools: Option<&Vec<String>>,
+) -> ToolPolicy {
+    let mut policy = ToolPolicy {
+        read_only,
+        ..Default::default()
+    };
+    if let Some(allowed) = allowed_tools {
+        policy = policy.with_allowed_tools(allowed.iter().cloned());
+    }
+    if let Some(denied) = denied_tools {
+        policy = policy.with_denied_tools(denied.iter().cloned());
+    }
+    policy
 }
@@ -1,6 +1,23 @@
 use anyhow::{Result, anyhow};
 use reqwest::Client;
 
+/// How to add a torrent from a .torrent file, see [`QBitClient::add_torrent_file`].
+#[derive(Debug, Default, Clone)]
+pub struct AddTorrentOptions {
+    /// Save path; automatic torrent management is turned off when set, so it sticks
+    pub save_path: Option<String>,
+    pub category: Option<String>,
+    /// Comma-separated tags
+    pub tags: Option<String>,
+    /// Skip the hash check, for data known to be complete
+    pub skip_checking: bool,
+    /// Add the torrent stopped
+    pub paused: bool,
+    pub ratio_limit: Option<f64>,
+    pub seeding_time_limit: Option<i64>,
+    pub inactive_seeding_time_limit: Option<i64>,
+}
+
 #[derive(Clone)]
 pub struct QBitClient {
     http: Client,
@@ -50,6 +67,11 @@ impl QBitClient {
         &self.base_url
     }
 
+    /// True if the client was created with credentials and has to log in.
+    pub fn requires_login(&self) -> bool {
+        self.username.is_some()
+    }
+
     pub async fn login(&self) -> Result<()> {
         let base_url = self.base_url.trim_end_matches('/');
         let url = format!("{}/api/v2/auth/login", base_url);
@@ -73,12 +95,11 @@ impl QBitClient {
 
         if resp.status().is_success() {
             // Check body text for "Ok." just to be sure, though status 200 usually implies success for qbit.
+            // Wrong credentials are reported as 200 with "Fails."
             let text = resp.text().await?;
-            if text == "Ok." {
-                Ok(())
+            if text.trim() == "Fails." {
+                Err(anyhow!("Login failed: wrong username or password"))
             } else {
-                // Sometimes it returns 200 even if fails? No, usually 200 with "Ok."
-                // But strictly checking "Ok." is safer.
                 Ok(())
             }
         } else {
@@ -173,6 +194,72 @@ impl QBitClient {
         }
     }
 
+    /// Adds a torrent from the contents of a .torrent file.
+    pub async fn add_torrent_file(
+        &self,
+        torrent: Vec<u8>,
+        file_name: &str,
+        options: &AddTorrentOptions,
+    ) -> Result<()> {
+        let url = format!("{}/api/v2/torrents/add", self.base_url);
+
+        let part = reqwest::multipart::Part::bytes(torrent)
+            .file_name(file_name.to_string())
+            .mime_str("application/x-bittorrent")?;
+        let mut form = reqwest::multipart::Form::new().part("torrents", part);
+
+        if let Some(path) = &options.save_path {
+            form = form.text("savepath", path.clone()).text("autoTMM", "false");
+        }
+        if let Some(category) = &options.category {
+            form = form.text("category", category.clone());
+        }
+        if let Some(tags) = &options.tags {
+            form = form.text("tags", tags.clone());
+        }
+        if options.skip_checking {
+            form = form.text("skip_checking", "true");
+        }
+        if options.paused {
+            // "stopped" for qBittorrent 5, "paused" for 4
+            form = form.text("stopped", "true").text("paused", "true");
+        }
+        if let Some(limit) = options.ratio_limit {
+            form = form.text("ratioLimit", limit.to_string());
+        }
+        if let Some(limit) = options.seeding_time_limit {
+            form = form.text("seedingTimeLimit", limit.to_string());
+        }
+        if let Some(limit) = options.inactive_seeding_time_limit {
+            form = form.text("inactiveSeedingTimeLimit", limit.to_string());
+        }
+
+        let resp = self.http.post(&url).multipart(form).send().await?;
+
+        if !resp.status().is_success() {
+            return Err(anyhow!("Failed to add torrent: {}", resp.status()));
+        }
+        // qBittorrent answers 200 with "Fails." when it rejects the torrent
+        if resp.text().await?.trim() == "Fails." {
+            return Err(anyhow!("Failed to add torrent: rejected by qBittorrent"));
+        }
+        Ok(())
+    }
+
+    /// The .torrent file of a torrent (qBittorrent 4.5 and later).
+    pub async fn export_torrent(&self, hash: &str) -> Result<Vec<u8>> {
+        let url = format!("{}/api/v2/torrents/export", self.base_url);
+        let params = [("hash", hash)];
+
+        let resp = self.http.post(&url).form(&params).send().await?;
+
+        if resp.status().is_success() {
+            Ok(resp.bytes().await?.to_vec())
+        } else {
+            Err(anyhow!("Failed to export torrent: {}", resp.status()))
+        }
+    }
+
     pub async fn pause_torrents(&self, hashes: &str) -> Result<()> {
         // Try v5 "stop" endpoint first
         let url_stop = format!("{}/api/v2/torrents/stop", self.base_url);
@@ -709,6 +796,18 @@ impl QBitClient {
         }
     }
 
+    pub async fn get_api_version(&self) -> Result<String> {
+        let url = format!("{}/api/v2/app/webapiVersion", self.base_url);
+        let resp = self.http.get(&url).send().await?;
+
+        if resp.status().is_success() {
+            let version = resp.text().await?;
+            Ok(version)
+        } else {
+            Err(anyhow!("Failed to get Web API version: {}", resp.status()))
+        }
+    }
+
     pub async fn get_build_info(&self) -> Result<crate::models::BuildInfo> {
         let url = format!("{}/api/v2/app/buildInfo", self.base_url);
         let resp = self.http.get(&url).send().await?;
@@ -887,6 +986,26 @@ impl QBitClient {
         }
     }
 
+    /// The peers of a torrent, keyed by `ip:port`.
+    pub async fn get_torrent_peers(
+        &self,
+        hash: &str,
+    ) -> Result<std::collections::HashMap<String, serde_json::Value>> {
+        let url = format!(
+            "{}/api/v2/sync/torrentPeers?hash={}&rid=0",
+            self.base_url, hash
+        );
+
+        let resp = self.http.get(&url).send().await?;
+
+        if resp.status().is_success() {
+            let data = resp.json::<crate::models::SyncTorrentPeers>().await?;
+            Ok(data.peers.unwrap_or_default())
+        } else {
+            Err(anyhow!("Failed to get torrent peers: {}", resp.status()))
+        }
+    }
+
     pub async fn toggle_sequential_download(&self, hashes: &str) -> Result<()> {
         let url = format!("{}/api/v2/torrents/toggleSequentialDownload", self.base_url);
         let params = [("hashes", hashes)];
@@ -1040,6 +1159,18 @@ impl QBitClient {
         }
     }
 
+    pub async fn get_tags(&self) -> Result<Vec<String>> {
+        let url = format!("{}/api/v2/torrents/tags", self.base_url);
+        let resp = self.http.get(&url).send().await?;
+
+        if resp.status().is_success() {
+            let tags = resp.json().await?;
+            Ok(tags)
+        } else {
+            Err(anyhow!("Failed to get tags: {}", resp.status()))
+        }
+    }
+
     pub async fn create_tags(&self, tags: &str) -> Result<()> {
         let url = format!("{}/api/v2/torrents/createTags", self.base_url);
         let params = [("tags", tags)];
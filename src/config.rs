@@ -1,24 +1,68 @@
+use crate::secret::{self, Secret};
+use crate::server::auth::Scope;
 use clap::ArgMatches;
+use clap::parser::ValueSource;
 use config::{Config, ConfigError, Environment, File};
 use serde::Deserialize;
+use std::path::{Path, PathBuf};
 
-#[derive(Debug, Deserialize, Clone)]
+/// Extensions the `config` crate tries for a configuration file named without one.
+const CONFIG_EXTENSIONS: &[&str] = &["toml", "json", "yaml", "yml", "json5", "ini", "ron"];
+
+#[derive(Debug, Deserialize, Clone, Default, PartialEq)]
 pub struct QBitInstance {
     pub name: String,
     pub host: String,
     pub port: Option<u16>,
     pub username: Option<String>,
-    pub password: Option<String>,
+    pub password: Option<Secret>,
+    /// Reads the password from this file, e.g. a Docker or systemd secret.
+    pub password_file: Option<String>,
+    /// Runs this command through the shell and uses its output as the password.
+    pub password_command: Option<String>,
     pub no_verify_ssl: Option<bool>,
+    /// Makes this instance read-only; it cannot lift the top-level `read_only`.
+    pub read_only: Option<bool>,
+    /// Overrides the top-level `allowed_tools` for this instance.
+    pub allowed_tools: Option<Vec<String>>,
+    /// Overrides the top-level `denied_tools` for this instance.
+    pub denied_tools: Option<Vec<String>>,
+}
+
+/// A named bearer token for the HTTP transport.
+#[derive(Debug, Deserialize, Clone, Default, PartialEq)]
+pub struct HttpToken {
+    /// Identifies the client in logs and the audit log.
+    pub name: String,
+    pub token: Option<Secret>,
+    pub token_file: Option<String>,
+    pub token_command: Option<String>,
+    #[serde(default)]
+    pub scopes: Vec<Scope>,
+    /// When set, only these instances may be used with the token.
+    pub instances: Option<Vec<String>>,
+    /// RFC 3339 timestamp after which the token is rejected.
+    pub expires: Option<String>,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+/// Where a configuration was loaded from, so that it can be loaded again.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigSource {
+    file_path: Option<String>,
+    cli_args: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, Clone, Default, PartialEq)]
 pub struct AppConfig {
     pub instances: Option<Vec<QBitInstance>>,
     pub qbittorrent_host: String,
     pub qbittorrent_port: Option<u16>,
     pub qbittorrent_username: Option<String>,
-    pub qbittorrent_password: Option<String>,
+    pub qbittorrent_password: Option<Secret>,
+    #[serde(default, alias = "password_file")]
+    pub qbittorrent_password_file: Option<String>,
+    #[serde(default, alias = "password_command")]
+    pub qbittorrent_password_command: Option<String>,
     pub server_mode: String,
     pub lazy_mode: bool,
     pub no_verify_ssl: bool,
@@ -27,9 +71,80 @@ pub struct AppConfig {
     pub log_dir: String,
     pub log_filename: String,
     pub log_rotate: String,
-    pub http_auth_token: Option<String>,
+    #[serde(default)]
+    pub http_host: String,
+    #[serde(default)]
+    pub http_port: u16,
+    /// PEM certificate chain; serves HTTPS together with `http_tls_key`.
+    #[serde(default)]
+    pub http_tls_cert: Option<String>,
+    #[serde(default)]
+    pub http_tls_key: Option<String>,
+    /// Listens on this Unix domain socket instead of TCP.
+    #[serde(default)]
+    pub http_unix_socket: Option<String>,
+    pub http_auth_token: Option<Secret>,
+    #[serde(default)]
+    pub http_auth_token_file: Option<String>,
+    #[serde(default)]
+    pub http_auth_token_command: Option<String>,
+    #[serde(default)]
+    pub http_tokens: Option<Vec<HttpToken>>,
+    /// Also accept tokens in a `?token=` query parameter, which ends up in access logs.
+    #[serde(default)]
+    pub http_allow_query_token: bool,
+    /// The OAuth authorization server whose JWTs are accepted over HTTP.
+    #[serde(default)]
+    pub oauth_issuer: Option<String>,
+    /// The canonical URL of this server, advertised to OAuth clients.
+    #[serde(default)]
+    pub oauth_resource: Option<String>,
+    /// The `aud` tokens must carry; defaults to `oauth_resource`.
+    #[serde(default)]
+    pub oauth_audience: Option<String>,
+    #[serde(default)]
+    pub oauth_jwks_url: Option<String>,
+    /// The issuer's keys on disk, instead of `oauth_jwks_url`.
+    #[serde(default)]
+    pub oauth_jwks_file: Option<String>,
+    /// OAuth scopes that grant the read, write and admin permissions.
+    #[serde(default)]
+    pub oauth_read_scope: String,
+    #[serde(default)]
+    pub oauth_write_scope: String,
+    #[serde(default)]
+    pub oauth_admin_scope: String,
     #[serde(default)]
     pub polling_interval_ms: u64,
+    #[serde(default)]
+    pub max_concurrent_requests: usize,
+    #[serde(default)]
+    pub require_confirmation: bool,
+    #[serde(default)]
+    pub toolsets: Option<Vec<String>>,
+    #[serde(default)]
+    pub read_only: bool,
+    #[serde(default)]
+    pub allowed_tools: Option<Vec<String>>,
+    #[serde(default)]
+    pub denied_tools: Option<Vec<String>>,
+    #[serde(default)]
+    pub audit_log_enable: bool,
+    #[serde(default)]
+    pub audit_log_dir: Option<String>,
+    #[serde(default)]
+    pub audit_log_filename: String,
+    #[serde(default)]
+    pub undo_journal_file: Option<String>,
+    #[serde(default)]
+    pub undo_journal_size: usize,
+    #[serde(default)]
+    pub humanize: bool,
+    #[serde(default)]
+    pub humanize_time_zone: String,
+    /// Set by [`AppConfig::load`]; configurations built otherwise cannot be reloaded.
+    #[serde(skip)]
+    pub source: Option<ConfigSource>,
 }
 
 impl AppConfig {
@@ -46,11 +161,48 @@ impl AppConfig {
             port: self.qbittorrent_port,
             username: self.qbittorrent_username.clone(),
             password: self.qbittorrent_password.clone(),
+            password_file: self.qbittorrent_password_file.clone(),
+            password_command: self.qbittorrent_password_command.clone(),
             no_verify_ssl: Some(self.no_verify_ssl),
+            ..Default::default()
         }]
     }
 
+    /// Loads the configuration again from where it was loaded from, picking up changes
+    /// to the configuration file and environment.
+    pub fn reload(&self) -> Result<Self, ConfigError> {
+        let source = self.source.clone().ok_or_else(|| {
+            ConfigError::Message("configuration was not loaded from a source".into())
+        })?;
+        Self::load(source.file_path, source.cli_args)
+    }
+
+    /// The configuration file this configuration was loaded from, if there is one.
+    pub fn config_file(&self) -> Option<PathBuf> {
+        let source = self.source.as_ref()?;
+        let path = match &source.file_path {
+            Some(path) => path.clone(),
+            None => parse_args(source.cli_args.clone())
+                .get_one::<String>("config")
+                .cloned()
+                .unwrap_or_else(|| "config".to_string()),
+        };
+        let path = Path::new(&path);
+        if path.is_file() {
+            return Some(path.to_path_buf());
+        }
+        // Like `File::with_name`, which also accepts a name without extension
+        CONFIG_EXTENSIONS
+            .iter()
+            .map(|ext| path.with_extension(ext))
+            .find(|candidate| candidate.is_file())
+    }
+
     pub fn load(file_path: Option<String>, cli_args: Vec<String>) -> Result<Self, ConfigError> {
+        let source = ConfigSource {
+            file_path: file_path.clone(),
+            cli_args: cli_args.clone(),
+        };
         let mut builder = Config::builder();
         let matches = parse_args(cli_args);
 
@@ -72,7 +224,20 @@ impl AppConfig {
             .set_default("log_dir", ".")?
             .set_default("log_filename", "qbittorrent-mcp-rs.log")?
             .set_default("log_rotate", "daily")?
-            .set_default("polling_interval_ms", 2000)?;
+            .set_default("http_host", "0.0.0.0")?
+            .set_default("http_port", 3000)?
+            .set_default("oauth_read_scope", "qbittorrent:read")?
+            .set_default("oauth_write_scope", "qbittorrent:write")?
+            .set_default("oauth_admin_scope", "qbittorrent:admin")?
+            .set_default("polling_interval_ms", 2000)?
+            .set_default("max_concurrent_requests", 16)?
+            .set_default("require_confirmation", false)?
+            .set_default("read_only", false)?
+            .set_default("audit_log_enable", false)?
+            .set_default("audit_log_filename", "audit.jsonl")?
+            .set_default("undo_journal_size", 100)?
+            .set_default("humanize", false)?
+            .set_default("humanize_time_zone", "UTC")?;
 
         // 3. Load from File
         if let Some(path) = path_to_load {
@@ -86,7 +251,11 @@ impl AppConfig {
             Environment::with_prefix("QBITTORRENT")
                 .prefix_separator("_")
                 .separator("__")
-                .try_parsing(true),
+                .try_parsing(true)
+                .list_separator(",")
+                .with_list_parse_key("toolsets")
+                .with_list_parse_key("allowed_tools")
+                .with_list_parse_key("denied_tools"),
         );
 
         // 5. Apply CLI overrides
@@ -105,35 +274,173 @@ impl AppConfig {
         if let Some(pass) = matches.get_one::<String>("qbittorrent_password") {
             builder = builder.set_override("qbittorrent_password", pass.as_str())?;
         }
+        if let Some(file) = matches.get_one::<String>("qbittorrent_password_file") {
+            builder = builder.set_override("qbittorrent_password_file", file.as_str())?;
+        }
         if matches.get_flag("lazy_mode") {
             builder = builder.set_override("lazy_mode", true)?;
         }
+        for key in ["toolsets", "allowed_tools", "denied_tools"] {
+            if let Some(values) = matches.get_many::<String>(key) {
+                let values: Vec<String> = values.cloned().collect();
+                builder = builder.set_override(key, values)?;
+            }
+        }
+        if matches.get_flag("read_only") {
+            builder = builder.set_override("read_only", true)?;
+        }
+        if matches.get_flag("humanize") {
+            builder = builder.set_override("humanize", true)?;
+        }
+        if let Some(tz) = matches.get_one::<String>("humanize_time_zone") {
+            builder = builder.set_override("humanize_time_zone", tz.as_str())?;
+        }
+        if matches.get_flag("audit_log_enable") {
+            builder = builder.set_override("audit_log_enable", true)?;
+        }
+        if let Some(dir) = matches.get_one::<String>("audit_log_dir") {
+            builder = builder.set_override("audit_log_dir", dir.as_str())?;
+        }
+        if let Some(filename) = matches.get_one::<String>("audit_log_filename") {
+            builder = builder.set_override("audit_log_filename", filename.as_str())?;
+        }
+        if let Some(file) = matches.get_one::<String>("undo_journal_file") {
+            builder = builder.set_override("undo_journal_file", file.as_str())?;
+        }
+        if let Some(size) = matches.get_one::<u64>("undo_journal_size") {
+            builder = builder.set_override("undo_journal_size", *size)?;
+        }
         if matches.get_flag("no_verify_ssl") {
             builder = builder.set_override("no_verify_ssl", true)?;
         }
-        if let Some(level) = matches.get_one::<String>("log_level") {
+        if let Some(level) = explicit(&matches, "log_level") {
             builder = builder.set_override("log_level", level.as_str())?;
         }
         if matches.get_flag("log_file_enable") {
             builder = builder.set_override("log_file_enable", true)?;
         }
-        if let Some(dir) = matches.get_one::<String>("log_dir") {
+        if let Some(dir) = explicit(&matches, "log_dir") {
             builder = builder.set_override("log_dir", dir.as_str())?;
         }
-        if let Some(filename) = matches.get_one::<String>("log_filename") {
+        if let Some(filename) = explicit(&matches, "log_filename") {
             builder = builder.set_override("log_filename", filename.as_str())?;
         }
-        if let Some(rotate) = matches.get_one::<String>("log_rotate") {
+        if let Some(rotate) = explicit(&matches, "log_rotate") {
             builder = builder.set_override("log_rotate", rotate.as_str())?;
         }
+        if let Some(host) = matches.get_one::<String>("http_host") {
+            builder = builder.set_override("http_host", host.as_str())?;
+        }
+        if let Some(port) = matches.get_one::<u16>("http_port") {
+            builder = builder.set_override("http_port", *port)?;
+        }
+        for key in ["http_tls_cert", "http_tls_key", "http_unix_socket"] {
+            if let Some(path) = matches.get_one::<String>(key) {
+                builder = builder.set_override(key, path.as_str())?;
+            }
+        }
         if let Some(token) = matches.get_one::<String>("http_auth_token") {
             builder = builder.set_override("http_auth_token", token.as_str())?;
         }
+        if let Some(file) = matches.get_one::<String>("http_auth_token_file") {
+            builder = builder.set_override("http_auth_token_file", file.as_str())?;
+        }
+        if matches.get_flag("http_allow_query_token") {
+            builder = builder.set_override("http_allow_query_token", true)?;
+        }
+        for key in [
+            "oauth_issuer",
+            "oauth_resource",
+            "oauth_audience",
+            "oauth_jwks_url",
+            "oauth_jwks_file",
+        ] {
+            if let Some(value) = matches.get_one::<String>(key) {
+                builder = builder.set_override(key, value.as_str())?;
+            }
+        }
         if let Some(interval) = matches.get_one::<u64>("polling_interval_ms") {
             builder = builder.set_override("polling_interval_ms", *interval)?;
         }
+        if let Some(limit) = matches.get_one::<u64>("max_concurrent_requests") {
+            builder = builder.set_override("max_concurrent_requests", *limit)?;
+        }
+        if matches.get_flag("require_confirmation") {
+            builder = builder.set_override("require_confirmation", true)?;
+        }
 
-        builder.build()?.try_deserialize()
+        let mut config: Self = builder.build()?.try_deserialize()?;
+        // A secret given on the command line replaces the other sources of it in the file
+        // and the environment, instead of being rejected as ambiguous
+        if matches.get_one::<String>("qbittorrent_password").is_some() {
+            config.qbittorrent_password_file = None;
+            config.qbittorrent_password_command = None;
+        } else if matches
+            .get_one::<String>("qbittorrent_password_file")
+            .is_some()
+        {
+            config.qbittorrent_password = None;
+            config.qbittorrent_password_command = None;
+        }
+        if matches.get_one::<String>("http_auth_token").is_some() {
+            config.http_auth_token_file = None;
+            config.http_auth_token_command = None;
+        } else if matches.get_one::<String>("http_auth_token_file").is_some() {
+            config.http_auth_token = None;
+            config.http_auth_token_command = None;
+        }
+        config
+            .resolve_secrets()
+            .map_err(|e| ConfigError::Message(format!("{:#}", e)))?;
+        config.source = Some(source);
+        Ok(config)
+    }
+
+    /// Replaces `password_file`, `password_command` and `${VAR}` references with the
+    /// secrets they point to.
+    fn resolve_secrets(&mut self) -> anyhow::Result<()> {
+        use anyhow::Context;
+
+        self.qbittorrent_password = secret::resolve(
+            "qbittorrent_password",
+            self.qbittorrent_password.as_ref(),
+            self.qbittorrent_password_file.as_deref(),
+            self.qbittorrent_password_command.as_deref(),
+        )?;
+        self.http_auth_token = secret::resolve(
+            "http_auth_token",
+            self.http_auth_token.as_ref(),
+            self.http_auth_token_file.as_deref(),
+            self.http_auth_token_command.as_deref(),
+        )?;
+        for token in self.http_tokens.iter_mut().flatten() {
+            token.token = secret::resolve(
+                "token",
+                token.token.as_ref(),
+                token.token_file.as_deref(),
+                token.token_command.as_deref(),
+            )
+            .with_context(|| format!("HTTP token '{}'", token.name))?;
+        }
+        for inst in self.instances.iter_mut().flatten() {
+            inst.password = secret::resolve(
+                "password",
+                inst.password.as_ref(),
+                inst.password_file.as_deref(),
+                inst.password_command.as_deref(),
+            )
+            .with_context(|| format!("Instance '{}'", inst.name))?;
+        }
+        Ok(())
+    }
+}
+
+/// The value of a CLI argument that has a default, if it was actually given, so that the
+/// default does not hide the configuration file.
+fn explicit<'a>(matches: &'a ArgMatches, id: &str) -> Option<&'a String> {
+    match matches.value_source(id) {
+        Some(ValueSource::CommandLine) => matches.get_one::<String>(id),
+        _ => None,
     }
 }
 
@@ -173,12 +480,42 @@ fn parse_args(args: Vec<String>) -> ArgMatches {
                 .long("qbittorrent-password")
                 .help("qBittorrent Password"),
         )
+        .arg(
+            Arg::new("qbittorrent_password_file")
+                .long("qbittorrent-password-file")
+                .conflicts_with("qbittorrent_password")
+                .help("File containing the qBittorrent password"),
+        )
         .arg(
             Arg::new("lazy_mode")
                 .long("lazy")
                 .action(ArgAction::SetTrue)
                 .help("Enable lazy mode (show fewer tools initially)"),
         )
+        .arg(
+            Arg::new("toolsets")
+                .long("toolsets")
+                .value_delimiter(',')
+                .help("Comma-separated toolsets enabled when a session starts (e.g. torrents,search)"),
+        )
+        .arg(
+            Arg::new("read_only")
+                .long("read-only")
+                .action(ArgAction::SetTrue)
+                .help("Hide and reject every tool that changes qBittorrent"),
+        )
+        .arg(
+            Arg::new("allowed_tools")
+                .long("allowed-tools")
+                .value_delimiter(',')
+                .help("Comma-separated tools that may be called; all others are rejected"),
+        )
+        .arg(
+            Arg::new("denied_tools")
+                .long("denied-tools")
+                .value_delimiter(',')
+                .help("Comma-separated tools that are always rejected"),
+        )
         .arg(
             Arg::new("no_verify_ssl")
                 .long("no-verify-ssl")
@@ -216,16 +553,129 @@ fn parse_args(args: Vec<String>) -> ArgMatches {
                 .help("Log rotation strategy (daily, hourly, never)")
                 .default_value("daily"),
         )
+        .arg(
+            Arg::new("audit_log_enable")
+                .long("audit-log-enable")
+                .action(ArgAction::SetTrue)
+                .help("Record every call to a tool that changes qBittorrent in a JSONL audit log"),
+        )
+        .arg(
+            Arg::new("audit_log_dir")
+                .long("audit-log-dir")
+                .help("Audit log directory (defaults to the log directory)"),
+        )
+        .arg(
+            Arg::new("audit_log_filename")
+                .long("audit-log-filename")
+                .help("Audit log filename prefix"),
+        )
+        .arg(
+            Arg::new("humanize")
+                .long("humanize")
+                .action(ArgAction::SetTrue)
+                .help("Show sizes, speeds, times and states in human-friendly form by default"),
+        )
+        .arg(
+            Arg::new("humanize_time_zone")
+                .long("humanize-time-zone")
+                .help("Time zone for humanized timestamps: UTC or an offset such as +02:00 (default: UTC)"),
+        )
+        .arg(
+            Arg::new("undo_journal_file")
+                .long("undo-journal-file")
+                .help("File that keeps the undo journal across restarts (default: in memory only)"),
+        )
+        .arg(
+            Arg::new("undo_journal_size")
+                .long("undo-journal-size")
+                .help("Number of recent actions kept in the undo journal")
+                .value_parser(clap::value_parser!(u64).range(1..)),
+        )
+        .arg(
+            Arg::new("http_host")
+                .long("http-host")
+                .help("Address the HTTP server listens on (default: 0.0.0.0)"),
+        )
+        .arg(
+            Arg::new("http_port")
+                .long("http-port")
+                .help("Port the HTTP server listens on (default: 3000)")
+                .value_parser(clap::value_parser!(u16)),
+        )
+        .arg(
+            Arg::new("http_tls_cert")
+                .long("http-tls-cert")
+                .help("PEM certificate chain for HTTPS; requires --http-tls-key"),
+        )
+        .arg(
+            Arg::new("http_tls_key")
+                .long("http-tls-key")
+                .help("PEM private key for HTTPS"),
+        )
+        .arg(
+            Arg::new("http_unix_socket")
+                .long("http-unix-socket")
+                .help("Listen on this Unix domain socket instead of TCP"),
+        )
         .arg(
             Arg::new("http_auth_token")
                 .long("http-auth-token")
                 .help("Authentication token for HTTP server mode"),
         )
+        .arg(
+            Arg::new("http_auth_token_file")
+                .long("http-auth-token-file")
+                .conflicts_with("http_auth_token")
+                .help("File containing the authentication token for HTTP server mode"),
+        )
+        .arg(
+            Arg::new("http_allow_query_token")
+                .long("http-allow-query-token")
+                .action(ArgAction::SetTrue)
+                .help("Also accept HTTP tokens in a ?token= query parameter (legacy, ends up in access logs)"),
+        )
+        .arg(
+            Arg::new("oauth_issuer")
+                .long("oauth-issuer")
+                .help("Accept JWTs from this OAuth authorization server in HTTP mode"),
+        )
+        .arg(
+            Arg::new("oauth_resource")
+                .long("oauth-resource")
+                .help("Canonical URL of this server, advertised to OAuth clients"),
+        )
+        .arg(
+            Arg::new("oauth_audience")
+                .long("oauth-audience")
+                .help("Audience OAuth tokens must carry (default: --oauth-resource)"),
+        )
+        .arg(
+            Arg::new("oauth_jwks_url")
+                .long("oauth-jwks-url")
+                .help("URL of the issuer's JSON Web Key Set"),
+        )
+        .arg(
+            Arg::new("oauth_jwks_file")
+                .long("oauth-jwks-file")
+                .help("File with the issuer's JSON Web Key Set, for offline setups"),
+        )
         .arg(
             Arg::new("polling_interval_ms")
                 .long("polling-interval-ms")
                 .help("Polling interval for notifications (ms)")
                 .value_parser(clap::value_parser!(u64)),
+        )
+        .arg(
+            Arg::new("max_concurrent_requests")
+                .long("max-concurrent-requests")
+                .help("Maximum number of requests processed concurrently in stdio mode")
+                .value_parser(clap::value_parser!(u64).range(1..)),
+        )
+        .arg(
+            Arg::new("require_confirmation")
+                .long("require-confirmation")
+                .action(ArgAction::SetTrue)
+                .help("Require a confirm token for destructive tools when the client lacks elicitation"),
         );
 
     if args.is_empty() {
@@ -246,7 +696,7 @@ mod tests {
             qbittorrent_host: "legacy_host".to_string(),
             qbittorrent_port: Some(1234),
             qbittorrent_username: Some("user".to_string()),
-            qbittorrent_password: Some("pass".to_string()),
+            qbittorrent_password: Some("pass".into()),
             server_mode: "stdio".to_string(),
             lazy_mode: false,
             no_verify_ssl: true,
@@ -257,6 +707,7 @@ mod tests {
             log_rotate: "daily".to_string(),
             http_auth_token: None,
             polling_interval_ms: 1000,
+            ..Default::default()
         };
 
         let instances = config.get_instances();
@@ -305,8 +756,34 @@ mod tests {
             "never".into(),
             "--http-auth-token".into(),
             "token123".into(),
+            "--http-host".into(),
+            "127.0.0.1".into(),
+            "--http-port".into(),
+            "8443".into(),
+            "--http-tls-cert".into(),
+            "/etc/mcp/cert.pem".into(),
+            "--http-tls-key".into(),
+            "/etc/mcp/key.pem".into(),
             "--polling-interval-ms".into(),
             "5000".into(),
+            "--max-concurrent-requests".into(),
+            "4".into(),
+            "--require-confirmation".into(),
+            "--toolsets".into(),
+            "torrents,search".into(),
+            "--read-only".into(),
+            "--denied-tools".into(),
+            "shutdown_app".into(),
+            "--audit-log-enable".into(),
+            "--audit-log-dir".into(),
+            "/tmp/audit".into(),
+            "--undo-journal-file".into(),
+            "/tmp/journal.json".into(),
+            "--undo-journal-size".into(),
+            "50".into(),
+            "--humanize".into(),
+            "--humanize-time-zone".into(),
+            "+02:00".into(),
         ];
         let config = AppConfig::load(None, args).unwrap();
         assert_eq!(config.qbittorrent_host, "myhost");
@@ -319,7 +796,28 @@ mod tests {
         assert_eq!(config.log_filename, "test.log");
         assert_eq!(config.log_rotate, "never");
         assert_eq!(config.http_auth_token, Some("token123".into()));
+        assert_eq!(config.http_host, "127.0.0.1");
+        assert_eq!(config.http_port, 8443);
+        assert_eq!(config.http_tls_cert, Some("/etc/mcp/cert.pem".into()));
+        assert_eq!(config.http_tls_key, Some("/etc/mcp/key.pem".into()));
+        assert_eq!(config.http_unix_socket, None);
         assert_eq!(config.polling_interval_ms, 5000);
+        assert_eq!(config.max_concurrent_requests, 4);
+        assert!(config.require_confirmation);
+        assert_eq!(
+            config.toolsets,
+            Some(vec!["torrents".to_string(), "search".to_string()])
+        );
+        assert!(config.read_only);
+        assert_eq!(config.allowed_tools, None);
+        assert_eq!(config.denied_tools, Some(vec!["shutdown_app".to_string()]));
+        assert!(config.audit_log_enable);
+        assert_eq!(config.audit_log_dir, Some("/tmp/audit".into()));
+        assert_eq!(config.audit_log_filename, "audit.jsonl");
+        assert_eq!(config.undo_journal_file, Some("/tmp/journal.json".into()));
+        assert_eq!(config.undo_journal_size, 50);
+        assert!(config.humanize);
+        assert_eq!(config.humanize_time_zone, "+02:00");
     }
 
     #[test]
@@ -333,6 +831,7 @@ mod tests {
                     username: None,
                     password: None,
                     no_verify_ssl: None,
+                    ..Default::default()
                 },
                 QBitInstance {
                     name: "i2".into(),
@@ -341,6 +840,7 @@ mod tests {
                     username: None,
                     password: None,
                     no_verify_ssl: None,
+                    ..Default::default()
                 },
             ]),
             qbittorrent_host: "h".into(),
@@ -357,6 +857,7 @@ mod tests {
             log_rotate: "d".into(),
             http_auth_token: None,
             polling_interval_ms: 100,
+            ..Default::default()
         };
         let instances = config.get_instances();
         assert_eq!(instances.len(), 2);
@@ -0,0 +1,352 @@
+//! Human-friendly view of the qBittorrent models: IEC sizes, speeds per second, ISO 8601
+//! timestamps, percentages, `∞` for qBittorrent's "no ETA" sentinel and plain-English
+//! torrent states. Field names are kept, so `fields` projection works on either view.
+
+use crate::models::{Torrent, TorrentFile, TorrentProperties, TransferInfo};
+use anyhow::{Result, anyhow};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use time::format_description::well_known::Rfc3339;
+use time::{OffsetDateTime, UtcOffset};
+
+/// qBittorrent reports this ETA when a torrent is not expected to finish.
+pub const ETA_INFINITE: i64 = 8_640_000;
+
+/// Renders the models for people (and models) rather than programs.
+#[derive(Clone, Copy, Debug)]
+pub struct Humanizer {
+    offset: UtcOffset,
+}
+
+impl Default for Humanizer {
+    fn default() -> Self {
+        Self {
+            offset: UtcOffset::UTC,
+        }
+    }
+}
+
+impl Humanizer {
+    /// A humanizer showing timestamps in `time_zone`: `UTC`, `Z` or an offset such as
+    /// `+02:00` or `-0530`.
+    pub fn new(time_zone: &str) -> Result<Self> {
+        Ok(Self {
+            offset: parse_offset(time_zone)?,
+        })
+    }
+
+    pub fn timestamp(&self, secs: i64) -> String {
+        if secs <= 0 {
+            return "never".to_string();
+        }
+        OffsetDateTime::from_unix_timestamp(secs)
+            .ok()
+            .and_then(|t| t.to_offset(self.offset).format(&Rfc3339).ok())
+            .unwrap_or_else(|| secs.to_string())
+    }
+}
+
+fn parse_offset(time_zone: &str) -> Result<UtcOffset> {
+    let tz = time_zone.trim();
+    if tz.eq_ignore_ascii_case("utc") || tz == "Z" {
+        return Ok(UtcOffset::UTC);
+    }
+    let invalid = || {
+        anyhow!(
+            "Invalid time zone '{}': expected UTC or an offset such as +02:00",
+            time_zone
+        )
+    };
+    let tz = tz.strip_prefix("UTC").unwrap_or(tz);
+    let (sign, rest) = match tz.split_at_checked(1) {
+        Some(("+", rest)) => (1, rest),
+        Some(("-", rest)) => (-1, rest),
+        _ => return Err(invalid()),
+    };
+    let digits: String = rest.chars().filter(|c| *c != ':').collect();
+    let (hours, minutes) = match digits.len() {
+        1 | 2 => (digits.as_str(), "0"),
+        4 => digits.split_at(2),
+        _ => return Err(invalid()),
+    };
+    let hours: i8 = hours.parse().map_err(|_| invalid())?;
+    let minutes: i8 = minutes.parse().map_err(|_| invalid())?;
+    UtcOffset::from_hms(sign * hours, sign * minutes, 0).map_err(|_| invalid())
+}
+
+pub fn format_size(bytes: i64) -> String {
+    const UNITS: [&str; 6] = ["B", "KiB", "MiB", "GiB", "TiB", "PiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+pub fn format_speed(bytes_per_sec: i64) -> String {
+    format!("{}/s", format_size(bytes_per_sec))
+}
+
+/// Speed limits use 0 (and sometimes -1) for "no limit".
+pub fn format_limit(bytes_per_sec: i64) -> String {
+    if bytes_per_sec <= 0 {
+        "unlimited".to_string()
+    } else {
+        format_speed(bytes_per_sec)
+    }
+}
+
+/// E.g. "2d 3h 4m" or "45s"; `∞` for [`ETA_INFINITE`].
+pub fn format_duration(secs: i64) -> String {
+    if secs >= ETA_INFINITE {
+        return "∞".to_string();
+    }
+    if secs < 0 {
+        return "unknown".to_string();
+    }
+    let (days, hours, minutes) = (secs / 86400, secs % 86400 / 3600, secs % 3600 / 60);
+    let parts: Vec<String> = [(days, "d"), (hours, "h"), (minutes, "m")]
+        .into_iter()
+        .filter(|(n, _)| *n > 0)
+        .map(|(n, unit)| format!("{}{}", n, unit))
+        .collect();
+    if parts.is_empty() {
+        format!("{}s", secs)
+    } else {
+        parts.join(" ")
+    }
+}
+
+/// Progress as reported by qBittorrent (0.0 to 1.0), e.g. "50.0%".
+pub fn format_progress(progress: f64) -> String {
+    format!("{:.1}%", progress * 100.0)
+}
+
+/// What a qBittorrent torrent state means, e.g. "stalledUP" becomes
+/// "Seeding, but no peers are downloading (stalledUP)".
+pub fn describe_state(state: &str) -> String {
+    let description = match state {
+        "error" => "Stopped because of an error",
+        "missingFiles" => "Data files are missing",
+        "uploading" => "Seeding to peers",
+        "pausedUP" | "stoppedUP" => "Complete and paused",
+        "queuedUP" => "Complete, queued for seeding",
+        "stalledUP" => "Seeding, but no peers are downloading",
+        "checkingUP" => "Complete, checking data",
+        "forcedUP" => "Seeding, forced past the queue limits",
+        "allocating" => "Allocating disk space",
+        "downloading" => "Downloading",
+        "metaDL" => "Fetching metadata",
+        "forcedMetaDL" => "Fetching metadata, forced past the queue limits",
+        "pausedDL" | "stoppedDL" => "Incomplete and paused",
+        "queuedDL" => "Queued for download",
+        "stalledDL" => "Downloading, but no peers are sending data",
+        "checkingDL" => "Incomplete, checking data",
+        "forcedDL" => "Downloading, forced past the queue limits",
+        "checkingResumeData" => "Checking resume data on startup",
+        "moving" => "Moving data to a new location",
+        _ => return state.to_string(),
+    };
+    format!("{} ({})", description, state)
+}
+
+fn describe_file_priority(priority: i64) -> String {
+    match priority {
+        0 => "do not download".to_string(),
+        1 => "normal".to_string(),
+        6 => "high".to_string(),
+        7 => "maximum".to_string(),
+        other => other.to_string(),
+    }
+}
+
+/// A model that has a humanized view.
+pub trait Humanize {
+    fn humanize(&self, humanizer: &Humanizer) -> Value;
+}
+
+impl<T: Humanize> Humanize for [T] {
+    fn humanize(&self, humanizer: &Humanizer) -> Value {
+        Value::Array(self.iter().map(|item| item.humanize(humanizer)).collect())
+    }
+}
+
+impl<T: Humanize> Humanize for Vec<T> {
+    fn humanize(&self, humanizer: &Humanizer) -> Value {
+        self.as_slice().humanize(humanizer)
+    }
+}
+
+/// `value` as JSON, humanized when a humanizer is given.
+pub fn view<T: Humanize + Serialize + ?Sized>(value: &T, humanizer: Option<&Humanizer>) -> Value {
+    match humanizer {
+        Some(humanizer) => value.humanize(humanizer),
+        None => serde_json::to_value(value).unwrap_or(Value::Null),
+    }
+}
+
+/// Which fields of a model hold what kind of value.
+#[derive(Default)]
+struct Kinds<'a> {
+    sizes: &'a [&'a str],
+    speeds: &'a [&'a str],
+    limits: &'a [&'a str],
+    timestamps: &'a [&'a str],
+    durations: &'a [&'a str],
+    progress: &'a [&'a str],
+}
+
+fn humanize_fields<T: Serialize>(
+    model: &T,
+    humanizer: &Humanizer,
+    kinds: Kinds<'_>,
+) -> Map<String, Value> {
+    let Ok(Value::Object(mut map)) = serde_json::to_value(model) else {
+        return Map::new();
+    };
+    let mut apply = |keys: &[&str], f: &dyn Fn(&Value) -> Option<String>| {
+        for key in keys {
+            if let Some(value) = map.get_mut(*key)
+                && let Some(text) = f(value)
+            {
+                *value = Value::String(text);
+            }
+        }
+    };
+    apply(kinds.sizes, &|v| v.as_i64().map(format_size));
+    apply(kinds.speeds, &|v| v.as_i64().map(format_speed));
+    apply(kinds.limits, &|v| v.as_i64().map(format_limit));
+    apply(kinds.timestamps, &|v| {
+        v.as_i64().map(|t| humanizer.timestamp(t))
+    });
+    apply(kinds.durations, &|v| v.as_i64().map(format_duration));
+    apply(kinds.progress, &|v| v.as_f64().map(format_progress));
+    map
+}
+
+impl Humanize for Torrent {
+    fn humanize(&self, humanizer: &Humanizer) -> Value {
+        let mut map = humanize_fields(
+            self,
+            humanizer,
+            Kinds {
+                sizes: &["size"],
+                speeds: &["dlspeed", "upspeed"],
+                limits: &["dl_limit", "up_limit"],
+                timestamps: &["added_on", "completion_on"],
+                durations: &["eta"],
+                progress: &["progress"],
+            },
+        );
+        map.insert(
+            "state".to_string(),
+            Value::String(describe_state(&self.state)),
+        );
+        Value::Object(map)
+    }
+}
+
+impl Humanize for TorrentFile {
+    fn humanize(&self, humanizer: &Humanizer) -> Value {
+        let mut map = humanize_fields(
+            self,
+            humanizer,
+            Kinds {
+                sizes: &["size"],
+                progress: &["progress"],
+                ..Default::default()
+            },
+        );
+        map.insert(
+            "priority".to_string(),
+            Value::String(describe_file_priority(self.priority)),
+        );
+        Value::Object(map)
+    }
+}
+
+impl Humanize for TorrentProperties {
+    fn humanize(&self, humanizer: &Humanizer) -> Value {
+        Value::Object(humanize_fields(
+            self,
+            humanizer,
+            Kinds {
+                sizes: &[
+                    "piece_size",
+                    "total_wasted",
+                    "total_uploaded",
+                    "total_downloaded",
+                    "total_size",
+                ],
+                speeds: &["dl_speed_avg", "dl_speed", "up_speed_avg", "up_speed"],
+                limits: &["dl_limit", "up_limit"],
+                timestamps: &[
+                    "creation_date",
+                    "addition_date",
+                    "completion_date",
+                    "last_seen",
+                ],
+                durations: &["time_elapsed", "seeding_time", "eta", "reannounce"],
+                ..Default::default()
+            },
+        ))
+    }
+}
+
+impl Humanize for TransferInfo {
+    fn humanize(&self, humanizer: &Humanizer) -> Value {
+        Value::Object(humanize_fields(
+            self,
+            humanizer,
+            Kinds {
+                sizes: &["dl_info_data", "up_info_data"],
+                speeds: &["dl_info_speed", "up_info_speed"],
+                limits: &["dl_rate_limit", "up_rate_limit"],
+                ..Default::default()
+            },
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_formatting() {
+        assert_eq!(format_size(2_000_000_000), "1.9 GiB");
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_speed(1536), "1.5 KiB/s");
+        assert_eq!(format_limit(0), "unlimited");
+        assert_eq!(format_duration(ETA_INFINITE), "∞");
+        assert_eq!(format_duration(93_784), "1d 2h 3m");
+        assert_eq!(format_duration(45), "45s");
+        assert_eq!(format_progress(0.5), "50.0%");
+        assert_eq!(
+            describe_state("stalledUP"),
+            "Seeding, but no peers are downloading (stalledUP)"
+        );
+        assert_eq!(describe_state("somethingNew"), "somethingNew");
+    }
+
+    #[test]
+    fn test_time_zones() {
+        let utc = Humanizer::default();
+        assert_eq!(utc.timestamp(1_700_000_000), "2023-11-14T22:13:20Z");
+        assert_eq!(utc.timestamp(-1), "never");
+        let plus_two = Humanizer::new("+02:00").unwrap();
+        assert_eq!(
+            plus_two.timestamp(1_700_000_000),
+            "2023-11-15T00:13:20+02:00"
+        );
+        let india = Humanizer::new("UTC+0530").unwrap();
+        assert_eq!(india.timestamp(1_700_000_000), "2023-11-15T03:43:20+05:30");
+        assert!(Humanizer::new("Europe/Berlin").is_err());
+    }
+}
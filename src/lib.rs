@@ -1,5 +1,8 @@
 pub mod app;
 pub mod client;
 pub mod config;
+pub mod humanize;
 pub mod models;
+pub mod reload;
+pub mod secret;
 pub mod server;
@@ -29,6 +29,17 @@ pub struct Torrent {
     pub tags: String,
     pub super_seeding: bool,
     pub force_start: bool,
+    // Per-torrent limits; not sent by every qBittorrent version
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ratio_limit: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seeding_time_limit: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub inactive_seeding_time_limit: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dl_limit: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub up_limit: Option<i64>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -231,6 +242,13 @@ pub struct SyncMainData {
     pub server_state: Option<serde_json::Value>,
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SyncTorrentPeers {
+    pub rid: i64,
+    pub full_update: Option<bool>,
+    pub peers: Option<std::collections::HashMap<String, serde_json::Value>>,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,267 @@
+//! Hot reload of the configuration. When the configuration file changes, or the process
+//! receives SIGHUP, the configuration is loaded again and applied to the running server:
+//! instances are added, removed or reconnected, and the log level, polling interval and
+//! tool policies take effect without dropping any session.
+
+use crate::app::{LogLevelSetter, build_instances, connection, register_secrets};
+use crate::config::AppConfig;
+use crate::server::instances::InstanceChanges;
+use crate::server::mcp::McpServer;
+use anyhow::{Result, bail};
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+use tokio::time::sleep;
+use tracing::{debug, error, info, warn};
+
+/// How often the configuration file is checked for changes.
+const WATCH_INTERVAL: Duration = Duration::from_secs(2);
+
+pub struct Reloader {
+    server: McpServer,
+    config: AppConfig,
+    set_log_level: LogLevelSetter,
+    watch_interval: Duration,
+}
+
+impl Reloader {
+    /// Reloads `config`, which `server` was started with, into `server`.
+    pub fn new(server: McpServer, config: AppConfig, set_log_level: LogLevelSetter) -> Self {
+        Self {
+            server,
+            config,
+            set_log_level,
+            watch_interval: WATCH_INTERVAL,
+        }
+    }
+
+    pub fn with_watch_interval(mut self, interval: Duration) -> Self {
+        self.watch_interval = interval;
+        self
+    }
+
+    /// The configuration that is currently applied.
+    pub fn config(&self) -> &AppConfig {
+        &self.config
+    }
+
+    /// Watches the configuration file and SIGHUP until the server shuts down.
+    pub async fn run(mut self) {
+        if self.config.source.is_none() {
+            debug!("The configuration was not loaded from a source; hot reload is off");
+            return;
+        }
+        let mut hangup = hangup_signal();
+        let mut last_seen = self.config.config_file().and_then(|f| fingerprint(&f));
+        while self.server.is_running() {
+            let reason = tokio::select! {
+                _ = sleep(self.watch_interval) => {
+                    let seen = self.config.config_file().and_then(|f| fingerprint(&f));
+                    if seen == last_seen {
+                        continue;
+                    }
+                    last_seen = seen;
+                    "the configuration file changed"
+                }
+                _ = hangup_received(&mut hangup) => "SIGHUP received",
+            };
+            if !self.server.is_running() {
+                break;
+            }
+            info!("Reloading the configuration: {}", reason);
+            if let Err(e) = self.reload().await {
+                error!(
+                    "Failed to reload the configuration, keeping the current one: {:#}",
+                    e
+                );
+            }
+        }
+    }
+
+    /// Loads the configuration again and applies it. On error nothing changes.
+    pub async fn reload(&mut self) -> Result<InstanceChanges> {
+        let config = self.config.reload()?;
+        self.apply(config).await
+    }
+
+    /// Applies `config` to the server and returns what changed about the instances.
+    pub async fn apply(&mut self, config: AppConfig) -> Result<InstanceChanges> {
+        let current = self.server.instances();
+        let previous = self.config.get_instances();
+        let mut changes = InstanceChanges::default();
+        let mut reuse = HashMap::new();
+        for inst in config.get_instances() {
+            if inst.host.trim().is_empty() {
+                continue;
+            }
+            let old = previous.iter().find(|old| old.name == inst.name);
+            match (old, current.client(&inst.name)) {
+                (Some(old), Some(client))
+                    if connection(old, &self.config) == connection(&inst, &config) =>
+                {
+                    reuse.insert(inst.name.clone(), client.clone());
+                }
+                (_, Some(_)) => changes.changed.push(inst.name.clone()),
+                (_, None) => changes.added.push(inst.name.clone()),
+            }
+        }
+
+        let instances = build_instances(&config, Some(&reuse));
+        if instances.is_empty() {
+            bail!("No qBittorrent instances configured");
+        }
+        changes.removed = current
+            .names()
+            .into_iter()
+            .filter(|name| instances.client(name).is_none())
+            .map(String::from)
+            .collect();
+        changes.policies_changed = !instances.same_policies(&current);
+
+        register_secrets(&config);
+        if config.log_level != self.config.log_level {
+            (self.set_log_level)(&config.log_level);
+            info!("Log level is now {}", config.log_level);
+        }
+        if config.polling_interval_ms != self.config.polling_interval_ms {
+            self.server.set_polling_interval(config.polling_interval_ms);
+            info!("Polling interval is now {} ms", config.polling_interval_ms);
+        }
+        for setting in restart_required(&self.config, &config) {
+            warn!(
+                "'{}' changed in the configuration; restart the server to apply it",
+                setting
+            );
+        }
+        if !changes.is_empty() {
+            self.server.apply_instances(instances, &changes).await;
+        }
+        self.config = config;
+        Ok(changes)
+    }
+}
+
+/// Settings that changed between `old` and `new` but only take effect on restart.
+fn restart_required(old: &AppConfig, new: &AppConfig) -> Vec<&'static str> {
+    let mut settings = Vec::new();
+    let mut check = |name, changed: bool| {
+        if changed {
+            settings.push(name);
+        }
+    };
+    check("server_mode", old.server_mode != new.server_mode);
+    check(
+        "http_host",
+        old.http_host != new.http_host
+            || old.http_port != new.http_port
+            || old.http_tls_cert != new.http_tls_cert
+            || old.http_tls_key != new.http_tls_key
+            || old.http_unix_socket != new.http_unix_socket,
+    );
+    check(
+        "http_auth_token",
+        old.http_auth_token != new.http_auth_token
+            || old.http_tokens != new.http_tokens
+            || old.http_allow_query_token != new.http_allow_query_token,
+    );
+    check(
+        "oauth_issuer",
+        old.oauth_issuer != new.oauth_issuer
+            || old.oauth_resource != new.oauth_resource
+            || old.oauth_audience != new.oauth_audience
+            || old.oauth_jwks_url != new.oauth_jwks_url
+            || old.oauth_jwks_file != new.oauth_jwks_file
+            || old.oauth_read_scope != new.oauth_read_scope
+            || old.oauth_write_scope != new.oauth_write_scope
+            || old.oauth_admin_scope != new.oauth_admin_scope,
+    );
+    check("lazy_mode", old.lazy_mode != new.lazy_mode);
+    check("toolsets", old.toolsets != new.toolsets);
+    check(
+        "max_concurrent_requests",
+        old.max_concurrent_requests != new.max_concurrent_requests,
+    );
+    check(
+        "require_confirmation",
+        old.require_confirmation != new.require_confirmation,
+    );
+    check(
+        "log_file_enable",
+        old.log_file_enable != new.log_file_enable
+            || old.log_dir != new.log_dir
+            || old.log_filename != new.log_filename
+            || old.log_rotate != new.log_rotate,
+    );
+    check(
+        "audit_log_enable",
+        old.audit_log_enable != new.audit_log_enable
+            || old.audit_log_dir != new.audit_log_dir
+            || old.audit_log_filename != new.audit_log_filename,
+    );
+    check(
+        "undo_journal_file",
+        old.undo_journal_file != new.undo_journal_file
+            || old.undo_journal_size != new.undo_journal_size,
+    );
+    check(
+        "humanize",
+        old.humanize != new.humanize || old.humanize_time_zone != new.humanize_time_zone,
+    );
+    settings
+}
+
+/// Changes when the file is written to, even twice within the mtime resolution.
+pub(crate) fn fingerprint(path: &Path) -> Option<(SystemTime, u64)> {
+    let metadata = std::fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+#[cfg(unix)]
+type Hangup = Option<tokio::signal::unix::Signal>;
+#[cfg(not(unix))]
+type Hangup = ();
+
+fn hangup_signal() -> Hangup {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{SignalKind, signal};
+        signal(SignalKind::hangup())
+            .inspect_err(|e| warn!("Cannot listen for SIGHUP: {}", e))
+            .ok()
+    }
+}
+
+/// Completes when SIGHUP is received; never, where there is no SIGHUP.
+#[cfg_attr(not(unix), allow(unused_variables))]
+async fn hangup_received(hangup: &mut Hangup) {
+    #[cfg(unix)]
+    if let Some(signal) = hangup
+        && signal.recv().await.is_some()
+    {
+        return;
+    }
+    std::future::pending::<()>().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_restart_required() {
+        let old = AppConfig::default();
+        let new = AppConfig {
+            log_level: "debug".into(),
+            polling_interval_ms: 500,
+            server_mode: "http".into(),
+            humanize: true,
+            ..Default::default()
+        };
+        // Log level and polling interval are applied live
+        assert_eq!(
+            restart_required(&old, &new),
+            vec!["server_mode", "humanize"]
+        );
+        assert!(restart_required(&new, &new).is_empty());
+    }
+}
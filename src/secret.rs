@@ -0,0 +1,195 @@
+//! Credentials in the configuration. A [`Secret`] never prints its value, and secrets can
+//! come from a file, the output of a command or an environment variable instead of sitting
+//! in the configuration in plaintext.
+
+use anyhow::{Context, Result, anyhow, bail};
+use serde::{Deserialize, Serialize, Serializer};
+use std::fmt;
+use std::process::Command;
+
+const REDACTED: &str = "[REDACTED]";
+
+/// A password or token. `Debug`, `Display` and `Serialize` print `[REDACTED]`; the value is
+/// only available through [`Secret::expose`].
+#[derive(Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(transparent)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn new(value: impl Into<String>) -> Self {
+        Self(value.into())
+    }
+
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl From<&str> for Secret {
+    fn from(value: &str) -> Self {
+        Self::new(value)
+    }
+}
+
+impl From<String> for Secret {
+    fn from(value: String) -> Self {
+        Self(value)
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Secret({})", REDACTED)
+    }
+}
+
+impl fmt::Display for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+impl Serialize for Secret {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(REDACTED)
+    }
+}
+
+/// Resolves a secret given inline, as a file or as a command; at most one may be set. An
+/// inline value has `${VAR}` references replaced by environment variables.
+pub fn resolve(
+    name: &str,
+    value: Option<&Secret>,
+    file: Option<&str>,
+    command: Option<&str>,
+) -> Result<Option<Secret>> {
+    let given = [value.is_some(), file.is_some(), command.is_some()];
+    if given.iter().filter(|g| **g).count() > 1 {
+        bail!(
+            "Only one of '{0}', '{0}_file' and '{0}_command' may be set",
+            name
+        );
+    }
+    if let Some(value) = value {
+        let value = interpolate(value.expose()).with_context(|| format!("In '{}'", name))?;
+        return Ok(Some(Secret(value)));
+    }
+    if let Some(file) = file {
+        let content = std::fs::read_to_string(file)
+            .with_context(|| format!("Failed to read '{}_file' {}", name, file))?;
+        return Ok(Some(Secret(trim_newline(content))));
+    }
+    if let Some(command) = command {
+        return run(command)
+            .with_context(|| format!("'{}_command' failed", name))
+            .map(Some);
+    }
+    Ok(None)
+}
+
+/// Replaces `${VAR}` with the environment variable `VAR`; `$${` is a literal `${`.
+pub fn interpolate(value: &str) -> Result<String> {
+    let mut result = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(start) = rest.find('$') {
+        result.push_str(&rest[..start]);
+        rest = &rest[start..];
+        if let Some(after) = rest.strip_prefix("$${") {
+            result.push_str("${");
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix("${") {
+            let end = after
+                .find('}')
+                .ok_or_else(|| anyhow!("Unterminated '${{' in secret"))?;
+            let var = &after[..end];
+            let resolved = std::env::var(var)
+                .map_err(|_| anyhow!("Environment variable {} is not set", var))?;
+            result.push_str(&resolved);
+            rest = &after[end + 1..];
+        } else {
+            result.push('$');
+            rest = &rest[1..];
+        }
+    }
+    result.push_str(rest);
+    Ok(result)
+}
+
+/// Runs `command` through the shell and returns its output, e.g. from a password manager.
+fn run(command: &str) -> Result<Secret> {
+    #[cfg(windows)]
+    let output = Command::new("cmd").args(["/C", command]).output();
+    #[cfg(not(windows))]
+    let output = Command::new("sh").args(["-c", command]).output();
+    let output = output.with_context(|| format!("Failed to run {}", command))?;
+    if !output.status.success() {
+        bail!(
+            "{} exited with {}: {}",
+            command,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    let stdout = String::from_utf8(output.stdout).context("Output is not valid UTF-8")?;
+    Ok(Secret(trim_newline(stdout)))
+}
+
+/// Files and commands usually end their secret with a newline, which is not part of it.
+fn trim_newline(mut value: String) -> String {
+    let len = value.trim_end_matches(['\r', '\n']).len();
+    value.truncate(len);
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_secret_is_never_printed() {
+        let secret = Secret::from("hunter2");
+        assert_eq!(format!("{:?}", secret), "Secret([REDACTED])");
+        assert_eq!(format!("{:?}", Some(&secret)), "Some(Secret([REDACTED]))");
+        assert_eq!(secret.to_string(), "[REDACTED]");
+        assert_eq!(serde_json::to_string(&secret).unwrap(), "\"[REDACTED]\"");
+        assert_eq!(secret.expose(), "hunter2");
+    }
+
+    #[test]
+    fn test_interpolate() {
+        // SAFETY: no other test reads or writes this variable
+        unsafe { std::env::set_var("QBT_SECRET_TEST_PASSWORD", "s3cret") };
+        assert_eq!(
+            interpolate("pre-${QBT_SECRET_TEST_PASSWORD}-post").unwrap(),
+            "pre-s3cret-post"
+        );
+        assert_eq!(interpolate("a$b$${c}").unwrap(), "a$b${c}");
+        assert!(interpolate("${QBT_SECRET_TEST_UNSET}").is_err());
+        assert!(interpolate("${UNTERMINATED").is_err());
+    }
+
+    #[test]
+    fn test_resolve() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("password");
+        std::fs::write(&file, "from-file\n").unwrap();
+        let file = file.to_str().unwrap();
+
+        let resolved = resolve("password", None, Some(file), None).unwrap();
+        assert_eq!(resolved.unwrap().expose(), "from-file");
+        let resolved = resolve("password", None, None, Some("echo from-command")).unwrap();
+        assert_eq!(resolved.unwrap().expose(), "from-command");
+        assert!(resolve("password", None, None, Some("exit 3")).is_err());
+        assert!(
+            resolve("password", Some(&"inline".into()), Some(file), None)
+                .unwrap_err()
+                .to_string()
+                .contains("Only one of")
+        );
+        assert_eq!(resolve("password", None, None, None).unwrap(), None);
+    }
+}
@@ -0,0 +1,238 @@
+//! Append-only JSONL record of every call to a tool that can change qBittorrent: who
+//! called it, on which instance, with what arguments, and how it ended.
+
+use crate::models::Torrent;
+use crate::server::logging;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use time::OffsetDateTime;
+use time::format_description::well_known::Rfc3339;
+use tracing::error;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+
+/// A torrent a tool call acted on.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct AuditTorrent {
+    pub hash: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
+impl From<&Torrent> for AuditTorrent {
+    fn from(t: &Torrent) -> Self {
+        Self {
+            hash: t.hash.clone(),
+            name: Some(t.name.clone()),
+        }
+    }
+}
+
+/// One line of the audit log.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct AuditEntry {
+    /// RFC 3339, UTC.
+    pub timestamp: String,
+    /// `stdio`, `http`, or `direct` for calls made without a client connection.
+    pub transport: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+    /// Who authenticated the HTTP session.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub identity: Option<String>,
+    pub instance: String,
+    pub tool: String,
+    /// The call's arguments with credentials replaced by `[REDACTED]`.
+    pub arguments: Value,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub torrents: Vec<AuditTorrent>,
+    /// `success`, `error`, `denied`, `cancelled`, `not_confirmed` or `dry_run`.
+    pub outcome: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Which entries `get_audit_log` returns.
+#[derive(Default)]
+pub struct AuditFilter {
+    pub since: Option<OffsetDateTime>,
+    pub until: Option<OffsetDateTime>,
+    pub tool: Option<String>,
+    pub instance: Option<String>,
+}
+
+impl AuditFilter {
+    fn matches(&self, entry: &AuditEntry) -> bool {
+        if self.tool.as_ref().is_some_and(|t| *t != entry.tool) {
+            return false;
+        }
+        if self.instance.as_ref().is_some_and(|i| *i != entry.instance) {
+            return false;
+        }
+        if self.since.is_none() && self.until.is_none() {
+            return true;
+        }
+        let Ok(at) = OffsetDateTime::parse(&entry.timestamp, &Rfc3339) else {
+            return false;
+        };
+        self.since.is_none_or(|since| at >= since) && self.until.is_none_or(|until| at <= until)
+    }
+}
+
+/// What a tool reported about its own call, for the audit entry.
+#[derive(Default)]
+pub(crate) struct AuditNotes {
+    pub(crate) torrents: Mutex<Vec<AuditTorrent>>,
+    pub(crate) outcome: Mutex<Option<&'static str>>,
+}
+
+/// The audit log files: `<filename>` plus the rotated `<filename>.<date>` files in `dir`.
+pub struct AuditLog {
+    dir: PathBuf,
+    filename: String,
+    writer: Mutex<RollingFileAppender>,
+}
+
+impl AuditLog {
+    pub fn new(dir: impl Into<PathBuf>, filename: &str, rotation: Rotation) -> Result<Self> {
+        let dir = dir.into();
+        let writer = RollingFileAppender::builder()
+            .rotation(rotation)
+            .filename_prefix(filename)
+            .build(&dir)
+            .context("Failed to create audit log appender")?;
+        Ok(Self {
+            dir,
+            filename: filename.to_string(),
+            writer: Mutex::new(writer),
+        })
+    }
+
+    /// Appends `entry`. Failures are logged rather than failing the tool call.
+    pub fn record(&self, entry: &AuditEntry) {
+        let result = serde_json::to_string(entry)
+            .map_err(anyhow::Error::from)
+            .and_then(|line| {
+                let mut writer = self.writer.lock().unwrap();
+                writeln!(writer, "{}", line)?;
+                writer.flush()?;
+                Ok(())
+            });
+        if let Err(e) = result {
+            error!("Failed to write audit log entry: {}", e);
+        }
+    }
+
+    /// The matching entries, oldest first, at most `limit` of the most recent ones.
+    pub fn read(&self, filter: &AuditFilter, limit: usize) -> Result<Vec<AuditEntry>> {
+        self.read_where(filter, limit, |_| true)
+    }
+
+    /// Like [`read`](Self::read), but also leaves out entries for which `keep` is false.
+    pub fn read_where(
+        &self,
+        filter: &AuditFilter,
+        limit: usize,
+        keep: impl Fn(&AuditEntry) -> bool,
+    ) -> Result<Vec<AuditEntry>> {
+        let rotated_prefix = format!("{}.", self.filename);
+        let mut files: Vec<PathBuf> = fs::read_dir(&self.dir)?
+            .filter_map(|e| e.ok())
+            .filter(|e| {
+                let name = e.file_name();
+                let name = name.to_string_lossy();
+                name == self.filename.as_str() || name.starts_with(&rotated_prefix)
+            })
+            .map(|e| e.path())
+            .collect();
+        // Rotated files carry their date in the name, so this is chronological
+        files.sort();
+
+        let mut entries = Vec::new();
+        for file in files {
+            let reader = BufReader::new(fs::File::open(&file)?);
+            for line in reader.lines() {
+                let line = line?;
+                match serde_json::from_str::<AuditEntry>(&line) {
+                    Ok(entry) if filter.matches(&entry) && keep(&entry) => entries.push(entry),
+                    Ok(_) => {}
+                    Err(_) if line.trim().is_empty() => {}
+                    Err(e) => error!("Skipping malformed audit log line in {:?}: {}", file, e),
+                }
+            }
+        }
+        let skip = entries.len().saturating_sub(limit);
+        Ok(entries.split_off(skip))
+    }
+}
+
+pub fn now_rfc3339() -> String {
+    OffsetDateTime::now_utc()
+        .format(&Rfc3339)
+        .unwrap_or_default()
+}
+
+/// Copy of tool arguments that is safe to write to disk: values of credential-like keys
+/// are replaced, and strings holding JSON objects (e.g. `set_app_preferences`) are
+/// scrubbed the same way.
+pub fn redact_arguments(args: &Value) -> Value {
+    match args {
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .map(|(key, value)| {
+                    let value = if is_secret_key(key) {
+                        Value::String("[REDACTED]".to_string())
+                    } else {
+                        redact_arguments(value)
+                    };
+                    (key.clone(), value)
+                })
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(items.iter().map(redact_arguments).collect()),
+        Value::String(s) => match serde_json::from_str::<Value>(s) {
+            Ok(nested @ Value::Object(_)) => Value::String(redact_arguments(&nested).to_string()),
+            _ => Value::String(logging::redact_secrets(s)),
+        },
+        other => other.clone(),
+    }
+}
+
+fn is_secret_key(key: &str) -> bool {
+    let key = key.to_ascii_lowercase();
+    [
+        "password", "passwd", "token", "secret", "api_key", "apikey", "cookie",
+    ]
+    .iter()
+    .any(|s| key.contains(s))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_redact_arguments() {
+        let args = json!({
+            "preferences": "{\"web_ui_password\":\"hunter2\",\"dl_limit\":5}",
+            "url": "http://user:pw@tracker.example/announce",
+            "hash": "abc"
+        });
+        let redacted = redact_arguments(&args);
+        assert_eq!(redacted["hash"], "abc");
+        assert_eq!(
+            redacted["url"],
+            "http://[REDACTED]@tracker.example/announce"
+        );
+        let prefs: Value = serde_json::from_str(redacted["preferences"].as_str().unwrap()).unwrap();
+        assert_eq!(
+            prefs,
+            json!({ "web_ui_password": "[REDACTED]", "dl_limit": 5 })
+        );
+    }
+}
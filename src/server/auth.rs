@@ -0,0 +1,332 @@
+//! Bearer tokens for the HTTP transport. Each token has a name, which identifies the client
+//! in logs and the audit log, and a [`Grant`]: the scopes it holds, the instances it may
+//! use and when it expires. Besides configured tokens, JWTs from an OAuth authorization
+//! server are accepted when [`OAuthVerifier`] is set up.
+
+use crate::secret::Secret;
+use crate::server::oauth::OAuthVerifier;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::sync::Arc;
+use time::OffsetDateTime;
+
+/// Name of the grant for the shared `http_auth_token`, which may do everything.
+pub const SHARED_TOKEN_IDENTITY: &str = "http_auth_token";
+
+/// Tools that control qBittorrent itself rather than torrents, or reveal what other
+/// clients did; they need the admin scope.
+pub const ADMIN_TOOLS: &[&str] = &[
+    "set_app_preferences",
+    "shutdown_app",
+    "install_search_plugin",
+    "uninstall_search_plugin",
+    "enable_search_plugin",
+    "update_search_plugins",
+    "get_audit_log",
+    // The undo journal holds every client's changes, like the audit log
+    "list_recent_actions",
+];
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Scope {
+    /// Read-only tools and resources.
+    Read,
+    /// Tools that change torrents, categories, tags, RSS and transfer settings.
+    Write,
+    /// The tools in [`ADMIN_TOOLS`].
+    Admin,
+}
+
+impl Scope {
+    /// The scope needed to call `tool`.
+    pub fn required_for(tool: &str, read_only_tool: bool) -> Self {
+        if ADMIN_TOOLS.contains(&tool) {
+            Self::Admin
+        } else if read_only_tool {
+            Self::Read
+        } else {
+            Self::Write
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Read => "read",
+            Self::Write => "write",
+            Self::Admin => "admin",
+        }
+    }
+}
+
+/// What the holder of a token may do.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Grant {
+    pub name: String,
+    pub scopes: BTreeSet<Scope>,
+    /// When set, only these instances may be used.
+    pub instances: Option<BTreeSet<String>>,
+    pub expires: Option<OffsetDateTime>,
+}
+
+impl Grant {
+    /// A grant with every scope on every instance, that never expires.
+    pub fn unrestricted(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            scopes: [Scope::Read, Scope::Write, Scope::Admin].into(),
+            instances: None,
+            expires: None,
+        }
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires
+            .is_some_and(|expires| expires <= OffsetDateTime::now_utc())
+    }
+
+    pub fn allows_instance(&self, instance: &str) -> bool {
+        self.instances
+            .as_ref()
+            .is_none_or(|allowed| allowed.contains(instance))
+    }
+
+    /// True if resources of `instance` may be read.
+    pub fn can_read(&self, instance: &str) -> bool {
+        self.denial_to_read(instance).is_none()
+    }
+
+    /// Why this grant forbids reading resources of `instance`, or `None` if it may.
+    pub fn denial_to_read(&self, instance: &str) -> Option<&'static str> {
+        self.denial_for(Scope::Read, instance)
+    }
+
+    /// Why this grant forbids calling `tool` on `instance`, or `None` if it may.
+    pub fn denial(&self, tool: &str, instance: &str, read_only_tool: bool) -> Option<&'static str> {
+        self.denial_for(Scope::required_for(tool, read_only_tool), instance)
+    }
+
+    fn denial_for(&self, scope: Scope, instance: &str) -> Option<&'static str> {
+        if self.is_expired() {
+            Some("the token has expired")
+        } else if !self.allows_instance(instance) {
+            Some("the token is not allowed on this instance")
+        } else if !self.scopes.contains(&scope) {
+            Some(match scope {
+                Scope::Read => "the token lacks the read scope",
+                Scope::Write => "the token lacks the write scope",
+                Scope::Admin => "the token lacks the admin scope",
+            })
+        } else {
+            None
+        }
+    }
+}
+
+/// Why a bearer token was rejected.
+#[derive(Debug, PartialEq)]
+pub enum AuthFailure {
+    Missing,
+    Unknown,
+    /// The token is known, but expired; carries its name.
+    Expired(String),
+    /// An OAuth token that failed validation, and why.
+    Invalid(String),
+    /// A valid OAuth token without any scope of this server; carries its name.
+    InsufficientScope(String),
+}
+
+/// The tokens the HTTP transport accepts. Without any, requests are not authenticated.
+#[derive(Default, Clone)]
+pub struct HttpAuth {
+    tokens: Vec<(Secret, Arc<Grant>)>,
+    allow_query_token: bool,
+    oauth: Option<Arc<OAuthVerifier>>,
+}
+
+impl HttpAuth {
+    /// Accepts only `token`, which may do everything.
+    pub fn shared(token: impl Into<Secret>) -> Self {
+        Self::default().with_token(token, Grant::unrestricted(SHARED_TOKEN_IDENTITY))
+    }
+
+    pub fn with_token(mut self, token: impl Into<Secret>, grant: Grant) -> Self {
+        self.tokens.push((token.into(), Arc::new(grant)));
+        self
+    }
+
+    /// Also accepts the token in a `?token=` query parameter. Off by default, since query
+    /// strings end up in access logs.
+    pub fn with_query_token(mut self, allow: bool) -> Self {
+        self.allow_query_token = allow;
+        self
+    }
+
+    /// Also accepts JWTs that `verifier` validates.
+    pub fn with_oauth(mut self, verifier: OAuthVerifier) -> Self {
+        self.oauth = Some(Arc::new(verifier));
+        self
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.tokens.is_empty() || self.oauth.is_some()
+    }
+
+    pub fn allows_query_token(&self) -> bool {
+        self.allow_query_token
+    }
+
+    pub fn oauth(&self) -> Option<&OAuthVerifier> {
+        self.oauth.as_deref()
+    }
+
+    /// Checks `token` against the configured tokens, then as an OAuth JWT.
+    pub async fn authenticate(&self, token: Option<&str>) -> Result<Arc<Grant>, AuthFailure> {
+        match (self.authenticate_static(token), &self.oauth, token) {
+            (Err(AuthFailure::Unknown), Some(oauth), Some(token)) => {
+                let grant = oauth.verify(token).await?;
+                Ok(Arc::new(grant))
+            }
+            (result, _, _) => result,
+        }
+    }
+
+    /// The `WWW-Authenticate` header for a request rejected with `failure` (RFC 6750),
+    /// pointing OAuth clients to the resource metadata.
+    pub fn challenge(&self, failure: &AuthFailure) -> String {
+        let mut params = Vec::new();
+        if let Some(oauth) = &self.oauth {
+            params.push(format!("resource_metadata=\"{}\"", oauth.metadata_url()));
+        }
+        match failure {
+            AuthFailure::Missing => {}
+            AuthFailure::Unknown => params.push("error=\"invalid_token\"".to_string()),
+            AuthFailure::Expired(_) => params.push(
+                "error=\"invalid_token\", error_description=\"The token has expired\"".to_string(),
+            ),
+            AuthFailure::Invalid(reason) => params.push(format!(
+                "error=\"invalid_token\", error_description=\"{}\"",
+                reason.replace(['"', '\\'], "'")
+            )),
+            AuthFailure::InsufficientScope(_) => {
+                params.push("error=\"insufficient_scope\"".to_string());
+                if let Some(oauth) = &self.oauth {
+                    params.push(format!("scope=\"{}\"", oauth.scopes_supported().join(" ")));
+                }
+            }
+        }
+        if params.is_empty() {
+            "Bearer".to_string()
+        } else {
+            format!("Bearer {}", params.join(", "))
+        }
+    }
+
+    fn authenticate_static(&self, token: Option<&str>) -> Result<Arc<Grant>, AuthFailure> {
+        let token = token.ok_or(AuthFailure::Missing)?;
+        // Compare against every token so the time taken does not tell which one matched
+        let mut found = None;
+        for (secret, grant) in &self.tokens {
+            if constant_time_eq(secret.expose().as_bytes(), token.as_bytes()) {
+                found = Some(grant);
+            }
+        }
+        let grant = found.ok_or(AuthFailure::Unknown)?;
+        if grant.is_expired() {
+            return Err(AuthFailure::Expired(grant.name.clone()));
+        }
+        Ok(grant.clone())
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::Duration;
+
+    fn grant(scopes: &[Scope], instances: Option<&[&str]>) -> Grant {
+        Grant {
+            name: "agent".into(),
+            scopes: scopes.iter().copied().collect(),
+            instances: instances.map(|i| i.iter().map(|s| s.to_string()).collect()),
+            expires: None,
+        }
+    }
+
+    #[test]
+    fn test_denial() {
+        let reader = grant(&[Scope::Read], Some(&["nas"]));
+        assert_eq!(reader.denial("list_torrents", "nas", true), None);
+        assert_eq!(
+            reader.denial("delete_torrent", "nas", false),
+            Some("the token lacks the write scope")
+        );
+        assert_eq!(
+            reader.denial("list_torrents", "seedbox", true),
+            Some("the token is not allowed on this instance")
+        );
+        assert!(reader.can_read("nas") && !reader.can_read("seedbox"));
+
+        let writer = grant(&[Scope::Read, Scope::Write], None);
+        assert_eq!(writer.denial("delete_torrent", "seedbox", false), None);
+        assert_eq!(
+            writer.denial("shutdown_app", "nas", false),
+            Some("the token lacks the admin scope")
+        );
+        assert_eq!(
+            writer.denial("get_audit_log", "nas", true),
+            Some("the token lacks the admin scope")
+        );
+
+        let expired = Grant {
+            expires: Some(OffsetDateTime::now_utc() - Duration::minutes(1)),
+            ..Grant::unrestricted("old")
+        };
+        assert_eq!(
+            expired.denial("list_torrents", "nas", true),
+            Some("the token has expired")
+        );
+    }
+
+    #[test]
+    fn test_authenticate() {
+        let auth = HttpAuth::default()
+            .with_token("r-token", grant(&[Scope::Read], None))
+            .with_token(
+                "old-token",
+                Grant {
+                    expires: Some(OffsetDateTime::now_utc() - Duration::days(1)),
+                    ..Grant::unrestricted("old")
+                },
+            );
+        assert!(auth.is_enabled());
+        assert_eq!(
+            auth.authenticate_static(Some("r-token")).unwrap().name,
+            "agent"
+        );
+        assert_eq!(
+            auth.authenticate_static(Some("r-toke")),
+            Err(AuthFailure::Unknown)
+        );
+        assert_eq!(auth.authenticate_static(None), Err(AuthFailure::Missing));
+        assert_eq!(
+            auth.authenticate_static(Some("old-token")),
+            Err(AuthFailure::Expired("old".into()))
+        );
+        assert!(!HttpAuth::default().is_enabled());
+    }
+
+    #[test]
+    fn test_challenge() {
+        let auth = HttpAuth::shared("token");
+        assert_eq!(auth.challenge(&AuthFailure::Missing), "Bearer");
+        assert_eq!(
+            auth.challenge(&AuthFailure::Invalid("bad \"sig\"".into())),
+            "Bearer error=\"invalid_token\", error_description=\"bad 'sig'\""
+        );
+    }
+}
@@ -0,0 +1,290 @@
+//! Health of each configured instance: whether it answers, how fast, which Web API version
+//! it speaks and whether we are logged in. The event loop's polls and failed tool calls
+//! feed it, and instances whose login failed are retried with exponential backoff.
+
+use crate::server::audit::now_rfc3339;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// URI of the resource with the health of every instance.
+pub const STATUS_URI: &str = "qbittorrent://instances/status";
+
+/// Wait before the first login retry; doubled after every failed attempt.
+const LOGIN_BACKOFF_START: Duration = Duration::from_secs(1);
+const LOGIN_BACKOFF_MAX: Duration = Duration::from_secs(300);
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    /// Not polled yet.
+    Unknown,
+    Up,
+    Down,
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LoginState {
+    /// The instance has no credentials configured.
+    NotRequired,
+    /// Credentials are configured, but no login was attempted yet, or the session has to
+    /// be renewed.
+    Pending,
+    LoggedIn,
+    Failed,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct InstanceHealth {
+    pub instance: String,
+    pub url: String,
+    pub status: Status,
+    pub login: LoginState,
+    pub api_version: Option<String>,
+    /// Round trip of the last successful poll.
+    pub latency_ms: Option<u64>,
+    pub last_success: Option<String>,
+    pub last_failure: Option<String>,
+    pub last_error: Option<String>,
+    pub consecutive_failures: u32,
+    /// Seconds until the next login attempt, while the login is being retried.
+    pub next_login_in_seconds: Option<u64>,
+    #[serde(skip)]
+    login_failures: u32,
+    #[serde(skip)]
+    next_login: Option<Instant>,
+}
+
+impl InstanceHealth {
+    fn new(instance: &str, url: &str, requires_login: bool) -> Self {
+        Self {
+            instance: instance.to_string(),
+            url: url.to_string(),
+            status: Status::Unknown,
+            login: if requires_login {
+                LoginState::Pending
+            } else {
+                LoginState::NotRequired
+            },
+            api_version: None,
+            latency_ms: None,
+            last_success: None,
+            last_failure: None,
+            last_error: None,
+            consecutive_failures: 0,
+            next_login_in_seconds: None,
+            login_failures: 0,
+            next_login: None,
+        }
+    }
+}
+
+/// A change of an instance's [`Status`] between up and down.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transition {
+    pub instance: String,
+    pub status: Status,
+    pub error: Option<String>,
+}
+
+#[derive(Default)]
+pub struct HealthMonitor {
+    instances: Mutex<BTreeMap<String, InstanceHealth>>,
+}
+
+impl HealthMonitor {
+    /// Starts tracking an instance, at `url`, as not polled yet.
+    pub fn add(&self, instance: &str, url: &str, requires_login: bool) {
+        self.instances.lock().unwrap().insert(
+            instance.to_string(),
+            InstanceHealth::new(instance, url, requires_login),
+        );
+    }
+
+    /// Stops tracking an instance that is no longer configured.
+    pub fn remove(&self, instance: &str) {
+        self.instances.lock().unwrap().remove(instance);
+    }
+
+    /// The health of every instance, in name order.
+    pub fn snapshot(&self) -> Vec<InstanceHealth> {
+        let now = Instant::now();
+        self.instances
+            .lock()
+            .unwrap()
+            .values()
+            .map(|h| InstanceHealth {
+                next_login_in_seconds: h
+                    .next_login
+                    .map(|at| at.saturating_duration_since(now).as_secs()),
+                ..h.clone()
+            })
+            .collect()
+    }
+
+    pub fn get(&self, instance: &str) -> Option<InstanceHealth> {
+        self.instances.lock().unwrap().get(instance).cloned()
+    }
+
+    /// Records a successful request. Returns the transition if the instance was down.
+    pub fn record_success(&self, instance: &str, latency: Option<Duration>) -> Option<Transition> {
+        let mut instances = self.instances.lock().unwrap();
+        let health = instances.get_mut(instance)?;
+        let was = health.status;
+        health.status = Status::Up;
+        health.consecutive_failures = 0;
+        health.last_success = Some(now_rfc3339());
+        if let Some(latency) = latency {
+            health.latency_ms = Some(latency.as_millis() as u64);
+        }
+        (was == Status::Down).then(|| Transition {
+            instance: instance.to_string(),
+            status: Status::Up,
+            error: None,
+        })
+    }
+
+    /// Records a failed request. Returns the transition if the instance was up or had not
+    /// been polled yet.
+    pub fn record_failure(&self, instance: &str, error: &anyhow::Error) -> Option<Transition> {
+        let mut instances = self.instances.lock().unwrap();
+        let health = instances.get_mut(instance)?;
+        let was = health.status;
+        let error = format!("{:#}", error);
+        health.status = Status::Down;
+        health.consecutive_failures += 1;
+        health.last_failure = Some(now_rfc3339());
+        health.last_error = Some(error.clone());
+        (was != Status::Down).then(|| Transition {
+            instance: instance.to_string(),
+            status: Status::Down,
+            error: Some(error),
+        })
+    }
+
+    pub fn set_api_version(&self, instance: &str, version: String) {
+        if let Some(health) = self.instances.lock().unwrap().get_mut(instance) {
+            health.api_version = Some(version);
+        }
+    }
+
+    /// Records the outcome of a login. A failure schedules the next attempt.
+    pub fn record_login(&self, instance: &str, result: &anyhow::Result<()>) {
+        let mut instances = self.instances.lock().unwrap();
+        let Some(health) = instances.get_mut(instance) else {
+            return;
+        };
+        match result {
+            Ok(()) => {
+                health.login = LoginState::LoggedIn;
+                health.login_failures = 0;
+                health.next_login = None;
+            }
+            Err(_) => {
+                health.login = LoginState::Failed;
+                health.login_failures += 1;
+                health.next_login = Some(Instant::now() + login_backoff(health.login_failures));
+            }
+        }
+    }
+
+    /// Marks the session of an instance that requires login as possibly lost, e.g. after
+    /// qBittorrent restarted, so that the event loop logs in again.
+    pub fn expire_login(&self, instance: &str) {
+        if let Some(health) = self.instances.lock().unwrap().get_mut(instance)
+            && health.login == LoginState::LoggedIn
+        {
+            health.login = LoginState::Pending;
+        }
+    }
+
+    /// True if the instance requires a login that has not succeeded (yet).
+    pub fn needs_login(&self, instance: &str) -> bool {
+        self.instances
+            .lock()
+            .unwrap()
+            .get(instance)
+            .is_some_and(|h| matches!(h.login, LoginState::Pending | LoginState::Failed))
+    }
+
+    /// True if a login is needed and its backoff has passed.
+    pub fn login_due(&self, instance: &str) -> bool {
+        self.instances
+            .lock()
+            .unwrap()
+            .get(instance)
+            .is_some_and(|h| {
+                matches!(h.login, LoginState::Pending | LoginState::Failed)
+                    && h.next_login.is_none_or(|at| at <= Instant::now())
+            })
+    }
+}
+
+/// Wait after the `failures`-th failed login in a row: 1s, 2s, 4s, ... up to 5 minutes.
+fn login_backoff(failures: u32) -> Duration {
+    LOGIN_BACKOFF_START
+        .saturating_mul(2u32.saturating_pow(failures.saturating_sub(1)))
+        .min(LOGIN_BACKOFF_MAX)
+}
+
+/// True if `err` means the instance could not be reached at all, as opposed to an error
+/// reported by qBittorrent.
+pub fn is_unreachable(err: &anyhow::Error) -> bool {
+    err.chain().any(|cause| {
+        cause
+            .downcast_ref::<reqwest::Error>()
+            .is_some_and(|e| e.is_connect() || e.is_timeout())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+
+    #[test]
+    fn test_transitions() {
+        let monitor = HealthMonitor::default();
+        monitor.add("nas", "http://nas:8080", false);
+
+        let down = monitor.record_failure("nas", &anyhow!("connection refused"));
+        assert_eq!(down.unwrap().status, Status::Down);
+        // Still down: no new transition
+        assert!(monitor.record_failure("nas", &anyhow!("again")).is_none());
+        assert_eq!(monitor.get("nas").unwrap().consecutive_failures, 2);
+
+        let up = monitor.record_success("nas", Some(Duration::from_millis(12)));
+        assert_eq!(up.unwrap().status, Status::Up);
+        let health = monitor.get("nas").unwrap();
+        assert_eq!(health.consecutive_failures, 0);
+        assert_eq!(health.latency_ms, Some(12));
+        assert_eq!(health.login, LoginState::NotRequired);
+
+        // The first successful poll is not a transition
+        monitor.add("seedbox", "http://seedbox", true);
+        assert!(monitor.record_success("seedbox", None).is_none());
+        assert!(monitor.record_success("unknown", None).is_none());
+    }
+
+    #[test]
+    fn test_login_backoff() {
+        assert_eq!(login_backoff(1), Duration::from_secs(1));
+        assert_eq!(login_backoff(4), Duration::from_secs(8));
+        assert_eq!(login_backoff(40), LOGIN_BACKOFF_MAX);
+
+        let monitor = HealthMonitor::default();
+        monitor.add("nas", "http://nas", true);
+        assert!(monitor.login_due("nas"));
+        monitor.record_login("nas", &Err(anyhow!("connection refused")));
+        assert!(monitor.needs_login("nas"));
+        assert!(!monitor.login_due("nas"));
+        assert_eq!(monitor.snapshot()[0].next_login_in_seconds, Some(0));
+
+        monitor.record_login("nas", &Ok(()));
+        assert!(!monitor.needs_login("nas"));
+        monitor.expire_login("nas");
+        assert!(monitor.login_due("nas"));
+    }
+}
@@ -1,7 +1,8 @@
 #![allow(clippy::collapsible_if)]
-use axum::http::StatusCode;
+use anyhow::Context;
+use axum::http::{StatusCode, header};
 use axum::{
-    Json, Router,
+    Extension, Json, Router,
     extract::{Query, Request, State},
     middleware::{self, Next},
     response::{
@@ -11,34 +12,49 @@ use axum::{
     routing::{get, post},
 };
 use dashmap::DashMap;
-use futures::stream::Stream;
+use futures::stream::{self, Stream, StreamExt};
 use serde::Deserialize;
-use std::{collections::HashMap, convert::Infallible, sync::Arc, time::Duration};
-use tokio::sync::mpsc;
-use tokio_stream::wrappers::ReceiverStream;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::{convert::Infallible, fmt, sync::Arc, time::Duration};
+use tokio::net::TcpListener;
+use tokio_stream::wrappers::UnboundedReceiverStream;
 use tower_http::{cors::CorsLayer, trace::TraceLayer};
-use tracing::{debug, error, info};
+use tracing::{Instrument, debug, error, info, info_span, warn};
 use uuid::Uuid;
 
-use crate::server::mcp::{JsonRpcError, JsonRpcRequest, JsonRpcResponse, McpServer};
+use crate::server::auth::{AuthFailure, Grant, HttpAuth};
+use crate::server::mcp::{McpServer, Peer, SessionInfo, parse_error_response};
+use crate::server::oauth::METADATA_PATH;
+use crate::server::tls::{TlsFiles, TlsListener};
 
 #[derive(Clone)]
 struct AppState {
     mcp_server: McpServer,
-    sessions: Arc<DashMap<String, mpsc::Sender<Result<Event, Infallible>>>>,
-    auth_token: Option<String>,
+    sessions: Arc<DashMap<String, Peer>>,
+    auth: Arc<HttpAuth>,
 }
 
+/// The grant of the token a request authenticated with, set by `auth_middleware`.
+#[derive(Clone)]
+struct AuthIdentity(Arc<Grant>);
+
 #[derive(Deserialize)]
 struct MessageParams {
     session_id: String,
 }
 
+/// A router that accepts only `auth_token`, if given, which may do everything.
 pub async fn create_router(mcp_server: McpServer, auth_token: Option<String>) -> Router {
+    let auth = auth_token.map(HttpAuth::shared).unwrap_or_default();
+    create_router_with_auth(mcp_server, auth).await
+}
+
+pub async fn create_router_with_auth(mcp_server: McpServer, auth: HttpAuth) -> Router {
     let state = AppState {
         mcp_server,
         sessions: Arc::new(DashMap::new()),
-        auth_token,
+        auth: Arc::new(auth),
     };
 
     Router::new()
@@ -48,160 +64,340 @@ pub async fn create_router(mcp_server: McpServer, auth_token: Option<String>) ->
             state.clone(),
             auth_middleware,
         ))
+        .route(METADATA_PATH, get(resource_metadata_handler))
+        .route(
+            &format!("{}/{{*resource}}", METADATA_PATH),
+            get(resource_metadata_handler),
+        )
         .layer(CorsLayer::permissive())
         .layer(TraceLayer::new_for_http())
         .with_state(state)
 }
 
+/// Where the HTTP server listens.
+#[derive(Debug, Clone, PartialEq)]
+pub enum HttpAddress {
+    /// TCP, with TLS when certificate and key are given.
+    Tcp {
+        host: String,
+        port: u16,
+        tls: Option<TlsFiles>,
+    },
+    /// A Unix domain socket, for local agents.
+    Unix(PathBuf),
+}
+
+impl fmt::Display for HttpAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Tcp { host, port, tls } => {
+                let scheme = if tls.is_some() { "https" } else { "http" };
+                write!(f, "{}://{}:{}", scheme, host, port)
+            }
+            Self::Unix(path) => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+
+/// A bound listener for [`serve_http`]. Binding separately from serving lets startup fail
+/// on a taken port or a bad certificate.
+pub enum HttpListener {
+    Tcp(TcpListener),
+    Tls(TlsListener),
+    #[cfg(unix)]
+    Unix(tokio::net::UnixListener),
+}
+
+impl HttpListener {
+    pub async fn bind(address: &HttpAddress) -> anyhow::Result<Self> {
+        match address {
+            HttpAddress::Tcp { host, port, tls } => {
+                let tcp = TcpListener::bind((host.as_str(), *port))
+                    .await
+                    .with_context(|| format!("Failed to listen on {}:{}", host, port))?;
+                match tls {
+                    Some(files) => Ok(Self::Tls(TlsListener::new(tcp, files.clone())?)),
+                    None => Ok(Self::Tcp(tcp)),
+                }
+            }
+            #[cfg(unix)]
+            HttpAddress::Unix(path) => {
+                // A socket left behind by a previous run would make bind fail
+                if std::fs::symlink_metadata(path)
+                    .is_ok_and(|m| std::os::unix::fs::FileTypeExt::is_socket(&m.file_type()))
+                {
+                    std::fs::remove_file(path)?;
+                }
+                let listener = tokio::net::UnixListener::bind(path)
+                    .with_context(|| format!("Failed to listen on {}", path.display()))?;
+                Ok(Self::Unix(listener))
+            }
+            #[cfg(not(unix))]
+            HttpAddress::Unix(_) => {
+                anyhow::bail!("Unix domain sockets are not supported on this platform")
+            }
+        }
+    }
+
+    /// The TCP address, e.g. to find the port when bound to port 0.
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        match self {
+            Self::Tcp(listener) => listener.local_addr().ok(),
+            Self::Tls(listener) => axum::serve::Listener::local_addr(listener).ok(),
+            #[cfg(unix)]
+            Self::Unix(_) => None,
+        }
+    }
+}
+
 pub async fn run_http_server(
     mcp_server: McpServer,
     host: &str,
     port: u16,
     auth_token: Option<String>,
 ) -> anyhow::Result<()> {
-    let app = create_router(mcp_server, auth_token).await;
-
-    let addr = format!("{}:{}", host, port);
-    info!("Starting HTTP MCP Server on {}", addr);
-
-    let listener = tokio::net::TcpListener::bind(&addr).await?;
-    axum::serve(listener, app).await?;
+    let address = HttpAddress::Tcp {
+        host: host.to_string(),
+        port,
+        tls: None,
+    };
+    info!("Starting HTTP MCP Server on {}", address);
+    let listener = HttpListener::bind(&address).await?;
+    let auth = auth_token.map(HttpAuth::shared).unwrap_or_default();
+    serve_http(mcp_server, listener, auth).await
+}
 
+/// Serves MCP on `listener` until the listener fails.
+pub async fn serve_http(
+    mcp_server: McpServer,
+    listener: HttpListener,
+    auth: HttpAuth,
+) -> anyhow::Result<()> {
+    let app = create_router_with_auth(mcp_server, auth).await;
+    match listener {
+        HttpListener::Tcp(listener) => axum::serve(listener, app).await?,
+        HttpListener::Tls(listener) => axum::serve(listener, app).await?,
+        #[cfg(unix)]
+        HttpListener::Unix(listener) => axum::serve(listener, app).await?,
+    }
     Ok(())
 }
 
 async fn sse_handler(
     State(state): State<AppState>,
+    identity: Option<Extension<AuthIdentity>>,
 ) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
     let session_id = Uuid::new_v4().to_string();
-    let (tx, rx) = mpsc::channel(100);
+    let grant = identity.map(|Extension(AuthIdentity(grant))| grant);
+    let (peer, rx) = Peer::channel();
+    let peer = peer.with_session(SessionInfo {
+        transport: "http",
+        session_id: Some(session_id.clone()),
+        identity: grant.as_ref().map(|g| g.name.clone()),
+        grant: grant.clone(),
+    });
 
-    state.sessions.insert(session_id.clone(), tx.clone());
+    state.mcp_server.connect(&peer);
+    state.sessions.insert(session_id.clone(), peer);
 
-    info!("New SSE session connected: {}", session_id);
+    match &grant {
+        Some(grant) => info!(
+            "New SSE session connected: {} (token '{}')",
+            session_id, grant.name
+        ),
+        None => info!("New SSE session connected: {}", session_id),
+    }
 
-    // Send the endpoint event immediately
+    // Send the endpoint event first, then everything the session's peer emits
     let endpoint_url = format!("/message?session_id={}", session_id);
-    let _ = tx
-        .send(Ok(Event::default().event("endpoint").data(endpoint_url)))
-        .await;
-
-    // Create a stream that removes the session on drop
-    let stream = ReceiverStream::new(rx);
+    let endpoint = stream::once(async move {
+        Ok::<_, Infallible>(Event::default().event("endpoint").data(endpoint_url))
+    });
+    let messages = UnboundedReceiverStream::new(rx).filter_map(|message| async move {
+        match serde_json::to_string(&message) {
+            Ok(data) => Some(Ok(Event::default().event("message").data(data))),
+            Err(e) => {
+                error!("Failed to serialize SSE message: {}", e);
+                None
+            }
+        }
+    });
+    // Dropped with the stream when the client disconnects
+    let guard = SessionGuard {
+        sessions: state.sessions.clone(),
+        session_id,
+    };
+    let stream = endpoint.chain(messages).map(move |event| {
+        let _ = &guard;
+        event
+    });
 
     Sse::new(stream)
         .keep_alive(axum::response::sse::KeepAlive::new().interval(Duration::from_secs(15)))
 }
 
+/// Forgets an SSE session once its stream is gone.
+struct SessionGuard {
+    sessions: Arc<DashMap<String, Peer>>,
+    session_id: String,
+}
+
+impl Drop for SessionGuard {
+    fn drop(&mut self) {
+        if let Some((_, peer)) = self.sessions.remove(&self.session_id) {
+            // Requests waiting for an answer over this stream will not get one
+            peer.fail_pending();
+            info!("SSE session closed: {}", self.session_id);
+        }
+    }
+}
+
 async fn message_handler(
     State(state): State<AppState>,
     Query(params): Query<MessageParams>,
-    Json(req): Json<JsonRpcRequest>,
+    identity: Option<Extension<AuthIdentity>>,
+    body: String,
 ) -> impl IntoResponse {
     let session_id = params.session_id;
 
-    let tx = if let Some(sender) = state.sessions.get(&session_id) {
-        sender.clone()
+    let peer = if let Some(peer) = state.sessions.get(&session_id) {
+        peer.clone()
     } else {
         return (axum::http::StatusCode::NOT_FOUND, "Session not found").into_response();
     };
+    // Only the token that opened a session may use it. Requests act with the grant they
+    // were sent with, so that a refreshed OAuth token's scopes and expiry apply.
+    let grant = identity.map(|Extension(AuthIdentity(grant))| grant);
+    let token = grant.as_ref().map(|grant| grant.name.clone());
+    if token != peer.session().identity {
+        warn!(
+            "Token {:?} tried to use session {} of token {:?}",
+            token,
+            session_id,
+            peer.session().identity
+        );
+        return (StatusCode::FORBIDDEN, "Session belongs to another token").into_response();
+    }
+    let peer = match grant {
+        Some(grant) => {
+            let session = SessionInfo {
+                grant: Some(grant),
+                ..peer.session().clone()
+            };
+            peer.with_session(session)
+        }
+        None => peer,
+    };
+
+    // Unparseable bodies are rejected inline; there is no request id to answer on the stream
+    let message: serde_json::Value = match serde_json::from_str(&body) {
+        Ok(message) => message,
+        Err(e) => {
+            debug!("Failed to parse JSON-RPC for session {}: {}", session_id, e);
+            return (StatusCode::BAD_REQUEST, Json(parse_error_response(&e))).into_response();
+        }
+    };
 
     let mcp = state.mcp_server.clone();
+    // Its `session` field is how the log layer tells whose request an event belongs to
+    let span = match &token {
+        Some(token) => info_span!("http", session = %session_id, token = %token),
+        None => info_span!("http", session = %session_id),
+    };
 
-    tokio::spawn(async move {
-        let req_id = req.id.clone();
+    let handle = async move {
         debug!(
-            "Received HTTP request for session {}: {:?}",
-            session_id, req
+            "Received HTTP request for session {}: {}",
+            session_id, message
         );
 
-        let resp = mcp.handle_request(req).await;
-
-        if let Some(id) = req_id {
-            let json_resp = match resp {
-                Ok(result) => JsonRpcResponse {
-                    jsonrpc: "2.0".to_string(),
-                    id: Some(id),
-                    result: Some(result),
-                    error: None,
-                },
-                Err(e) => JsonRpcResponse {
-                    jsonrpc: "2.0".to_string(),
-                    id: Some(id),
-                    result: None,
-                    error: Some(JsonRpcError {
-                        code: -32603,
-                        message: e.to_string(),
-                        data: None,
-                    }),
-                },
-            };
-
-            if let Ok(data) = serde_json::to_string(&json_resp) {
-                // Send response as 'message' event
-                if let Err(e) = tx
-                    .send(Ok(Event::default().event("message").data(data)))
-                    .await
-                {
-                    error!("Failed to send SSE event to session {}: {}", session_id, e);
-                }
-            }
+        if let Some(resp) = mcp.handle_message(message, &peer).await {
+            // Send response as 'message' event
+            peer.send(resp);
 
             // Check for notifications
-            if mcp.check_notification() {
-                let notification = serde_json::json!({
+            if peer.take_tools_changed() {
+                peer.send(serde_json::json!({
                     "jsonrpc": "2.0",
                     "method": "notifications/tools/list_changed"
-                });
-                if let Ok(data) = serde_json::to_string(&notification) {
-                    if let Err(e) = tx
-                        .send(Ok(Event::default().event("message").data(data)))
-                        .await
-                    {
-                        error!(
-                            "Failed to send notification to session {}: {}",
-                            session_id, e
-                        );
-                    }
-                }
+                }));
             }
         }
-    });
+    };
+    tokio::spawn(handle.instrument(span));
 
     // Return 202 Accepted immediately
     (axum::http::StatusCode::ACCEPTED, "Accepted").into_response()
 }
 
-async fn auth_middleware(
-    State(state): State<AppState>,
-    req: Request,
-    next: Next,
-) -> Result<Response, StatusCode> {
-    if let Some(ref token) = state.auth_token {
-        // 1. Check Header
-        if let Some(auth_header) = req.headers().get("Authorization") {
-            if let Ok(auth_str) = auth_header.to_str() {
-                if auth_str == format!("Bearer {}", token) {
-                    return Ok(next.run(req).await);
-                }
-            }
-        }
+async fn auth_middleware(State(state): State<AppState>, mut req: Request, next: Next) -> Response {
+    if !state.auth.is_enabled() {
+        return next.run(req).await;
+    }
 
-        // 2. Check Query Param
-        if let Some(query) = req.uri().query() {
-            let params: HashMap<String, String> = url::form_urlencoded::parse(query.as_bytes())
-                .into_owned()
-                .collect();
+    let header = req
+        .headers()
+        .get("Authorization")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::to_string);
+    // Legacy clients that cannot set headers, if allowed
+    let query = req
+        .uri()
+        .query()
+        .filter(|_| header.is_none() && state.auth.allows_query_token())
+        .and_then(|query| {
+            url::form_urlencoded::parse(query.as_bytes())
+                .find(|(key, _)| key == "token")
+                .map(|(_, token)| token.into_owned())
+        });
 
-            if let Some(t) = params.get("token") {
-                if t == token {
-                    return Ok(next.run(req).await);
-                }
-            }
+    let failure = match state.auth.authenticate(header.or(query).as_deref()).await {
+        Ok(grant) => {
+            req.extensions_mut().insert(AuthIdentity(grant));
+            return next.run(req).await;
+        }
+        Err(failure) => failure,
+    };
+    let status = match &failure {
+        AuthFailure::Expired(name) => {
+            warn!("Rejected HTTP request with expired token '{}'", name);
+            StatusCode::UNAUTHORIZED
+        }
+        AuthFailure::Unknown => {
+            warn!("Rejected HTTP request with an unknown token");
+            StatusCode::UNAUTHORIZED
+        }
+        AuthFailure::Invalid(reason) => {
+            warn!(
+                "Rejected HTTP request with an invalid OAuth token: {}",
+                reason
+            );
+            StatusCode::UNAUTHORIZED
         }
+        AuthFailure::InsufficientScope(name) => {
+            warn!(
+                "Rejected HTTP request from '{}' without any scope of this server",
+                name
+            );
+            StatusCode::FORBIDDEN
+        }
+        AuthFailure::Missing => {
+            debug!("Rejected HTTP request without a token");
+            StatusCode::UNAUTHORIZED
+        }
+    };
+    (
+        status,
+        [(header::WWW_AUTHENTICATE, state.auth.challenge(&failure))],
+    )
+        .into_response()
+}
 
-        return Err(StatusCode::UNAUTHORIZED);
+/// The OAuth protected resource metadata (RFC 9728), served without authentication.
+async fn resource_metadata_handler(State(state): State<AppState>) -> Response {
+    match state.auth.oauth() {
+        Some(oauth) => Json(oauth.metadata()).into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
     }
-
-    Ok(next.run(req).await)
 }
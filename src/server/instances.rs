@@ -0,0 +1,101 @@
+//! The configured qBittorrent instances and the tool policies that apply to them. The
+//! server keeps them as one snapshot that a configuration reload swaps out; a request
+//! keeps working with the snapshot it started with.
+
+use crate::client::QBitClient;
+use crate::server::mcp::invalid_argument;
+use crate::server::policy::ToolPolicy;
+use anyhow::{Result, anyhow};
+use std::collections::HashMap;
+
+#[derive(Clone, Default)]
+pub struct Instances {
+    clients: HashMap<String, QBitClient>,
+    policy: ToolPolicy,
+    // Overrides of `policy` for single instances
+    instance_policies: HashMap<String, ToolPolicy>,
+}
+
+impl Instances {
+    pub fn new(clients: HashMap<String, QBitClient>) -> Self {
+        Self {
+            clients,
+            ..Default::default()
+        }
+    }
+
+    /// Restricts which tools may be called on every instance without its own policy.
+    pub fn with_policy(mut self, policy: ToolPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Replaces the server-wide policy for calls that target `instance`.
+    pub fn with_instance_policy(mut self, instance: impl Into<String>, policy: ToolPolicy) -> Self {
+        self.instance_policies.insert(instance.into(), policy);
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.clients.is_empty()
+    }
+
+    pub fn client(&self, name: &str) -> Option<&QBitClient> {
+        self.clients.get(name)
+    }
+
+    /// Instance names, sorted.
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.clients.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        names
+    }
+
+    /// The named instance, or the default one: "default" if it exists, otherwise any.
+    pub fn resolve(&self, instance: Option<&str>) -> Result<(&str, &QBitClient)> {
+        let entry = if let Some(name) = instance {
+            self.clients.get_key_value(name).ok_or_else(|| {
+                invalid_argument(
+                    "instance",
+                    "name of a configured instance",
+                    format!("Instance not found: {}", name),
+                )
+            })?
+        } else {
+            self.clients
+                .get_key_value("default")
+                .or_else(|| self.clients.iter().next())
+                .ok_or_else(|| anyhow!("No instances configured"))?
+        };
+        Ok((entry.0.as_str(), entry.1))
+    }
+
+    pub fn policy_for(&self, instance: &str) -> &ToolPolicy {
+        self.instance_policies.get(instance).unwrap_or(&self.policy)
+    }
+
+    /// True if both apply the same policies to the same instances.
+    pub fn same_policies(&self, other: &Self) -> bool {
+        self.policy == other.policy && self.instance_policies == other.instance_policies
+    }
+}
+
+/// What a configuration reload changed about the instances.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct InstanceChanges {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    /// Instances with a new address or credentials. They get a new client, which logs in
+    /// again.
+    pub changed: Vec<String>,
+    pub policies_changed: bool,
+}
+
+impl InstanceChanges {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.changed.is_empty()
+            && !self.policies_changed
+    }
+}
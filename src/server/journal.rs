@@ -0,0 +1,326 @@
+//! Undo journal: what the last torrent changes were and how to put things back.
+//!
+//! Before a reversible change (category, tags, limits, pause state, ...) the tool snapshots
+//! the affected torrents and records the calls that would restore them. Irreversible
+//! changes such as deletions are recorded too, but marked as not undoable.
+
+use crate::client::QBitClient;
+use crate::models::Torrent;
+use crate::server::audit::{AuditTorrent, now_rfc3339};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tracing::error;
+
+/// How many entries the journal keeps unless configured otherwise.
+pub const DEFAULT_JOURNAL_SIZE: usize = 100;
+
+/// One call that restores part of the previous state.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum UndoStep {
+    Pause {
+        hashes: String,
+    },
+    Resume {
+        hashes: String,
+    },
+    SetCategory {
+        hashes: String,
+        category: String,
+    },
+    AddTags {
+        hashes: String,
+        tags: String,
+    },
+    RemoveTags {
+        hashes: String,
+        tags: String,
+    },
+    SetShareLimits {
+        hashes: String,
+        ratio_limit: f64,
+        seeding_time_limit: i64,
+        inactive_seeding_time_limit: Option<i64>,
+    },
+    SetDownloadLimit {
+        hashes: String,
+        limit: i64,
+    },
+    SetUploadLimit {
+        hashes: String,
+        limit: i64,
+    },
+    ToggleSequential {
+        hashes: String,
+    },
+    ToggleFirstLastPrio {
+        hashes: String,
+    },
+    SetForceStart {
+        hashes: String,
+        value: bool,
+    },
+    SetSuperSeeding {
+        hashes: String,
+        value: bool,
+    },
+}
+
+impl UndoStep {
+    pub async fn apply(&self, client: &QBitClient) -> Result<()> {
+        match self {
+            Self::Pause { hashes } => client.pause_torrents(hashes).await,
+            Self::Resume { hashes } => client.resume_torrents(hashes).await,
+            Self::SetCategory { hashes, category } => client.set_category(hashes, category).await,
+            Self::AddTags { hashes, tags } => client.add_tags(hashes, tags).await,
+            Self::RemoveTags { hashes, tags } => client.remove_tags(hashes, tags).await,
+            Self::SetShareLimits {
+                hashes,
+                ratio_limit,
+                seeding_time_limit,
+                inactive_seeding_time_limit,
+            } => {
+                client
+                    .set_torrent_share_limits(
+                        hashes,
+                        *ratio_limit,
+                        *seeding_time_limit,
+                        *inactive_seeding_time_limit,
+                    )
+                    .await
+            }
+            Self::SetDownloadLimit { hashes, limit } => {
+                client.set_torrent_download_limit(hashes, *limit).await
+            }
+            Self::SetUploadLimit { hashes, limit } => {
+                client.set_torrent_upload_limit(hashes, *limit).await
+            }
+            Self::ToggleSequential { hashes } => client.toggle_sequential_download(hashes).await,
+            Self::ToggleFirstLastPrio { hashes } => {
+                client.toggle_first_last_piece_priority(hashes).await
+            }
+            Self::SetForceStart { hashes, value } => client.set_force_start(hashes, *value).await,
+            Self::SetSuperSeeding { hashes, value } => {
+                client.set_super_seeding(hashes, *value).await
+            }
+        }
+    }
+}
+
+/// Groups torrents by the value `key` had before a change, so that restoring it takes
+/// one call per distinct value. Returns `(value, "hash1|hash2")` pairs.
+pub fn group_by<T: Serialize>(
+    torrents: &[Torrent],
+    key: impl Fn(&Torrent) -> T,
+) -> Vec<(T, String)> {
+    let mut groups: BTreeMap<String, (T, Vec<&str>)> = BTreeMap::new();
+    for t in torrents {
+        let value = key(t);
+        let id = serde_json::to_string(&value).unwrap_or_default();
+        groups
+            .entry(id)
+            .or_insert_with(|| (value, Vec::new()))
+            .1
+            .push(&t.hash);
+    }
+    groups
+        .into_values()
+        .map(|(value, hashes)| (value, hashes.join("|")))
+        .collect()
+}
+
+/// A change made through a tool.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct JournalEntry {
+    pub id: u64,
+    /// RFC 3339, UTC.
+    pub timestamp: String,
+    pub instance: String,
+    pub tool: String,
+    /// What was done, e.g. "set_category 'movies'".
+    pub action: String,
+    pub torrents: Vec<AuditTorrent>,
+    /// Calls that restore the previous state, in order. `None` if the change cannot be
+    /// undone.
+    pub undo: Option<Vec<UndoStep>>,
+    #[serde(default)]
+    pub undone: bool,
+}
+
+impl JournalEntry {
+    pub fn undoable(&self) -> bool {
+        self.undo.is_some() && !self.undone
+    }
+}
+
+/// The most recent changes, oldest first, optionally saved to a JSON file so that they
+/// survive restarts.
+pub struct Journal {
+    path: Option<PathBuf>,
+    capacity: usize,
+    entries: Mutex<VecDeque<JournalEntry>>,
+}
+
+impl Journal {
+    /// A journal that is lost when the server exits.
+    pub fn in_memory(capacity: usize) -> Self {
+        Self {
+            path: None,
+            capacity: capacity.max(1),
+            entries: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// A journal saved to `path`, loading the entries already there.
+    pub fn open(path: impl Into<PathBuf>, capacity: usize) -> Result<Self> {
+        let path = path.into();
+        let mut entries: VecDeque<JournalEntry> = match fs::read_to_string(&path) {
+            Ok(data) => serde_json::from_str(&data)
+                .with_context(|| format!("Failed to parse undo journal {:?}", path))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => VecDeque::new(),
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {:?}", path)),
+        };
+        let capacity = capacity.max(1);
+        while entries.len() > capacity {
+            entries.pop_front();
+        }
+        Ok(Self {
+            path: Some(path),
+            capacity,
+            entries: Mutex::new(entries),
+        })
+    }
+
+    /// Adds an entry for a change on `instance` and returns its id.
+    pub fn record(
+        &self,
+        instance: &str,
+        tool: &str,
+        action: String,
+        torrents: &[Torrent],
+        undo: Option<Vec<UndoStep>>,
+    ) -> u64 {
+        let mut entries = self.entries.lock().unwrap();
+        let id = entries.back().map_or(1, |e| e.id + 1);
+        entries.push_back(JournalEntry {
+            id,
+            timestamp: now_rfc3339(),
+            instance: instance.to_string(),
+            tool: tool.to_string(),
+            action,
+            torrents: torrents.iter().map(AuditTorrent::from).collect(),
+            undo,
+            undone: false,
+        });
+        while entries.len() > self.capacity {
+            entries.pop_front();
+        }
+        self.save(&entries);
+        id
+    }
+
+    /// The latest `limit` entries, newest first.
+    pub fn recent(&self, instance: Option<&str>, limit: usize) -> Vec<JournalEntry> {
+        self.recent_where(limit, |e| instance.is_none_or(|i| e.instance == i))
+    }
+
+    /// The newest `limit` entries that `filter` keeps, newest first.
+    pub fn recent_where(
+        &self,
+        limit: usize,
+        filter: impl Fn(&JournalEntry) -> bool,
+    ) -> Vec<JournalEntry> {
+        self.entries
+            .lock()
+            .unwrap()
+            .iter()
+            .rev()
+            .filter(|e| filter(e))
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+
+    /// The entry with `id`, or the newest one on `instance` that can still be undone.
+    pub fn find(&self, instance: &str, id: Option<u64>) -> Option<JournalEntry> {
+        let entries = self.entries.lock().unwrap();
+        match id {
+            Some(id) => entries.iter().find(|e| e.id == id).cloned(),
+            None => entries
+                .iter()
+                .rev()
+                .find(|e| e.instance == instance && e.undoable())
+                .cloned(),
+        }
+    }
+
+    pub fn mark_undone(&self, id: u64) {
+        let mut entries = self.entries.lock().unwrap();
+        if let Some(entry) = entries.iter_mut().find(|e| e.id == id) {
+            entry.undone = true;
+        }
+        self.save(&entries);
+    }
+
+    fn save(&self, entries: &VecDeque<JournalEntry>) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        // Write to a temporary file first so a crash never leaves a truncated journal
+        let tmp = path.with_extension("tmp");
+        let result = serde_json::to_vec_pretty(entries)
+            .map_err(anyhow::Error::from)
+            .and_then(|data| Ok(fs::write(&tmp, data)?))
+            .and_then(|_| Ok(fs::rename(&tmp, path)?));
+        if let Err(e) = result {
+            error!("Failed to save undo journal to {:?}: {}", path, e);
+        }
+    }
+}
+
+impl Default for Journal {
+    fn default() -> Self {
+        Self::in_memory(DEFAULT_JOURNAL_SIZE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_journal_is_capped_and_persisted() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("journal.json");
+        let journal = Journal::open(&path, 2).unwrap();
+        for i in 0..3 {
+            let undo = vec![UndoStep::Resume {
+                hashes: format!("h{}", i),
+            }];
+            journal.record(
+                "default",
+                "manage_torrents",
+                format!("pause {}", i),
+                &[],
+                Some(undo),
+            );
+        }
+        journal.record("default", "delete_torrent", "delete".into(), &[], None);
+
+        let reopened = Journal::open(&path, 2).unwrap();
+        let recent = reopened.recent(None, 10);
+        assert_eq!(recent.len(), 2);
+        assert_eq!(recent[0].tool, "delete_torrent");
+        assert_eq!(recent[0].id, 4);
+
+        // The newest entry cannot be undone, so the one before it is picked
+        let entry = reopened.find("default", None).unwrap();
+        assert_eq!(entry.id, 3);
+        reopened.mark_undone(3);
+        assert!(reopened.find("default", None).is_none());
+    }
+}
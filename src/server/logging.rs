@@ -0,0 +1,310 @@
+//! Bridges `tracing` to MCP `notifications/message`, so clients that called
+//! `logging/setLevel` see the server's log output.
+//!
+//! Sessions with the admin scope (and those without any grant, like stdio) get every
+//! event. Other sessions only get the events logged while handling their own requests,
+//! i.e. inside a span with their `session` field.
+
+use crate::server::auth::Scope;
+use crate::server::mcp::Peer;
+use regex::Regex;
+use serde_json::json;
+use std::fmt::Write;
+use std::sync::{LazyLock, Mutex};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::Layer;
+use tracing_subscriber::filter::Targets;
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::registry::LookupSpan;
+
+/// Syslog severities defined by the MCP spec, least severe first.
+pub const LOG_LEVELS: &[&str] = &[
+    "debug",
+    "info",
+    "notice",
+    "warning",
+    "error",
+    "critical",
+    "alert",
+    "emergency",
+];
+
+/// Sessions that asked for log messages, and the literal secrets to scrub from them.
+#[derive(Default)]
+struct Sinks {
+    peers: Vec<(Peer, usize)>,
+    secrets: Vec<String>,
+}
+
+static SINKS: LazyLock<Mutex<Sinks>> = LazyLock::new(Default::default);
+
+static REDACTIONS: LazyLock<Vec<(Regex, &'static str)>> = LazyLock::new(|| {
+    vec![
+        // user:pass@ in URLs
+        (Regex::new(r"://[^/\s:@]+:[^/\s@]+@").unwrap(), "://[REDACTED]@"),
+        (
+            Regex::new(r"(?i)\b(bearer)\s+[A-Za-z0-9._~+/=-]+").unwrap(),
+            "$1 [REDACTED]",
+        ),
+        (
+            Regex::new(r#"(?i)\b(password|passwd|token|secret|api_?key|sid)(["']?\s*[=:]\s*["']?)[^\s&"',;]+"#)
+                .unwrap(),
+            "$1$2[REDACTED]",
+        ),
+    ]
+});
+
+/// Maps a level name from `logging/setLevel` to its rank in [`LOG_LEVELS`].
+pub fn level_rank(level: &str) -> Option<usize> {
+    LOG_LEVELS.iter().position(|l| *l == level)
+}
+
+/// Starts (or updates) forwarding log messages at or above `level` to `peer`.
+pub fn subscribe(peer: &Peer, level: usize) {
+    let mut sinks = SINKS.lock().unwrap();
+    match sinks
+        .peers
+        .iter_mut()
+        .find(|(p, _)| p.same_connection(peer))
+    {
+        Some(entry) => entry.1 = level,
+        None => sinks.peers.push((peer.clone(), level)),
+    }
+}
+
+/// Registers a credential that must never appear in forwarded log messages.
+pub fn register_secret(secret: &str) {
+    if secret.is_empty() {
+        return;
+    }
+    let mut sinks = SINKS.lock().unwrap();
+    if !sinks.secrets.iter().any(|s| s == secret) {
+        sinks.secrets.push(secret.to_string());
+    }
+}
+
+/// Scrubs credentials from a log message: registered secrets, URL user info,
+/// bearer tokens and `password=`/`token:`-style pairs.
+pub fn redact(message: &str, secrets: &[String]) -> String {
+    let mut message = message.to_string();
+    for secret in secrets {
+        message = message.replace(secret.as_str(), "[REDACTED]");
+    }
+    for (re, replacement) in REDACTIONS.iter() {
+        message = re.replace_all(&message, *replacement).into_owned();
+    }
+    message
+}
+
+/// [`redact`] with the secrets registered through [`register_secret`].
+pub fn redact_secrets(message: &str) -> String {
+    let secrets = SINKS.lock().unwrap().secrets.clone();
+    redact(message, &secrets)
+}
+
+fn mcp_level(level: &Level) -> &'static str {
+    match *level {
+        Level::ERROR => "error",
+        Level::WARN => "warning",
+        Level::INFO => "info",
+        _ => "debug",
+    }
+}
+
+/// Tracing layer that forwards events to subscribed MCP sessions.
+pub struct McpLogLayer;
+
+impl McpLogLayer {
+    /// The layer with its filter: only this crate's events, since dependency logs are
+    /// noise to a client and may include raw request details.
+    pub fn filtered<S>() -> impl Layer<S>
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+    {
+        McpLogLayer.with_filter(Targets::new().with_target(env!("CARGO_CRATE_NAME"), Level::DEBUG))
+    }
+}
+
+/// The `session` field of a span, kept in its extensions.
+struct SessionField(String);
+
+/// Whether `peer` may see an event logged in `session`'s span, or outside any session.
+fn may_see(peer: &Peer, session: Option<&str>) -> bool {
+    let info = peer.session();
+    match &info.grant {
+        Some(grant) if !grant.scopes.contains(&Scope::Admin) => {
+            session.is_some() && session == info.session_id.as_deref()
+        }
+        _ => true,
+    }
+}
+
+impl<S> Layer<S> for McpLogLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let mut visitor = SessionVisitor(None);
+        attrs.record(&mut visitor);
+        if let (Some(session), Some(span)) = (visitor.0, ctx.span(id)) {
+            span.extensions_mut().insert(SessionField(session));
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        let level = mcp_level(metadata.level());
+        let rank = level_rank(level).unwrap_or(0);
+
+        let session = ctx.event_scope(event).and_then(|scope| {
+            scope.from_root().find_map(|span| {
+                span.extensions()
+                    .get::<SessionField>()
+                    .map(|field| field.0.clone())
+            })
+        });
+
+        let mut sinks = SINKS.lock().unwrap();
+        sinks.peers.retain(|(peer, _)| !peer.is_closed());
+        let recipients: Vec<&Peer> = sinks
+            .peers
+            .iter()
+            .filter(|(peer, min)| rank >= *min && may_see(peer, session.as_deref()))
+            .map(|(peer, _)| peer)
+            .collect();
+        if recipients.is_empty() {
+            return;
+        }
+
+        let mut visitor = MessageVisitor::default();
+        event.record(&mut visitor);
+        let params = json!({
+            "level": level,
+            "logger": metadata.target(),
+            "data": redact(&visitor.message, &sinks.secrets)
+        });
+        for peer in recipients {
+            peer.notify("notifications/message", params.clone());
+        }
+    }
+}
+
+/// Wraps the writer of a fmt layer so that the console and log file get the same
+/// scrubbing as [`McpLogLayer`].
+pub struct Redacting<M>(pub M);
+
+impl<'a, M: MakeWriter<'a>> MakeWriter<'a> for Redacting<M> {
+    type Writer = RedactingWriter<M::Writer>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        RedactingWriter(self.0.make_writer())
+    }
+}
+
+/// The fmt layer writes every event with a single `write_all`, so each write is
+/// scrubbed as a whole line.
+pub struct RedactingWriter<W>(W);
+
+impl<W: std::io::Write> std::io::Write for RedactingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let line = redact_secrets(&String::from_utf8_lossy(buf));
+        self.0.write_all(line.as_bytes())?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.0.flush()
+    }
+}
+
+struct SessionVisitor(Option<String>);
+
+impl Visit for SessionVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "session" {
+            self.0 = Some(format!("{:?}", value));
+        }
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "session" {
+            self.0 = Some(value.to_string());
+        }
+    }
+}
+
+/// Renders an event like the fmt layer does: the message, then `key=value` fields.
+#[derive(Default)]
+struct MessageVisitor {
+    message: String,
+}
+
+impl Visit for MessageVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            let fields = std::mem::take(&mut self.message);
+            let _ = write!(self.message, "{:?}{}", value, fields);
+        } else {
+            let _ = write!(self.message, " {}={:?}", field.name(), value);
+        }
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message.insert_str(0, value);
+        } else {
+            let _ = write!(self.message, " {}={}", field.name(), value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact() {
+        let secrets = vec!["hunter2".to_string()];
+        assert_eq!(
+            redact("Login failed for admin with hunter2", &secrets),
+            "Login failed for admin with [REDACTED]"
+        );
+        assert_eq!(
+            redact("GET http://admin:pw@nas:8080/api", &[]),
+            "GET http://[REDACTED]@nas:8080/api"
+        );
+        assert_eq!(
+            redact("username=admin&password=abc123", &[]),
+            "username=admin&password=[REDACTED]"
+        );
+        assert_eq!(
+            redact("Authorization: Bearer abc.def", &[]),
+            "Authorization: Bearer [REDACTED]"
+        );
+        assert_eq!(
+            redact("Polling error: timeout", &[]),
+            "Polling error: timeout"
+        );
+    }
+
+    #[test]
+    fn test_redacting_writer() {
+        use std::io::Write as _;
+
+        register_secret("correct-horse-battery");
+        let mut out = Vec::new();
+        RedactingWriter(&mut out)
+            .write_all(b"Logging in with correct-horse-battery\n")
+            .unwrap();
+        assert_eq!(out, b"Logging in with [REDACTED]\n");
+    }
+
+    #[test]
+    fn test_level_rank() {
+        assert!(level_rank("warning").unwrap() > level_rank("info").unwrap());
+        assert_eq!(level_rank("verbose"), None);
+    }
+}
@@ -1,13 +1,30 @@
 use crate::client::QBitClient;
+use crate::humanize::Humanizer;
+use crate::server::audit::{
+    AuditEntry, AuditLog, AuditNotes, AuditTorrent, now_rfc3339, redact_arguments,
+};
+use crate::server::auth::Grant;
+use crate::server::health::{self, HealthMonitor, Transition};
+use crate::server::instances::{InstanceChanges, Instances};
+use crate::server::journal::Journal;
+use crate::server::logging;
+use crate::server::policy::{PolicyDenied, ToolPolicy};
+use crate::server::tools::toolsets::{self, LAZY_TOOLSETS, META_TOOLS};
+use crate::server::tools::{Tool, ToolContext, ToolRegistry, unknown_tool};
+use crate::server::{prompts, resources};
 use anyhow::Result;
-use regex::Regex;
+use dashmap::DashMap;
 use serde::{Deserialize, Serialize};
 use serde_json::{Value, json};
-use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
-use std::time::Duration;
+use std::collections::{BTreeSet, HashMap};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+use tokio::sync::{Semaphore, mpsc, oneshot};
 use tokio::time::sleep;
-use tracing::{debug, error, info};
+use tokio_util::sync::CancellationToken;
+use tokio_util::task::TaskTracker;
+use tracing::{debug, error, info, warn};
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct JsonRpcRequest {
@@ -35,32 +52,765 @@ pub struct JsonRpcError {
     pub data: Option<Value>,
 }
 
+pub const PARSE_ERROR: i64 = -32700;
+pub const INVALID_REQUEST: i64 = -32600;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+pub const INTERNAL_ERROR: i64 = -32603;
+
+impl JsonRpcError {
+    pub fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            data: None,
+        }
+    }
+
+    pub fn with_data(mut self, data: Value) -> Self {
+        self.data = Some(data);
+        self
+    }
+
+    pub fn method_not_found(method: &str) -> Self {
+        Self::new(METHOD_NOT_FOUND, format!("Method not found: {}", method))
+    }
+
+    pub fn invalid_params(message: impl Into<String>) -> Self {
+        Self::new(INVALID_PARAMS, message)
+    }
+}
+
+impl std::fmt::Display for JsonRpcError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for JsonRpcError {}
+
+/// Error for a tool argument that is missing or has the wrong type. The `data` tells the
+/// client which argument to fix and what was expected.
+pub(crate) fn invalid_argument(name: &str, expected: &str, message: String) -> anyhow::Error {
+    JsonRpcError::invalid_params(message)
+        .with_data(json!({ "argument": name, "expected": expected }))
+        .into()
+}
+
+fn optional_arg<'a, T>(
+    args: &'a Value,
+    name: &str,
+    expected: &str,
+    get: impl Fn(&'a Value) -> Option<T>,
+) -> Result<Option<T>> {
+    match args.get(name) {
+        None | Some(Value::Null) => Ok(None),
+        Some(v) => get(v).map(Some).ok_or_else(|| {
+            invalid_argument(
+                name,
+                expected,
+                format!("Invalid {}: expected {}", name, expected),
+            )
+        }),
+    }
+}
+
+fn required_arg<'a, T>(
+    args: &'a Value,
+    name: &str,
+    expected: &str,
+    get: impl Fn(&'a Value) -> Option<T>,
+) -> Result<T> {
+    optional_arg(args, name, expected, get)?
+        .ok_or_else(|| invalid_argument(name, expected, format!("Missing {}", name)))
+}
+
+fn arg_str<'a>(args: &'a Value, name: &str) -> Result<&'a str> {
+    required_arg(args, name, "string", Value::as_str)
+}
+
+pub(crate) fn opt_str<'a>(args: &'a Value, name: &str) -> Result<Option<&'a str>> {
+    optional_arg(args, name, "string", Value::as_str)
+}
+
+/// Returned in place of a result when the client cancelled the request.
+/// Transports must not send a response for it.
+#[derive(Debug)]
+pub struct RequestCancelled;
+
+impl std::fmt::Display for RequestCancelled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Request cancelled")
+    }
+}
+
+impl std::error::Error for RequestCancelled {}
+
+pub fn is_cancelled(err: &anyhow::Error) -> bool {
+    err.is::<RequestCancelled>()
+}
+
+/// Outbound half of a client connection. Handlers use it to emit messages while a
+/// request is still in flight; the transport drains the receiving end.
+#[derive(Clone, Debug)]
+pub struct Peer {
+    tx: mpsc::UnboundedSender<Value>,
+    // In-flight requests on this connection, keyed by serialized JSON-RPC id
+    in_flight: Arc<DashMap<String, CancellationToken>>,
+    // Requests sent to the client that are still waiting for a response
+    pending: Arc<DashMap<String, oneshot::Sender<std::result::Result<Value, JsonRpcError>>>>,
+    next_request_id: Arc<AtomicU64>,
+    client_capabilities: Arc<Mutex<Value>>,
+    // Toolsets this session switched to; `None` until it changes them
+    toolsets: Arc<Mutex<Option<BTreeSet<String>>>>,
+    tools_changed: Arc<AtomicBool>,
+    session: Arc<SessionInfo>,
+    // Bounds how many of this connection's requests run at once
+    limiter: Option<Arc<Semaphore>>,
+}
+
+/// Who is on the other end of a connection, as recorded in the audit log.
+#[derive(Clone, Debug)]
+pub struct SessionInfo {
+    /// `stdio` or `http`.
+    pub transport: &'static str,
+    pub session_id: Option<String>,
+    /// Name of the credential the client authenticated with, if any.
+    pub identity: Option<String>,
+    /// What that credential may do; `None` means everything.
+    pub grant: Option<Arc<Grant>>,
+}
+
+impl Peer {
+    pub fn channel() -> (Self, mpsc::UnboundedReceiver<Value>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        (
+            Self {
+                tx,
+                in_flight: Arc::new(DashMap::new()),
+                pending: Arc::new(DashMap::new()),
+                next_request_id: Arc::new(AtomicU64::new(1)),
+                client_capabilities: Arc::new(Mutex::new(json!({}))),
+                toolsets: Arc::new(Mutex::new(None)),
+                tools_changed: Arc::new(AtomicBool::new(false)),
+                session: Arc::new(SessionInfo {
+                    transport: "stdio",
+                    session_id: None,
+                    identity: None,
+                    grant: None,
+                }),
+                limiter: None,
+            },
+            rx,
+        )
+    }
+
+    /// Runs at most `limit` of this connection's requests at once; the others wait, and
+    /// can be cancelled while they do.
+    pub fn with_limit(mut self, limit: usize) -> Self {
+        self.limiter = Some(Arc::new(Semaphore::new(limit.max(1))));
+        self
+    }
+
+    pub fn with_session(mut self, session: SessionInfo) -> Self {
+        self.session = Arc::new(session);
+        self
+    }
+
+    pub fn session(&self) -> &SessionInfo {
+        &self.session
+    }
+
+    /// Records the capabilities the client declared in `initialize`.
+    pub fn set_client_capabilities(&self, capabilities: Value) {
+        *self.client_capabilities.lock().unwrap() = capabilities;
+    }
+
+    pub fn supports(&self, capability: &str) -> bool {
+        self.client_capabilities
+            .lock()
+            .unwrap()
+            .get(capability)
+            .is_some()
+    }
+
+    /// Sends a request to the client and waits for its response.
+    pub async fn request(&self, method: &str, params: Value) -> Result<Value> {
+        let id = format!(
+            "srv-{}",
+            self.next_request_id.fetch_add(1, Ordering::Relaxed)
+        );
+        let (tx, rx) = oneshot::channel();
+        self.pending.insert(id.clone(), tx);
+        // Drop the pending entry if the calling request is cancelled while waiting
+        let _guard = PendingGuard {
+            pending: self.pending.clone(),
+            id: id.clone(),
+        };
+
+        self.send(json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": method,
+            "params": params
+        }));
+
+        match tokio::time::timeout(CLIENT_REQUEST_TIMEOUT, rx).await {
+            Ok(Ok(Ok(result))) => Ok(result),
+            Ok(Ok(Err(e))) => anyhow::bail!("Client rejected {}: {}", method, e.message),
+            Ok(Err(_)) => anyhow::bail!("Connection closed before the client answered {}", method),
+            Err(_) => anyhow::bail!(
+                "Client did not answer {} within {} s",
+                method,
+                CLIENT_REQUEST_TIMEOUT.as_secs()
+            ),
+        }
+    }
+
+    /// Fails the requests to the client that are still waiting for an answer. Transports
+    /// call this once the connection is gone, as no answer can arrive any more.
+    pub fn fail_pending(&self) {
+        self.pending.clear();
+    }
+
+    /// Hands a response from the client to the request waiting for it. Returns false if
+    /// nothing was waiting.
+    pub fn resolve(&self, response: &Value) -> bool {
+        let Some(id) = response.get("id").and_then(|id| id.as_str()) else {
+            return false;
+        };
+        let Some((_, tx)) = self.pending.remove(id) else {
+            return false;
+        };
+        let outcome = match response.get("error") {
+            Some(error) => Err(serde_json::from_value(error.clone())
+                .unwrap_or_else(|_| JsonRpcError::new(INTERNAL_ERROR, error.to_string()))),
+            None => Ok(response.get("result").cloned().unwrap_or(Value::Null)),
+        };
+        let _ = tx.send(outcome);
+        true
+    }
+
+    fn track(&self, id: &Value) -> InFlightGuard {
+        let key = id.to_string();
+        let token = CancellationToken::new();
+        self.in_flight.insert(key.clone(), token.clone());
+        InFlightGuard {
+            in_flight: self.in_flight.clone(),
+            key,
+            token,
+        }
+    }
+
+    /// Cancels the in-flight request with the given id. Returns false if it already finished.
+    pub fn cancel(&self, id: &Value) -> bool {
+        match self.in_flight.get(&id.to_string()) {
+            Some(token) => {
+                token.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn send(&self, message: Value) {
+        let _ = self.tx.send(message);
+    }
+
+    /// True (once) after this session's tool list changed, i.e. when the transport should
+    /// send `notifications/tools/list_changed`.
+    pub fn take_tools_changed(&self) -> bool {
+        self.tools_changed.swap(false, Ordering::Relaxed)
+    }
+
+    /// True once the transport has stopped reading from this connection.
+    pub fn is_closed(&self) -> bool {
+        self.tx.is_closed()
+    }
+
+    /// Whether two handles refer to the same client connection.
+    pub fn same_connection(&self, other: &Peer) -> bool {
+        self.tx.same_channel(&other.tx)
+    }
+
+    pub fn notify(&self, method: &str, params: Value) {
+        self.send(json!({
+            "jsonrpc": "2.0",
+            "method": method,
+            "params": params
+        }));
+    }
+}
+
+struct PendingGuard {
+    pending: Arc<DashMap<String, oneshot::Sender<std::result::Result<Value, JsonRpcError>>>>,
+    id: String,
+}
+
+impl Drop for PendingGuard {
+    fn drop(&mut self) {
+        self.pending.remove(&self.id);
+    }
+}
+
+struct InFlightGuard {
+    in_flight: Arc<DashMap<String, CancellationToken>>,
+    key: String,
+    token: CancellationToken,
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        self.in_flight.remove(&self.key);
+    }
+}
+
+/// Per-request state handed down to tool handlers.
+#[derive(Clone, Default)]
+pub struct RequestContext {
+    peer: Option<Peer>,
+    progress_token: Option<Value>,
+    cancellation: CancellationToken,
+}
+
+impl RequestContext {
+    pub fn new(peer: Peer) -> Self {
+        Self {
+            peer: Some(peer),
+            ..Default::default()
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancellation.is_cancelled()
+    }
+
+    pub fn progress_token(&self) -> Option<&Value> {
+        self.progress_token.as_ref()
+    }
+
+    /// What the client's credential may do; `None` means everything.
+    pub fn grant(&self) -> Option<&Grant> {
+        self.peer.as_ref()?.session().grant.as_deref()
+    }
+
+    /// Emits `notifications/progress` if the client asked for it via `_meta.progressToken`.
+    /// `progress` must increase with every call for the same request.
+    pub fn report_progress(&self, progress: f64, total: Option<f64>, message: impl Into<String>) {
+        if let (Some(peer), Some(token)) = (&self.peer, &self.progress_token) {
+            let mut params = json!({
+                "progressToken": token,
+                "progress": progress,
+                "message": message.into()
+            });
+            if let Some(total) = total {
+                params["total"] = json!(total);
+            }
+            peer.notify("notifications/progress", params);
+        }
+    }
+}
+
+/// Builds the JSON-RPC response for a handled request, or `None` when nothing should be
+/// sent back (notifications and cancelled requests).
+pub fn build_response(id: Option<Value>, resp: Result<Value>) -> Option<JsonRpcResponse> {
+    let id = id?;
+    match resp {
+        Ok(result) => Some(JsonRpcResponse {
+            jsonrpc: "2.0".to_string(),
+            id: Some(id),
+            result: Some(result),
+            error: None,
+        }),
+        Err(e) if is_cancelled(&e) => None,
+        Err(e) => Some(error_response(
+            Some(id),
+            e.downcast::<JsonRpcError>()
+                .unwrap_or_else(|e| JsonRpcError::new(INTERNAL_ERROR, e.to_string())),
+        )),
+    }
+}
+
+pub fn error_response(id: Option<Value>, error: JsonRpcError) -> JsonRpcResponse {
+    JsonRpcResponse {
+        jsonrpc: "2.0".to_string(),
+        id,
+        result: None,
+        error: Some(error),
+    }
+}
+
+/// Response for input that is not valid JSON at all.
+pub fn parse_error_response(err: &serde_json::Error) -> JsonRpcResponse {
+    error_response(
+        None,
+        JsonRpcError::new(PARSE_ERROR, format!("Parse error: {}", err)),
+    )
+}
+
+/// Validates a decoded message as a JSON-RPC 2.0 request object.
+/// On failure, returns the serialized error response to send back.
+fn parse_request(message: Value) -> std::result::Result<JsonRpcRequest, Value> {
+    // Echo the id back when it is usable, so the client can match the error up
+    let id = message
+        .get("id")
+        .filter(|id| id.is_string() || id.is_number())
+        .cloned();
+    let invalid = |reason: String| {
+        response_value(error_response(
+            id.clone(),
+            JsonRpcError::new(INVALID_REQUEST, format!("Invalid Request: {}", reason)),
+        ))
+    };
+
+    if message.get("jsonrpc").and_then(|v| v.as_str()) != Some("2.0") {
+        return Err(invalid("jsonrpc must be \"2.0\"".to_string()));
+    }
+    if matches!(message.get("id"), Some(id) if !(id.is_string() || id.is_number())) {
+        return Err(invalid("id must be a string or number".to_string()));
+    }
+    if matches!(message.get("params"), Some(p) if !(p.is_object() || p.is_array())) {
+        return Err(invalid("params must be an object or array".to_string()));
+    }
+    serde_json::from_value(message).map_err(|e| invalid(e.to_string()))
+}
+
+fn response_value(resp: JsonRpcResponse) -> Value {
+    serde_json::to_value(resp).unwrap_or_else(|e| {
+        error!("Failed to serialize response: {}", e);
+        Value::Null
+    })
+}
+
+/// Orders completion candidates by how well they match `input`: prefix matches first,
+/// then substrings, then fuzzy (in-order subsequence) matches. Matching is
+/// case-insensitive and considers both the value and its label.
+fn rank_completions(input: &str, candidates: Vec<(String, String)>) -> Vec<String> {
+    let input = input.to_lowercase();
+    let score = |text: &str| {
+        let text = text.to_lowercase();
+        if text.starts_with(&input) {
+            Some(0)
+        } else if text.contains(&input) {
+            Some(1)
+        } else {
+            let mut chars = text.chars();
+            input.chars().all(|c| chars.any(|t| t == c)).then_some(2)
+        }
+    };
+
+    let mut ranked: Vec<(u8, String, String)> = candidates
+        .into_iter()
+        .filter_map(|(value, label)| {
+            let best = [score(&value), score(&label)].into_iter().flatten().min()?;
+            Some((best, label, value))
+        })
+        .collect();
+    ranked.sort();
+    let mut seen = std::collections::HashSet::new();
+    ranked
+        .into_iter()
+        .filter_map(|(_, _, value)| seen.insert(value.clone()).then_some(value))
+        .collect()
+}
+
+/// Identifies a tool call by name and arguments, minus the `confirm` token itself.
+fn confirmation_fingerprint(tool: &str, args: &Value) -> String {
+    let mut args = args.clone();
+    if let Some(obj) = args.as_object_mut() {
+        obj.remove("confirm");
+    }
+    format!("{}:{}", tool, args)
+}
+
+/// Wraps a tool execution failure in a `tools/call` result with `isError: true`.
+fn tool_error_result(err: &anyhow::Error) -> Value {
+    json!({
+        "content": [{ "type": "text", "text": format!("Error: {:#}", err) }],
+        "isError": true
+    })
+}
+
+fn audit_entry(
+    tool: &str,
+    instance: &str,
+    args: &Value,
+    ctx: &RequestContext,
+    torrents: Vec<AuditTorrent>,
+    result: std::result::Result<&Value, &anyhow::Error>,
+) -> AuditEntry {
+    let session = ctx.peer.as_ref().map(|p| p.session());
+    let (outcome, error) = match result {
+        Ok(_) => ("success", None),
+        Err(e) if is_cancelled(e) => ("cancelled", None),
+        Err(e) => ("error", Some(logging::redact_secrets(&format!("{:#}", e)))),
+    };
+    let mut arguments = redact_arguments(args);
+    if let Some(obj) = arguments.as_object_mut() {
+        obj.remove("instance");
+    }
+    AuditEntry {
+        timestamp: now_rfc3339(),
+        transport: session.map_or("direct", |s| s.transport).to_string(),
+        session_id: session.and_then(|s| s.session_id.clone()),
+        identity: session.and_then(|s| s.identity.clone()),
+        instance: instance.to_string(),
+        tool: tool.to_string(),
+        arguments,
+        torrents,
+        outcome: outcome.to_string(),
+        error,
+    }
+}
+
+/// The torrents named by a call's `hash`/`hashes` argument, with their names where
+/// qBittorrent still knows them.
+async fn audited_torrents(client: &QBitClient, args: &Value) -> Vec<AuditTorrent> {
+    let Some(hashes) = ["hash", "hashes"]
+        .iter()
+        .find_map(|key| args.get(*key).and_then(Value::as_str))
+    else {
+        return Vec::new();
+    };
+    let known = client.get_torrents_info(hashes).await.unwrap_or_default();
+    if hashes == "all" {
+        return known.iter().map(AuditTorrent::from).collect();
+    }
+    hashes
+        .split('|')
+        .filter(|h| !h.is_empty())
+        .map(|hash| AuditTorrent {
+            hash: hash.to_string(),
+            name: known
+                .iter()
+                .find(|t| t.hash.eq_ignore_ascii_case(hash))
+                .map(|t| t.name.clone()),
+        })
+        .collect()
+}
+
+/// Default number of requests a single stdio connection may run concurrently.
+pub const DEFAULT_MAX_IN_FLIGHT: usize = 16;
+/// How many requests may wait per concurrently running one before stdin is not read on.
+const QUEUED_PER_SLOT: usize = 4;
+
+/// How often the event loop polls the instances unless told otherwise.
+pub const DEFAULT_POLLING_INTERVAL_MS: u64 = 2000;
+
+/// Protocol revisions we can speak, newest first.
+pub const SUPPORTED_PROTOCOL_VERSIONS: &[&str] = &["2025-06-18", "2025-03-26", "2024-11-05"];
+
+/// Most values a completion/complete response may carry, per the spec.
+const MAX_COMPLETIONS: usize = 100;
+
+/// How long a `confirm` token from a destructive tool's first call stays valid.
+const CONFIRMATION_TTL: Duration = Duration::from_secs(300);
+/// How long a request to the client, e.g. an elicitation, waits for its answer.
+const CLIENT_REQUEST_TIMEOUT: Duration = Duration::from_secs(300);
+
 struct McpState {
-    lazy_mode: bool,
-    tools_loaded: bool,
-    should_notify: bool,
-    notification_queue: Vec<Value>,
+    // Toolsets for requests that arrive without a session (e.g. library calls)
+    toolsets: Option<BTreeSet<String>>,
+    // Connected sessions, which get the notifications that are not about one request
+    sessions: Vec<Peer>,
     running: bool,
 }
 
 #[derive(Clone)]
 pub struct McpServer {
-    clients: HashMap<String, QBitClient>,
+    // Replaced as a whole when the configuration is reloaded
+    instances: Arc<RwLock<Arc<Instances>>>,
+    tools: Arc<ToolRegistry>,
+    // Toolsets a session starts with; `None` means all of them
+    default_toolsets: Option<BTreeSet<String>>,
     state: Arc<Mutex<McpState>>,
+    max_in_flight: usize,
+    require_confirmation: bool,
+    audit: Option<Arc<AuditLog>>,
+    journal: Arc<Journal>,
+    // Whether tools and resources use the humanized view unless asked otherwise
+    humanize: bool,
+    humanizer: Humanizer,
+    // Outstanding confirm tokens: token -> (tool call fingerprint, issued at)
+    confirmations: Arc<DashMap<String, (String, Instant)>>,
+    health: Arc<HealthMonitor>,
+    polling_interval_ms: Arc<AtomicU64>,
 }
 
 impl McpServer {
     pub fn new(clients: HashMap<String, QBitClient>, lazy_mode: bool) -> Self {
+        let health = HealthMonitor::default();
+        for (name, client) in &clients {
+            health.add(name, client.base_url(), client.requires_login());
+        }
         Self {
-            clients,
+            instances: Arc::new(RwLock::new(Arc::new(Instances::new(clients)))),
+            tools: Arc::new(ToolRegistry::builtin()),
+            default_toolsets: lazy_mode
+                .then(|| LAZY_TOOLSETS.iter().map(|t| t.to_string()).collect()),
             state: Arc::new(Mutex::new(McpState {
-                lazy_mode,
-                tools_loaded: !lazy_mode,
-                should_notify: false,
-                notification_queue: Vec::new(),
+                toolsets: None,
+                sessions: Vec::new(),
                 running: true,
             })),
+            max_in_flight: DEFAULT_MAX_IN_FLIGHT,
+            require_confirmation: false,
+            audit: None,
+            journal: Arc::new(Journal::default()),
+            humanize: false,
+            humanizer: Humanizer::default(),
+            confirmations: Arc::new(DashMap::new()),
+            health: Arc::new(health),
+            polling_interval_ms: Arc::new(AtomicU64::new(DEFAULT_POLLING_INTERVAL_MS)),
+        }
+    }
+
+    /// Limits how many requests a stdio connection processes at the same time.
+    pub fn with_max_in_flight(mut self, limit: usize) -> Self {
+        self.max_in_flight = limit.max(1);
+        self
+    }
+
+    /// Makes destructive tools require a `confirm` token when the client cannot be asked
+    /// through elicitation.
+    pub fn with_require_confirmation(mut self, require: bool) -> Self {
+        self.require_confirmation = require;
+        self
+    }
+
+    /// Restricts which tools may be called on every instance without its own policy.
+    pub fn with_policy(self, policy: ToolPolicy) -> Self {
+        let instances = self.instances().as_ref().clone().with_policy(policy);
+        self.with_instances(instances)
+    }
+
+    /// Replaces the server-wide policy for calls that target `instance`.
+    pub fn with_instance_policy(self, instance: impl Into<String>, policy: ToolPolicy) -> Self {
+        let instances = self
+            .instances()
+            .as_ref()
+            .clone()
+            .with_instance_policy(instance, policy);
+        self.with_instances(instances)
+    }
+
+    /// Replaces the instances and policies the server was created with.
+    pub fn with_instances(self, instances: Instances) -> Self {
+        for name in self.instances().names() {
+            self.health.remove(name);
+        }
+        for name in instances.names() {
+            let client = instances.client(name).expect("listed instance");
+            self.health
+                .add(name, client.base_url(), client.requires_login());
+        }
+        *self.instances.write().unwrap() = Arc::new(instances);
+        self
+    }
+
+    /// The current instances and policies. Hold on to the snapshot for the duration of a
+    /// request, so that a reload in between cannot mix old and new instances.
+    pub fn instances(&self) -> Arc<Instances> {
+        self.instances.read().unwrap().clone()
+    }
+
+    /// Switches to the instances and policies of a reloaded configuration. New instances
+    /// and those with new credentials are logged in to, and clients are told that the
+    /// resource and tool lists changed.
+    pub async fn apply_instances(&self, instances: Instances, changes: &InstanceChanges) {
+        *self.instances.write().unwrap() = Arc::new(instances);
+        let snapshot = self.instances();
+        for name in &changes.removed {
+            self.health.remove(name);
+            info!("Removed qBittorrent instance '{}'", name);
+        }
+        for name in changes.added.iter().chain(&changes.changed) {
+            let Some(client) = snapshot.client(name) else {
+                continue;
+            };
+            self.health
+                .add(name, client.base_url(), client.requires_login());
+            if changes.added.contains(name) {
+                info!(
+                    "Added qBittorrent instance '{}' at {}",
+                    name,
+                    client.base_url()
+                );
+            } else {
+                info!(
+                    "Reconnecting to qBittorrent instance '{}' at {}",
+                    name,
+                    client.base_url()
+                );
+            }
+            if client.requires_login() && self.login(name, client).await {
+                info!("Logged in to qBittorrent instance '{}' successfully", name);
+            }
+        }
+        if !changes.added.is_empty() || !changes.removed.is_empty() {
+            self.notify_sessions(None, "notifications/resources/list_changed", json!({}));
         }
+        // Which tools are listed depends on the policies of the instances
+        if !changes.added.is_empty() || !changes.removed.is_empty() || changes.policies_changed {
+            self.notify_sessions(None, "notifications/tools/list_changed", json!({}));
+        }
+    }
+
+    /// Changes how often the event loop polls the instances.
+    pub fn set_polling_interval(&self, interval_ms: u64) {
+        self.polling_interval_ms
+            .store(interval_ms, Ordering::Relaxed);
+    }
+
+    /// Records every call to a tool that is not read-only in `audit`.
+    pub fn with_audit_log(mut self, audit: AuditLog) -> Self {
+        self.audit = Some(Arc::new(audit));
+        self
+    }
+
+    pub(crate) fn audit_log(&self) -> Option<&AuditLog> {
+        self.audit.as_deref()
+    }
+
+    /// Replaces the default in-memory undo journal, e.g. with one saved to a file.
+    pub fn with_journal(mut self, journal: Journal) -> Self {
+        self.journal = Arc::new(journal);
+        self
+    }
+
+    pub(crate) fn journal(&self) -> &Journal {
+        &self.journal
+    }
+
+    /// Sets how sizes, speeds and timestamps are humanized, and whether that is the
+    /// default for calls that do not pass `humanize`.
+    pub fn with_humanize(mut self, by_default: bool, humanizer: Humanizer) -> Self {
+        self.humanize = by_default;
+        self.humanizer = humanizer;
+        self
+    }
+
+    /// The humanizer to use for a call that passed `humanize` (or not).
+    pub(crate) fn humanizer_for(&self, requested: Option<bool>) -> Option<&Humanizer> {
+        requested
+            .unwrap_or(self.humanize)
+            .then_some(&self.humanizer)
+    }
+
+    /// Sets the toolsets each session starts with, overriding the lazy-mode default.
+    pub fn with_default_toolsets<I, S>(mut self, toolsets: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.default_toolsets = Some(toolsets.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Registers an additional tool, or replaces a built-in one with the same name.
+    pub fn with_tool<T: Tool>(mut self, tool: T) -> Self {
+        Arc::make_mut(&mut self.tools).register(tool);
+        self
     }
 
     pub fn shutdown(&self) {
@@ -73,1773 +823,1184 @@ impl McpServer {
         state.running
     }
 
-    pub fn push_notification(&self, method: &str, params: Value) {
+    /// Makes `peer` receive the server's notifications (instance status, finished
+    /// torrents, reloads) until its connection closes.
+    pub fn connect(&self, peer: &Peer) {
         let mut state = self.state.lock().unwrap();
-        state.notification_queue.push(json!({
-            "jsonrpc": "2.0",
-            "method": method,
-            "params": params
-        }));
+        state.sessions.retain(|p| !p.is_closed());
+        if !state.sessions.iter().any(|p| p.same_connection(peer)) {
+            state.sessions.push(peer.clone());
+        }
     }
 
-    async fn flush_notifications_async<W: tokio::io::AsyncWrite + Unpin>(
-        &self,
-        writer: &mut W,
-    ) -> Result<()> {
-        use tokio::io::AsyncWriteExt;
+    /// Stops sending notifications to `peer`.
+    pub fn disconnect(&self, peer: &Peer) {
+        let mut state = self.state.lock().unwrap();
+        state
+            .sessions
+            .retain(|p| !p.is_closed() && !p.same_connection(peer));
+    }
 
-        let mut pending = Vec::new();
-        {
-            let mut state = self.state.lock().unwrap();
-            if state.should_notify {
-                state.should_notify = false;
-                pending.push(json!({
-                    "jsonrpc": "2.0",
-                    "method": "notifications/tools/list_changed"
-                }));
-            }
-            while !state.notification_queue.is_empty() {
-                pending.push(state.notification_queue.remove(0));
+    /// Sends a notification to every connected session, or only to those allowed to
+    /// read `instance` when it is about one.
+    pub fn notify_sessions(&self, instance: Option<&str>, method: &str, params: Value) {
+        let mut state = self.state.lock().unwrap();
+        state.sessions.retain(|p| !p.is_closed());
+        for peer in &state.sessions {
+            let allowed = match (&peer.session().grant, instance) {
+                (Some(grant), Some(instance)) => grant.can_read(instance),
+                _ => true,
+            };
+            if allowed {
+                peer.notify(method, params.clone());
             }
         }
+    }
 
-        for n in pending {
-            let out = serde_json::to_string(&n)? + "\n";
-            writer.write_all(out.as_bytes()).await?;
+    fn flush_notifications(&self, peer: &Peer) {
+        if peer.take_tools_changed() {
+            peer.send(json!({
+                "jsonrpc": "2.0",
+                "method": "notifications/tools/list_changed"
+            }));
         }
-        writer.flush().await?;
-        Ok(())
     }
 
-    fn get_client(&self, instance: Option<&str>) -> Result<&QBitClient> {
-        if let Some(name) = instance {
-            self.clients
-                .get(name)
-                .ok_or_else(|| anyhow::anyhow!("Instance not found: {}", name))
-        } else {
-            // Use "default" if exists, otherwise first one
-            if let Some(client) = self.clients.get("default") {
-                Ok(client)
-            } else {
-                self.clients
-                    .values()
-                    .next()
-                    .ok_or_else(|| anyhow::anyhow!("No instances configured"))
+    /// Logs in to every instance that has credentials. Instances that fail are marked down
+    /// and retried by the event loop.
+    pub async fn login_all(&self) {
+        let instances = self.instances();
+        for name in instances.names() {
+            let client = instances.client(name).expect("listed instance");
+            if client.requires_login() && self.login(name, client).await {
+                info!("Logged in to qBittorrent instance '{}' successfully", name);
             }
         }
     }
 
-    pub fn check_notification(&self) -> bool {
-        let mut state = self.state.lock().unwrap();
-        if state.should_notify {
-            state.should_notify = false;
-            true
-        } else {
-            false
+    /// Logs in to `name` and records the outcome. Returns true if that worked.
+    async fn login(&self, name: &str, client: &QBitClient) -> bool {
+        let result = client.login().await;
+        self.health.record_login(name, &result);
+        match result {
+            Ok(()) => true,
+            Err(e) => {
+                self.record_failure(name, &e.context("Failed to login"));
+                false
+            }
         }
     }
 
-    pub async fn run_stdio(&mut self) -> Result<()> {
-        use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, stdin, stdout};
-        let mut reader = BufReader::new(stdin()).lines();
-        let mut stdout = stdout();
-
-        loop {
-            if !self.is_running() {
-                break;
-            }
-            tokio::task::yield_now().await;
-            tokio::select! {
-                line_res = reader.next_line() => {
-                    let line = match line_res? {
-                        Some(l) => l,
-                        None => break, // EOF
-                    };
-                    let input = line.trim();
-                    if input.is_empty() {
-                        continue;
-                    }
+    /// The health of the instances `grant` may read.
+    pub(crate) fn instance_status(&self, grant: Option<&Grant>) -> Vec<Value> {
+        let instances = self.instances();
+        let default = instances.resolve(None).ok().map(|(name, _)| name);
+        self.health
+            .snapshot()
+            .into_iter()
+            .filter(|health| grant.is_none_or(|g| g.can_read(&health.instance)))
+            .map(|health| {
+                let is_default = default == Some(health.instance.as_str());
+                let mut value = json!(health);
+                value["default"] = json!(is_default);
+                value
+            })
+            .collect()
+    }
 
-                    debug!("Received: {}", input);
+    fn record_success(&self, name: &str, latency: Option<Duration>) {
+        if let Some(transition) = self.health.record_success(name, latency) {
+            info!("qBittorrent instance '{}' is back up", name);
+            self.notify_transition(transition);
+        }
+    }
 
-                    match serde_json::from_str::<JsonRpcRequest>(input) {
-                        Ok(req) => {
-                            let id = req.id.clone();
-                            let resp = self.handle_request(req).await;
-
-                            if let Some(req_id) = id {
-                                let json_resp = match resp {
-                                    Ok(result) => JsonRpcResponse {
-                                        jsonrpc: "2.0".to_string(),
-                                        id: Some(req_id),
-                                        result: Some(result),
-                                        error: None,
-                                    },
-                                    Err(e) => JsonRpcResponse {
-                                        jsonrpc: "2.0".to_string(),
-                                        id: Some(req_id),
-                                        result: None,
-                                        error: Some(JsonRpcError {
-                                            code: -32603,
-                                            message: e.to_string(),
-                                            data: None,
-                                        }),
-                                    },
-                                };
-
-                                let out = serde_json::to_string(&json_resp)? + "\n";
-                                stdout.write_all(out.as_bytes()).await?;
-                                stdout.flush().await?;
-                            } else {
-                                // Notification, no response expected
-                                if let Err(e) = resp {
-                                    error!("Error handling notification: {}", e);
-                                }
-                            }
-                        }
-                        Err(e) => {
-                            error!("Failed to parse JSON-RPC: {}", e);
-                        }
-                    }
-                    self.flush_notifications_async(&mut stdout).await?;
-                }
-                _ = sleep(Duration::from_millis(100)) => {
-                    if !self.is_running() {
-                        break;
-                    }
-                    self.flush_notifications_async(&mut stdout).await?;
-                }
+    fn record_failure(&self, name: &str, err: &anyhow::Error) {
+        match self.health.record_failure(name, err) {
+            Some(transition) => {
+                warn!("qBittorrent instance '{}' is down: {:#}", name, err);
+                self.notify_transition(transition);
             }
+            None => debug!("qBittorrent instance '{}' is still down: {:#}", name, err),
         }
-        Ok(())
     }
 
-    pub async fn handle_request(&self, req: JsonRpcRequest) -> Result<Value> {
-        match req.method.as_str() {
-            "initialize" => Ok(json!({
-                "protocolVersion": "2024-11-05",
-                "serverInfo": {
-                    "name": "qbittorrent-mcp-rs",
-                    "version": env!("CARGO_PKG_VERSION")
-                },
-                "capabilities": {
-                    "tools": {
-                        "listChanged": true
-                    },
-                    "resources": {
-                        "listChanged": false,
-                        "subscribe": false
-                    },
-                    "prompts": {
-                        "listChanged": false
-                    }
-                }
-            })),
-            "notifications/initialized" => {
-                info!("Client initialized");
-                Ok(Value::Null)
+    fn notify_transition(&self, transition: Transition) {
+        let instance = Some(transition.instance.as_str());
+        self.notify_sessions(
+            instance,
+            "notifications/instance_status",
+            json!({
+                "instance": transition.instance,
+                "status": transition.status,
+                "error": transition.error
+            }),
+        );
+        self.notify_sessions(
+            instance,
+            "notifications/resources/updated",
+            json!({ "uri": health::STATUS_URI }),
+        );
+    }
+
+    /// The client of `instance` for looking up completions, if `grant` may read it.
+    fn get_client(&self, instance: Option<&str>, grant: Option<&Grant>) -> Result<QBitClient> {
+        let instances = self.instances();
+        let (instance, client) = instances.resolve(instance)?;
+        if let Some(reason) = grant.and_then(|g| g.denial_to_read(instance)) {
+            return Err(PolicyDenied {
+                tool: "completion/complete".to_string(),
+                instance: instance.to_string(),
+                reason,
             }
-            "ping" => Ok(json!({})),
-            "prompts/list" => Ok(json!({
-                "prompts": self.get_prompt_definitions()
-            })),
-            "prompts/get" => {
-                if let Some(params) = req.params {
-                    let name = params.get("name").and_then(|n| n.as_str()).unwrap_or("");
-                    let args = params.get("arguments").unwrap_or(&Value::Null);
-                    self.handle_prompt_get(name, args).await
-                } else {
-                    anyhow::bail!("Missing params for prompts/get");
-                }
+            .into());
+        }
+        Ok(client.clone())
+    }
+
+    /// Another instance a tool works with, e.g. the target of a migration, named by the
+    /// tool argument `argument`. The policy and the client's grant must allow the tool
+    /// there too.
+    pub(crate) fn other_instance(
+        &self,
+        tool: &str,
+        argument: &str,
+        name: &str,
+        request: &RequestContext,
+    ) -> Result<(String, QBitClient)> {
+        let instances = self.instances();
+        let client = instances.client(name).ok_or_else(|| {
+            invalid_argument(
+                argument,
+                "name of a configured instance",
+                format!("Instance not found: {}", name),
+            )
+        })?;
+        if let Some(reason) = self.denial(&instances, tool, name, request.grant()) {
+            return Err(PolicyDenied {
+                tool: tool.to_string(),
+                instance: name.to_string(),
+                reason,
             }
-            "tools/list" => Ok(json!({
-                "tools": self.get_tool_definitions()
-            })),
-            "tools/call" => {
-                if let Some(params) = req.params {
-                    let name = params.get("name").and_then(|n| n.as_str()).unwrap_or("");
-                    let args = params.get("arguments").unwrap_or(&Value::Null);
-                    self.call_tool(name, args).await
-                } else {
-                    anyhow::bail!("Missing params for tools/call");
+            .into());
+        }
+        Ok((name.to_string(), client.clone()))
+    }
+
+    /// Why `tool` may not run on `instance`: the instance's policy or the client's grant.
+    fn denial(
+        &self,
+        instances: &Instances,
+        tool: &str,
+        instance: &str,
+        grant: Option<&Grant>,
+    ) -> Option<&'static str> {
+        let read_only = self.tools.is_read_only(tool);
+        instances
+            .policy_for(instance)
+            .denial(tool, read_only)
+            .or_else(|| grant.and_then(|g| g.denial(tool, instance, read_only)))
+    }
+
+    /// The instances a tool call targets, and whether it fans out: `instance: "*"` or a
+    /// list of names, which only tools that fan out accept.
+    fn resolve_targets<'a>(
+        &self,
+        instances: &'a Instances,
+        tool: &str,
+        args: &'a Value,
+    ) -> Result<(Vec<(&'a str, &'a QBitClient)>, bool)> {
+        let expected = "name of a configured instance";
+        let names: Vec<&str> = match args.get("instance") {
+            Some(Value::String(all)) if all == "*" => instances.names(),
+            Some(Value::Array(list)) => {
+                let mut names = Vec::new();
+                for name in list {
+                    let name = name.as_str().ok_or_else(|| {
+                        invalid_argument(
+                            "instance",
+                            expected,
+                            "Invalid instance: expected a list of instance names".to_string(),
+                        )
+                    })?;
+                    if !names.contains(&name) {
+                        names.push(name);
+                    }
                 }
-            }
-            "resources/list" => Ok(json!({
-                "resources": self.get_resource_definitions()
-            })),
-            "resources/templates/list" => Ok(json!({
-                "resourceTemplates": self.get_resource_template_definitions()
-            })),
-            "resources/read" => {
-                if let Some(params) = req.params {
-                    let uri = params.get("uri").and_then(|n| n.as_str()).unwrap_or("");
-                    self.handle_resource_read(uri).await
-                } else {
-                    anyhow::bail!("Missing params for resources/read");
+                if names.is_empty() {
+                    return Err(invalid_argument(
+                        "instance",
+                        expected,
+                        "Invalid instance: the list of instances is empty".to_string(),
+                    ));
                 }
+                names
             }
             _ => {
-                anyhow::bail!("Method not found: {}", req.method);
+                return Ok((vec![instances.resolve(opt_str(args, "instance")?)?], false));
             }
+        };
+        if !self.tools.fans_out(tool) {
+            return Err(invalid_argument(
+                "instance",
+                expected,
+                format!(
+                    "{} targets one instance at a time; \"*\" and lists of instances work with: {}",
+                    tool,
+                    self.tools.fan_out_tools().join(", ")
+                ),
+            ));
         }
-    }
-
-    fn get_resource_definitions(&self) -> Vec<Value> {
-        let mut res = Vec::new();
-        for name in self.clients.keys() {
-            res.push(json!({
-                "uri": format!("qbittorrent://{}/torrents", name),
-                "name": format!("Torrent List ({})", name),
-                "description": format!("A live list of all torrents on instance: {}", name),
-                "mimeType": "application/json"
-            }));
-            res.push(json!({
-                "uri": format!("qbittorrent://{}/transfer", name),
-                "name": format!("Global Transfer Info ({})", name),
-                "description": format!("Current speeds and limits on instance: {}", name),
-                "mimeType": "application/json"
-            }));
-            res.push(json!({
-                "uri": format!("qbittorrent://{}/categories", name),
-                "name": format!("Categories ({})", name),
-                "description": format!("All defined categories on instance: {}", name),
-                "mimeType": "application/json"
-            }));
+        let targets = names
+            .into_iter()
+            .map(|name| instances.resolve(Some(name)))
+            .collect::<Result<Vec<_>>>()?;
+        if targets.is_empty() {
+            anyhow::bail!("No instances configured");
         }
-        res
+        Ok((targets, true))
     }
 
-    fn get_resource_template_definitions(&self) -> Vec<Value> {
-        vec![
-            json!({
-                "uriTemplate": "qbittorrent://{instance}/torrent/{hash}/properties",
-                "name": "Torrent Properties",
-                "description": "Detailed properties and metadata for a specific torrent",
-                "mimeType": "application/json"
-            }),
-            json!({
-                "uriTemplate": "qbittorrent://{instance}/torrent/{hash}/files",
-                "name": "Torrent Files",
-                "description": "List of files and their progress within a specific torrent",
-                "mimeType": "application/json"
-            }),
-            json!({
-                "uriTemplate": "qbittorrent://{instance}/torrent/{hash}/trackers",
-                "name": "Torrent Trackers",
-                "description": "Current trackers and their status for a specific torrent",
-                "mimeType": "application/json"
-            }),
-        ]
-    }
-
-    async fn handle_resource_read(&self, uri: &str) -> Result<Value> {
-        let re_torrents = Regex::new(r"qbittorrent://([^/]+)/torrents")?;
-        let re_transfer = Regex::new(r"qbittorrent://([^/]+)/transfer")?;
-        let re_categories = Regex::new(r"qbittorrent://([^/]+)/categories")?;
-        let re_props = Regex::new(r"qbittorrent://([^/]+)/torrent/([^/]+)/properties")?;
-        let re_files = Regex::new(r"qbittorrent://([^/]+)/torrent/([^/]+)/files")?;
-        let re_trackers = Regex::new(r"qbittorrent://([^/]+)/torrent/([^/]+)/trackers")?;
-
-        if let Some(caps) = re_torrents.captures(uri) {
-            let instance = &caps[1];
-            let client = self.get_client(Some(instance))?;
-            let torrents = client
-                .get_torrent_list(None, None, None, None, None, None, None)
-                .await?;
-            let content = serde_json::to_string_pretty(&torrents)?;
-            return Ok(json!({
-                "contents": [{
-                    "uri": uri,
-                    "mimeType": "application/json",
-                    "text": content
-                }]
-            }));
-        }
-
-        if let Some(caps) = re_transfer.captures(uri) {
-            let instance = &caps[1];
-            let client = self.get_client(Some(instance))?;
-            let info = client.get_global_transfer_info().await?;
-            let content = serde_json::to_string_pretty(&info)?;
-            return Ok(json!({
-                "contents": [{
-                    "uri": uri,
-                    "mimeType": "application/json",
-                    "text": content
-                }]
-            }));
-        }
-
-        if let Some(caps) = re_categories.captures(uri) {
-            let instance = &caps[1];
-            let client = self.get_client(Some(instance))?;
-            let categories = client.get_categories().await?;
-            let content = serde_json::to_string_pretty(&categories)?;
-            return Ok(json!({
-                "contents": [{
-                    "uri": uri,
-                    "mimeType": "application/json",
-                    "text": content
-                }]
-            }));
-        }
-
-        if let Some(caps) = re_props.captures(uri) {
-            let instance = &caps[1];
-            let hash = &caps[2];
-            let client = self.get_client(Some(instance))?;
-            let props = client.get_torrent_properties(hash).await?;
-            let content = serde_json::to_string_pretty(&props)?;
-            return Ok(json!({
-                "contents": [{
-                    "uri": uri,
-                    "mimeType": "application/json",
-                    "text": content
-                }]
-            }));
-        }
+    pub async fn run_stdio(&mut self) -> Result<()> {
+        use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, stdin, stdout};
+        let mut reader = BufReader::new(stdin()).lines();
 
-        if let Some(caps) = re_files.captures(uri) {
-            let instance = &caps[1];
-            let hash = &caps[2];
-            let client = self.get_client(Some(instance))?;
-            let files = client.get_torrent_files(hash).await?;
-            let content = serde_json::to_string_pretty(&files)?;
-            return Ok(json!({
-                "contents": [{
-                    "uri": uri,
-                    "mimeType": "application/json",
-                    "text": content
-                }]
-            }));
-        }
+        // A single writer owns stdout so that responses and notifications emitted by
+        // concurrently running handlers never interleave.
+        let (peer, mut outbound) = Peer::channel();
+        let peer = peer.with_limit(self.max_in_flight);
+        self.connect(&peer);
+        let writer = tokio::spawn(async move {
+            let mut stdout = stdout();
+            while let Some(message) = outbound.recv().await {
+                let out = serde_json::to_string(&message)? + "\n";
+                stdout.write_all(out.as_bytes()).await?;
+                stdout.flush().await?;
+            }
+            anyhow::Ok(())
+        });
 
-        if let Some(caps) = re_trackers.captures(uri) {
-            let instance = &caps[1];
-            let hash = &caps[2];
-            let client = self.get_client(Some(instance))?;
-            let trackers = client.get_torrent_trackers(hash).await?;
-            let content = serde_json::to_string_pretty(&trackers)?;
-            return Ok(json!({
-                "contents": [{
-                    "uri": uri,
-                    "mimeType": "application/json",
-                    "text": content
-                }]
-            }));
-        }
+        // Requests beyond this many, running or waiting for the peer's limiter, are not
+        // read until some finish, so that a flood of requests cannot pile up tasks
+        let queue = Arc::new(Semaphore::new(self.max_in_flight * QUEUED_PER_SLOT));
+        let tasks = TaskTracker::new();
 
-        // Fallback for legacy URIs (without instance) - use default client
-        match uri {
-            "qbittorrent://torrents" => {
-                let client = self.get_client(None)?;
-                let torrents = client
-                    .get_torrent_list(None, None, None, None, None, None, None)
-                    .await?;
-                let content = serde_json::to_string_pretty(&torrents)?;
-                Ok(json!({
-                    "contents": [{
-                        "uri": uri,
-                        "mimeType": "application/json",
-                        "text": content
-                    }]
-                }))
-            }
-            "qbittorrent://transfer" => {
-                let client = self.get_client(None)?;
-                let info = client.get_global_transfer_info().await?;
-                let content = serde_json::to_string_pretty(&info)?;
-                Ok(json!({
-                    "contents": [{
-                        "uri": uri,
-                        "mimeType": "application/json",
-                        "text": content
-                    }]
-                }))
-            }
-            "qbittorrent://categories" => {
-                let client = self.get_client(None)?;
-                let categories = client.get_categories().await?;
-                let content = serde_json::to_string_pretty(&categories)?;
-                Ok(json!({
-                    "contents": [{
-                        "uri": uri,
-                        "mimeType": "application/json",
-                        "text": content
-                    }]
-                }))
+        loop {
+            if !self.is_running() {
+                break;
             }
-            _ => anyhow::bail!("Resource not found: {}", uri),
-        }
-    }
-
-    fn get_prompt_definitions(&self) -> Vec<Value> {
-        vec![
-            json!({
-                "name": "troubleshoot_torrent",
-                "description": "Unified troubleshooting for various torrent issues (stalled, slow, connection, etc.)",
-                "arguments": [
-                    {
-                        "name": "hash",
-                        "description": "Torrent hash to troubleshoot (optional for general connection issues)",
-                        "required": false
-                    },
-                    {
-                        "name": "issue_type",
-                        "description": "Type of issue: 'stalled', 'slow', 'connection', or 'general'",
-                        "required": true
-                    },
-                    {
-                        "name": "instance",
-                        "description": "Instance name (optional)",
-                        "required": false
-                    }
-                ]
-            }),
-            json!({
-                "name": "analyze_disk_space",
-                "description": "Check if there is enough disk space for current downloads",
-                "arguments": [
-                    {
-                        "name": "instance",
-                        "description": "Instance name (optional)",
-                        "required": false
+            tokio::task::yield_now().await;
+            tokio::select! {
+                line_res = reader.next_line() => {
+                    let line = match line_res? {
+                        Some(l) => l,
+                        None => break, // EOF
+                    };
+                    let input = line.trim();
+                    if input.is_empty() {
+                        continue;
                     }
-                ]
-            }),
-            json!({
-                "name": "rules-of-engagement",
-                "description": "Get the behavioral rules and best practices for interacting with this qBittorrent MCP server",
-                "arguments": []
-            }),
-        ]
-    }
 
-    async fn handle_prompt_get(&self, name: &str, args: &Value) -> Result<Value> {
-        let instance = args
-            .get("instance")
-            .and_then(|v| v.as_str())
-            .unwrap_or("default");
-
-        match name {
-            "troubleshoot_torrent" => {
-                let issue_type = args
-                    .get("issue_type")
-                    .and_then(|v| v.as_str())
-                    .unwrap_or("general");
-                let hash = args.get("hash").and_then(|v| v.as_str());
-
-                let prompt_text = match issue_type {
-                    "stalled" | "slow" => {
-                        let h = hash.ok_or(anyhow::anyhow!(
-                            "Missing hash for stalled/slow troubleshooting"
-                        ))?;
-                        format!(
-                            "I have a torrent with hash '{}' on instance '{}' that is {} . \
-                             Please investigate it. Follow these steps:\n\
-                             1. Check the torrent details using 'inspect_torrent'.\n\
-                             2. Look for global limits or mode using 'get_system_info'.\n\
-                             After investigating, suggest specific fixes (like re-announcing, toggling sequential download, or changing limits via 'manage_torrents').",
-                            h, instance, issue_type
-                        )
-                    }
-                    "connection" => {
-                        format!(
-                            "I think I have connection issues on instance '{}'. Please check my DHT node count and connection status via 'get_system_info', \
-                             and verify if alternative speed limits are accidentally enabled.",
-                            instance
-                        )
-                    }
-                    _ => {
-                        format!(
-                            "Please provide a general health check for instance '{}'. \
-                             Check global transfer info and list active torrents to identify any potential issues.",
-                            instance
-                        )
-                    }
-                };
+                    debug!("Received: {}", input);
 
-                Ok(json!({
-                    "description": format!("Troubleshooting for {} issue on instance {}", issue_type, instance),
-                    "messages": [
+                    match serde_json::from_str::<Value>(input) {
+                        Ok(message)
+                            if message.is_object()
+                                && (message.get("id").is_none()
+                                    || message.get("method").is_none()) =>
                         {
-                            "role": "user",
-                            "content": {
-                                "type": "text",
-                                "text": prompt_text
+                            // Notifications (including cancellations) and responses to our own
+                            // requests are cheap and must not queue behind running requests,
+                            // which may be the ones waiting for them.
+                            if let Some(resp) = self.handle_message(message, &peer).await {
+                                peer.send(resp);
                             }
+                            self.flush_notifications(&peer);
                         }
-                    ]
-                }))
-            }
-            "analyze_disk_space" => Ok(json!({
-                "description": format!("Analyze disk space on instance {}", instance),
-                "messages": [
-                    {
-                        "role": "user",
-                        "content": {
-                            "type": "text",
-                            "text": format!(
-                                "I want to check if I have enough disk space for my downloads on instance '{}'. \
-                                 Please check the current free space on disk and compare it with the total size of active/downloading torrents. \
-                                 You can get global transfer info and list all torrents to calculate the required space.",
-                                instance
-                            )
-                        }
-                    }
-                ]
-            })),
-            "rules-of-engagement" => Ok(json!({
-                "description": "Rules of Engagement for qBittorrent MCP",
-                "messages": [
-                    {
-                        "role": "user",
-                        "content": {
-                            "type": "text",
-                            "text": "Please provide the Rules of Engagement for this MCP server."
+                        Ok(message) => {
+                            let server = self.clone();
+                            let peer = peer.clone();
+                            let Ok(queued) = queue.clone().acquire_owned().await else {
+                                break;
+                            };
+                            tasks.spawn(async move {
+                                let _queued = queued;
+                                if let Some(resp) = server.handle_message(message, &peer).await {
+                                    peer.send(resp);
+                                }
+                                // Queued after the response, so e.g. tools/list_changed from
+                                // show_all_tools follows the result that caused it.
+                                server.flush_notifications(&peer);
+                            });
                         }
-                    },
-                    {
-                        "role": "assistant",
-                        "content": {
-                            "type": "text",
-                            "text": "As an AI agent interacting with the qBittorrent MCP server, you must adhere to the following Rules of Engagement:\n\n\
-                                     1. **State Verification**: Always verify the current state of a torrent (via `list_torrents` or resources) before performing actions like pause, resume, or delete.\n\
-                                     2. **Destructive Actions**: Clearly inform the user and obtain confirmation before calling `delete_torrent` or `shutdown_app`. For these \"destructive\" actions, use the `destructiveHint` annotation or require a separate confirmation step.\n\
-                                     3. **Search Etiquette**: Search is asynchronous. Use `get_search_results` for polling and always call `stop_search` once finished to save resources.\n\
-                                     4. **Error Handling**: Treat errors as information for self-correction. Return helpful hints and use `isError: true` to prevent hallucination.\n\
-                                     5. **Idempotency**: Avoid redundant commands (e.g., do not pause an already paused torrent).\n\
-                                     6. **Semantic Feedback**: Translate technical tool results into meaningful context for the user.\n\
-                                     7. **Security**: Never expose sensitive credentials or session cookies in logs or to the user."
+                        Err(e) => {
+                            error!("Failed to parse JSON-RPC: {}", e);
+                            peer.send(response_value(parse_error_response(&e)));
                         }
                     }
-                ]
-            })),
-            _ => anyhow::bail!("Prompt not found: {}", name),
-        }
-    }
-
-    fn get_tool_definitions(&self) -> Vec<Value> {
-        let state = self.state.lock().unwrap();
-        if state.lazy_mode && !state.tools_loaded {
-            return vec![
-                json!({
-                    "name": "list_torrents",
-                    "description": "List all torrents with optional filtering and sorting",
-                    "inputSchema": {
-                        "type": "object",
-                        "properties": {
-                            "instance": { "type": "string", "description": "Optional: Name of the qBittorrent instance" },
-                            "filter": { "type": "string", "description": "Filter by status (all, downloading, completed, paused, active, inactive, resumed, stalled, stalled_uploading, stalled_downloading, errored)" },
-                            "category": { "type": "string", "description": "Filter by category" },
-                            "tag": { "type": "string", "description": "Filter by tag" },
-                            "sort": { "type": "string", "description": "Sort by column name" },
-                            "reverse": { "type": "boolean", "description": "True to reverse sort order" },
-                            "limit": { "type": "integer", "description": "Maximum number of torrents to return" },
-                            "offset": { "type": "integer", "description": "Number of torrents to skip" }
-                        },
-                        "required": []
-                    }
-                }),
-                json!({
-                    "name": "show_all_tools",
-                    "description": "Enable all available tools",
-                    "inputSchema": {
-                        "type": "object",
-                        "properties": {},
-                        "required": []
-                    }
-                }),
-            ];
-        }
-
-        let mut tools = Vec::new();
-        tools.extend(self.get_torrent_tools());
-        tools.extend(self.get_search_tools());
-        tools.extend(self.get_transfer_tools());
-        tools.extend(self.get_rss_tools());
-        tools.extend(self.get_app_tools());
-
-        // Inject instance parameter into all tools
-        tools
-            .into_iter()
-            .map(|mut t| {
-                let props = t
-                    .get_mut("inputSchema")
-                    .and_then(|s| s.get_mut("properties"));
-
-                if let Some(obj) = props.and_then(|p| p.as_object_mut()) {
-                    obj.insert(
-                        "instance".to_string(),
-                        json!({ "type": "string", "description": "Optional: Name of the qBittorrent instance to target" }),
-                    );
-                }
-                t
-            })
-            .collect()
-    }
-
-    fn get_torrent_tools(&self) -> Vec<Value> {
-        vec![
-            json!({
-                "name": "list_torrents",
-                "description": "List all torrents with optional filtering and sorting",
-                "inputSchema": {
-                    "type": "object",
-                    "properties": {
-                        "filter": { "type": "string", "description": "Filter by status (all, downloading, completed, paused, active, inactive, resumed, stalled, stalled_uploading, stalled_downloading, errored)" },
-                        "category": { "type": "string", "description": "Filter by category" },
-                        "tag": { "type": "string", "description": "Filter by tag" },
-                        "sort": { "type": "string", "description": "Sort by column name (e.g., name, size, progress, added_on, dlspeed, upspeed, ratio, eta, state, category, tags)" },
-                        "reverse": { "type": "boolean", "description": "True to reverse sort order" },
-                        "limit": { "type": "integer", "description": "Maximum number of torrents to return" },
-                        "offset": { "type": "integer", "description": "Number of torrents to skip" },
-                        "include_properties": { "type": "boolean", "description": "Include detailed properties for each torrent" },
-                        "include_files": { "type": "boolean", "description": "Include file list for each torrent" }
-                    },
-                    "required": []
-                }
-            }),
-            json!({
-                "name": "manage_torrents",
-                "description": "Unified tool for multiple torrent actions (pause, resume, category, tags, limits, etc.)",
-                "inputSchema": {
-                    "type": "object",
-                    "properties": {
-                        "hashes": { "type": "string", "description": "Torrent hashes (pipe-separated)" },
-                        "action": {
-                            "type": "string",
-                            "enum": [
-                                "pause", "resume", "reannounce", "recheck",
-                                "set_category", "add_tags", "remove_tags",
-                                "set_share_limits", "set_speed_limits",
-                                "toggle_sequential", "toggle_first_last_prio",
-                                "set_force_start", "set_super_seeding"
-                            ],
-                            "description": "Action to perform"
-                        },
-                        "category": { "type": "string", "description": "For 'set_category'" },
-                        "tags": { "type": "string", "description": "For 'add_tags' or 'remove_tags' (comma-separated)" },
-                        "ratio_limit": { "type": "number", "description": "For 'set_share_limits'" },
-                        "seeding_time_limit": { "type": "integer", "description": "For 'set_share_limits' (minutes)" },
-                        "inactive_seeding_time_limit": { "type": "integer", "description": "For 'set_share_limits' (minutes)" },
-                        "dl_limit": { "type": "integer", "description": "For 'set_speed_limits' (bytes/s)" },
-                        "up_limit": { "type": "integer", "description": "For 'set_speed_limits' (bytes/s)" },
-                        "value": { "type": "boolean", "description": "For 'set_force_start' or 'set_super_seeding'" }
-                    },
-                    "required": ["hashes", "action"]
-                }
-            }),
-            json!({
-                "name": "add_torrent",
-                "description": "Add a new torrent",
-                "inputSchema": {
-                    "type": "object",
-                    "properties": {
-                        "url": { "type": "string", "description": "Magnet URI or HTTP URL" },
-                        "save_path": { "type": "string", "description": "Optional save path" },
-                        "category": { "type": "string", "description": "Optional category" }
-                    },
-                    "required": ["url"]
-                }
-            }),
-            json!({
-                "name": "delete_torrent",
-                "description": "Delete a torrent. DESTRUCTIVE: Inform the user and confirm before calling, especially if delete_files is true.",
-                "inputSchema": {
-                    "type": "object",
-                    "properties": {
-                        "hash": { "type": "string", "description": "Torrent hash (pipe-separated for multiple)" },
-                        "delete_files": { "type": "boolean", "description": "Also delete files from disk" }
-                    },
-                    "required": ["hash", "delete_files"]
-                }
-            }),
-            json!({
-                "name": "get_torrent_files",
-                "description": "Get file list of a torrent",
-                "inputSchema": {
-                    "type": "object",
-                    "properties": {
-                        "hash": { "type": "string", "description": "Torrent hash" }
-                    },
-                    "required": ["hash"]
-                }
-            }),
-            json!({
-                "name": "inspect_torrent",
-                "description": "Comprehensive inspection of a torrent (properties, files, and trackers in one call)",
-                "inputSchema": {
-                    "type": "object",
-                    "properties": {
-                        "hash": { "type": "string", "description": "Torrent hash" }
-                    },
-                    "required": ["hash"]
-                }
-            }),
-            json!({
-                "name": "get_categories",
-                "description": "Get all categories",
-                "inputSchema": {
-                    "type": "object",
-                    "properties": {},
-                    "required": []
-                }
-            }),
-            json!({
-                "name": "wait_for_torrent_status",
-                "description": "Poll a torrent until it reaches a desired state or timeout",
-                "inputSchema": {
-                    "type": "object",
-                    "properties": {
-                        "hash": { "type": "string", "description": "Torrent hash" },
-                        "target_status": { "type": "string", "description": "Status to wait for (e.g., uploading, stalledUP)" },
-                        "timeout_seconds": { "type": "integer", "description": "Max wait time (default 60, max 300)" }
-                    },
-                    "required": ["hash", "target_status"]
-                }
-            }),
-            json!({
-                "name": "cleanup_completed",
-                "description": "Bulk remove completed torrents based on ratio or age. DESTRUCTIVE: Inform the user and confirm before calling.",
-                "inputSchema": {
-                    "type": "object",
-                    "properties": {
-                        "min_ratio": { "type": "number", "description": "Minimum ratio to trigger removal" },
-                        "max_age_days": { "type": "integer", "description": "Maximum age in days since completion to trigger removal" },
-                        "delete_files": { "type": "boolean", "description": "Also delete downloaded files from disk" }
-                    },
-                    "required": ["delete_files"]
-                }
-            }),
-            json!({
-                "name": "mass_rename",
-                "description": "Rename files in a torrent using a regex pattern",
-                "inputSchema": {
-                    "type": "object",
-                    "properties": {
-                        "hash": { "type": "string", "description": "Torrent hash" },
-                        "pattern": { "type": "string", "description": "Regex pattern to match" },
-                        "replacement": { "type": "string", "description": "Replacement string (supports $1, $2, etc.)" }
-                    },
-                    "required": ["hash", "pattern", "replacement"]
-                }
-            }),
-            json!({
-                "name": "find_duplicates",
-                "description": "Find duplicate torrents by name",
-                "inputSchema": {
-                    "type": "object",
-                    "properties": {},
-                    "required": []
-                }
-            }),
-            json!({
-                "name": "add_trackers",
-                "description": "Add trackers to torrents",
-                "inputSchema": {
-                    "type": "object",
-                    "properties": {
-                        "hashes": { "type": "string", "description": "Torrent hashes (pipe-separated)" },
-                        "urls": { "type": "string", "description": "URLs of the trackers (newline-separated)" }
-                    },
-                    "required": ["hashes", "urls"]
-                }
-            }),
-            json!({
-                "name": "edit_tracker",
-                "description": "Edit a tracker URL for a torrent",
-                "inputSchema": {
-                    "type": "object",
-                    "properties": {
-                        "hash": { "type": "string", "description": "Torrent hash" },
-                        "orig_url": { "type": "string", "description": "Original tracker URL" },
-                        "new_url": { "type": "string", "description": "New tracker URL" }
-                    },
-                    "required": ["hash", "orig_url", "new_url"]
                 }
-            }),
-            json!({
-                "name": "remove_trackers",
-                "description": "Remove trackers from torrents",
-                "inputSchema": {
-                    "type": "object",
-                    "properties": {
-                        "hashes": { "type": "string", "description": "Torrent hashes (pipe-separated)" },
-                        "urls": { "type": "string", "description": "URLs of the trackers to remove (newline-separated)" }
-                    },
-                    "required": ["hashes", "urls"]
-                }
-            }),
-            json!({
-                "name": "rename_folder",
-                "description": "Rename a folder in a torrent",
-                "inputSchema": {
-                    "type": "object",
-                    "properties": {
-                        "hash": { "type": "string", "description": "Torrent hash" },
-                        "old_path": { "type": "string", "description": "Current folder path" },
-                        "new_path": { "type": "string", "description": "New folder path" }
-                    },
-                    "required": ["hash", "old_path", "new_path"]
-                }
-            }),
-            json!({
-                "name": "set_file_priority",
-                "description": "Set priority for files in a torrent",
-                "inputSchema": {
-                    "type": "object",
-                    "properties": {
-                        "hash": { "type": "string", "description": "Torrent hash" },
-                        "id": { "type": "string", "description": "File IDs (pipe-separated)" },
-                        "priority": { "type": "integer", "description": "Priority (0: Do not download, 1: Normal, 6: High, 7: Maximal)" }
-                    },
-                    "required": ["hash", "id", "priority"]
-                }
-            }),
-        ]
-    }
-
-    fn get_search_tools(&self) -> Vec<Value> {
-        vec![
-            json!({
-                "name": "search_torrents",
-                "description": "Search for torrents. ASYNCHRONOUS: Results might be incomplete on the first call. Use get_search_results for polling if needed.",
-                "inputSchema": {
-                    "type": "object",
-                    "properties": {
-                        "query": { "type": "string", "description": "Search query" },
-                        "category": { "type": "string", "description": "Optional category" }
-                    },
-                    "required": ["query"]
-                }
-            }),
-            json!({
-                "name": "install_search_plugin",
-                "description": "Install a search plugin",
-                "inputSchema": {
-                    "type": "object",
-                    "properties": {
-                        "url": { "type": "string", "description": "URL to the plugin file" }
-                    },
-                    "required": ["url"]
-                }
-            }),
-            json!({
-                "name": "uninstall_search_plugin",
-                "description": "Uninstall a search plugin",
-                "inputSchema": {
-                    "type": "object",
-                    "properties": {
-                        "name": { "type": "string", "description": "Name of the plugin" }
-                    },
-                    "required": ["name"]
-                }
-            }),
-            json!({
-                "name": "enable_search_plugin",
-                "description": "Enable or disable a search plugin",
-                "inputSchema": {
-                    "type": "object",
-                    "properties": {
-                        "name": { "type": "string", "description": "Name of the plugin" },
-                        "enable": { "type": "boolean", "description": "True to enable, False to disable" }
-                    },
-                    "required": ["name", "enable"]
-                }
-            }),
-            json!({
-                "name": "update_search_plugins",
-                "description": "Update all search plugins",
-                "inputSchema": {
-                    "type": "object",
-                    "properties": {},
-                    "required": []
-                }
-            }),
-            json!({
-                "name": "get_search_plugins",
-                "description": "List installed search plugins",
-                "inputSchema": {
-                    "type": "object",
-                    "properties": {},
-                    "required": []
-                }
-            }),
-        ]
-    }
-
-    fn get_transfer_tools(&self) -> Vec<Value> {
-        vec![json!({
-            "name": "ban_peers",
-            "description": "Ban a list of peers",
-            "inputSchema": {
-                "type": "object",
-                "properties": {
-                    "peers": { "type": "string", "description": "Peers to ban (host:port, pipe-separated)" }
-                },
-                "required": ["peers"]
-            }
-        })]
-    }
-
-    fn get_rss_tools(&self) -> Vec<Value> {
-        vec![
-            json!({
-                "name": "add_rss_feed",
-                "description": "Add a new RSS feed",
-                "inputSchema": {
-                    "type": "object",
-                    "properties": {
-                        "url": { "type": "string", "description": "URL of the RSS feed" },
-                        "path": { "type": "string", "description": "Internal path/name for the feed" }
-                    },
-                    "required": ["url", "path"]
-                }
-            }),
-            json!({
-                "name": "get_rss_feeds",
-                "description": "Get all RSS feeds and their items",
-                "inputSchema": {
-                    "type": "object",
-                    "properties": {},
-                    "required": []
-                }
-            }),
-            json!({
-                "name": "set_rss_rule",
-                "description": "Create or update an RSS auto-download rule",
-                "inputSchema": {
-                    "type": "object",
-                    "properties": {
-                        "name": { "type": "string", "description": "Name of the rule" },
-                        "definition": { "type": "string", "description": "JSON string defining the rule" }
-                    },
-                    "required": ["name", "definition"]
-                }
-            }),
-            json!({
-                "name": "get_rss_rules",
-                "description": "Get all RSS auto-download rules",
-                "inputSchema": {
-                    "type": "object",
-                    "properties": {},
-                    "required": []
-                }
-            }),
-            json!({
-                "name": "move_rss_item",
-                "description": "Move an RSS item (feed or folder)",
-                "inputSchema": {
-                    "type": "object",
-                    "properties": {
-                        "item_path": { "type": "string", "description": "Current path of the item" },
-                        "dest_path": { "type": "string", "description": "Destination path" }
-                    },
-                    "required": ["item_path", "dest_path"]
-                }
-            }),
-        ]
-    }
-
-    fn get_app_tools(&self) -> Vec<Value> {
-        vec![
-            json!({
-                "name": "set_app_preferences",
-                "description": "Set one or more application preferences",
-                "inputSchema": {
-                    "type": "object",
-                    "properties": {
-                        "preferences": { "type": "string", "description": "JSON string of preferences to update" }
-                    },
-                    "required": ["preferences"]
-                }
-            }),
-            json!({
-                "name": "get_main_log",
-                "description": "Get the main application log",
-                "inputSchema": {
-                    "type": "object",
-                    "properties": {
-                        "severity": { "type": "string", "description": "Filter by severity (all, info, warning, critical)" },
-                        "last_id": { "type": "integer", "description": "Exclude logs with ID less than or equal to this" }
-                    },
-                    "required": []
-                }
-            }),
-            json!({
-                "name": "get_peer_log",
-                "description": "Get the peer connection log",
-                "inputSchema": {
-                    "type": "object",
-                    "properties": {
-                        "last_id": { "type": "integer", "description": "Exclude logs with ID less than or equal to this" }
-                    },
-                    "required": []
-                }
-            }),
-            json!({
-                "name": "get_system_info",
-                "description": "Comprehensive system information (transfer speeds, preferences, version, and build info in one call)",
-                "inputSchema": {
-                    "type": "object",
-                    "properties": {},
-                    "required": []
-                }
-            }),
-            json!({
-                "name": "shutdown_app",
-                "description": "Shutdown qBittorrent. DESTRUCTIVE: Inform the user and confirm before calling as this terminates the service.",
-                "inputSchema": {
-                    "type": "object",
-                    "properties": {},
-                    "required": []
-                }
-            }),
-        ]
-    }
+                _ = sleep(Duration::from_millis(100)) => {
+                    if !self.is_running() {
+                        break;
+                    }
+                    self.flush_notifications(&peer);
+                }
+            }
+        }
 
-    pub async fn call_tool(&self, name: &str, args: &Value) -> Result<Value> {
-        if name == "show_all_tools" {
-            return self.handle_show_all_tools();
+        // Let in-flight requests finish and flush their responses before exiting. Nothing
+        // answers requests to the client any more, so those waiting for one give up.
+        peer.fail_pending();
+        tasks.close();
+        if self.is_running() {
+            tasks.wait().await;
         }
 
-        let instance = args.get("instance").and_then(|v| v.as_str());
-        let client = self.get_client(instance)?;
+        // The writer stops once every handle to the connection is gone
+        self.disconnect(&peer);
+        drop(peer);
+        writer.await??;
+        Ok(())
+    }
 
-        match name {
-            // Torrent Management
-            "list_torrents" => self.handle_list_torrents(client, args).await,
-            "manage_torrents" => self.handle_manage_torrents(client, args).await,
-            "add_torrent" => self.handle_add_torrent(client, args).await,
-            "delete_torrent" => self.handle_delete_torrent(client, args).await,
-            "get_torrent_files" => self.handle_get_torrent_files(client, args).await,
-            "inspect_torrent" => self.handle_inspect_torrent(client, args).await,
-            "get_categories" => self.handle_get_categories(client).await,
-            "wait_for_torrent_status" => self.handle_wait_for_torrent_status(client, args).await,
-            "cleanup_completed" => self.handle_cleanup_completed(client, args).await,
-            "mass_rename" => self.handle_mass_rename(client, args).await,
-            "find_duplicates" => self.handle_find_duplicates(client).await,
-            "add_trackers" => self.handle_add_trackers(client, args).await,
-            "edit_tracker" => self.handle_edit_tracker(client, args).await,
-            "remove_trackers" => self.handle_remove_trackers(client, args).await,
-            "rename_folder" => self.handle_rename_folder(client, args).await,
-            "set_file_priority" => self.handle_set_file_priority(client, args).await,
-
-            // Search
-            "search_torrents" => self.handle_search_torrents(client, args).await,
-            "install_search_plugin" => self.handle_install_search_plugin(client, args).await,
-            "uninstall_search_plugin" => self.handle_uninstall_search_plugin(client, args).await,
-            "enable_search_plugin" => self.handle_enable_search_plugin(client, args).await,
-            "update_search_plugins" => self.handle_update_search_plugins(client).await,
-            "get_search_plugins" => self.handle_get_search_plugins(client).await,
-
-            // RSS
-            "add_rss_feed" => self.handle_add_rss_feed(client, args).await,
-            "get_rss_feeds" => self.handle_get_rss_feeds(client).await,
-            "set_rss_rule" => self.handle_set_rss_rule(client, args).await,
-            "get_rss_rules" => self.handle_get_rss_rules(client).await,
-            "move_rss_item" => self.handle_move_rss_item(client, args).await,
-
-            // Transfer / App
-            "ban_peers" => self.handle_ban_peers(client, args).await,
-            "set_app_preferences" => self.handle_set_app_preferences(client, args).await,
-            "get_main_log" => self.handle_get_main_log(client, args).await,
-            "get_peer_log" => self.handle_get_peer_log(client, args).await,
-            "get_system_info" => self.handle_get_system_info(client).await,
-            "shutdown_app" => self.handle_shutdown_app(client).await,
-
-            _ => anyhow::bail!("Unknown tool: {}", name),
+    /// Handles one decoded JSON-RPC message, either a single request or a batch, and
+    /// returns what should be written back to the client, if anything.
+    pub async fn handle_message(&self, message: Value, peer: &Peer) -> Option<Value> {
+        match message {
+            Value::Array(batch) if batch.is_empty() => Some(response_value(error_response(
+                None,
+                JsonRpcError::new(INVALID_REQUEST, "Invalid Request: empty batch"),
+            ))),
+            Value::Array(batch) => {
+                let responses: Vec<Value> = futures::future::join_all(
+                    batch.into_iter().map(|m| self.handle_single(m, peer)),
+                )
+                .await
+                .into_iter()
+                .flatten()
+                .collect();
+                // A batch made up only of notifications gets no response at all
+                (!responses.is_empty()).then_some(Value::Array(responses))
+            }
+            message => self.handle_single(message, peer).await,
         }
     }
 
-    fn handle_show_all_tools(&self) -> Result<Value> {
-        let mut state = self.state.lock().unwrap();
-        state.lazy_mode = false;
-        state.tools_loaded = true;
-        state.should_notify = true;
-        Ok(
-            json!({ "content": [{ "type": "text", "text": "All tools enabled. Please refresh your tool list." }] }),
-        )
+    async fn handle_single(&self, message: Value, peer: &Peer) -> Option<Value> {
+        if message.get("method").is_none()
+            && (message.get("result").is_some() || message.get("error").is_some())
+        {
+            if !peer.resolve(&message) {
+                debug!("Ignoring unexpected response from client: {}", message);
+            }
+            return None;
+        }
+
+        let req = match parse_request(message) {
+            Ok(req) => req,
+            Err(resp) => return Some(resp),
+        };
+        let id = req.id.clone();
+        let ctx = RequestContext::new(peer.clone());
+        let resp = self.handle_request_with_context(req, ctx).await;
+        build_response(id, resp).map(response_value)
+    }
+
+    pub async fn handle_request(&self, req: JsonRpcRequest) -> Result<Value> {
+        self.handle_request_with_context(req, RequestContext::default())
+            .await
     }
 
-    async fn handle_list_torrents(&self, client: &QBitClient, args: &Value) -> Result<Value> {
-        let filter = args.get("filter").and_then(|v| v.as_str());
-        let category = args.get("category").and_then(|v| v.as_str());
-        let tag = args.get("tag").and_then(|v| v.as_str());
-        let sort = args.get("sort").and_then(|v| v.as_str());
-        let reverse = args.get("reverse").and_then(|v| v.as_bool());
-        let limit = args.get("limit").and_then(|v| v.as_i64());
-        let offset = args.get("offset").and_then(|v| v.as_i64());
-
-        let include_properties = args
-            .get("include_properties")
-            .and_then(|v| v.as_bool())
-            .unwrap_or(false);
-        let include_files = args
-            .get("include_files")
-            .and_then(|v| v.as_bool())
-            .unwrap_or(false);
-
-        let torrents = client
-            .get_torrent_list(filter, category, tag, sort, reverse, limit, offset)
-            .await?;
-
-        if !include_properties && !include_files {
-            let text = serde_json::to_string_pretty(&torrents)?;
-            return Ok(json!({ "content": [{ "type": "text", "text": text }] }));
+    pub async fn handle_request_with_context(
+        &self,
+        req: JsonRpcRequest,
+        mut ctx: RequestContext,
+    ) -> Result<Value> {
+        if req.method == "notifications/cancelled" {
+            self.handle_cancelled(req.params.as_ref(), &ctx);
+            return Ok(Value::Null);
         }
 
-        let mut detailed_torrents = Vec::new();
-        for t in torrents {
-            let mut detailed = json!(t);
-            if include_properties && let Ok(props) = client.get_torrent_properties(&t.hash).await {
-                detailed["properties"] = json!(props);
-            }
-            if include_files && let Ok(files) = client.get_torrent_files(&t.hash).await {
-                detailed["files"] = json!(files);
-            }
-            detailed_torrents.push(detailed);
+        // Track requests on this connection so that a later notifications/cancelled can
+        // abort them. `initialize` must not be cancelled per the spec.
+        let guard = match (&ctx.peer, &req.id) {
+            (Some(peer), Some(id)) if req.method != "initialize" => Some(peer.track(id)),
+            _ => None,
+        };
+        let Some(guard) = guard else {
+            return self.dispatch(req, ctx).await;
+        };
+
+        ctx.cancellation = guard.token.clone();
+        // Wait for a slot only after the request is tracked, so that it can be cancelled
+        // while it waits
+        let limiter = ctx.peer.as_ref().and_then(|peer| peer.limiter.clone());
+        let _permit = match limiter {
+            Some(limiter) => tokio::select! {
+                permit = limiter.acquire_owned() => permit.ok(),
+                _ = guard.token.cancelled() => {
+                    debug!("Request {} was cancelled before it started", guard.key);
+                    return Err(RequestCancelled.into());
+                }
+            },
+            None => None,
+        };
+        tokio::select! {
+            res = self.dispatch(req, ctx) => res,
+            _ = guard.token.cancelled() => Err(RequestCancelled.into()),
         }
+    }
 
-        let text = serde_json::to_string_pretty(&detailed_torrents)?;
-        Ok(json!({ "content": [{ "type": "text", "text": text }] }))
-    }
-
-    async fn handle_manage_torrents(&self, client: &QBitClient, args: &Value) -> Result<Value> {
-        let hashes = args
-            .get("hashes")
-            .and_then(|v| v.as_str())
-            .ok_or(anyhow::anyhow!("Missing hashes"))?;
-        let action = args
-            .get("action")
-            .and_then(|v| v.as_str())
-            .ok_or(anyhow::anyhow!("Missing action"))?;
-
-        match action {
-            "pause" => client.pause_torrents(hashes).await?,
-            "resume" => client.resume_torrents(hashes).await?,
-            "reannounce" => client.reannounce_torrents(hashes).await?,
-            "recheck" => client.recheck_torrents(hashes).await?,
-            "set_category" => {
-                let category = args
-                    .get("category")
-                    .and_then(|v| v.as_str())
-                    .ok_or(anyhow::anyhow!("Missing category"))?;
-                client.set_category(hashes, category).await?;
-            }
-            "add_tags" => {
-                let tags = args
-                    .get("tags")
+    fn handle_cancelled(&self, params: Option<&Value>, ctx: &RequestContext) {
+        let Some(id) = params.and_then(|p| p.get("requestId")) else {
+            warn!("Ignoring notifications/cancelled without requestId");
+            return;
+        };
+        let reason = params
+            .and_then(|p| p.get("reason"))
+            .and_then(|r| r.as_str())
+            .unwrap_or("no reason given");
+
+        match &ctx.peer {
+            Some(peer) if peer.cancel(id) => info!("Cancelled request {}: {}", id, reason),
+            _ => debug!("Cancel for unknown or finished request {}", id),
+        }
+    }
+
+    async fn dispatch(&self, req: JsonRpcRequest, mut ctx: RequestContext) -> Result<Value> {
+        match req.method.as_str() {
+            "initialize" => {
+                let params = req.params.unwrap_or_default();
+                if let Some(peer) = &ctx.peer {
+                    peer.set_client_capabilities(
+                        params.get("capabilities").cloned().unwrap_or(json!({})),
+                    );
+                }
+                // Echo the client's version when we speak it, otherwise offer our latest
+                let version = params
+                    .get("protocolVersion")
                     .and_then(|v| v.as_str())
-                    .ok_or(anyhow::anyhow!("Missing tags"))?;
-                client.add_tags(hashes, tags).await?;
+                    .filter(|v| SUPPORTED_PROTOCOL_VERSIONS.contains(v))
+                    .unwrap_or(SUPPORTED_PROTOCOL_VERSIONS[0]);
+                Ok(self.initialize_result(version))
             }
-            "remove_tags" => {
-                let tags = args
-                    .get("tags")
-                    .and_then(|v| v.as_str())
-                    .ok_or(anyhow::anyhow!("Missing tags"))?;
-                client.remove_tags(hashes, tags).await?;
+            "notifications/initialized" => {
+                info!("Client initialized");
+                Ok(Value::Null)
             }
-            "set_share_limits" => {
-                let ratio_limit = args
-                    .get("ratio_limit")
-                    .and_then(|v| v.as_f64())
-                    .ok_or(anyhow::anyhow!("Missing ratio_limit"))?;
-                let seeding_time_limit = args
-                    .get("seeding_time_limit")
-                    .and_then(|v| v.as_i64())
-                    .ok_or(anyhow::anyhow!("Missing seeding_time_limit"))?;
-                let inactive_seeding_time_limit = args
-                    .get("inactive_seeding_time_limit")
-                    .and_then(|v| v.as_i64());
-                client
-                    .set_torrent_share_limits(
-                        hashes,
-                        ratio_limit,
-                        seeding_time_limit,
-                        inactive_seeding_time_limit,
-                    )
-                    .await?;
+            "ping" => Ok(json!({})),
+            "prompts/list" => Ok(json!({
+                "prompts": self.get_prompt_definitions()
+            })),
+            "prompts/get" => {
+                if let Some(params) = req.params {
+                    let name = params.get("name").and_then(|n| n.as_str()).unwrap_or("");
+                    let args = params.get("arguments").unwrap_or(&Value::Null);
+                    self.handle_prompt_get(name, args, ctx.grant()).await
+                } else {
+                    Err(JsonRpcError::invalid_params("Missing params for prompts/get").into())
+                }
             }
-            "set_speed_limits" => {
-                if let Some(limit) = args.get("dl_limit").and_then(|v| v.as_i64()) {
-                    client.set_torrent_download_limit(hashes, limit).await?;
+            "tools/list" => Ok(json!({
+                "tools": self.get_tool_definitions(&ctx)
+            })),
+            "tools/call" => {
+                if let Some(params) = req.params {
+                    let name = params.get("name").and_then(|n| n.as_str()).unwrap_or("");
+                    let args = params.get("arguments").unwrap_or(&Value::Null);
+                    ctx.progress_token = params
+                        .get("_meta")
+                        .and_then(|m| m.get("progressToken"))
+                        .cloned();
+                    match self.call_tool_with_context(name, args, &ctx).await {
+                        // Failures while running the tool are reported to the model as a
+                        // result; only bad calls are protocol errors.
+                        Err(e) if !e.is::<JsonRpcError>() && !is_cancelled(&e) => {
+                            Ok(tool_error_result(&e))
+                        }
+                        res => res,
+                    }
+                } else {
+                    Err(JsonRpcError::invalid_params("Missing params for tools/call").into())
                 }
-                if let Some(limit) = args.get("up_limit").and_then(|v| v.as_i64()) {
-                    client.set_torrent_upload_limit(hashes, limit).await?;
+            }
+            "logging/setLevel" => {
+                let level = arg_str(req.params.as_ref().unwrap_or(&Value::Null), "level")?;
+                let rank = logging::level_rank(level).ok_or_else(|| {
+                    invalid_argument(
+                        "level",
+                        "one of debug, info, notice, warning, error, critical, alert, emergency",
+                        format!("Invalid log level: {}", level),
+                    )
+                })?;
+                match &ctx.peer {
+                    Some(peer) => logging::subscribe(peer, rank),
+                    None => debug!("logging/setLevel without a connection to log to"),
                 }
+                Ok(json!({}))
             }
-            "toggle_sequential" => client.toggle_sequential_download(hashes).await?,
-            "toggle_first_last_prio" => client.toggle_first_last_piece_priority(hashes).await?,
-            "set_force_start" => {
-                let value = args
-                    .get("value")
-                    .and_then(|v| v.as_bool())
-                    .ok_or(anyhow::anyhow!("Missing value"))?;
-                client.set_force_start(hashes, value).await?;
+            "completion/complete" => {
+                if let Some(params) = req.params {
+                    self.handle_completion(&params, ctx.grant()).await
+                } else {
+                    Err(
+                        JsonRpcError::invalid_params("Missing params for completion/complete")
+                            .into(),
+                    )
+                }
             }
-            "set_super_seeding" => {
-                let value = args
-                    .get("value")
-                    .and_then(|v| v.as_bool())
-                    .ok_or(anyhow::anyhow!("Missing value"))?;
-                client.set_super_seeding(hashes, value).await?;
+            "resources/list" => Ok(json!({
+                "resources": self.get_resource_definitions(ctx.grant())
+            })),
+            "resources/templates/list" => Ok(json!({
+                "resourceTemplates": self.get_resource_template_definitions()
+            })),
+            "resources/read" => {
+                if let Some(params) = req.params {
+                    let uri = params.get("uri").and_then(|n| n.as_str()).unwrap_or("");
+                    self.handle_resource_read(uri, ctx.grant()).await
+                } else {
+                    Err(JsonRpcError::invalid_params("Missing params for resources/read").into())
+                }
             }
-            _ => anyhow::bail!("Unsupported action: {}", action),
+            _ => Err(JsonRpcError::method_not_found(&req.method).into()),
         }
+    }
 
-        Ok(
-            json!({ "content": [{ "type": "text", "text": format!("Action '{}' performed successfully on torrents.", action) }] }),
-        )
+    fn initialize_result(&self, protocol_version: &str) -> Value {
+        json!({
+            "protocolVersion": protocol_version,
+            "serverInfo": {
+                "name": "qbittorrent-mcp-rs",
+                "version": env!("CARGO_PKG_VERSION")
+            },
+            "capabilities": {
+                "completions": {},
+                "logging": {},
+                "tools": {
+                    "listChanged": true
+                },
+                "resources": {
+                    "listChanged": true,
+                    "subscribe": false
+                },
+                "prompts": {
+                    "listChanged": false
+                }
+            }
+        })
     }
 
-    async fn handle_search_torrents(&self, client: &QBitClient, args: &Value) -> Result<Value> {
-        let query = args
-            .get("query")
-            .and_then(|v| v.as_str())
-            .ok_or(anyhow::anyhow!("Missing query"))?;
-        let category = args.get("category").and_then(|v| v.as_str());
+    /// The resources `grant` may read.
+    fn get_resource_definitions(&self, grant: Option<&Grant>) -> Vec<Value> {
+        let instances = self.instances();
+        let readable: Vec<&str> = instances
+            .names()
+            .into_iter()
+            .filter(|name| grant.is_none_or(|g| g.can_read(name)))
+            .collect();
+        if readable.is_empty() {
+            return Vec::new();
+        }
+        let mut definitions = vec![json!({
+            "uri": health::STATUS_URI,
+            "name": "Instance Status",
+            "description": "Health of every configured instance: reachability, last successful poll, latency, Web API version, login state and consecutive failures",
+            "mimeType": "application/json"
+        })];
+        definitions.extend(resources::definitions(readable));
+        definitions
+    }
 
-        let id = client.start_search(query, category).await?;
+    fn get_resource_template_definitions(&self) -> Vec<Value> {
+        resources::template_definitions()
+    }
 
-        let mut final_results = Vec::new();
-        for _ in 0..5 {
-            sleep(Duration::from_secs(1)).await;
-            let resp = client.get_search_results(id, None, None).await;
-            if let Ok(r) = resp {
-                if r.status == "Stopped" {
-                    final_results = r.results;
-                    break;
-                }
-                final_results = r.results;
-            }
+    async fn handle_resource_read(&self, uri: &str, grant: Option<&Grant>) -> Result<Value> {
+        if uri.split('?').next() == Some(health::STATUS_URI) {
+            return Ok(json!({
+                "contents": [{
+                    "uri": uri,
+                    "mimeType": "application/json",
+                    "text": serde_json::to_string_pretty(&self.instance_status(grant))?
+                }]
+            }));
         }
-        let _ = client.stop_search(id).await;
-        let _ = client.delete_search(id).await;
-        let text = serde_json::to_string_pretty(&final_results)?;
-        Ok(json!({ "content": [{ "type": "text", "text": text }] }))
-    }
-
-    async fn handle_add_torrent(&self, client: &QBitClient, args: &Value) -> Result<Value> {
-        let url = args
-            .get("url")
-            .and_then(|v| v.as_str())
-            .ok_or(anyhow::anyhow!("Missing url"))?;
-        let save_path = args.get("save_path").and_then(|v| v.as_str());
-        let category = args.get("category").and_then(|v| v.as_str());
-        client.add_torrent(url, save_path, category).await?;
-        Ok(json!({ "content": [{ "type": "text", "text": "Torrent added successfully" }] }))
-    }
-
-    async fn handle_delete_torrent(&self, client: &QBitClient, args: &Value) -> Result<Value> {
-        let hash = args
-            .get("hash")
-            .and_then(|v| v.as_str())
-            .ok_or(anyhow::anyhow!("Missing hash"))?;
-        let delete_files = args
-            .get("delete_files")
-            .and_then(|v| v.as_bool())
-            .unwrap_or(false);
-        client.delete_torrents(hash, delete_files).await?;
-        Ok(json!({ "content": [{ "type": "text", "text": "Torrent deleted successfully" }] }))
-    }
-
-    async fn handle_get_torrent_files(&self, client: &QBitClient, args: &Value) -> Result<Value> {
-        let hash = args
-            .get("hash")
-            .and_then(|v| v.as_str())
-            .ok_or(anyhow::anyhow!("Missing hash"))?;
-        let files = client.get_torrent_files(hash).await?;
-        let text = serde_json::to_string_pretty(&files)?;
-        Ok(json!({ "content": [{ "type": "text", "text": text }] }))
-    }
-
-    async fn handle_inspect_torrent(&self, client: &QBitClient, args: &Value) -> Result<Value> {
-        let hash = args
-            .get("hash")
-            .and_then(|v| v.as_str())
-            .ok_or(anyhow::anyhow!("Missing hash"))?;
-
-        let properties = client.get_torrent_properties(hash).await?;
-        let files = client.get_torrent_files(hash).await?;
-        let trackers = client.get_torrent_trackers(hash).await?;
-
-        let result = json!({
-            "properties": properties,
-            "files": files,
-            "trackers": trackers
+        let Some((instance, path)) = resources::parse_uri(uri) else {
+            anyhow::bail!("Resource not found: {}", uri);
+        };
+        // `?humanize=true|false` overrides the server default for this read
+        let query = uri.split_once('?').map_or("", |(_, query)| query);
+        let humanize = query.split('&').find_map(|pair| match pair {
+            "humanize=true" => Some(true),
+            "humanize=false" => Some(false),
+            _ => None,
         });
-
-        Ok(
-            json!({ "content": [{ "type": "text", "text": serde_json::to_string_pretty(&result)? }] }),
-        )
+        let snapshot = self.instances();
+        let (instance, client) = snapshot.resolve(instance)?;
+        if let Some(reason) = grant.and_then(|g| g.denial_to_read(instance)) {
+            return Err(PolicyDenied {
+                tool: format!("reading {}", uri),
+                instance: instance.to_string(),
+                reason,
+            }
+            .into());
+        }
+        let value = resources::read(client, path, self.humanizer_for(humanize)).await?;
+        Ok(json!({
+            "contents": [{
+                "uri": uri,
+                "mimeType": "application/json",
+                "text": serde_json::to_string_pretty(&value)?
+            }]
+        }))
     }
 
-    async fn handle_get_system_info(&self, client: &QBitClient) -> Result<Value> {
-        let transfer_info = client.get_global_transfer_info().await?;
-        let app_preferences = client.get_app_preferences().await?;
-        let app_version = client.get_app_version().await?;
-        let build_info = client.get_build_info().await?;
+    fn get_prompt_definitions(&self) -> Vec<Value> {
+        prompts::definitions()
+    }
+
+    async fn handle_completion(&self, params: &Value, grant: Option<&Grant>) -> Result<Value> {
+        let reference = params.get("ref").unwrap_or(&Value::Null);
+        let argument = params.get("argument").unwrap_or(&Value::Null);
+        let name = arg_str(argument, "name")?;
+        let input = opt_str(argument, "value")?.unwrap_or("");
+
+        // Only complete arguments that actually exist on the referenced prompt or template
+        let known = match arg_str(reference, "type")? {
+            "ref/prompt" => {
+                let prompt = arg_str(reference, "name")?;
+                self.get_prompt_definitions().iter().any(|p| {
+                    p["name"] == prompt
+                        && p["arguments"]
+                            .as_array()
+                            .is_some_and(|args| args.iter().any(|a| a["name"] == name))
+                })
+            }
+            "ref/resource" => {
+                let uri = arg_str(reference, "uri")?;
+                uri.contains(&format!("{{{}}}", name))
+                    && self
+                        .get_resource_template_definitions()
+                        .iter()
+                        .any(|t| t["uriTemplate"] == uri)
+            }
+            other => {
+                return Err(invalid_argument(
+                    "type",
+                    "ref/prompt or ref/resource",
+                    format!("Unsupported completion reference: {}", other),
+                ));
+            }
+        };
+        if !known {
+            return Err(JsonRpcError::invalid_params(format!(
+                "No completable argument '{}' for {}",
+                name, reference
+            ))
+            .into());
+        }
 
-        let result = json!({
-            "transfer_info": transfer_info,
-            "app_preferences": app_preferences,
-            "app_version": app_version,
-            "build_info": build_info
-        });
+        // Values already chosen for other arguments, e.g. the instance to look hashes up on
+        let instance = params
+            .get("context")
+            .and_then(|c| c.get("arguments"))
+            .and_then(|a| a.get("instance"))
+            .and_then(|i| i.as_str());
+        let candidates = match self.completion_candidates(name, instance, grant).await {
+            Ok(candidates) => candidates,
+            Err(e) => {
+                debug!("No completions for {}: {}", name, e);
+                Vec::new()
+            }
+        };
 
-        Ok(
-            json!({ "content": [{ "type": "text", "text": serde_json::to_string_pretty(&result)? }] }),
-        )
+        let values = rank_completions(input, candidates);
+        let total = values.len();
+        Ok(json!({
+            "completion": {
+                "values": values.into_iter().take(MAX_COMPLETIONS).collect::<Vec<_>>(),
+                "total": total,
+                "hasMore": total > MAX_COMPLETIONS
+            }
+        }))
     }
 
-    async fn handle_get_categories(&self, client: &QBitClient) -> Result<Value> {
-        let categories = client.get_categories().await?;
-        let text = serde_json::to_string_pretty(&categories)?;
-        Ok(json!({ "content": [{ "type": "text", "text": text }] }))
+    /// Possible values for a prompt or template argument as (value, label) pairs. The label
+    /// is what users are likely to type, e.g. the torrent name for a hash. Only instances
+    /// `grant` can read are offered or looked into.
+    async fn completion_candidates(
+        &self,
+        argument: &str,
+        instance: Option<&str>,
+        grant: Option<&Grant>,
+    ) -> Result<Vec<(String, String)>> {
+        let pairs = |values: Vec<String>| values.into_iter().map(|v| (v.clone(), v)).collect();
+        match argument {
+            "instance" => Ok(pairs(
+                self.instances()
+                    .names()
+                    .into_iter()
+                    .filter(|name| grant.is_none_or(|g| g.can_read(name)))
+                    .map(String::from)
+                    .collect(),
+            )),
+            "issue_type" => Ok(pairs(
+                ["stalled", "slow", "connection", "general"]
+                    .map(String::from)
+                    .to_vec(),
+            )),
+            "hash" => {
+                let client = self.get_client(instance, grant)?;
+                let torrents = client
+                    .get_torrent_list(None, None, None, None, None, None, None)
+                    .await?;
+                Ok(torrents.into_iter().map(|t| (t.hash, t.name)).collect())
+            }
+            "category" => {
+                let client = self.get_client(instance, grant)?;
+                Ok(pairs(client.get_categories().await?.into_keys().collect()))
+            }
+            "tag" => {
+                let client = self.get_client(instance, grant)?;
+                Ok(pairs(client.get_tags().await?))
+            }
+            _ => Ok(Vec::new()),
+        }
     }
 
-    async fn handle_wait_for_torrent_status(
+    async fn handle_prompt_get(
         &self,
-        client: &QBitClient,
+        name: &str,
         args: &Value,
+        grant: Option<&Grant>,
     ) -> Result<Value> {
-        let hash = args
-            .get("hash")
-            .and_then(|v| v.as_str())
-            .ok_or(anyhow::anyhow!("Missing hash"))?;
-        let target_status = args
-            .get("target_status")
-            .and_then(|v| v.as_str())
-            .ok_or(anyhow::anyhow!("Missing target_status"))?;
-        let timeout = args
-            .get("timeout_seconds")
-            .and_then(|v| v.as_i64())
-            .unwrap_or(60)
-            .clamp(1, 300);
-
-        let start_time = std::time::Instant::now();
-        while start_time.elapsed().as_secs() < timeout as u64 {
-            let torrents = client.get_torrents_info(hash).await?;
-            if let Some(t) = torrents.first() {
-                if t.state == target_status {
-                    return Ok(
-                        json!({ "content": [{ "type": "text", "text": format!("Torrent reached target status: {}", target_status) }] }),
-                    );
+        match prompts::takes_instance(name) {
+            Some(true) => {
+                let instances = self.instances();
+                let (instance, client) = instances.resolve(opt_str(args, "instance")?)?;
+                // The prompt embeds the instance's data
+                if let Some(reason) = grant.and_then(|g| g.denial_to_read(instance)) {
+                    return Err(PolicyDenied {
+                        tool: format!("prompt {}", name),
+                        instance: instance.to_string(),
+                        reason,
+                    }
+                    .into());
                 }
-            } else {
-                anyhow::bail!("Torrent not found: {}", hash);
+                let target = prompts::Target {
+                    instance,
+                    client,
+                    resource_view: self.humanizer_for(None),
+                    humanizer: &self.humanizer,
+                };
+                prompts::get(name, args, &target).await
             }
-            sleep(Duration::from_secs(2)).await;
+            Some(false) => Ok(prompts::rules_of_engagement()),
+            None => Err(invalid_argument(
+                "name",
+                "name of a prompt from prompts/list",
+                format!("Prompt not found: {}", name),
+            )),
         }
-        Ok(
-            json!({ "content": [{ "type": "text", "text": format!("Timed out waiting for status {}", target_status) }], "isError": true }),
-        )
     }
 
-    async fn handle_cleanup_completed(&self, client: &QBitClient, args: &Value) -> Result<Value> {
-        let min_ratio = args.get("min_ratio").and_then(|v| v.as_f64());
-        let max_age_days = args.get("max_age_days").and_then(|v| v.as_i64());
-        let delete_files = args
-            .get("delete_files")
-            .and_then(|v| v.as_bool())
-            .unwrap_or(false);
-
-        let torrents = client
-            .get_torrent_list(Some("completed"), None, None, None, None, None, None)
-            .await?;
-
-        let now = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)?
-            .as_secs() as i64;
+    fn get_tool_definitions(&self, ctx: &RequestContext) -> Vec<Value> {
+        let enabled = self.enabled_toolsets(ctx);
+        let mut tools = self.tools.definitions_where(|name, toolset| {
+            enabled.contains(toolset) && self.is_permitted(name, ctx.grant())
+        });
+        tools.extend(toolsets::meta_tool_definitions());
+        tools
+    }
 
-        let to_delete: Vec<String> = torrents
+    /// True if the policy and `grant` let `tool` run on at least one instance, i.e. it is
+    /// worth listing.
+    fn is_permitted(&self, tool: &str, grant: Option<&Grant>) -> bool {
+        let instances = self.instances();
+        instances
+            .names()
             .into_iter()
-            .filter(|t| {
-                let mut should_delete = false;
-                if let Some(r) = min_ratio {
-                    should_delete = t.ratio >= r;
-                }
-                if let Some(age_days) = max_age_days {
-                    let age_secs = age_days * 24 * 3600;
-                    if t.completion_on > 0 && (now - t.completion_on) >= age_secs {
-                        should_delete = true;
-                    }
-                }
-                // If neither is specified, we don't delete anything automatically
-                // to avoid accidental wipes of all completed torrents.
-                // UNLESS the user explicitly wants to delete all completed.
-                // But let's require at least one condition for safety in this macro.
-                if min_ratio.is_none() && max_age_days.is_none() {
-                    return false;
-                }
-                should_delete
-            })
-            .map(|t| t.hash)
-            .collect();
-
-        if to_delete.is_empty() {
-            return Ok(
-                json!({ "content": [{ "type": "text", "text": "No torrents matched the cleanup criteria." }] }),
-            );
-        }
+            .any(|instance| self.denial(&instances, tool, instance, grant).is_none())
+    }
 
-        let count = to_delete.len();
-        let hashes = to_delete.join("|");
-        client.delete_torrents(&hashes, delete_files).await?;
+    /// The toolsets whose tools the requesting session can see and call.
+    fn enabled_toolsets(&self, ctx: &RequestContext) -> BTreeSet<String> {
+        let session = match &ctx.peer {
+            Some(peer) => peer.toolsets.lock().unwrap().clone(),
+            None => self.state.lock().unwrap().toolsets.clone(),
+        };
+        session
+            .or_else(|| self.default_toolsets.clone())
+            .unwrap_or_else(|| self.all_toolsets())
+    }
 
-        Ok(
-            json!({ "content": [{ "type": "text", "text": format!("Successfully cleaned up {} torrents.", count) }] }),
-        )
+    fn all_toolsets(&self) -> BTreeSet<String> {
+        self.tools
+            .toolset_names()
+            .into_iter()
+            .map(String::from)
+            .collect()
     }
 
-    async fn handle_mass_rename(&self, client: &QBitClient, args: &Value) -> Result<Value> {
-        let hash = args
-            .get("hash")
-            .and_then(|v| v.as_str())
-            .ok_or(anyhow::anyhow!("Missing hash"))?;
-        let pattern = args
-            .get("pattern")
-            .and_then(|v| v.as_str())
-            .ok_or(anyhow::anyhow!("Missing pattern"))?;
-        let replacement = args
-            .get("replacement")
-            .and_then(|v| v.as_str())
-            .ok_or(anyhow::anyhow!("Missing replacement"))?;
-
-        let re = Regex::new(pattern)?;
-        let files = client.get_torrent_files(hash).await?;
-
-        let mut rename_count = 0;
-        for file in files {
-            if re.is_match(&file.name) {
-                let new_name = re.replace_all(&file.name, replacement).to_string();
-                if new_name != file.name {
-                    client.rename_file(hash, &file.name, &new_name).await?;
-                    rename_count += 1;
-                }
+    fn set_enabled_toolsets(&self, ctx: &RequestContext, enabled: BTreeSet<String>) {
+        match &ctx.peer {
+            Some(peer) => {
+                *peer.toolsets.lock().unwrap() = Some(enabled);
+                peer.tools_changed.store(true, Ordering::Relaxed);
             }
+            // Without a session there is nobody to notify
+            None => self.state.lock().unwrap().toolsets = Some(enabled),
         }
-
-        Ok(
-            json!({ "content": [{ "type": "text", "text": format!("Successfully renamed {} files.", rename_count) }] }),
-        )
     }
 
-    async fn handle_find_duplicates(&self, client: &QBitClient) -> Result<Value> {
-        let torrents = client
-            .get_torrent_list(None, None, None, None, None, None, None)
-            .await?;
+    pub async fn call_tool(&self, name: &str, args: &Value) -> Result<Value> {
+        self.call_tool_with_context(name, args, &RequestContext::default())
+            .await
+    }
 
-        let mut names: std::collections::HashMap<String, Vec<crate::models::Torrent>> =
-            std::collections::HashMap::new();
+    pub async fn call_tool_with_context(
+        &self,
+        name: &str,
+        args: &Value,
+        ctx: &RequestContext,
+    ) -> Result<Value> {
+        if META_TOOLS.contains(&name) {
+            return self.handle_meta_tool(name, args, ctx);
+        }
+        let Some(toolset) = self.tools.toolset_of(name) else {
+            return Err(unknown_tool(name));
+        };
+        if !self.enabled_toolsets(ctx).contains(toolset) {
+            return Err(invalid_argument(
+                "name",
+                "name of a tool from tools/list",
+                format!(
+                    "Tool not enabled: {}. Call enable_toolset with toolset '{}' first.",
+                    name, toolset
+                ),
+            ));
+        }
 
-        for t in torrents {
-            names.entry(t.name.clone()).or_default().push(t);
+        let snapshot = self.instances();
+        let (targets, fan_out) = self.resolve_targets(&snapshot, name, args)?;
+        let read_only = self.tools.is_read_only(name);
+        let audit = self.audit.as_deref().filter(|_| !read_only);
+        // A fan-out skips the instances the policy denies; the call is only denied if
+        // that leaves none
+        let mut instances = Vec::new();
+        let mut skipped = Vec::new();
+        let mut denied = None;
+        for (instance, client) in targets {
+            match self.denial(&snapshot, name, instance, ctx.grant()) {
+                None => instances.push((instance, client)),
+                Some(reason) => {
+                    let err = PolicyDenied {
+                        tool: name.to_string(),
+                        instance: instance.to_string(),
+                        reason,
+                    };
+                    skipped.push((instance.to_string(), err.to_string()));
+                    denied.get_or_insert(err);
+                }
+            }
         }
+        let Some(&(instance, client)) = instances.first() else {
+            let denied = denied.expect("at least one instance is targeted");
+            let instance = denied.instance.clone();
+            let err = denied.into();
+            if let Some(audit) = audit {
+                let entry = audit_entry(name, &instance, args, ctx, Vec::new(), Err(&err));
+                audit.record(&AuditEntry {
+                    outcome: "denied".to_string(),
+                    ..entry
+                });
+            }
+            return Err(err);
+        };
 
-        let duplicates: Vec<Value> = names
-            .into_iter()
-            .filter(|(_, v)| v.len() > 1)
-            .map(|(name, v)| {
-                json!({
-                    "name": name,
-                    "count": v.len(),
-                    "torrents": v.into_iter().map(|t| json!({
-                        "hash": t.hash,
-                        "size": t.size_bytes,
-                        "progress": t.progress,
-                        "state": t.state
-                    })).collect::<Vec<Value>>()
-                })
-            })
-            .collect();
+        // Look the torrents up now, since a delete leaves nothing to name afterwards
+        let torrents = match audit {
+            Some(_) => audited_torrents(client, args).await,
+            None => Vec::new(),
+        };
+        let tool_ctx = ToolContext {
+            server: self,
+            tool: name,
+            instance,
+            client,
+            instances,
+            fan_out,
+            skipped,
+            request: ctx,
+            arguments: args,
+            audit: AuditNotes::default(),
+        };
+        let result = match self.tools.call(name, &tool_ctx, args).await {
+            // Say which instance is gone rather than just passing on the HTTP error
+            Err(e) if !fan_out && health::is_unreachable(&e) => {
+                self.record_failure(instance, &e);
+                Err(e.context(format!(
+                    "qBittorrent instance '{}' is unreachable",
+                    instance
+                )))
+            }
+            result => result,
+        };
 
-        if duplicates.is_empty() {
-            return Ok(
-                json!({ "content": [{ "type": "text", "text": "No duplicate torrents found." }] }),
-            );
+        if let Some(audit) = audit {
+            let mut recorded = tool_ctx.audit.torrents.into_inner().unwrap();
+            if recorded.is_empty() {
+                recorded = torrents;
+            }
+            let mut entry = audit_entry(name, instance, args, ctx, recorded, result.as_ref());
+            if result.is_ok() {
+                if let Some(outcome) = tool_ctx.audit.outcome.into_inner().unwrap() {
+                    entry.outcome = outcome.to_string();
+                } else if args.get("dry_run") == Some(&Value::Bool(true)) {
+                    entry.outcome = "dry_run".to_string();
+                }
+            }
+            audit.record(&entry);
         }
-
-        let text = serde_json::to_string_pretty(&duplicates)?;
-        Ok(json!({ "content": [{ "type": "text", "text": text }] }))
-    }
-
-    async fn handle_add_trackers(&self, client: &QBitClient, args: &Value) -> Result<Value> {
-        let hashes = args
-            .get("hashes")
-            .and_then(|v| v.as_str())
-            .ok_or(anyhow::anyhow!("Missing hashes"))?;
-        let urls = args
-            .get("urls")
-            .and_then(|v| v.as_str())
-            .ok_or(anyhow::anyhow!("Missing urls"))?;
-        client.add_trackers(hashes, urls).await?;
-        Ok(json!({ "content": [{ "type": "text", "text": "Trackers added successfully" }] }))
-    }
-
-    async fn handle_edit_tracker(&self, client: &QBitClient, args: &Value) -> Result<Value> {
-        let hash = args
-            .get("hash")
-            .and_then(|v| v.as_str())
-            .ok_or(anyhow::anyhow!("Missing hash"))?;
-        let orig_url = args
-            .get("orig_url")
-            .and_then(|v| v.as_str())
-            .ok_or(anyhow::anyhow!("Missing orig_url"))?;
-        let new_url = args
-            .get("new_url")
-            .and_then(|v| v.as_str())
-            .ok_or(anyhow::anyhow!("Missing new_url"))?;
-        client.edit_tracker(hash, orig_url, new_url).await?;
-        Ok(json!({ "content": [{ "type": "text", "text": "Tracker edited successfully" }] }))
-    }
-
-    async fn handle_remove_trackers(&self, client: &QBitClient, args: &Value) -> Result<Value> {
-        let hashes = args
-            .get("hashes")
-            .and_then(|v| v.as_str())
-            .ok_or(anyhow::anyhow!("Missing hashes"))?;
-        let urls = args
-            .get("urls")
-            .and_then(|v| v.as_str())
-            .ok_or(anyhow::anyhow!("Missing urls"))?;
-        client.remove_trackers(hashes, urls).await?;
-        Ok(json!({ "content": [{ "type": "text", "text": "Trackers removed successfully" }] }))
-    }
-
-    async fn handle_rename_folder(&self, client: &QBitClient, args: &Value) -> Result<Value> {
-        let hash = args
-            .get("hash")
-            .and_then(|v| v.as_str())
-            .ok_or(anyhow::anyhow!("Missing hash"))?;
-        let old_path = args
-            .get("old_path")
-            .and_then(|v| v.as_str())
-            .ok_or(anyhow::anyhow!("Missing old_path"))?;
-        let new_path = args
-            .get("new_path")
-            .and_then(|v| v.as_str())
-            .ok_or(anyhow::anyhow!("Missing new_path"))?;
-        client.rename_folder(hash, old_path, new_path).await?;
-        Ok(json!({ "content": [{ "type": "text", "text": "Folder renamed successfully" }] }))
-    }
-
-    async fn handle_set_file_priority(&self, client: &QBitClient, args: &Value) -> Result<Value> {
-        let hash = args
-            .get("hash")
-            .and_then(|v| v.as_str())
-            .ok_or(anyhow::anyhow!("Missing hash"))?;
-        let id = args
-            .get("id")
-            .and_then(|v| v.as_str())
-            .ok_or(anyhow::anyhow!("Missing id"))?;
-        let priority = args
-            .get("priority")
-            .and_then(|v| v.as_i64())
-            .ok_or(anyhow::anyhow!("Missing priority"))? as i32;
-        client.set_file_priority(hash, id, priority).await?;
-        Ok(json!({ "content": [{ "type": "text", "text": "File priority updated successfully" }] }))
-    }
-
-    async fn handle_install_search_plugin(
-        &self,
-        client: &QBitClient,
-        args: &Value,
-    ) -> Result<Value> {
-        let url = args
-            .get("url")
-            .and_then(|v| v.as_str())
-            .ok_or(anyhow::anyhow!("Missing url"))?;
-        client.install_search_plugin(url).await?;
-        Ok(
-            json!({ "content": [{ "type": "text", "text": "Search plugin installed successfully" }] }),
-        )
+        result
     }
 
-    async fn handle_uninstall_search_plugin(
-        &self,
-        client: &QBitClient,
-        args: &Value,
-    ) -> Result<Value> {
-        let name = args
-            .get("name")
-            .and_then(|v| v.as_str())
-            .ok_or(anyhow::anyhow!("Missing name"))?;
-        client.uninstall_search_plugin(name).await?;
-        Ok(
-            json!({ "content": [{ "type": "text", "text": "Search plugin uninstalled successfully" }] }),
-        )
+    fn handle_meta_tool(&self, name: &str, args: &Value, ctx: &RequestContext) -> Result<Value> {
+        let mut enabled = self.enabled_toolsets(ctx);
+        match name {
+            "list_toolsets" => {
+                let sets: Vec<Value> = self
+                    .tools
+                    .toolset_names()
+                    .into_iter()
+                    .map(|set| {
+                        json!({
+                            "name": set,
+                            "description": toolsets::description(set),
+                            "enabled": enabled.contains(set),
+                            "tools": self
+                                .tools
+                                .toolset_tools(set)
+                                .into_iter()
+                                .filter(|tool| self.is_permitted(tool, ctx.grant()))
+                                .collect::<Vec<_>>()
+                        })
+                    })
+                    .collect();
+                let text = serde_json::to_string_pretty(&sets)?;
+                Ok(json!({ "content": [{ "type": "text", "text": text }] }))
+            }
+            "enable_toolset" | "disable_toolset" => {
+                let toolset = toolsets::toolset_arg(args)?;
+                let known = self.tools.toolset_names();
+                if !known.contains(&toolset.as_str()) {
+                    return Err(invalid_argument(
+                        "toolset",
+                        &format!("one of {}", known.join(", ")),
+                        format!("Unknown toolset: {}", toolset),
+                    ));
+                }
+                let enable = name == "enable_toolset";
+                let changed = if enable {
+                    enabled.insert(toolset.clone())
+                } else {
+                    enabled.remove(&toolset)
+                };
+                let text = match (changed, enable) {
+                    (false, true) => format!("Toolset '{}' is already enabled.", toolset),
+                    (false, false) => format!("Toolset '{}' is not enabled.", toolset),
+                    (true, true) => format!(
+                        "Enabled toolset '{}': {}. Please refresh your tool list.",
+                        toolset,
+                        self.tools
+                            .toolset_tools(&toolset)
+                            .into_iter()
+                            .filter(|tool| self.is_permitted(tool, ctx.grant()))
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ),
+                    (true, false) => format!(
+                        "Disabled toolset '{}'. Please refresh your tool list.",
+                        toolset
+                    ),
+                };
+                if changed {
+                    self.set_enabled_toolsets(ctx, enabled);
+                }
+                Ok(json!({ "content": [{ "type": "text", "text": text }] }))
+            }
+            _ => {
+                // show_all_tools
+                if !args.as_object().is_none_or(|a| a.is_empty()) {
+                    return Err(invalid_argument(
+                        "arguments",
+                        "no arguments",
+                        format!("{} takes no arguments", name),
+                    ));
+                }
+                self.set_enabled_toolsets(ctx, self.all_toolsets());
+                Ok(
+                    json!({ "content": [{ "type": "text", "text": "All tools enabled. Please refresh your tool list." }] }),
+                )
+            }
+        }
     }
 
-    async fn handle_enable_search_plugin(
+    /// Gets the user's go-ahead for a destructive `action` ("delete 3 torrents, ...").
+    /// Returns `None` to proceed, or the result to return instead of running the tool.
+    ///
+    /// Clients with the `elicitation` capability are asked directly. For other clients,
+    /// and only when `require_confirmation` is set, the first call returns a one-time
+    /// `confirm` token that must accompany an otherwise identical second call.
+    pub(crate) async fn confirm_destructive(
         &self,
-        client: &QBitClient,
+        tool: &str,
         args: &Value,
-    ) -> Result<Value> {
-        let name = args
-            .get("name")
-            .and_then(|v| v.as_str())
-            .ok_or(anyhow::anyhow!("Missing name"))?;
-        let enable = args
-            .get("enable")
-            .and_then(|v| v.as_bool())
-            .ok_or(anyhow::anyhow!("Missing enable"))?;
-        client.enable_search_plugin(name, enable).await?;
-        Ok(
-            json!({ "content": [{ "type": "text", "text": "Search plugin status updated successfully" }] }),
-        )
-    }
-
-    async fn handle_update_search_plugins(&self, client: &QBitClient) -> Result<Value> {
-        client.update_search_plugins().await?;
-        Ok(
-            json!({ "content": [{ "type": "text", "text": "Search plugins updated successfully" }] }),
-        )
-    }
-
-    async fn handle_get_search_plugins(&self, client: &QBitClient) -> Result<Value> {
-        let plugins = client.get_search_plugins().await?;
-        let text = serde_json::to_string_pretty(&plugins)?;
-        Ok(json!({ "content": [{ "type": "text", "text": text }] }))
-    }
-
-    async fn handle_add_rss_feed(&self, client: &QBitClient, args: &Value) -> Result<Value> {
-        let url = args
-            .get("url")
-            .and_then(|v| v.as_str())
-            .ok_or(anyhow::anyhow!("Missing url"))?;
-        let path = args
-            .get("path")
-            .and_then(|v| v.as_str())
-            .ok_or(anyhow::anyhow!("Missing path"))?;
-        client.add_rss_feed(url, path).await?;
-        Ok(json!({ "content": [{ "type": "text", "text": "RSS feed added successfully" }] }))
-    }
-
-    async fn handle_get_rss_feeds(&self, client: &QBitClient) -> Result<Value> {
-        let feeds = client.get_all_rss_feeds().await?;
-        let text = serde_json::to_string_pretty(&feeds)?;
-        Ok(json!({ "content": [{ "type": "text", "text": text }] }))
-    }
-
-    async fn handle_set_rss_rule(&self, client: &QBitClient, args: &Value) -> Result<Value> {
-        let name = args
-            .get("name")
-            .and_then(|v| v.as_str())
-            .ok_or(anyhow::anyhow!("Missing name"))?;
-        let definition = args
-            .get("definition")
-            .and_then(|v| v.as_str())
-            .ok_or(anyhow::anyhow!("Missing definition"))?;
-        client.set_rss_rule(name, definition).await?;
-        Ok(json!({ "content": [{ "type": "text", "text": "RSS rule set successfully" }] }))
-    }
-
-    async fn handle_get_rss_rules(&self, client: &QBitClient) -> Result<Value> {
-        let rules = client.get_all_rss_rules().await?;
-        let text = serde_json::to_string_pretty(&rules)?;
-        Ok(json!({ "content": [{ "type": "text", "text": text }] }))
-    }
-
-    async fn handle_move_rss_item(&self, client: &QBitClient, args: &Value) -> Result<Value> {
-        let item_path = args
-            .get("item_path")
-            .and_then(|v| v.as_str())
-            .ok_or(anyhow::anyhow!("Missing item_path"))?;
-        let dest_path = args
-            .get("dest_path")
-            .and_then(|v| v.as_str())
-            .ok_or(anyhow::anyhow!("Missing dest_path"))?;
-        client.move_rss_item(item_path, dest_path).await?;
-        Ok(json!({ "content": [{ "type": "text", "text": "RSS item moved successfully" }] }))
-    }
-
-    async fn handle_ban_peers(&self, client: &QBitClient, args: &Value) -> Result<Value> {
-        let peers = args
-            .get("peers")
-            .and_then(|v| v.as_str())
-            .ok_or(anyhow::anyhow!("Missing peers"))?;
-        client.ban_peers(peers).await?;
-        Ok(json!({ "content": [{ "type": "text", "text": "Peers banned successfully" }] }))
-    }
-
-    async fn handle_set_app_preferences(&self, client: &QBitClient, args: &Value) -> Result<Value> {
-        let prefs_str = args
-            .get("preferences")
-            .and_then(|v| v.as_str())
-            .ok_or(anyhow::anyhow!("Missing preferences"))?;
-        let prefs_val: serde_json::Value = serde_json::from_str(prefs_str)?;
-        client.set_app_preferences(&prefs_val).await?;
-        Ok(
-            json!({ "content": [{ "type": "text", "text": "App preferences updated successfully" }] }),
-        )
-    }
+        ctx: &RequestContext,
+        action: &str,
+    ) -> Result<Option<Value>> {
+        if let Some(peer) = ctx.peer.as_ref().filter(|p| p.supports("elicitation")) {
+            let answer = peer
+                .request(
+                    "elicitation/create",
+                    json!({
+                        "message": format!("The assistant wants to {}. Proceed?", action),
+                        "requestedSchema": {
+                            "type": "object",
+                            "properties": {
+                                "confirm": {
+                                    "type": "boolean",
+                                    "title": "Proceed",
+                                    "description": format!("Allow {} to {}", tool, action)
+                                }
+                            },
+                            "required": ["confirm"]
+                        }
+                    }),
+                )
+                .await;
+            // No answer is no go-ahead
+            let response = answer.unwrap_or_else(|e| {
+                warn!("No confirmation for {}: {:#}", tool, e);
+                Value::Null
+            });
+
+            let accepted = response.get("action").and_then(|a| a.as_str()) == Some("accept")
+                && response
+                    .get("content")
+                    .and_then(|c| c.get("confirm"))
+                    .and_then(|c| c.as_bool())
+                    .unwrap_or(false);
+            if accepted {
+                return Ok(None);
+            }
+            info!("User declined {}: {}", tool, action);
+            return Ok(Some(json!({
+                "content": [{ "type": "text", "text": format!("Aborted: the user declined to {}. Nothing was changed.", action) }]
+            })));
+        }
 
-    async fn handle_get_main_log(&self, client: &QBitClient, args: &Value) -> Result<Value> {
-        let severity = args
-            .get("severity")
-            .and_then(|v| v.as_str())
-            .unwrap_or("all");
-        let last_id = args.get("last_id").and_then(|v| v.as_i64());
-        let (normal, info, warning, critical) = match severity {
-            "info" => (false, true, false, false),
-            "warning" => (false, false, true, false),
-            "critical" => (false, false, false, true),
-            _ => (true, true, true, true),
-        };
-        let logs = client
-            .get_main_log(normal, info, warning, critical, last_id)
-            .await?;
-        let text = serde_json::to_string_pretty(&logs)?;
-        Ok(json!({ "content": [{ "type": "text", "text": text }] }))
-    }
+        if !self.require_confirmation {
+            return Ok(None);
+        }
 
-    async fn handle_get_peer_log(&self, client: &QBitClient, args: &Value) -> Result<Value> {
-        let last_id = args.get("last_id").and_then(|v| v.as_i64());
-        let logs = client.get_peer_log(last_id).await?;
-        let text = serde_json::to_string_pretty(&logs)?;
-        Ok(json!({ "content": [{ "type": "text", "text": text }] }))
-    }
+        let fingerprint = confirmation_fingerprint(tool, args);
+        if let Some(token) = opt_str(args, "confirm")? {
+            return match self.confirmations.remove(token) {
+                Some((_, (expected, issued)))
+                    if expected == fingerprint && issued.elapsed() < CONFIRMATION_TTL =>
+                {
+                    Ok(None)
+                }
+                _ => Err(invalid_argument(
+                    "confirm",
+                    "token returned by a previous call with the same arguments",
+                    "Invalid or expired confirm token".to_string(),
+                )),
+            };
+        }
 
-    async fn handle_shutdown_app(&self, client: &QBitClient) -> Result<Value> {
-        client.shutdown_app().await?;
-        Ok(json!({ "content": [{ "type": "text", "text": "Shutdown command sent" }] }))
+        self.confirmations
+            .retain(|_, (_, issued)| issued.elapsed() < CONFIRMATION_TTL);
+        let token = uuid::Uuid::new_v4().to_string();
+        self.confirmations
+            .insert(token.clone(), (fingerprint, Instant::now()));
+        Ok(Some(json!({
+            "content": [{ "type": "text", "text": format!(
+                "Confirmation required. This would {}. Nothing has been changed yet. \
+                 After the user agrees, repeat the call with the same arguments plus \
+                 \"confirm\": \"{}\" (valid for {} minutes).",
+                action,
+                token,
+                CONFIRMATION_TTL.as_secs() / 60
+            ) }],
+            "confirm": token
+        })))
     }
 
     pub fn start_event_loop(&self, interval_ms: u64) {
+        self.set_polling_interval(interval_ms);
         let server = self.clone();
         tokio::spawn(async move {
-            server.event_loop(interval_ms).await;
+            server.event_loop().await;
         });
     }
 
-    async fn event_loop(&self, interval_ms: u64) {
+    async fn event_loop(&self) {
         let mut last_rids: HashMap<String, i64> = HashMap::new();
         let mut notified_finished: HashMap<String, std::collections::HashSet<String>> =
             HashMap::new();
-
-        for name in self.clients.keys() {
-            last_rids.insert(name.clone(), 0);
-            notified_finished.insert(name.clone(), std::collections::HashSet::new());
-        }
+        let mut polled_urls: HashMap<String, String> = HashMap::new();
 
         loop {
             if !self.is_running() {
                 break;
             }
             tokio::task::yield_now().await;
-            sleep(Duration::from_millis(interval_ms)).await;
-            for (name, client) in &self.clients {
+            sleep(Duration::from_millis(
+                self.polling_interval_ms.load(Ordering::Relaxed),
+            ))
+            .await;
+            // A reload may have removed instances or pointed them somewhere else
+            let instances = self.instances();
+            polled_urls.retain(|name, url| {
+                instances
+                    .client(name)
+                    .is_some_and(|client| client.base_url() == url)
+            });
+            last_rids.retain(|name, _| polled_urls.contains_key(name));
+            notified_finished.retain(|name, _| polled_urls.contains_key(name));
+            for name in instances.names() {
+                let client = instances.client(name).expect("listed instance");
+                polled_urls
+                    .entry(name.to_string())
+                    .or_insert_with(|| client.base_url().to_string());
                 if !self.is_running() {
                     break;
                 }
+                // Instances that were down at startup, or lost their session, log in
+                // again with backoff before they are polled
+                if client.requires_login() && self.health.needs_login(name) {
+                    if !self.health.login_due(name) || !self.login(name, client).await {
+                        continue;
+                    }
+                    info!("Logged in to qBittorrent instance '{}' successfully", name);
+                }
                 let rid = *last_rids.get(name).unwrap_or(&0);
+                let started = Instant::now();
                 match client.get_main_data(rid).await {
                     Ok(data) => {
-                        last_rids.insert(name.clone(), data.rid);
+                        self.record_success(name, Some(started.elapsed()));
+                        if self
+                            .health
+                            .get(name)
+                            .is_some_and(|h| h.api_version.is_none())
+                            && let Ok(version) = client.get_api_version().await
+                        {
+                            self.health.set_api_version(name, version);
+                        }
+                        last_rids.insert(name.to_string(), data.rid);
 
                         // Track finished torrents to notify only once
                         if let Some(torrents) = data.torrents {
@@ -1856,8 +2017,9 @@ impl McpServer {
                                 });
 
                                 if progress.is_some_and(|p| p >= 1.0) || is_finished_state {
-                                    let already_notified =
-                                        notified_finished.get_mut(name).unwrap().contains(&hash);
+                                    let notified =
+                                        notified_finished.entry(name.to_string()).or_default();
+                                    let already_notified = notified.contains(&hash);
                                     if !already_notified {
                                         let torrent_name = torrent_val
                                             .get("name")
@@ -1869,7 +2031,8 @@ impl McpServer {
                                         );
 
                                         // Custom notification
-                                        self.push_notification(
+                                        self.notify_sessions(
+                                            Some(name),
                                             "notifications/torrent_finished",
                                             json!({
                                                 "instance": name,
@@ -1879,15 +2042,13 @@ impl McpServer {
                                         );
 
                                         // Standard resource update notification
-                                        self.push_notification(
+                                        self.notify_sessions(
+                                                    Some(name),
                                                     "notifications/resources/updated",
                                                     json!({ "uri": format!("qbittorrent://{}/torrents", name) }),
                                                 );
 
-                                        notified_finished
-                                            .get_mut(name)
-                                            .unwrap()
-                                            .insert(hash.clone());
+                                        notified.insert(hash.clone());
                                     }
                                 }
                             }
@@ -1895,7 +2056,9 @@ impl McpServer {
                     }
                     Err(e) => {
                         if self.is_running() {
-                            error!("Polling error for instance {}: {}", name, e);
+                            self.record_failure(name, &e.context("Polling failed"));
+                            // qBittorrent may have restarted and forgotten the session
+                            self.health.expire_login(name);
                         }
                     }
                 }
@@ -2093,14 +2256,33 @@ mod tests {
     }
 
     #[test]
-    fn test_push_notification() {
-        let clients = HashMap::new();
-        let server = McpServer::new(clients, false);
-        server.push_notification("test_method", json!({"param": "val"}));
+    fn test_notify_sessions() {
+        let server = McpServer::new(HashMap::new(), false);
+        let (first, mut first_rx) = Peer::channel();
+        let (limited, mut limited_rx) = Peer::channel();
+        let limited = limited.with_session(SessionInfo {
+            transport: "http",
+            session_id: Some("limited".into()),
+            identity: Some("limited".into()),
+            grant: Some(Arc::new(Grant {
+                instances: Some(["nas".to_string()].into()),
+                ..Grant::unrestricted("limited")
+            })),
+        });
+        let (closed, closed_rx) = Peer::channel();
+        for peer in [&first, &limited, &first, &closed] {
+            server.connect(peer);
+        }
+        drop(closed_rx);
 
-        let state = server.state.lock().unwrap();
-        assert_eq!(state.notification_queue.len(), 1);
-        assert_eq!(state.notification_queue[0]["method"], "test_method");
+        server.notify_sessions(None, "test_method", json!({"param": "val"}));
+        server.notify_sessions(Some("seedbox"), "test_instance", json!({}));
+        assert_eq!(first_rx.try_recv().unwrap()["method"], "test_method");
+        assert_eq!(first_rx.try_recv().unwrap()["method"], "test_instance");
+        assert!(first_rx.try_recv().is_err());
+        assert_eq!(limited_rx.try_recv().unwrap()["method"], "test_method");
+        assert!(limited_rx.try_recv().is_err());
+        assert_eq!(server.state.lock().unwrap().sessions.len(), 2);
     }
 
     #[tokio::test]
@@ -2137,7 +2319,7 @@ mod tests {
             QBitClient::new("http://localhost", "a", "b", false),
         );
         let server = McpServer::new(clients, false);
-        let res = server.get_resource_definitions();
+        let res = server.get_resource_definitions(None);
         assert!(!res.is_empty());
         // Should have qbittorrent://test/torrents etc.
         let found = res
@@ -2148,7 +2330,12 @@ mod tests {
 
     #[tokio::test]
     async fn test_handle_prompt_variations() {
-        let clients = HashMap::new();
+        // Nothing listens here, so the prompts are built with notes instead of data
+        let mut clients = HashMap::new();
+        clients.insert(
+            "inst1".to_string(),
+            QBitClient::new_no_auth("http://127.0.0.1:1", false),
+        );
         let server = McpServer::new(clients, false);
 
         let prompts = vec![
@@ -2157,21 +2344,44 @@ mod tests {
                 json!({ "issue_type": "stalled", "hash": "abc", "instance": "inst1" }),
             ),
             ("analyze_disk_space", json!({ "instance": "inst1" })),
+            ("weekly_activity_report", json!({})),
+            ("free_up_space", json!({ "target_gb": "10" })),
+            ("organize_library", json!({ "category": "movies" })),
+            ("review_seeding_obligations", json!({})),
+            ("rules-of-engagement", json!({})),
         ];
 
         for (name, args) in prompts {
-            let res = server.handle_prompt_get(name, &args).await.unwrap();
+            let res = server.handle_prompt_get(name, &args, None).await.unwrap();
             assert!(res.get("description").is_some());
         }
 
         // Test error case: missing hash for stalled
         let res = server
-            .handle_prompt_get("troubleshoot_torrent", &json!({ "issue_type": "stalled" }))
+            .handle_prompt_get(
+                "troubleshoot_torrent",
+                &json!({ "issue_type": "stalled" }),
+                None,
+            )
+            .await;
+        assert!(res.is_err());
+
+        // Test error case: missing target_gb
+        let res = server
+            .handle_prompt_get("free_up_space", &json!({}), None)
+            .await;
+        assert!(res.is_err());
+
+        // Test error case: unknown instance
+        let res = server
+            .handle_prompt_get("analyze_disk_space", &json!({ "instance": "nope" }), None)
             .await;
         assert!(res.is_err());
 
         // Test error case: unknown prompt
-        let res = server.handle_prompt_get("unknown_prompt", &json!({})).await;
+        let res = server
+            .handle_prompt_get("unknown_prompt", &json!({}), None)
+            .await;
         assert!(res.is_err());
     }
 
@@ -2190,17 +2400,17 @@ mod tests {
 
         // Explicit instance
         assert_eq!(
-            server.get_client(Some("inst1")).unwrap().base_url(),
+            server.get_client(Some("inst1"), None).unwrap().base_url(),
             "http://h1"
         );
         assert_eq!(
-            server.get_client(Some("inst2")).unwrap().base_url(),
+            server.get_client(Some("inst2"), None).unwrap().base_url(),
             "http://h2"
         );
-        assert!(server.get_client(Some("inst3")).is_err());
+        assert!(server.get_client(Some("inst3"), None).is_err());
 
         // Default logic (first one since no "default" key)
-        let default_client = server.get_client(None).unwrap();
+        let default_client = server.get_client(None, None).unwrap();
         assert!(
             default_client.base_url() == "http://h1" || default_client.base_url() == "http://h2"
         );
@@ -2217,7 +2427,7 @@ mod tests {
         );
         let server2 = McpServer::new(clients2, false);
         assert_eq!(
-            server2.get_client(None).unwrap().base_url(),
+            server2.get_client(None, None).unwrap().base_url(),
             "http://default"
         );
     }
@@ -2326,4 +2536,30 @@ mod tests {
             assert_ne!(error["message"], "Unknown tool: get_system_info");
         }
     }
+
+    #[test]
+    fn test_rank_completions() {
+        let candidates = vec![
+            ("aaa111".to_string(), "Ubuntu Server".to_string()),
+            ("bbb222".to_string(), "Debian Netinst".to_string()),
+            ("ccc333".to_string(), "Arch Linux".to_string()),
+        ];
+
+        // Prefix on the label beats a substring, which beats a subsequence
+        assert_eq!(
+            rank_completions("deb", candidates.clone()),
+            vec!["bbb222".to_string()]
+        );
+        assert_eq!(
+            rank_completions("n", candidates.clone()),
+            vec!["ccc333", "bbb222", "aaa111"]
+        );
+        assert_eq!(
+            rank_completions("ubsrv", candidates.clone()),
+            vec!["aaa111"]
+        );
+        // Hash prefixes match too, and an empty input returns everything
+        assert_eq!(rank_completions("CCC", candidates.clone()), vec!["ccc333"]);
+        assert_eq!(rank_completions("", candidates).len(), 3);
+    }
 }
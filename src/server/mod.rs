@@ -1,2 +1,14 @@
+pub mod audit;
+pub mod auth;
+pub mod health;
 pub mod http;
+pub mod instances;
+pub mod journal;
+pub mod logging;
 pub mod mcp;
+pub mod oauth;
+pub mod policy;
+pub mod prompts;
+pub mod resources;
+pub mod tls;
+pub mod tools;
@@ -0,0 +1,392 @@
+//! OAuth 2.1 resource server for the HTTP transport: bearer JWTs issued by an authorization
+//! server are checked against its JWKS, and their scopes are mapped to a [`Grant`].
+
+use crate::reload::fingerprint;
+use crate::server::auth::{AuthFailure, Grant, Scope};
+use anyhow::{Context, Result, anyhow};
+use jsonwebtoken::jwk::{Jwk, JwkSet};
+use jsonwebtoken::{Algorithm, DecodingKey, Validation, decode, decode_header};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+use time::OffsetDateTime;
+use tracing::{debug, info, warn};
+
+/// Path of the protected resource metadata (RFC 9728).
+pub const METADATA_PATH: &str = "/.well-known/oauth-protected-resource";
+/// Prefix of the `sub` claim in grant names, so they cannot be mistaken for named tokens.
+pub const IDENTITY_PREFIX: &str = "oauth:";
+
+/// How long keys fetched from a URL are used before they are fetched again.
+const JWKS_TTL: Duration = Duration::from_secs(3600);
+/// A token signed with an unknown key makes the keys be loaded again, but not more often
+/// than this, so that made-up key ids cannot flood the issuer. Also how long to wait after
+/// a failed load before trying again.
+const JWKS_MIN_REFRESH: Duration = Duration::from_secs(30);
+const JWKS_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Only signatures made with a private key; a shared secret would let anyone holding the
+/// public JWKS mint tokens.
+const ALGORITHMS: &[Algorithm] = &[
+    Algorithm::RS256,
+    Algorithm::RS384,
+    Algorithm::RS512,
+    Algorithm::PS256,
+    Algorithm::PS384,
+    Algorithm::PS512,
+    Algorithm::ES256,
+    Algorithm::ES384,
+    Algorithm::EdDSA,
+];
+
+/// Where the issuer's signing keys come from.
+#[derive(Debug, Clone, PartialEq)]
+pub enum JwksSource {
+    Url(String),
+    /// A JWKS document on disk, for setups without access to the issuer.
+    File(PathBuf),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OAuthSettings {
+    /// The authorization server; tokens must carry it as `iss`.
+    pub issuer: String,
+    /// The canonical URL of this server, advertised in the metadata.
+    pub resource: String,
+    /// The `aud` tokens must carry.
+    pub audience: String,
+    pub jwks: JwksSource,
+    /// The OAuth scope that grants each [`Scope`].
+    pub scopes: Vec<(String, Scope)>,
+}
+
+#[derive(Deserialize)]
+struct Claims {
+    sub: Option<String>,
+    client_id: Option<String>,
+    exp: i64,
+    /// Space-separated, as in RFC 9068.
+    #[serde(default)]
+    scope: Option<String>,
+    /// A list, as some issuers send it.
+    #[serde(default)]
+    scp: Option<Vec<String>>,
+}
+
+struct Keys {
+    set: Arc<JwkSet>,
+    loaded_at: Instant,
+    /// Of the file the keys were read from.
+    fingerprint: Option<(SystemTime, u64)>,
+}
+
+#[derive(Default)]
+struct KeyCache {
+    keys: Option<Keys>,
+    /// When loading last failed, and why.
+    failure: Option<(Instant, String)>,
+}
+
+/// Validates bearer JWTs for [`OAuthSettings`].
+pub struct OAuthVerifier {
+    settings: OAuthSettings,
+    http: reqwest::Client,
+    cache: Mutex<KeyCache>,
+    // Held while loading, so that one request loads and those without a usable key wait
+    loading: tokio::sync::Mutex<()>,
+}
+
+impl OAuthVerifier {
+    /// Keys in a file are loaded right away, so that a broken file fails at startup; keys
+    /// at a URL are fetched on the first request.
+    pub fn new(settings: OAuthSettings) -> Result<Self> {
+        let http = reqwest::Client::builder().timeout(JWKS_TIMEOUT).build()?;
+        let keys = match &settings.jwks {
+            JwksSource::File(path) => Some(Keys {
+                set: Arc::new(read_jwks(path)?),
+                loaded_at: Instant::now(),
+                fingerprint: fingerprint(path),
+            }),
+            JwksSource::Url(_) => None,
+        };
+        Ok(Self {
+            settings,
+            http,
+            cache: Mutex::new(KeyCache {
+                keys,
+                failure: None,
+            }),
+            loading: tokio::sync::Mutex::new(()),
+        })
+    }
+
+    pub fn settings(&self) -> &OAuthSettings {
+        &self.settings
+    }
+
+    /// The protected resource metadata served at [`METADATA_PATH`].
+    pub fn metadata(&self) -> Value {
+        json!({
+            "resource": self.settings.resource,
+            "authorization_servers": [self.settings.issuer],
+            "scopes_supported": self.scopes_supported(),
+            "bearer_methods_supported": ["header"],
+            "resource_name": "qBittorrent MCP server",
+        })
+    }
+
+    /// Where clients find the metadata: the well-known path inserted before the path of
+    /// the resource URL.
+    pub fn metadata_url(&self) -> String {
+        match url::Url::parse(&self.settings.resource) {
+            Ok(resource) => {
+                let path = resource.path().trim_end_matches('/');
+                format!(
+                    "{}{}{}",
+                    resource.origin().ascii_serialization(),
+                    METADATA_PATH,
+                    path
+                )
+            }
+            Err(_) => format!(
+                "{}{}",
+                self.settings.resource.trim_end_matches('/'),
+                METADATA_PATH
+            ),
+        }
+    }
+
+    pub fn scopes_supported(&self) -> Vec<&str> {
+        self.settings
+            .scopes
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect()
+    }
+
+    /// Checks the signature, issuer, audience and expiry of `token`, and maps its scopes.
+    pub async fn verify(&self, token: &str) -> Result<Grant, AuthFailure> {
+        let header =
+            decode_header(token).map_err(|e| invalid(format!("malformed token: {}", e)))?;
+        if !ALGORITHMS.contains(&header.alg) {
+            return Err(invalid(format!(
+                "algorithm {:?} is not accepted",
+                header.alg
+            )));
+        }
+        let jwk = self
+            .key(header.kid.as_deref(), header.alg)
+            .await
+            .map_err(|e| invalid(format!("{:#}", e)))?;
+        let key = DecodingKey::from_jwk(&jwk).map_err(|e| invalid(format!("bad key: {}", e)))?;
+
+        let mut validation = Validation::new(header.alg);
+        validation.set_issuer(&[&self.settings.issuer]);
+        validation.set_audience(&[&self.settings.audience]);
+        validation.set_required_spec_claims(&["exp", "iss", "aud"]);
+        let claims = decode::<Claims>(token, &key, &validation)
+            .map_err(|e| invalid(e.to_string()))?
+            .claims;
+
+        let subject = claims
+            .sub
+            .or(claims.client_id)
+            .ok_or_else(|| invalid("no sub claim".into()))?;
+        let granted: BTreeSet<&str> = claims
+            .scope
+            .as_deref()
+            .unwrap_or_default()
+            .split_whitespace()
+            .chain(claims.scp.iter().flatten().map(String::as_str))
+            .collect();
+        let scopes: BTreeSet<Scope> = self
+            .settings
+            .scopes
+            .iter()
+            .filter(|(name, _)| granted.contains(name.as_str()))
+            .map(|(_, scope)| *scope)
+            .collect();
+        let name = format!("{}{}", IDENTITY_PREFIX, subject);
+        if scopes.is_empty() {
+            return Err(AuthFailure::InsufficientScope(name));
+        }
+        Ok(Grant {
+            name,
+            scopes,
+            instances: None,
+            expires: OffsetDateTime::from_unix_timestamp(claims.exp).ok(),
+        })
+    }
+
+    /// The key with id `kid`, or without an id the only key for `alg`. Keys are loaded
+    /// again when they are stale or the key is not among them. If that fails, the keys
+    /// already loaded stay in use, and loading is not tried again for a while.
+    async fn key(&self, kid: Option<&str>, alg: Algorithm) -> Result<Jwk> {
+        let (cached, due) = self.lookup(kid, alg);
+        if !due {
+            return cached.ok_or_else(|| self.missing(kid));
+        }
+        let _loading = match (self.loading.try_lock(), cached) {
+            (Ok(guard), _) => guard,
+            // A request with a usable key does not wait for another one's load
+            (Err(_), Some(jwk)) => return Ok(jwk),
+            (Err(_), None) => self.loading.lock().await,
+        };
+        // Another request may have loaded the keys in the meantime
+        let (cached, due) = self.lookup(kid, alg);
+        if !due {
+            return cached.ok_or_else(|| self.missing(kid));
+        }
+
+        if cached.is_none() {
+            debug!("Key {:?} is not in the JWKS, loading it again", kid);
+        }
+        match self.load().await {
+            Ok(keys) => {
+                let jwk = find_key(&keys.set, kid, alg).cloned();
+                *self.cache.lock().unwrap() = KeyCache {
+                    keys: Some(keys),
+                    failure: None,
+                };
+                jwk.ok_or_else(|| self.missing(kid))
+            }
+            Err(e) => {
+                warn!("{:#}; retrying in {} s", e, JWKS_MIN_REFRESH.as_secs());
+                self.cache.lock().unwrap().failure = Some((Instant::now(), format!("{:#}", e)));
+                cached.ok_or(e)
+            }
+        }
+    }
+
+    /// The cached key for `kid` and `alg`, and whether the keys should be loaded again.
+    fn lookup(&self, kid: Option<&str>, alg: Algorithm) -> (Option<Jwk>, bool) {
+        let cache = self.cache.lock().unwrap();
+        let jwk = cache
+            .keys
+            .as_ref()
+            .and_then(|keys| find_key(&keys.set, kid, alg).cloned());
+        let backing_off = cache
+            .failure
+            .as_ref()
+            .is_some_and(|(at, _)| at.elapsed() < JWKS_MIN_REFRESH);
+        let due = !backing_off
+            && cache.keys.as_ref().is_none_or(|keys| {
+                self.is_stale(keys)
+                    || (jwk.is_none() && keys.loaded_at.elapsed() >= JWKS_MIN_REFRESH)
+            });
+        (jwk, due)
+    }
+
+    fn missing(&self, kid: Option<&str>) -> anyhow::Error {
+        let cache = self.cache.lock().unwrap();
+        match (&cache.keys, &cache.failure) {
+            (None, Some((_, error))) => anyhow!("the JWKS could not be loaded: {}", error),
+            _ => anyhow!("no key {} in the JWKS", kid.unwrap_or("for the algorithm")),
+        }
+    }
+
+    fn is_stale(&self, keys: &Keys) -> bool {
+        match &self.settings.jwks {
+            JwksSource::Url(_) => keys.loaded_at.elapsed() >= JWKS_TTL,
+            JwksSource::File(path) => fingerprint(path) != keys.fingerprint,
+        }
+    }
+
+    async fn load(&self) -> Result<Keys> {
+        let (set, fingerprint) = match &self.settings.jwks {
+            JwksSource::Url(url) => {
+                let set = self
+                    .http
+                    .get(url)
+                    .send()
+                    .await
+                    .and_then(|resp| resp.error_for_status())
+                    .with_context(|| format!("Failed to fetch the JWKS from {}", url))?
+                    .json::<JwkSet>()
+                    .await
+                    .with_context(|| format!("Invalid JWKS at {}", url))?;
+                info!("Fetched {} signing keys from {}", set.keys.len(), url);
+                (set, None)
+            }
+            JwksSource::File(path) => {
+                let set = read_jwks(path)?;
+                info!(
+                    "Loaded {} signing keys from {}",
+                    set.keys.len(),
+                    path.display()
+                );
+                (set, fingerprint(path))
+            }
+        };
+        if set.keys.is_empty() {
+            warn!("The JWKS has no keys; every OAuth token will be rejected");
+        }
+        Ok(Keys {
+            set: Arc::new(set),
+            loaded_at: Instant::now(),
+            fingerprint,
+        })
+    }
+}
+
+fn read_jwks(path: &Path) -> Result<JwkSet> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read the JWKS {}", path.display()))?;
+    serde_json::from_str(&content).with_context(|| format!("Invalid JWKS {}", path.display()))
+}
+
+fn find_key<'a>(set: &'a JwkSet, kid: Option<&str>, alg: Algorithm) -> Option<&'a Jwk> {
+    // A key that names its algorithm may only be used with that one
+    let usable = |jwk: &&Jwk| {
+        jwk.common
+            .key_algorithm
+            .is_none_or(|key_alg| key_alg.to_string() == format!("{:?}", alg))
+    };
+    match kid {
+        Some(kid) => set.find(kid).filter(usable),
+        None => {
+            let mut candidates = set.keys.iter().filter(usable).filter(|jwk| {
+                DecodingKey::from_jwk(jwk).is_ok_and(|key| key.family() == alg.family())
+            });
+            let jwk = candidates.next()?;
+            candidates.next().is_none().then_some(jwk)
+        }
+    }
+}
+
+fn invalid(reason: String) -> AuthFailure {
+    AuthFailure::Invalid(reason)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(resource: &str) -> OAuthSettings {
+        OAuthSettings {
+            issuer: "https://auth.example.com".into(),
+            resource: resource.into(),
+            audience: resource.into(),
+            jwks: JwksSource::Url("https://auth.example.com/jwks.json".into()),
+            scopes: vec![("mcp:read".into(), Scope::Read)],
+        }
+    }
+
+    #[test]
+    fn test_metadata_url() {
+        let verifier = OAuthVerifier::new(settings("https://mcp.example.com")).unwrap();
+        assert_eq!(
+            verifier.metadata_url(),
+            "https://mcp.example.com/.well-known/oauth-protected-resource"
+        );
+        let verifier = OAuthVerifier::new(settings("https://example.com/qbt/")).unwrap();
+        assert_eq!(
+            verifier.metadata_url(),
+            "https://example.com/.well-known/oauth-protected-resource/qbt"
+        );
+        assert_eq!(verifier.metadata()["scopes_supported"], json!(["mcp:read"]));
+    }
+}
@@ -0,0 +1,103 @@
+//! Which tools the server lets clients call, globally or per qBittorrent instance.
+
+use std::collections::BTreeSet;
+
+/// Restrictions on the tools a client may call.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ToolPolicy {
+    /// Reject every tool that is not annotated as read-only.
+    pub read_only: bool,
+    /// When set, only these tools may be called.
+    pub allowed_tools: Option<BTreeSet<String>>,
+    /// Tools that may never be called. Takes precedence over `allowed_tools`.
+    pub denied_tools: BTreeSet<String>,
+}
+
+impl ToolPolicy {
+    pub fn read_only() -> Self {
+        Self {
+            read_only: true,
+            ..Self::default()
+        }
+    }
+
+    pub fn with_allowed_tools<I, S>(mut self, tools: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.allowed_tools = Some(tools.into_iter().map(Into::into).collect());
+        self
+    }
+
+    pub fn with_denied_tools<I, S>(mut self, tools: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.denied_tools = tools.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Why this policy forbids calling `tool`, or `None` if it may be called.
+    pub fn denial(&self, tool: &str, read_only_tool: bool) -> Option<&'static str> {
+        if self.denied_tools.contains(tool) {
+            Some("it is in denied_tools")
+        } else if self
+            .allowed_tools
+            .as_ref()
+            .is_some_and(|allowed| !allowed.contains(tool))
+        {
+            Some("it is not in allowed_tools")
+        } else if self.read_only && !read_only_tool {
+            Some("the server is in read-only mode")
+        } else {
+            None
+        }
+    }
+}
+
+/// A tool call rejected by the configured [`ToolPolicy`].
+#[derive(Debug)]
+pub struct PolicyDenied {
+    pub tool: String,
+    pub instance: String,
+    pub reason: &'static str,
+}
+
+impl std::fmt::Display for PolicyDenied {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Policy denied: {} is not allowed on instance '{}' because {}",
+            self.tool, self.instance, self.reason
+        )
+    }
+}
+
+impl std::error::Error for PolicyDenied {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_denial() {
+        let open = ToolPolicy::default();
+        assert_eq!(open.denial("delete_torrent", false), None);
+
+        let read_only = ToolPolicy::read_only();
+        assert_eq!(read_only.denial("list_torrents", true), None);
+        assert!(read_only.denial("delete_torrent", false).is_some());
+
+        let allow = ToolPolicy::default().with_allowed_tools(["list_torrents", "delete_torrent"]);
+        assert_eq!(allow.denial("delete_torrent", false), None);
+        assert!(allow.denial("add_torrent", false).is_some());
+
+        let deny = allow.with_denied_tools(["delete_torrent"]);
+        assert_eq!(
+            deny.denial("delete_torrent", false),
+            Some("it is in denied_tools")
+        );
+    }
+}
@@ -0,0 +1,700 @@
+//! MCP prompts. Prompts about an instance fetch its current state server-side and embed
+//! it in the messages, as `qbittorrent://{instance}/...` resources and short summaries,
+//! so the model starts from the data rather than from a list of tools to call. Data that
+//! cannot be fetched is noted in the prompt instead of failing it.
+
+use crate::client::QBitClient;
+use crate::humanize::{Humanize, Humanizer, format_duration, format_size};
+use crate::models::Torrent;
+use crate::server::mcp::{invalid_argument, opt_str};
+use crate::server::resources::{self, SCHEME};
+use anyhow::Result;
+use serde_json::{Value, json};
+
+/// Embedded torrent lists are cut at this many entries.
+const MAX_TORRENTS: usize = 50;
+/// How many recent main log entries troubleshooting prompts include.
+const MAX_LOG_ENTRIES: usize = 20;
+const WEEK_SECS: i64 = 7 * 86400;
+/// States worth a closer look in health checks and reports.
+const PROBLEM_STATES: &[&str] = &["error", "missingFiles", "stalledDL", "metaDL", "unknown"];
+
+/// The instance a prompt is about.
+pub struct Target<'a> {
+    pub instance: &'a str,
+    pub client: &'a QBitClient,
+    /// How embedded resources are rendered, the same as `resources/read` would.
+    pub resource_view: Option<&'a Humanizer>,
+    /// Renders the summaries written for the prompt.
+    pub humanizer: &'a Humanizer,
+}
+
+fn instance_argument() -> Value {
+    json!({
+        "name": "instance",
+        "description": "Instance name (optional)",
+        "required": false
+    })
+}
+
+/// `prompts/list` entries.
+pub fn definitions() -> Vec<Value> {
+    vec![
+        json!({
+            "name": "troubleshoot_torrent",
+            "description": "Unified troubleshooting for various torrent issues (stalled, slow, connection, etc.), with the torrent's properties, trackers, peers and recent log entries attached",
+            "arguments": [
+                {
+                    "name": "hash",
+                    "description": "Torrent hash to troubleshoot (optional for general connection issues)",
+                    "required": false
+                },
+                {
+                    "name": "issue_type",
+                    "description": "Type of issue: 'stalled', 'slow', 'connection', or 'general'",
+                    "required": true
+                },
+                instance_argument()
+            ]
+        }),
+        json!({
+            "name": "analyze_disk_space",
+            "description": "Check if there is enough disk space for current downloads",
+            "arguments": [instance_argument()]
+        }),
+        json!({
+            "name": "weekly_activity_report",
+            "description": "Summarize what was added, completed and transferred over the last 7 days",
+            "arguments": [instance_argument()]
+        }),
+        json!({
+            "name": "free_up_space",
+            "description": "Propose completed torrents to remove to free up a target amount of disk space",
+            "arguments": [
+                {
+                    "name": "target_gb",
+                    "description": "How much space to free, in GB",
+                    "required": true
+                },
+                instance_argument()
+            ]
+        }),
+        json!({
+            "name": "organize_library",
+            "description": "Suggest categories and tags for torrents in a category, or for uncategorized torrents",
+            "arguments": [
+                {
+                    "name": "category",
+                    "description": "Category to organize (optional; uncategorized torrents by default)",
+                    "required": false
+                },
+                instance_argument()
+            ]
+        }),
+        json!({
+            "name": "review_seeding_obligations",
+            "description": "Review which completed torrents still have to seed to meet their ratio and seeding time limits",
+            "arguments": [instance_argument()]
+        }),
+        json!({
+            "name": "rules-of-engagement",
+            "description": "Get the behavioral rules and best practices for interacting with this qBittorrent MCP server",
+            "arguments": []
+        }),
+    ]
+}
+
+/// Whether the prompt `name` is about an instance, or `None` if there is no such prompt.
+pub fn takes_instance(name: &str) -> Option<bool> {
+    definitions()
+        .into_iter()
+        .find(|p| p["name"] == name)
+        .map(|p| {
+            p["arguments"]
+                .as_array()
+                .is_some_and(|args| args.iter().any(|a| a["name"] == "instance"))
+        })
+}
+
+/// The rules of engagement, the one prompt that is not about an instance.
+pub fn rules_of_engagement() -> Value {
+    json!({
+        "description": "Rules of Engagement for qBittorrent MCP",
+        "messages": [
+            {
+                "role": "user",
+                "content": {
+                    "type": "text",
+                    "text": "Please provide the Rules of Engagement for this MCP server."
+                }
+            },
+            {
+                "role": "assistant",
+                "content": {
+                    "type": "text",
+                    "text": "As an AI agent interacting with the qBittorrent MCP server, you must adhere to the following Rules of Engagement:\n\n\
+                             1. **State Verification**: Always verify the current state of a torrent (via `list_torrents` or resources) before performing actions like pause, resume, or delete.\n\
+                             2. **Destructive Actions**: Clearly inform the user and obtain confirmation before calling `delete_torrent` or `shutdown_app`. For these \"destructive\" actions, use the `destructiveHint` annotation or require a separate confirmation step.\n\
+                             3. **Search Etiquette**: Search is asynchronous. Use `get_search_results` for polling and always call `stop_search` once finished to save resources.\n\
+                             4. **Error Handling**: Treat errors as information for self-correction. Return helpful hints and use `isError: true` to prevent hallucination.\n\
+                             5. **Idempotency**: Avoid redundant commands (e.g., do not pause an already paused torrent).\n\
+                             6. **Semantic Feedback**: Translate technical tool results into meaningful context for the user.\n\
+                             7. **Security**: Never expose sensitive credentials or session cookies in logs or to the user."
+                }
+            }
+        ]
+    })
+}
+
+/// `prompts/get` for the instance prompts.
+pub async fn get(name: &str, args: &Value, target: &Target<'_>) -> Result<Value> {
+    let mut prompt = Prompt::new(target);
+    match name {
+        "troubleshoot_torrent" => troubleshoot_torrent(&mut prompt, args).await?,
+        "analyze_disk_space" => analyze_disk_space(&mut prompt).await,
+        "weekly_activity_report" => weekly_activity_report(&mut prompt).await,
+        "free_up_space" => free_up_space(&mut prompt, args).await?,
+        "organize_library" => organize_library(&mut prompt, args).await?,
+        "review_seeding_obligations" => review_seeding_obligations(&mut prompt).await,
+        _ => {
+            return Err(invalid_argument(
+                "name",
+                "name of a prompt from prompts/list",
+                format!("Prompt not found: {}", name),
+            ));
+        }
+    }
+    Ok(json!({
+        "description": prompt.description,
+        "messages": prompt.messages
+    }))
+}
+
+/// Collects the messages of a prompt: the request first, then the data it is about.
+struct Prompt<'a> {
+    target: &'a Target<'a>,
+    description: String,
+    messages: Vec<Value>,
+}
+
+impl<'a> Prompt<'a> {
+    fn new(target: &'a Target<'a>) -> Self {
+        Self {
+            target,
+            description: String::new(),
+            messages: Vec::new(),
+        }
+    }
+
+    fn text(&mut self, text: impl Into<String>) {
+        self.messages.push(json!({
+            "role": "user",
+            "content": { "type": "text", "text": text.into() }
+        }));
+    }
+
+    /// Embeds the resource at `path` on the target instance and returns its contents.
+    async fn resource(&mut self, path: &str) -> Option<Value> {
+        let uri = format!("{}{}/{}", SCHEME, self.target.instance, path);
+        match resources::read(self.target.client, path, self.target.resource_view).await {
+            Ok(value) => {
+                self.messages.push(json!({
+                    "role": "user",
+                    "content": {
+                        "type": "resource",
+                        "resource": {
+                            "uri": uri,
+                            "mimeType": "application/json",
+                            "text": serde_json::to_string_pretty(&value).unwrap_or_default()
+                        }
+                    }
+                }));
+                Some(value)
+            }
+            Err(e) => {
+                self.unavailable(&uri, &e);
+                None
+            }
+        }
+    }
+
+    /// Adds data put together for the prompt, under a title.
+    fn data(&mut self, title: &str, value: &Value) {
+        self.text(format!(
+            "{}:\n```json\n{}\n```",
+            title,
+            serde_json::to_string_pretty(value).unwrap_or_default()
+        ));
+    }
+
+    /// Adds up to [`MAX_TORRENTS`] torrents, noting how many were left out.
+    fn torrents(&mut self, title: &str, torrents: Vec<Value>) {
+        let total = torrents.len();
+        if total == 0 {
+            self.text(format!("{}: none.", title));
+        } else if total > MAX_TORRENTS {
+            let shown: Vec<Value> = torrents.into_iter().take(MAX_TORRENTS).collect();
+            self.data(
+                &format!("{} (the first {} of {})", title, MAX_TORRENTS, total),
+                &Value::Array(shown),
+            );
+        } else {
+            self.data(title, &Value::Array(torrents));
+        }
+    }
+
+    fn unavailable(&mut self, what: &str, err: &anyhow::Error) {
+        self.text(format!("(Could not fetch {}: {})", what, err));
+    }
+
+    /// All torrents on the instance, or `None` (noted in the prompt) if they could not be listed.
+    async fn torrent_list(&mut self) -> Option<Vec<Torrent>> {
+        let result = self
+            .target
+            .client
+            .get_torrent_list(None, None, None, None, None, None, None)
+            .await;
+        match result {
+            Ok(torrents) => Some(torrents),
+            Err(e) => {
+                self.unavailable("the torrent list", &e);
+                None
+            }
+        }
+    }
+
+    /// The last warnings and critical messages of the main log.
+    async fn recent_problems(&mut self) {
+        let result = self
+            .target
+            .client
+            .get_main_log(false, false, true, true, None)
+            .await;
+        match result {
+            Ok(log) => {
+                let start = log.len().saturating_sub(MAX_LOG_ENTRIES);
+                let entries: Vec<Value> = log[start..]
+                    .iter()
+                    .map(|entry| {
+                        json!({
+                            "time": self.target.humanizer.timestamp(entry.timestamp),
+                            "severity": if entry.severity == 8 { "critical" } else { "warning" },
+                            "message": entry.message
+                        })
+                    })
+                    .collect();
+                if entries.is_empty() {
+                    self.text("Recent warnings and errors in the main log: none.");
+                } else {
+                    self.data(
+                        "Recent warnings and errors in the main log",
+                        &Value::Array(entries),
+                    );
+                }
+            }
+            Err(e) => self.unavailable("the main log", &e),
+        }
+    }
+
+    /// A humanized torrent with only the fields a prompt needs.
+    fn summary(&self, torrent: &Torrent, fields: &[&str]) -> Value {
+        let full = torrent.humanize(self.target.humanizer);
+        let mut summary = serde_json::Map::new();
+        for field in ["name", "hash"].iter().chain(fields) {
+            if let Some(value) = full.get(*field) {
+                summary.insert(field.to_string(), value.clone());
+            }
+        }
+        Value::Object(summary)
+    }
+}
+
+fn now() -> i64 {
+    time::OffsetDateTime::now_utc().unix_timestamp()
+}
+
+fn is_complete(torrent: &Torrent) -> bool {
+    torrent.progress >= 1.0
+}
+
+fn remaining_bytes(torrent: &Torrent) -> i64 {
+    (torrent.size_bytes as f64 * (1.0 - torrent.progress.clamp(0.0, 1.0))) as i64
+}
+
+fn server_state_bytes(state: Option<&Value>, key: &str) -> Option<i64> {
+    state.and_then(|s| s.get(key)).and_then(Value::as_i64)
+}
+
+async fn troubleshoot_torrent(prompt: &mut Prompt<'_>, args: &Value) -> Result<()> {
+    let instance = prompt.target.instance;
+    let issue_type = opt_str(args, "issue_type")?.unwrap_or("general");
+    let hash = opt_str(args, "hash")?;
+    prompt.description = format!(
+        "Troubleshooting for {} issue on instance {}",
+        issue_type, instance
+    );
+
+    match issue_type {
+        "stalled" | "slow" => {
+            let hash = hash.ok_or_else(|| {
+                invalid_argument(
+                    "hash",
+                    "string",
+                    "Missing hash for stalled/slow troubleshooting".to_string(),
+                )
+            })?;
+            prompt.text(format!(
+                "I have a torrent with hash '{}' on instance '{}' that is {}. Its current state, \
+                 properties, trackers and peers, and recent warnings from the log, are attached. \
+                 Please work out the likely cause (for example no seeds, tracker errors, speed limits, \
+                 queueing or a firewalled connection) and suggest specific fixes, like re-announcing, \
+                 toggling sequential download or changing limits via 'manage_torrents'.",
+                hash, instance, issue_type
+            ));
+            match prompt.target.client.get_torrents_info(hash).await {
+                Ok(torrents) => match torrents.iter().find(|t| t.hash.eq_ignore_ascii_case(hash)) {
+                    Some(torrent) => {
+                        let value = torrent.humanize(prompt.target.humanizer);
+                        prompt.data("The torrent", &value);
+                    }
+                    None => prompt.text(format!(
+                        "No torrent with hash '{}' was found on instance '{}'.",
+                        hash, instance
+                    )),
+                },
+                Err(e) => prompt.unavailable("the torrent", &e),
+            }
+            for part in ["properties", "trackers", "peers"] {
+                prompt.resource(&format!("torrent/{}/{}", hash, part)).await;
+            }
+            prompt.recent_problems().await;
+        }
+        "connection" => {
+            prompt.text(format!(
+                "I think I have connection issues on instance '{}'. The transfer info, server state \
+                 and recent warnings from the log are attached. Please check the connection status \
+                 and DHT node count, whether alternative speed limits are accidentally enabled, and \
+                 what the log says, then suggest fixes.",
+                instance
+            ));
+            prompt.resource("transfer").await;
+            prompt.resource("server-state").await;
+            prompt.recent_problems().await;
+        }
+        _ => {
+            prompt.text(format!(
+                "Please provide a general health check for instance '{}'. The transfer info, server \
+                 state and the torrents that need attention are attached. Point out anything that \
+                 looks wrong and how to fix it.",
+                instance
+            ));
+            prompt.resource("transfer").await;
+            prompt.resource("server-state").await;
+            if let Some(torrents) = prompt.torrent_list().await {
+                let problems = torrents
+                    .iter()
+                    .filter(|t| PROBLEM_STATES.contains(&t.state.as_str()))
+                    .map(|t| prompt.summary(t, &["state", "progress", "num_seeds", "category"]))
+                    .collect();
+                prompt.torrents(
+                    &format!(
+                        "Torrents that need attention, out of {} in total",
+                        torrents.len()
+                    ),
+                    problems,
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn analyze_disk_space(prompt: &mut Prompt<'_>) {
+    let instance = prompt.target.instance;
+    prompt.description = format!("Analyze disk space on instance {}", instance);
+    prompt.text(format!(
+        "I want to check if I have enough disk space for my downloads on instance '{}'. The \
+         server state (with the free space on disk) and the incomplete torrents, with how much \
+         each still has to download, are attached. Please compare the two and tell me whether \
+         the downloads will fit, and if not, what to do about it.",
+        instance
+    ));
+    let state = prompt.resource("server-state").await;
+    let Some(torrents) = prompt.torrent_list().await else {
+        return;
+    };
+    let mut incomplete: Vec<&Torrent> = torrents.iter().filter(|t| !is_complete(t)).collect();
+    incomplete.sort_by_key(|t| std::cmp::Reverse(remaining_bytes(t)));
+    let remaining: i64 = incomplete.iter().map(|t| remaining_bytes(t)).sum();
+    let mut totals = json!({
+        "incomplete_torrents": incomplete.len(),
+        "remaining_to_download": format_size(remaining),
+    });
+    if let Some(free) = server_state_bytes(state.as_ref(), "free_space_on_disk") {
+        totals["free_space_on_disk"] = json!(format_size(free));
+        totals["free_space_after_downloads"] = json!(format_size(free - remaining));
+    }
+    prompt.data("Totals", &totals);
+    let listed = incomplete
+        .iter()
+        .map(|t| {
+            let mut summary = prompt.summary(t, &["size", "progress", "state", "category"]);
+            summary["remaining"] = json!(format_size(remaining_bytes(t)));
+            summary
+        })
+        .collect();
+    prompt.torrents("Incomplete torrents, largest remaining first", listed);
+}
+
+async fn weekly_activity_report(prompt: &mut Prompt<'_>) {
+    let instance = prompt.target.instance;
+    let since = now() - WEEK_SECS;
+    prompt.description = format!("Weekly activity report for instance {}", instance);
+    prompt.text(format!(
+        "Please write a short activity report for instance '{}' covering the last 7 days (since \
+         {}). The torrents added and completed in that time, the server state with all-time \
+         totals, the current transfer info and the torrents that need attention are attached. \
+         Cover what was added and finished, how much was transferred, and anything that needs \
+         my attention.",
+        instance,
+        prompt.target.humanizer.timestamp(since)
+    ));
+    prompt.resource("server-state").await;
+    prompt.resource("transfer").await;
+    let Some(torrents) = prompt.torrent_list().await else {
+        return;
+    };
+
+    let mut added: Vec<&Torrent> = torrents.iter().filter(|t| t.added_on >= since).collect();
+    added.sort_by_key(|t| std::cmp::Reverse(t.added_on));
+    let mut completed: Vec<&Torrent> = torrents
+        .iter()
+        .filter(|t| t.completion_on >= since)
+        .collect();
+    completed.sort_by_key(|t| std::cmp::Reverse(t.completion_on));
+    prompt.data(
+        "Totals",
+        &json!({
+            "torrents": torrents.len(),
+            "added_this_week": added.len(),
+            "added_size": format_size(added.iter().map(|t| t.size_bytes).sum()),
+            "completed_this_week": completed.len(),
+            "completed_size": format_size(completed.iter().map(|t| t.size_bytes).sum()),
+        }),
+    );
+
+    let listed = added
+        .iter()
+        .map(|t| prompt.summary(t, &["added_on", "size", "progress", "state", "category"]))
+        .collect();
+    prompt.torrents("Added in the last 7 days", listed);
+    let listed = completed
+        .iter()
+        .map(|t| prompt.summary(t, &["completion_on", "size", "ratio", "category"]))
+        .collect();
+    prompt.torrents("Completed in the last 7 days", listed);
+    let listed = torrents
+        .iter()
+        .filter(|t| PROBLEM_STATES.contains(&t.state.as_str()))
+        .map(|t| prompt.summary(t, &["state", "progress", "category"]))
+        .collect();
+    prompt.torrents("Torrents that need attention", listed);
+}
+
+fn parse_target_gb(args: &Value) -> Result<f64> {
+    let expected = "positive number of GB";
+    let value = match args.get("target_gb") {
+        None | Some(Value::Null) => {
+            return Err(invalid_argument(
+                "target_gb",
+                expected,
+                "Missing target_gb".to_string(),
+            ));
+        }
+        // Prompt arguments are strings, but accept a number too
+        Some(Value::String(s)) => s.trim().parse::<f64>().ok(),
+        Some(v) => v.as_f64(),
+    };
+    value
+        .filter(|gb| gb.is_finite() && *gb > 0.0)
+        .ok_or_else(|| {
+            invalid_argument(
+                "target_gb",
+                expected,
+                format!("Invalid target_gb: expected {}", expected),
+            )
+        })
+}
+
+async fn free_up_space(prompt: &mut Prompt<'_>, args: &Value) -> Result<()> {
+    let instance = prompt.target.instance;
+    let target_gb = parse_target_gb(args)?;
+    let target_bytes = (target_gb * 1e9) as i64;
+    prompt.description = format!("Free up {} GB on instance {}", target_gb, instance);
+    prompt.text(format!(
+        "I need to free up {} GB on instance '{}'. The server state (with the free space on \
+         disk) and the completed torrents, largest first, are attached. Please propose a set of \
+         torrents to remove that reaches the target, preferring ones that have met their \
+         seeding ratio and that have not been active recently, and show the total it frees. \
+         Before deleting anything, call 'delete_torrent' with dry_run=true to show me the plan, \
+         and only delete after I confirm.",
+        target_gb, instance
+    ));
+    let state = prompt.resource("server-state").await;
+    let Some(torrents) = prompt.torrent_list().await else {
+        return Ok(());
+    };
+    let mut candidates: Vec<&Torrent> = torrents.iter().filter(|t| is_complete(t)).collect();
+    candidates.sort_by_key(|t| std::cmp::Reverse(t.size_bytes));
+
+    let reclaimable: i64 = candidates.iter().map(|t| t.size_bytes).sum();
+    let mut totals = json!({
+        "target": format_size(target_bytes),
+        "completed_torrents": candidates.len(),
+        "reclaimable_by_removing_all_with_files": format_size(reclaimable),
+    });
+    if let Some(free) = server_state_bytes(state.as_ref(), "free_space_on_disk") {
+        totals["free_space_on_disk"] = json!(format_size(free));
+    }
+    if reclaimable < target_bytes {
+        totals["note"] = json!("Removing every completed torrent would not reach the target");
+    }
+    prompt.data("Totals", &totals);
+
+    let listed = candidates
+        .iter()
+        .map(|t| {
+            prompt.summary(
+                t,
+                &[
+                    "size",
+                    "ratio",
+                    "ratio_limit",
+                    "completion_on",
+                    "upspeed",
+                    "state",
+                    "category",
+                    "tags",
+                ],
+            )
+        })
+        .collect();
+    prompt.torrents("Completed torrents, largest first", listed);
+    Ok(())
+}
+
+async fn organize_library(prompt: &mut Prompt<'_>, args: &Value) -> Result<()> {
+    let instance = prompt.target.instance;
+    let category = opt_str(args, "category")?.unwrap_or("");
+    let scope = if category.is_empty() {
+        "uncategorized torrents".to_string()
+    } else {
+        format!("torrents in the category '{}'", category)
+    };
+    prompt.description = format!("Organize {} on instance {}", scope, instance);
+    prompt.text(format!(
+        "Please help me organize the {} on instance '{}'. The existing categories (with their \
+         save paths), the existing tags and the torrents are attached. Suggest a category and \
+         tags for each torrent based on its name, reusing existing ones where they fit, and \
+         group the changes so they can be applied with 'manage_torrents' (set_category, \
+         add_tags). Changes can be reverted with 'undo_last_action'. Ask me before applying them.",
+        scope, instance
+    ));
+    prompt.resource("categories").await;
+    prompt.resource("tags").await;
+    let Some(torrents) = prompt.torrent_list().await else {
+        return Ok(());
+    };
+    let listed = torrents
+        .iter()
+        .filter(|t| t.category == category)
+        .map(|t| prompt.summary(t, &["size", "category", "tags", "added_on"]))
+        .collect();
+    prompt.torrents(&format!("The {}", scope), listed);
+    Ok(())
+}
+
+async fn review_seeding_obligations(prompt: &mut Prompt<'_>) {
+    let instance = prompt.target.instance;
+    prompt.description = format!("Review seeding obligations on instance {}", instance);
+    prompt.text(format!(
+        "Please review the seeding obligations on instance '{}'. The global share limits and the \
+         completed torrents, lowest ratio first, are attached. A per-torrent ratio_limit or \
+         seeding_time_limit of -2 means the global limit applies and -1 means no limit; seeding \
+         time limits are in minutes. Tell me which torrents still have to seed (especially any \
+         that are paused or stopped and should be resumed), which have met their obligations and \
+         can be removed, and whether the global limits look sensible.",
+        instance
+    ));
+    match prompt.target.client.get_app_preferences().await {
+        Ok(preferences) => {
+            let keys = [
+                "max_ratio_enabled",
+                "max_ratio",
+                "max_seeding_time_enabled",
+                "max_seeding_time",
+                "max_inactive_seeding_time_enabled",
+                "max_inactive_seeding_time",
+                "max_ratio_act",
+            ];
+            let limits: serde_json::Map<String, Value> = keys
+                .iter()
+                .filter_map(|k| preferences.get(*k).map(|v| (k.to_string(), v.clone())))
+                .collect();
+            prompt.data("Global share limits", &Value::Object(limits));
+        }
+        Err(e) => prompt.unavailable("the preferences", &e),
+    }
+    let Some(torrents) = prompt.torrent_list().await else {
+        return;
+    };
+    let now = now();
+    let mut completed: Vec<&Torrent> = torrents.iter().filter(|t| is_complete(t)).collect();
+    completed.sort_by(|a, b| a.ratio.total_cmp(&b.ratio));
+    let listed = completed
+        .iter()
+        .map(|t| {
+            let mut summary = prompt.summary(
+                t,
+                &[
+                    "ratio",
+                    "ratio_limit",
+                    "seeding_time_limit",
+                    "completion_on",
+                    "state",
+                    "category",
+                    "tags",
+                ],
+            );
+            if t.completion_on > 0 {
+                summary["seeding_for"] = json!(format_duration((now - t.completion_on).max(0)));
+            }
+            summary
+        })
+        .collect();
+    prompt.torrents("Completed torrents, lowest ratio first", listed);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_definitions() {
+        assert_eq!(takes_instance("free_up_space"), Some(true));
+        assert_eq!(takes_instance("rules-of-engagement"), Some(false));
+        assert_eq!(takes_instance("unknown"), None);
+    }
+
+    #[test]
+    fn test_parse_target_gb() {
+        assert_eq!(
+            parse_target_gb(&json!({ "target_gb": "12.5" })).unwrap(),
+            12.5
+        );
+        assert_eq!(parse_target_gb(&json!({ "target_gb": 3 })).unwrap(), 3.0);
+        assert!(parse_target_gb(&json!({})).is_err());
+        assert!(parse_target_gb(&json!({ "target_gb": "-1" })).is_err());
+        assert!(parse_target_gb(&json!({ "target_gb": "lots" })).is_err());
+    }
+}
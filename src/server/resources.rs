@@ -0,0 +1,250 @@
+//! MCP resources: `qbittorrent://{instance}/...` views of each configured instance.
+//! The catalog below drives `resources/list`, `resources/templates/list` and
+//! `resources/read`, so a resource cannot be listed without being readable.
+
+use crate::client::QBitClient;
+use crate::humanize::{Humanizer, view};
+use crate::models::Torrent;
+use crate::server::audit::redact_arguments;
+use anyhow::{Result, bail};
+use serde_json::{Value, json};
+
+pub const SCHEME: &str = "qbittorrent://";
+
+/// A resource every instance has, at `qbittorrent://{instance}/{path}`.
+struct Resource {
+    path: &'static str,
+    name: &'static str,
+    description: &'static str,
+}
+
+const RESOURCES: &[Resource] = &[
+    Resource {
+        path: "torrents",
+        name: "Torrent List",
+        description: "A live list of all torrents",
+    },
+    Resource {
+        path: "transfer",
+        name: "Global Transfer Info",
+        description: "Current speeds and limits",
+    },
+    Resource {
+        path: "categories",
+        name: "Categories",
+        description: "All defined categories",
+    },
+    Resource {
+        path: "tags",
+        name: "Tags",
+        description: "All defined tags",
+    },
+    Resource {
+        path: "server-state",
+        name: "Server State",
+        description: "Free disk space, all-time totals, connection status and other server state",
+    },
+    Resource {
+        path: "preferences",
+        name: "Preferences",
+        description: "Application preferences, with passwords and other secrets redacted",
+    },
+    Resource {
+        path: "main-log",
+        name: "Main Log",
+        description: "The main application log",
+    },
+    Resource {
+        path: "peer-log",
+        name: "Peer Log",
+        description: "The log of banned and blocked peers",
+    },
+    Resource {
+        path: "rss/feeds",
+        name: "RSS Feeds",
+        description: "All RSS feeds and their items",
+    },
+    Resource {
+        path: "rss/rules",
+        name: "RSS Rules",
+        description: "All RSS auto-download rules",
+    },
+    Resource {
+        path: "search-plugins",
+        name: "Search Plugins",
+        description: "Installed search plugins",
+    },
+];
+
+/// URI templates; `{instance}` and the other variables can be completed with
+/// `completion/complete`.
+const TEMPLATES: &[Resource] = &[
+    Resource {
+        path: "torrent/{hash}/properties",
+        name: "Torrent Properties",
+        description: "Detailed properties and metadata for a specific torrent",
+    },
+    Resource {
+        path: "torrent/{hash}/files",
+        name: "Torrent Files",
+        description: "List of files and their progress within a specific torrent",
+    },
+    Resource {
+        path: "torrent/{hash}/trackers",
+        name: "Torrent Trackers",
+        description: "Current trackers and their status for a specific torrent",
+    },
+    Resource {
+        path: "torrent/{hash}/peers",
+        name: "Torrent Peers",
+        description: "Peers connected for a specific torrent, keyed by ip:port",
+    },
+    Resource {
+        path: "category/{category}/torrents",
+        name: "Torrents in Category",
+        description: "All torrents in a category (an empty name lists uncategorized torrents)",
+    },
+    Resource {
+        path: "tag/{tag}/torrents",
+        name: "Torrents with Tag",
+        description: "All torrents with a tag",
+    },
+];
+
+/// `resources/list` entries for the given instances.
+pub fn definitions<'a>(instances: impl IntoIterator<Item = &'a str>) -> Vec<Value> {
+    let mut instances: Vec<&str> = instances.into_iter().collect();
+    instances.sort_unstable();
+    instances
+        .into_iter()
+        .flat_map(|instance| {
+            RESOURCES.iter().map(move |r| {
+                json!({
+                    "uri": format!("{}{}/{}", SCHEME, instance, r.path),
+                    "name": format!("{} ({})", r.name, instance),
+                    "description": format!("{} on instance: {}", r.description, instance),
+                    "mimeType": "application/json"
+                })
+            })
+        })
+        .collect()
+}
+
+/// `resources/templates/list` entries.
+pub fn template_definitions() -> Vec<Value> {
+    TEMPLATES
+        .iter()
+        .map(|t| {
+            json!({
+                "uriTemplate": format!("{}{{instance}}/{}", SCHEME, t.path),
+                "name": t.name,
+                "description": t.description,
+                "mimeType": "application/json"
+            })
+        })
+        .collect()
+}
+
+/// Splits `qbittorrent://{instance}/{path}` into its instance and path. The legacy
+/// `qbittorrent://{path}` form, without an instance, only has single-segment paths.
+pub fn parse_uri(uri: &str) -> Option<(Option<&str>, &str)> {
+    let rest = uri.strip_prefix(SCHEME)?;
+    let rest = rest.split_once('?').map_or(rest, |(path, _)| path);
+    match rest.split_once('/') {
+        Some((instance, path)) => Some((Some(instance), path)),
+        None => Some((None, rest)),
+    }
+}
+
+/// Reads the resource at `path` on `client`. Views of torrents, files, properties and
+/// transfer info are humanized when `humanizer` is given.
+pub async fn read(client: &QBitClient, path: &str, humanizer: Option<&Humanizer>) -> Result<Value> {
+    let segments: Vec<String> = path.split('/').map(percent_decode).collect();
+    let segments: Vec<&str> = segments.iter().map(String::as_str).collect();
+    Ok(match segments.as_slice() {
+        ["torrents"] => view(&torrents(client, None, None).await?, humanizer),
+        ["transfer"] => view(&client.get_global_transfer_info().await?, humanizer),
+        ["categories"] => json!(client.get_categories().await?),
+        ["tags"] => json!(client.get_tags().await?),
+        ["server-state"] => client
+            .get_main_data(0)
+            .await?
+            .server_state
+            .unwrap_or_else(|| json!({})),
+        ["preferences"] => redact_arguments(&client.get_app_preferences().await?),
+        ["main-log"] => json!(client.get_main_log(true, true, true, true, None).await?),
+        ["peer-log"] => json!(client.get_peer_log(None).await?),
+        ["rss", "feeds"] => json!(client.get_all_rss_feeds().await?),
+        ["rss", "rules"] => json!(client.get_all_rss_rules().await?),
+        ["search-plugins"] => json!(client.get_search_plugins().await?),
+        ["torrent", hash, "properties"] => {
+            view(&client.get_torrent_properties(hash).await?, humanizer)
+        }
+        ["torrent", hash, "files"] => view(&client.get_torrent_files(hash).await?, humanizer),
+        ["torrent", hash, "trackers"] => json!(client.get_torrent_trackers(hash).await?),
+        ["torrent", hash, "peers"] => json!(client.get_torrent_peers(hash).await?),
+        ["category", category, "torrents"] => {
+            view(&torrents(client, Some(category), None).await?, humanizer)
+        }
+        ["tag", tag, "torrents"] => view(&torrents(client, None, Some(tag)).await?, humanizer),
+        _ => bail!("Resource not found: {}", path),
+    })
+}
+
+async fn torrents(
+    client: &QBitClient,
+    category: Option<&str>,
+    tag: Option<&str>,
+) -> Result<Vec<Torrent>> {
+    client
+        .get_torrent_list(None, category, tag, None, None, None, None)
+        .await
+}
+
+/// Decodes `%XX` escapes, e.g. in category names with spaces.
+fn percent_decode(segment: &str) -> String {
+    let bytes = segment.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                out.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                out.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_uri() {
+        assert_eq!(
+            parse_uri("qbittorrent://nas/rss/feeds"),
+            Some((Some("nas"), "rss/feeds"))
+        );
+        assert_eq!(
+            parse_uri("qbittorrent://nas/transfer?humanize=true"),
+            Some((Some("nas"), "transfer"))
+        );
+        assert_eq!(
+            parse_uri("qbittorrent://torrents"),
+            Some((None, "torrents"))
+        );
+        assert_eq!(parse_uri("unknown://uri"), None);
+        assert_eq!(percent_decode("My%20Movies"), "My Movies");
+        assert_eq!(percent_decode("100%"), "100%");
+    }
+}
@@ -0,0 +1,158 @@
+//! TLS for the HTTP transport. The certificate and key are loaded from PEM files and loaded
+//! again when either changes, so that a renewed certificate is picked up without a restart.
+
+use crate::reload::fingerprint;
+use anyhow::{Context, Result, anyhow};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio::time::{MissedTickBehavior, interval, sleep, timeout};
+use tokio_rustls::TlsAcceptor;
+use tokio_rustls::rustls::ServerConfig;
+use tokio_rustls::rustls::crypto::aws_lc_rs;
+use tokio_rustls::rustls::pki_types::pem::PemObject;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use tokio_rustls::server::TlsStream;
+use tracing::{debug, error, info};
+
+/// How often the certificate and key are checked for changes.
+const WATCH_INTERVAL: Duration = Duration::from_secs(2);
+/// Connections that do not finish the handshake in time are dropped.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// PEM files with the certificate chain and the private key.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TlsFiles {
+    pub cert: PathBuf,
+    pub key: PathBuf,
+}
+
+impl TlsFiles {
+    pub fn load(&self) -> Result<TlsAcceptor> {
+        let certs = CertificateDer::pem_file_iter(&self.cert)
+            .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+            .with_context(|| format!("Failed to read certificate {}", self.cert.display()))?;
+        if certs.is_empty() {
+            return Err(anyhow!("No certificate in {}", self.cert.display()));
+        }
+        let key = PrivateKeyDer::from_pem_file(&self.key)
+            .with_context(|| format!("Failed to read private key {}", self.key.display()))?;
+
+        let mut config =
+            ServerConfig::builder_with_provider(Arc::new(aws_lc_rs::default_provider()))
+                .with_safe_default_protocol_versions()?
+                .with_no_client_auth()
+                .with_single_cert(certs, key)
+                .context("Certificate and private key do not match")?;
+        config.alpn_protocols = vec![b"http/1.1".to_vec()];
+        Ok(TlsAcceptor::from(Arc::new(config)))
+    }
+
+    fn fingerprint(&self) -> [Option<(SystemTime, u64)>; 2] {
+        [fingerprint(&self.cert), fingerprint(&self.key)]
+    }
+}
+
+/// A TCP listener that terminates TLS. Handshakes run concurrently in the background, so a
+/// slow client does not hold up the others.
+pub struct TlsListener {
+    connections: mpsc::Receiver<(TlsStream<TcpStream>, SocketAddr)>,
+    local_addr: SocketAddr,
+}
+
+impl TlsListener {
+    /// Serves `files` on `tcp`; fails if they cannot be loaded.
+    pub fn new(tcp: TcpListener, files: TlsFiles) -> Result<Self> {
+        Self::with_watch_interval(tcp, files, WATCH_INTERVAL)
+    }
+
+    pub fn with_watch_interval(
+        tcp: TcpListener,
+        files: TlsFiles,
+        watch_interval: Duration,
+    ) -> Result<Self> {
+        let acceptor = files.load()?;
+        let local_addr = tcp.local_addr()?;
+        let (tx, connections) = mpsc::channel(64);
+        tokio::spawn(accept_loop(tcp, files, acceptor, watch_interval, tx));
+        Ok(Self {
+            connections,
+            local_addr,
+        })
+    }
+}
+
+async fn accept_loop(
+    tcp: TcpListener,
+    files: TlsFiles,
+    mut acceptor: TlsAcceptor,
+    watch_interval: Duration,
+    tx: mpsc::Sender<(TlsStream<TcpStream>, SocketAddr)>,
+) {
+    let mut last_seen = files.fingerprint();
+    let mut watch = interval(watch_interval);
+    watch.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    watch.tick().await;
+    while !tx.is_closed() {
+        tokio::select! {
+            accepted = tcp.accept() => {
+                let (stream, addr) = match accepted {
+                    Ok(accepted) => accepted,
+                    Err(e) => {
+                        error!("Failed to accept a connection: {}", e);
+                        sleep(Duration::from_millis(100)).await;
+                        continue;
+                    }
+                };
+                let acceptor = acceptor.clone();
+                let tx = tx.clone();
+                tokio::spawn(async move {
+                    match timeout(HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await {
+                        Ok(Ok(stream)) => {
+                            let _ = tx.send((stream, addr)).await;
+                        }
+                        Ok(Err(e)) => debug!("TLS handshake with {} failed: {}", addr, e),
+                        Err(_) => debug!("TLS handshake with {} timed out", addr),
+                    }
+                });
+            }
+            _ = watch.tick() => {
+                let seen = files.fingerprint();
+                if seen == last_seen {
+                    continue;
+                }
+                last_seen = seen;
+                match files.load() {
+                    Ok(reloaded) => {
+                        acceptor = reloaded;
+                        info!("Reloaded the TLS certificate {}", files.cert.display());
+                    }
+                    Err(e) => error!(
+                        "Failed to reload the TLS certificate, keeping the current one: {:#}",
+                        e
+                    ),
+                }
+            }
+        }
+    }
+}
+
+impl axum::serve::Listener for TlsListener {
+    type Io = TlsStream<TcpStream>;
+    type Addr = SocketAddr;
+
+    async fn accept(&mut self) -> (Self::Io, Self::Addr) {
+        match self.connections.recv().await {
+            Some(connection) => connection,
+            // The accept loop only ends once this listener is dropped
+            None => std::future::pending().await,
+        }
+    }
+
+    fn local_addr(&self) -> std::io::Result<Self::Addr> {
+        Ok(self.local_addr)
+    }
+}
@@ -0,0 +1,415 @@
+use super::fan_out::tag;
+use super::output::{OutputFormat, OutputOptions, listing_result};
+use super::{NoArgs, Tool, ToolAnnotations, ToolContext, ToolRegistry, json_result, text_result};
+use crate::client::QBitClient;
+use crate::server::audit::AuditFilter;
+use crate::server::mcp::invalid_argument;
+use anyhow::Result;
+use async_trait::async_trait;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::{Value, json};
+use time::OffsetDateTime;
+use time::format_description::well_known::Rfc3339;
+
+pub(super) fn register(registry: &mut ToolRegistry) {
+    registry.register(SetAppPreferences);
+    registry.register(GetMainLog);
+    registry.register(GetPeerLog);
+    registry.register(GetSystemInfo);
+    registry.register(ListInstances);
+    registry.register(ShutdownApp);
+    registry.register(GetAuditLog);
+}
+
+pub struct SetAppPreferences;
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct SetAppPreferencesArgs {
+    /// JSON string of preferences to update
+    preferences: String,
+    /// Only return a before/after diff of the preferences, without changing them
+    #[serde(default)]
+    dry_run: bool,
+}
+
+#[async_trait]
+impl Tool for SetAppPreferences {
+    type Args = SetAppPreferencesArgs;
+
+    fn name(&self) -> &str {
+        "set_app_preferences"
+    }
+
+    fn toolset(&self) -> &str {
+        "app"
+    }
+
+    fn description(&self) -> &str {
+        "Set one or more application preferences"
+    }
+
+    fn annotations(&self) -> ToolAnnotations {
+        ToolAnnotations::destructive().idempotent()
+    }
+
+    fn confirms(&self) -> bool {
+        true
+    }
+
+    async fn call(&self, ctx: &ToolContext<'_>, args: Self::Args) -> Result<Value> {
+        let prefs_val: Value = serde_json::from_str(&args.preferences).map_err(|e| {
+            invalid_argument(
+                "preferences",
+                "JSON object string",
+                format!("Invalid preferences: {}", e),
+            )
+        })?;
+
+        if args.dry_run {
+            let current = ctx.client.get_app_preferences().await?;
+            return json_result(&preferences_diff(&current, &prefs_val));
+        }
+
+        let keys: Vec<&str> = prefs_val
+            .as_object()
+            .map(|prefs| prefs.keys().map(String::as_str).collect())
+            .unwrap_or_default();
+        let action = format!(
+            "change {} preference{} ({}) on instance {}",
+            keys.len(),
+            if keys.len() == 1 { "" } else { "s" },
+            keys.join(", "),
+            ctx.instance
+        );
+        if let Some(result) = ctx.confirm(&action).await? {
+            return Ok(result);
+        }
+
+        ctx.client.set_app_preferences(&prefs_val).await?;
+        Ok(text_result("App preferences updated successfully"))
+    }
+}
+
+/// What setting `update` would change relative to `current`: a before/after pair for each
+/// changed preference, and the keys that are already set or that qBittorrent does not know.
+fn preferences_diff(current: &Value, update: &Value) -> Value {
+    let mut changes = serde_json::Map::new();
+    let mut unchanged = Vec::new();
+    let mut unknown = Vec::new();
+    for (key, after) in update.as_object().into_iter().flatten() {
+        match current.get(key) {
+            None => unknown.push(key.as_str()),
+            Some(before) if before == after => unchanged.push(key.as_str()),
+            Some(before) => {
+                changes.insert(key.clone(), json!({ "before": before, "after": after }));
+            }
+        }
+    }
+    json!({
+        "dry_run": true,
+        "changes": changes,
+        "unchanged": unchanged,
+        "unknown": unknown
+    })
+}
+
+pub struct GetMainLog;
+
+#[derive(Deserialize, JsonSchema, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    #[default]
+    All,
+    Info,
+    Warning,
+    Critical,
+}
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct GetMainLogArgs {
+    /// Filter by severity (all, info, warning, critical)
+    severity: Option<Severity>,
+    /// Exclude logs with ID less than or equal to this
+    last_id: Option<i64>,
+    /// Only return these fields of each item
+    fields: Option<Vec<String>>,
+    /// Output format (json, compact, csv, markdown; default: json)
+    format: Option<OutputFormat>,
+    /// Truncate the output to about this many characters and return a cursor for the rest
+    max_output_chars: Option<usize>,
+    /// Cursor from a truncated result, to continue where it stopped
+    cursor: Option<String>,
+}
+
+#[async_trait]
+impl Tool for GetMainLog {
+    type Args = GetMainLogArgs;
+
+    fn name(&self) -> &str {
+        "get_main_log"
+    }
+
+    fn toolset(&self) -> &str {
+        "app"
+    }
+
+    fn description(&self) -> &str {
+        "Get the main application log"
+    }
+
+    fn annotations(&self) -> ToolAnnotations {
+        ToolAnnotations::read_only()
+    }
+
+    async fn call(&self, ctx: &ToolContext<'_>, args: Self::Args) -> Result<Value> {
+        let (normal, info, warning, critical) = match args.severity.unwrap_or_default() {
+            Severity::Info => (false, true, false, false),
+            Severity::Warning => (false, false, true, false),
+            Severity::Critical => (false, false, false, true),
+            Severity::All => (true, true, true, true),
+        };
+        let logs = ctx
+            .client
+            .get_main_log(normal, info, warning, critical, args.last_id)
+            .await?;
+        listing_result(
+            &logs,
+            &OutputOptions {
+                fields: args.fields.as_deref(),
+                format: args.format,
+                max_output_chars: args.max_output_chars,
+                cursor: args.cursor.as_deref(),
+            },
+        )
+    }
+}
+
+pub struct GetPeerLog;
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct GetPeerLogArgs {
+    /// Exclude logs with ID less than or equal to this
+    last_id: Option<i64>,
+}
+
+#[async_trait]
+impl Tool for GetPeerLog {
+    type Args = GetPeerLogArgs;
+
+    fn name(&self) -> &str {
+        "get_peer_log"
+    }
+
+    fn toolset(&self) -> &str {
+        "app"
+    }
+
+    fn description(&self) -> &str {
+        "Get the peer connection log"
+    }
+
+    fn annotations(&self) -> ToolAnnotations {
+        ToolAnnotations::read_only()
+    }
+
+    async fn call(&self, ctx: &ToolContext<'_>, args: Self::Args) -> Result<Value> {
+        let logs = ctx.client.get_peer_log(args.last_id).await?;
+        json_result(&logs)
+    }
+}
+
+pub struct GetSystemInfo;
+
+#[async_trait]
+impl Tool for GetSystemInfo {
+    type Args = NoArgs;
+
+    fn name(&self) -> &str {
+        "get_system_info"
+    }
+
+    fn toolset(&self) -> &str {
+        "app"
+    }
+
+    fn description(&self) -> &str {
+        "Comprehensive system information (transfer speeds, preferences, version, and build info in one call)"
+    }
+
+    fn annotations(&self) -> ToolAnnotations {
+        ToolAnnotations::read_only()
+    }
+
+    fn fans_out(&self) -> bool {
+        true
+    }
+
+    async fn call(&self, ctx: &ToolContext<'_>, _args: Self::Args) -> Result<Value> {
+        if !ctx.is_fan_out() {
+            return json_result(&system_info(ctx.client).await?);
+        }
+        let fan_out = ctx.fan_out(|_, client| system_info(client)).await?;
+        let infos: Vec<Value> = fan_out
+            .results
+            .iter()
+            .map(|(instance, info)| tag(instance, info.clone()))
+            .collect();
+        Ok(fan_out.report_failures(json_result(&infos)?))
+    }
+}
+
+pub struct ListInstances;
+
+#[async_trait]
+impl Tool for ListInstances {
+    type Args = NoArgs;
+
+    fn name(&self) -> &str {
+        "list_instances"
+    }
+
+    fn toolset(&self) -> &str {
+        "app"
+    }
+
+    fn description(&self) -> &str {
+        "List the configured qBittorrent instances and their health: whether each is up, when it last answered, its latency, Web API version, login state and consecutive failures"
+    }
+
+    fn annotations(&self) -> ToolAnnotations {
+        ToolAnnotations::read_only()
+    }
+
+    async fn call(&self, ctx: &ToolContext<'_>, _args: Self::Args) -> Result<Value> {
+        json_result(&ctx.server.instance_status(ctx.request.grant()))
+    }
+}
+
+async fn system_info(client: &QBitClient) -> Result<Value> {
+    let transfer_info = client.get_global_transfer_info().await?;
+    let app_preferences = client.get_app_preferences().await?;
+    let app_version = client.get_app_version().await?;
+    let build_info = client.get_build_info().await?;
+
+    Ok(json!({
+        "transfer_info": transfer_info,
+        "app_preferences": app_preferences,
+        "app_version": app_version,
+        "build_info": build_info
+    }))
+}
+
+pub struct ShutdownApp;
+
+#[async_trait]
+impl Tool for ShutdownApp {
+    type Args = NoArgs;
+
+    fn name(&self) -> &str {
+        "shutdown_app"
+    }
+
+    fn toolset(&self) -> &str {
+        "app"
+    }
+
+    fn description(&self) -> &str {
+        "Shutdown qBittorrent. DESTRUCTIVE: Inform the user and confirm before calling as this terminates the service."
+    }
+
+    fn annotations(&self) -> ToolAnnotations {
+        ToolAnnotations::destructive().idempotent()
+    }
+
+    fn confirms(&self) -> bool {
+        true
+    }
+
+    async fn call(&self, ctx: &ToolContext<'_>, _args: Self::Args) -> Result<Value> {
+        let action = format!("shut down qBittorrent on instance {}", ctx.instance);
+        if let Some(result) = ctx.confirm(&action).await? {
+            return Ok(result);
+        }
+        ctx.client.shutdown_app().await?;
+        Ok(text_result("Shutdown command sent"))
+    }
+}
+
+pub struct GetAuditLog;
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct GetAuditLogArgs {
+    /// Only entries at or after this time (RFC 3339, e.g. 2025-01-31T00:00:00Z)
+    since: Option<String>,
+    /// Only entries at or before this time (RFC 3339)
+    until: Option<String>,
+    /// Only calls to this tool
+    tool: Option<String>,
+    /// Maximum number of entries, most recent last (default: 100)
+    limit: Option<usize>,
+}
+
+fn parse_time(name: &str, value: Option<&str>) -> Result<Option<OffsetDateTime>> {
+    value
+        .map(|v| {
+            OffsetDateTime::parse(v, &Rfc3339).map_err(|e| {
+                invalid_argument(
+                    name,
+                    "RFC 3339 timestamp",
+                    format!("Invalid {}: {}", name, e),
+                )
+            })
+        })
+        .transpose()
+}
+
+#[async_trait]
+impl Tool for GetAuditLog {
+    type Args = GetAuditLogArgs;
+
+    fn name(&self) -> &str {
+        "get_audit_log"
+    }
+
+    fn toolset(&self) -> &str {
+        "app"
+    }
+
+    fn description(&self) -> &str {
+        "Read the audit log of tool calls that changed qBittorrent. Filters by time and tool, and by instance when one is given."
+    }
+
+    fn annotations(&self) -> ToolAnnotations {
+        ToolAnnotations::read_only()
+    }
+
+    async fn call(&self, ctx: &ToolContext<'_>, args: Self::Args) -> Result<Value> {
+        let Some(audit) = ctx.server.audit_log() else {
+            anyhow::bail!(
+                "Audit logging is disabled. Set audit_log_enable = true to record tool calls."
+            );
+        };
+        let filter = AuditFilter {
+            since: parse_time("since", args.since.as_deref())?,
+            until: parse_time("until", args.until.as_deref())?,
+            tool: args.tool,
+            // Only filter when asked; otherwise the default instance would hide the rest
+            instance: ctx
+                .arguments
+                .get("instance")
+                .is_some()
+                .then(|| ctx.instance.to_string()),
+        };
+        // Entries of instances the client may not read stay hidden
+        let grant = ctx.request.grant();
+        let entries = audit.read_where(&filter, args.limit.unwrap_or(100), |e| {
+            grant.is_none_or(|g| g.can_read(&e.instance))
+        })?;
+        json_result(&entries)
+    }
+}
@@ -0,0 +1,134 @@
+//! Fan-out of read tools across instances: `instance: "*"` or a list of names runs the
+//! tool on each of them concurrently, and the tool merges the results, tagged with their
+//! instance. An instance that fails is reported next to the results instead of failing
+//! the whole call.
+
+use crate::client::QBitClient;
+use anyhow::{Result, anyhow};
+use serde_json::{Value, json};
+use std::cmp::Ordering;
+use std::future::Future;
+
+/// What running a tool on several instances gave.
+pub struct FanOut<'a, T> {
+    /// Results of the instances that answered, in instance order.
+    pub results: Vec<(&'a str, T)>,
+    /// Instances that failed or were skipped, with the reason.
+    pub failures: Vec<(String, String)>,
+}
+
+/// Runs `f` on every target concurrently. Fails only if no instance could be queried.
+pub(crate) async fn run<'a, T, F, Fut>(
+    targets: &[(&'a str, &'a QBitClient)],
+    skipped: &[(String, String)],
+    f: F,
+) -> Result<FanOut<'a, T>>
+where
+    F: Fn(&'a str, &'a QBitClient) -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let outcomes =
+        futures::future::join_all(targets.iter().map(|(instance, client)| f(instance, client)))
+            .await;
+    let mut results = Vec::new();
+    let mut failures = skipped.to_vec();
+    for ((instance, _), outcome) in targets.iter().zip(outcomes) {
+        match outcome {
+            Ok(value) => results.push((*instance, value)),
+            Err(e) => failures.push((instance.to_string(), format!("{:#}", e))),
+        }
+    }
+    if results.is_empty() {
+        return Err(anyhow!(
+            "No instance could be queried: {}",
+            failures
+                .iter()
+                .map(|(instance, reason)| format!("{}: {}", instance, reason))
+                .collect::<Vec<_>>()
+                .join("; ")
+        ));
+    }
+    Ok(FanOut { results, failures })
+}
+
+impl<T> FanOut<'_, T> {
+    /// Adds a note on the instances that could not be queried to a tool result.
+    pub fn report_failures(&self, mut result: Value) -> Value {
+        if self.failures.is_empty() {
+            return result;
+        }
+        let lines: Vec<String> = self
+            .failures
+            .iter()
+            .map(|(instance, reason)| format!("- {}: {}", instance, reason))
+            .collect();
+        if let Some(content) = result["content"].as_array_mut() {
+            content.push(json!({
+                "type": "text",
+                "text": format!(
+                    "Results are missing the instances that could not be queried:\n{}",
+                    lines.join("\n")
+                )
+            }));
+        }
+        result
+    }
+}
+
+/// `value` with an `instance` field added, if it is an object.
+pub fn tag(instance: &str, mut value: Value) -> Value {
+    if let Some(obj) = value.as_object_mut() {
+        obj.insert("instance".to_string(), json!(instance));
+    }
+    value
+}
+
+/// Orders the sort keys of a merged list: numbers numerically, strings case-insensitively,
+/// and missing values last.
+pub fn compare_values(a: &Value, b: &Value) -> Ordering {
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => a
+            .as_f64()
+            .partial_cmp(&b.as_f64())
+            .unwrap_or(Ordering::Equal),
+        (Value::String(a), Value::String(b)) => a.to_lowercase().cmp(&b.to_lowercase()),
+        (Value::Bool(a), Value::Bool(b)) => a.cmp(b),
+        (Value::Null, Value::Null) => Ordering::Equal,
+        (Value::Null, _) => Ordering::Greater,
+        (_, Value::Null) => Ordering::Less,
+        _ => Ordering::Equal,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compare_values() {
+        assert_eq!(compare_values(&json!(2), &json!(10)), Ordering::Less);
+        assert_eq!(compare_values(&json!("b"), &json!("A")), Ordering::Greater);
+        assert_eq!(compare_values(&json!(1), &Value::Null), Ordering::Less);
+        assert_eq!(
+            tag("nas", json!({ "name": "x" })),
+            json!({ "name": "x", "instance": "nas" })
+        );
+    }
+
+    #[test]
+    fn test_report_failures() {
+        let fan_out = FanOut {
+            results: vec![("a", ())],
+            failures: vec![("b".to_string(), "connection refused".to_string())],
+        };
+        let result =
+            fan_out.report_failures(json!({ "content": [{ "type": "text", "text": "[]" }] }));
+        assert_eq!(result["content"].as_array().unwrap().len(), 2);
+        assert!(
+            result["content"][1]["text"]
+                .as_str()
+                .unwrap()
+                .contains("- b: connection refused")
+        );
+    }
+}
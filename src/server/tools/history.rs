@@ -0,0 +1,150 @@
+use super::{Tool, ToolAnnotations, ToolContext, ToolRegistry, json_result, text_result};
+use crate::server::mcp::invalid_argument;
+use anyhow::Result;
+use async_trait::async_trait;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+pub(super) fn register(registry: &mut ToolRegistry) {
+    registry.register(ListRecentActions);
+    registry.register(UndoLastAction);
+}
+
+pub struct ListRecentActions;
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ListRecentActionsArgs {
+    /// Maximum number of actions, newest first (default: 20)
+    limit: Option<usize>,
+}
+
+#[async_trait]
+impl Tool for ListRecentActions {
+    type Args = ListRecentActionsArgs;
+
+    fn name(&self) -> &str {
+        "list_recent_actions"
+    }
+
+    fn toolset(&self) -> &str {
+        "torrents"
+    }
+
+    fn description(&self) -> &str {
+        "List recent torrent changes from the undo journal, newest first, with their ids and whether they can be undone with undo_last_action"
+    }
+
+    fn annotations(&self) -> ToolAnnotations {
+        ToolAnnotations::read_only()
+    }
+
+    async fn call(&self, ctx: &ToolContext<'_>, args: Self::Args) -> Result<Value> {
+        // Only filter when asked; otherwise the default instance would hide the rest
+        let instance = ctx.arguments.get("instance").map(|_| ctx.instance);
+        let grant = ctx.request.grant();
+        let entries: Vec<Value> = ctx
+            .server
+            .journal()
+            .recent_where(args.limit.unwrap_or(20), |e| {
+                instance.is_none_or(|i| e.instance == i)
+                    && grant.is_none_or(|g| g.can_read(&e.instance))
+            })
+            .into_iter()
+            .map(|e| {
+                json!({
+                    "id": e.id,
+                    "timestamp": e.timestamp,
+                    "instance": e.instance,
+                    "tool": e.tool,
+                    "action": e.action,
+                    "torrents": e.torrents,
+                    "undoable": e.undoable(),
+                    "undone": e.undone
+                })
+            })
+            .collect();
+        json_result(&entries)
+    }
+}
+
+pub struct UndoLastAction;
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct UndoLastActionArgs {
+    /// Id of the action to undo, from list_recent_actions. Defaults to the most recent action that can be undone.
+    id: Option<u64>,
+}
+
+#[async_trait]
+impl Tool for UndoLastAction {
+    type Args = UndoLastActionArgs;
+
+    fn name(&self) -> &str {
+        "undo_last_action"
+    }
+
+    fn toolset(&self) -> &str {
+        "torrents"
+    }
+
+    fn description(&self) -> &str {
+        "Revert a category, tag, limit or pause change made with manage_torrents by restoring the previous values. Deletions cannot be undone."
+    }
+
+    fn annotations(&self) -> ToolAnnotations {
+        ToolAnnotations::additive()
+    }
+
+    async fn call(&self, ctx: &ToolContext<'_>, args: Self::Args) -> Result<Value> {
+        let journal = ctx.server.journal();
+        let Some(entry) = journal.find(ctx.instance, args.id) else {
+            return match args.id {
+                Some(id) => Err(invalid_argument(
+                    "id",
+                    "id from list_recent_actions",
+                    format!("No action with id {} in the undo journal", id),
+                )),
+                None => anyhow::bail!("Nothing to undo on instance {}", ctx.instance),
+            };
+        };
+        if entry.instance != ctx.instance {
+            return Err(invalid_argument(
+                "instance",
+                "instance the action was made on",
+                format!(
+                    "Action {} was made on instance {}; pass instance '{}' to undo it",
+                    entry.id, entry.instance, entry.instance
+                ),
+            ));
+        }
+        if entry.undone {
+            anyhow::bail!("Action {} ({}) was already undone", entry.id, entry.action);
+        }
+        let Some(steps) = &entry.undo else {
+            anyhow::bail!("Action {} ({}) cannot be undone", entry.id, entry.action);
+        };
+
+        for (done, step) in steps.iter().enumerate() {
+            step.apply(ctx.client).await.map_err(|e| {
+                e.context(format!(
+                    "Undo of action {} failed after {} of {} steps",
+                    entry.id,
+                    done,
+                    steps.len()
+                ))
+            })?;
+        }
+        journal.mark_undone(entry.id);
+
+        Ok(text_result(format!(
+            "Undid action {} ({}) on {} torrent{}.",
+            entry.id,
+            entry.action,
+            entry.torrents.len(),
+            if entry.torrents.len() == 1 { "" } else { "s" }
+        )))
+    }
+}
@@ -0,0 +1,391 @@
+//! `migrate_torrent`: moves a torrent between instances that share storage. The .torrent
+//! is exported from the source and added to the target at the mapped save path; the
+//! source copy is only removed (keeping the files) once the target has checked the data,
+//! and the target copy is removed again if the check fails.
+
+use super::torrents::is_paused;
+use super::{Tool, ToolAnnotations, ToolContext, ToolRegistry, json_result};
+use crate::client::{AddTorrentOptions, QBitClient};
+use crate::humanize::{describe_state, format_progress};
+use crate::models::Torrent;
+use crate::server::mcp::invalid_argument;
+use anyhow::{Result, anyhow, bail};
+use async_trait::async_trait;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+use tokio::time::sleep;
+use tracing::{debug, warn};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+/// A torrent may briefly show as incomplete before its check starts.
+const CHECK_GRACE: Duration = Duration::from_secs(10);
+
+pub(super) fn register(registry: &mut ToolRegistry) {
+    registry.register(MigrateTorrent);
+}
+
+pub struct MigrateTorrent;
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct MigrateTorrentArgs {
+    /// Torrent hash
+    hash: String,
+    /// Instance to move the torrent to
+    target_instance: String,
+    /// Save path on the target (default: the source save path, rewritten by path_map)
+    save_path: Option<String>,
+    /// Save path prefixes to rewrite for the target, e.g. {"/downloads": "/data/torrents"}; the longest matching prefix wins
+    path_map: Option<BTreeMap<String, String>>,
+    /// Skip the hash check on the target; only for complete torrents whose data is known to be in place
+    #[serde(default)]
+    skip_checking: bool,
+    /// How long to wait for the target to check the data, in seconds (default 300, max 3600)
+    timeout_seconds: Option<u64>,
+    /// Only return the migration plan, without changing anything
+    #[serde(default)]
+    dry_run: bool,
+}
+
+#[async_trait]
+impl Tool for MigrateTorrent {
+    type Args = MigrateTorrentArgs;
+
+    fn name(&self) -> &str {
+        "migrate_torrent"
+    }
+
+    fn toolset(&self) -> &str {
+        "torrents"
+    }
+
+    fn description(&self) -> &str {
+        "Move a torrent to another instance that shares its storage: adds it to the target with a mapped save path and the same category, tags and share limits, waits for the target to check the data, then removes it from the source, keeping the files. Rolls back if the check fails."
+    }
+
+    fn annotations(&self) -> ToolAnnotations {
+        ToolAnnotations::destructive()
+    }
+
+    async fn call(&self, ctx: &ToolContext<'_>, args: Self::Args) -> Result<Value> {
+        let source = ctx.client;
+        let (target_name, target) = ctx.server.other_instance(
+            self.name(),
+            "target_instance",
+            &args.target_instance,
+            ctx.request,
+        )?;
+        let target = &target;
+        if target_name == ctx.instance {
+            return Err(invalid_argument(
+                "target_instance",
+                "an instance other than the source",
+                format!("The torrent is already on instance '{}'", target_name),
+            ));
+        }
+
+        let torrent = find(source, &args.hash).await?.ok_or_else(|| {
+            anyhow!(
+                "Torrent not found on instance '{}': {}",
+                ctx.instance,
+                args.hash
+            )
+        })?;
+        if args.skip_checking && torrent.progress < 1.0 {
+            return Err(invalid_argument(
+                "skip_checking",
+                "false for incomplete torrents",
+                format!(
+                    "Cannot skip checking: '{}' is only {} complete",
+                    torrent.name,
+                    format_progress(torrent.progress)
+                ),
+            ));
+        }
+        if find(target, &torrent.hash).await?.is_some() {
+            bail!(
+                "Torrent '{}' is already on instance '{}'",
+                torrent.name,
+                target_name
+            );
+        }
+
+        let properties = source.get_torrent_properties(&torrent.hash).await?;
+        let save_path = args
+            .save_path
+            .clone()
+            .unwrap_or_else(|| map_path(&properties.save_path, args.path_map.as_ref()));
+        let was_running = !is_paused(&torrent.state);
+        let options = AddTorrentOptions {
+            save_path: Some(save_path.clone()),
+            category: Some(torrent.category.clone()).filter(|c| !c.is_empty()),
+            tags: Some(torrent.tags.clone()).filter(|t| !t.is_empty()),
+            skip_checking: args.skip_checking,
+            paused: !was_running,
+            ratio_limit: torrent.ratio_limit,
+            seeding_time_limit: torrent.seeding_time_limit,
+            inactive_seeding_time_limit: torrent.inactive_seeding_time_limit,
+        };
+        let plan = json!({
+            "hash": torrent.hash,
+            "name": torrent.name,
+            "from": ctx.instance,
+            "to": target_name,
+            "source_save_path": properties.save_path,
+            "save_path": save_path,
+            "category": torrent.category,
+            "tags": torrent.tags,
+            "ratio_limit": torrent.ratio_limit,
+            "seeding_time_limit": torrent.seeding_time_limit,
+            "skip_checking": args.skip_checking,
+        });
+        if args.dry_run {
+            return json_result(&json!({ "dry_run": true, "plan": plan }));
+        }
+
+        let file = source.export_torrent(&torrent.hash).await?;
+        // Only one instance may work on the files at a time
+        if was_running {
+            source.pause_torrents(&torrent.hash).await?;
+        }
+        let mut migration = Migration {
+            source: source.clone(),
+            source_name: ctx.instance.to_string(),
+            target: target.clone(),
+            target_name: target_name.to_string(),
+            hash: torrent.hash.clone(),
+            resume_source: was_running,
+            active: true,
+        };
+        let added = target
+            .add_torrent_file(file, &format!("{}.torrent", torrent.hash), &options)
+            .await;
+        let checked = match added {
+            Ok(()) => {
+                let timeout =
+                    Duration::from_secs(args.timeout_seconds.unwrap_or(300).clamp(1, 3600));
+                wait_for_check(ctx, target, &torrent, &save_path, timeout).await
+            }
+            Err(e) => Err(e),
+        };
+        let state = match checked {
+            Ok(state) => state,
+            Err(e) => match migration.rollback().await {
+                Ok(()) => bail!(
+                    "Migration of '{}' to instance '{}' failed: {:#}. Rolled back: the torrent was removed from '{}' (keeping the files) and is still on '{}'.",
+                    torrent.name,
+                    target_name,
+                    e,
+                    target_name,
+                    ctx.instance
+                ),
+                Err(undone) => bail!(
+                    "Migration of '{}' to instance '{}' failed: {:#}. Rolling back did not complete: {:#}",
+                    torrent.name,
+                    target_name,
+                    e,
+                    undone
+                ),
+            },
+        };
+        migration.finish();
+
+        if let Err(e) = source.delete_torrents(&torrent.hash, false).await {
+            bail!(
+                "Torrent '{}' is now on instance '{}', but could not be removed from '{}': {:#}. Remove it there without deleting files.",
+                torrent.name,
+                target_name,
+                ctx.instance,
+                e
+            );
+        }
+        ctx.record_change(
+            format!("migrate to instance {}", target_name),
+            std::slice::from_ref(&torrent),
+            None,
+        );
+
+        let mut result = plan;
+        result["state"] = json!(describe_state(&state));
+        json_result(&result)
+    }
+}
+
+async fn find(client: &QBitClient, hash: &str) -> Result<Option<Torrent>> {
+    let torrents = client.get_torrents_info(hash).await?;
+    Ok(torrents
+        .into_iter()
+        .find(|t| t.hash.eq_ignore_ascii_case(hash)))
+}
+
+/// Rewrites the longest prefix of `path` found in `path_map`, on a path segment boundary.
+fn map_path(path: &str, path_map: Option<&BTreeMap<String, String>>) -> String {
+    let Some(path_map) = path_map else {
+        return path.to_string();
+    };
+    path_map
+        .iter()
+        .filter(|(from, _)| {
+            let from = from.trim_end_matches(['/', '\\']);
+            path.strip_prefix(from)
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with(['/', '\\']))
+        })
+        .max_by_key(|(from, _)| from.trim_end_matches(['/', '\\']).len())
+        .map(|(from, to)| {
+            let rest = &path[from.trim_end_matches(['/', '\\']).len()..];
+            format!("{}{}", to.trim_end_matches(['/', '\\']), rest)
+        })
+        .unwrap_or_else(|| path.to_string())
+}
+
+/// Waits until the target has checked the data and has as much of it as the source had.
+/// Returns the torrent's state on the target.
+async fn wait_for_check(
+    ctx: &ToolContext<'_>,
+    target: &QBitClient,
+    source: &Torrent,
+    save_path: &str,
+    timeout: Duration,
+) -> Result<String> {
+    let start = Instant::now();
+    loop {
+        if let Some(t) = find(target, &source.hash).await? {
+            match t.state.as_str() {
+                "error" | "missingFiles" => {
+                    bail!("the target reports: {}", describe_state(&t.state))
+                }
+                state if is_checking(state) => {}
+                _ if t.progress + 1e-9 >= source.progress => return Ok(t.state),
+                _ if start.elapsed() >= CHECK_GRACE => bail!(
+                    "the target only found {} of the data at {} (the source had {})",
+                    format_progress(t.progress),
+                    save_path,
+                    format_progress(source.progress)
+                ),
+                _ => {}
+            }
+            ctx.request.report_progress(
+                start.elapsed().as_secs_f64(),
+                Some(timeout.as_secs_f64()),
+                format!("{}: {} ({})", t.name, format_progress(t.progress), t.state),
+            );
+        }
+        if start.elapsed() >= timeout {
+            bail!(
+                "timed out after {}s waiting for the target to check the data",
+                timeout.as_secs()
+            );
+        }
+        sleep(POLL_INTERVAL).await;
+    }
+}
+
+fn is_checking(state: &str) -> bool {
+    matches!(
+        state,
+        "checkingUP"
+            | "checkingDL"
+            | "checkingResumeData"
+            | "allocating"
+            | "metaDL"
+            | "forcedMetaDL"
+            | "moving"
+    )
+}
+
+/// Undoes a migration that did not finish: removes the torrent from the target (keeping
+/// the files) and resumes it on the source. Also runs if the call is cancelled.
+#[derive(Clone)]
+struct Migration {
+    source: QBitClient,
+    source_name: String,
+    target: QBitClient,
+    target_name: String,
+    hash: String,
+    resume_source: bool,
+    active: bool,
+}
+
+impl Migration {
+    fn finish(&mut self) {
+        self.active = false;
+    }
+
+    async fn rollback(&mut self) -> Result<()> {
+        self.active = false;
+        self.undo().await
+    }
+
+    /// Fails with what could not be undone and has to be fixed by hand.
+    async fn undo(&self) -> Result<()> {
+        let mut left = Vec::new();
+        if let Err(e) = self.target.delete_torrents(&self.hash, false).await {
+            left.push(format!(
+                "the torrent is still on '{}'; remove it there without deleting its files ({:#})",
+                self.target_name, e
+            ));
+        }
+        if self.resume_source
+            && let Err(e) = self.source.resume_torrents(&self.hash).await
+        {
+            left.push(format!(
+                "the torrent is still paused on '{}'; resume it there ({:#})",
+                self.source_name, e
+            ));
+        }
+        if !left.is_empty() {
+            bail!("{}", left.join("; "));
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Migration {
+    fn drop(&mut self) {
+        if !self.active {
+            return;
+        }
+        let mut migration = self.clone();
+        migration.active = false;
+        if let Ok(handle) = tokio::runtime::Handle::try_current() {
+            handle.spawn(async move {
+                debug!("Rolling back abandoned migration of {}", migration.hash);
+                if let Err(e) = migration.undo().await {
+                    warn!(
+                        "Rollback of the abandoned migration of {} did not complete: {:#}",
+                        migration.hash, e
+                    );
+                }
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_map_path() {
+        let map: BTreeMap<String, String> = [
+            ("/downloads".to_string(), "/data".to_string()),
+            (
+                "/downloads/movies/".to_string(),
+                "/media/movies".to_string(),
+            ),
+        ]
+        .into();
+        assert_eq!(map_path("/downloads/tv", Some(&map)), "/data/tv");
+        assert_eq!(
+            map_path("/downloads/movies/x", Some(&map)),
+            "/media/movies/x"
+        );
+        assert_eq!(map_path("/downloads", Some(&map)), "/data");
+        // Only whole path segments match
+        assert_eq!(map_path("/downloads2/a", Some(&map)), "/downloads2/a");
+        assert_eq!(map_path("/other", None), "/other");
+    }
+}
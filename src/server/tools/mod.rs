@@ -0,0 +1,647 @@
+//! MCP tools. Each tool declares its arguments as a serde struct; the registry derives
+//! the `tools/list` schema from that struct and validates `tools/call` arguments
+//! against it, so the two cannot drift apart.
+
+mod app;
+mod fan_out;
+mod history;
+mod migrate;
+mod output;
+mod rss;
+mod search;
+pub(crate) mod toolsets;
+mod torrents;
+mod transfer;
+
+pub use fan_out::FanOut;
+pub use toolsets::{CUSTOM_TOOLSET, TOOLSETS};
+
+use crate::client::QBitClient;
+use crate::models::Torrent;
+use crate::server::audit::{AuditNotes, AuditTorrent};
+use crate::server::journal::UndoStep;
+use crate::server::mcp::{McpServer, RequestContext, invalid_argument};
+use anyhow::Result;
+use async_trait::async_trait;
+use regex::Regex;
+use schemars::JsonSchema;
+use schemars::generate::SchemaSettings;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::future::Future;
+use std::sync::{Arc, LazyLock};
+
+/// Behaviour hints shown to clients alongside a tool (MCP `ToolAnnotations`).
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolAnnotations {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub read_only_hint: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub destructive_hint: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub idempotent_hint: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub open_world_hint: Option<bool>,
+}
+
+impl ToolAnnotations {
+    /// Only reads state; calling it again changes nothing.
+    pub fn read_only() -> Self {
+        Self {
+            read_only_hint: Some(true),
+            destructive_hint: Some(false),
+            idempotent_hint: Some(true),
+            open_world_hint: Some(false),
+            ..Default::default()
+        }
+    }
+
+    /// Changes state, but only additively (nothing is lost).
+    pub fn additive() -> Self {
+        Self {
+            read_only_hint: Some(false),
+            destructive_hint: Some(false),
+            idempotent_hint: Some(false),
+            open_world_hint: Some(false),
+            ..Default::default()
+        }
+    }
+
+    /// May remove data or stop the service.
+    pub fn destructive() -> Self {
+        Self {
+            destructive_hint: Some(true),
+            ..Self::additive()
+        }
+    }
+
+    /// Repeating the call with the same arguments has no further effect.
+    pub fn idempotent(mut self) -> Self {
+        self.idempotent_hint = Some(true);
+        self
+    }
+
+    /// Reaches out to the internet (trackers, search engines, plugin URLs).
+    pub fn open_world(mut self) -> Self {
+        self.open_world_hint = Some(true);
+        self
+    }
+}
+
+/// What a tool gets to work with for a single call.
+pub struct ToolContext<'a> {
+    pub(crate) server: &'a McpServer,
+    /// Name the tool was called by.
+    pub tool: &'a str,
+    /// Instance the call targets, after applying the default. The first of `instances`
+    /// when the call fans out.
+    pub instance: &'a str,
+    pub client: &'a QBitClient,
+    /// Every instance the call targets, in name order when it fans out.
+    pub instances: Vec<(&'a str, &'a QBitClient)>,
+    /// True if the call asked for several instances (`instance: "*"` or a list).
+    pub(crate) fan_out: bool,
+    /// Instances left out of a fan-out by policy, with the reason.
+    pub(crate) skipped: Vec<(String, String)>,
+    pub request: &'a RequestContext,
+    /// The raw arguments, including `instance` and `confirm`.
+    pub arguments: &'a Value,
+    pub(crate) audit: AuditNotes,
+}
+
+impl ToolContext<'_> {
+    /// Gets the user's go-ahead for a destructive `action` ("delete 3 torrents, ...").
+    /// Returns `None` to proceed, or the result to return instead of running the tool.
+    pub async fn confirm(&self, action: &str) -> Result<Option<Value>> {
+        let result = self
+            .server
+            .confirm_destructive(self.tool, self.arguments, self.request, action)
+            .await?;
+        if result.is_some() {
+            *self.audit.outcome.lock().unwrap() = Some("not_confirmed");
+        }
+        Ok(result)
+    }
+
+    /// Adds this call to the undo journal. `undo` lists the calls that restore the state
+    /// before it, or is `None` if the change cannot be undone.
+    pub fn record_change(
+        &self,
+        action: impl Into<String>,
+        torrents: &[Torrent],
+        undo: Option<Vec<UndoStep>>,
+    ) -> u64 {
+        self.server
+            .journal()
+            .record(self.instance, self.tool, action.into(), torrents, undo)
+    }
+
+    /// Names the torrents this call acts on in the audit log. Only needed by tools that
+    /// pick torrents themselves rather than taking a `hash`/`hashes` argument.
+    pub fn record_torrents(&self, torrents: &[Torrent]) {
+        *self.audit.torrents.lock().unwrap() = torrents.iter().map(AuditTorrent::from).collect();
+    }
+}
+
+impl<'a> ToolContext<'a> {
+    /// True if the call targets several instances, which only happens for tools that
+    /// [fan out](Tool::fans_out). Results then carry the instance they came from.
+    pub fn is_fan_out(&self) -> bool {
+        self.fan_out
+    }
+
+    /// Runs `f` on every targeted instance concurrently. Instances that fail are listed in
+    /// [`FanOut::failures`]; the call only fails if none of them answered.
+    pub async fn fan_out<T, F, Fut>(&self, f: F) -> Result<FanOut<'a, T>>
+    where
+        F: Fn(&'a str, &'a QBitClient) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        fan_out::run(&self.instances, &self.skipped, f).await
+    }
+
+    /// The client of a targeted instance.
+    pub fn client_for(&self, instance: &str) -> Option<&'a QBitClient> {
+        self.instances
+            .iter()
+            .find(|(name, _)| *name == instance)
+            .map(|(_, client)| *client)
+    }
+}
+
+/// An MCP tool with typed arguments.
+///
+/// `Args` should use `#[serde(deny_unknown_fields)]` so that misspelled arguments are
+/// reported instead of silently ignored. Doc comments on its fields become the property
+/// descriptions in `tools/list`. Every tool also accepts `instance`, and tools that
+/// [confirm](Tool::confirms) accept `confirm`. Both are handled by the registry and never
+/// reach `Args`.
+#[async_trait]
+pub trait Tool: Send + Sync + 'static {
+    type Args: DeserializeOwned + JsonSchema + Send;
+
+    fn name(&self) -> &str;
+
+    fn description(&self) -> &str;
+
+    fn annotations(&self) -> ToolAnnotations {
+        ToolAnnotations::default()
+    }
+
+    /// The toolset (see [`TOOLSETS`]) that enables this tool.
+    fn toolset(&self) -> &str {
+        CUSTOM_TOOLSET
+    }
+
+    /// True if the tool accepts `instance: "*"` or a list of instances, and merges their
+    /// results via [`ToolContext::fan_out`]. Only honoured for read-only tools.
+    fn fans_out(&self) -> bool {
+        false
+    }
+
+    /// True if the tool asks for the user's go-ahead via [`ToolContext::confirm`], and so
+    /// takes the `confirm` token of a previous call.
+    fn confirms(&self) -> bool {
+        false
+    }
+
+    async fn call(&self, ctx: &ToolContext<'_>, args: Self::Args) -> Result<Value>;
+}
+
+/// Arguments of a tool that takes none (besides `instance`).
+#[derive(Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct NoArgs {}
+
+/// Object-safe view of a [`Tool`], working on raw JSON.
+#[async_trait]
+trait DynTool: Send + Sync {
+    fn name(&self) -> &str;
+
+    fn toolset(&self) -> &str;
+
+    fn read_only(&self) -> bool;
+
+    fn fans_out(&self) -> bool;
+
+    fn confirms(&self) -> bool;
+
+    fn definition(&self) -> Value;
+
+    async fn call_json(&self, ctx: &ToolContext<'_>, args: Value) -> Result<Value>;
+}
+
+#[async_trait]
+impl<T: Tool> DynTool for T {
+    fn name(&self) -> &str {
+        Tool::name(self)
+    }
+
+    fn toolset(&self) -> &str {
+        Tool::toolset(self)
+    }
+
+    fn read_only(&self) -> bool {
+        self.annotations().read_only_hint == Some(true)
+    }
+
+    fn fans_out(&self) -> bool {
+        Tool::fans_out(self) && DynTool::read_only(self)
+    }
+
+    fn confirms(&self) -> bool {
+        Tool::confirms(self)
+    }
+
+    fn definition(&self) -> Value {
+        json!({
+            "name": Tool::name(self),
+            "description": self.description(),
+            "inputSchema": input_schema::<T::Args>(),
+            "annotations": self.annotations()
+        })
+    }
+
+    async fn call_json(&self, ctx: &ToolContext<'_>, args: Value) -> Result<Value> {
+        let args = parse_args::<T::Args>(args)?;
+        self.call(ctx, args).await
+    }
+}
+
+/// The tools a server offers, in `tools/list` order.
+#[derive(Clone, Default)]
+pub struct ToolRegistry {
+    tools: Vec<Arc<dyn DynTool>>,
+}
+
+impl ToolRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// All qBittorrent tools that ship with this crate.
+    pub fn builtin() -> Self {
+        let mut registry = Self::new();
+        torrents::register(&mut registry);
+        migrate::register(&mut registry);
+        history::register(&mut registry);
+        search::register(&mut registry);
+        transfer::register(&mut registry);
+        rss::register(&mut registry);
+        app::register(&mut registry);
+        registry
+    }
+
+    /// Adds a tool, replacing any existing tool with the same name.
+    pub fn register<T: Tool>(&mut self, tool: T) {
+        let tool: Arc<dyn DynTool> = Arc::new(tool);
+        match self.tools.iter_mut().find(|t| t.name() == tool.name()) {
+            Some(existing) => *existing = tool,
+            None => self.tools.push(tool),
+        }
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.tools.iter().map(|t| t.name())
+    }
+
+    /// The toolset a registered tool belongs to.
+    pub fn toolset_of(&self, name: &str) -> Option<&str> {
+        self.get(name).map(|t| t.toolset())
+    }
+
+    /// True if the tool is annotated as read-only. Unknown tools are not.
+    pub fn is_read_only(&self, name: &str) -> bool {
+        self.get(name).is_some_and(|t| t.read_only())
+    }
+
+    /// True if the tool can run on several instances at once.
+    pub fn fans_out(&self, name: &str) -> bool {
+        self.get(name).is_some_and(|t| t.fans_out())
+    }
+
+    /// Names of the tools that can run on several instances at once.
+    pub fn fan_out_tools(&self) -> Vec<&str> {
+        self.tools
+            .iter()
+            .filter(|t| t.fans_out())
+            .map(|t| t.name())
+            .collect()
+    }
+
+    /// Names of the registered tools in `toolset`.
+    pub fn toolset_tools(&self, toolset: &str) -> Vec<&str> {
+        self.tools
+            .iter()
+            .filter(|t| t.toolset() == toolset)
+            .map(|t| t.name())
+            .collect()
+    }
+
+    /// Every toolset that has at least one tool: the built-in ones in [`TOOLSETS`] order,
+    /// then any others used by added tools.
+    pub fn toolset_names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = TOOLSETS
+            .iter()
+            .map(|(name, _)| *name)
+            .filter(|name| self.tools.iter().any(|t| t.toolset() == *name))
+            .collect();
+        for tool in &self.tools {
+            if !names.contains(&tool.toolset()) {
+                names.push(tool.toolset());
+            }
+        }
+        names
+    }
+
+    fn get(&self, name: &str) -> Option<&Arc<dyn DynTool>> {
+        self.tools.iter().find(|t| t.name() == name)
+    }
+
+    /// The `tools/list` entry for one tool.
+    pub fn definition(&self, name: &str) -> Option<Value> {
+        self.get(name).map(|t| with_registry_properties(t.as_ref()))
+    }
+
+    /// The `tools/list` entries for all tools.
+    pub fn definitions(&self) -> Vec<Value> {
+        self.tools
+            .iter()
+            .map(|t| with_registry_properties(t.as_ref()))
+            .collect()
+    }
+
+    /// The `tools/list` entries for the tools for which `listed(name, toolset)` is true.
+    pub fn definitions_where(&self, listed: impl Fn(&str, &str) -> bool) -> Vec<Value> {
+        self.tools
+            .iter()
+            .filter(|t| listed(t.name(), t.toolset()))
+            .map(|t| with_registry_properties(t.as_ref()))
+            .collect()
+    }
+
+    /// Validates `args` against the tool's argument type and runs it. `args` may still
+    /// contain `instance` and `confirm`; they are dropped here since `ctx` already carries
+    /// the instance and reads `confirm` from its raw arguments.
+    pub async fn call(&self, name: &str, ctx: &ToolContext<'_>, args: &Value) -> Result<Value> {
+        let tool = self.get(name).ok_or_else(|| unknown_tool(name))?;
+        let mut args = match args {
+            Value::Null => json!({}),
+            args => args.clone(),
+        };
+        if let Some(obj) = args.as_object_mut() {
+            obj.remove("instance");
+            if tool.confirms() {
+                obj.remove("confirm");
+            }
+        }
+        tool.call_json(ctx, args).await
+    }
+}
+
+pub(crate) fn unknown_tool(name: &str) -> anyhow::Error {
+    invalid_argument(
+        "name",
+        "name of a tool from tools/list",
+        format!("Unknown tool: {}", name),
+    )
+}
+
+/// The tool's definition plus the arguments the registry handles for it.
+fn with_registry_properties(tool: &dyn DynTool) -> Value {
+    let mut definition = tool.definition();
+    let instance = if tool.fans_out() {
+        json!({
+            "anyOf": [
+                { "type": "string" },
+                { "type": "array", "items": { "type": "string" } }
+            ],
+            "description": "Optional: Name of the qBittorrent instance to target, \"*\" for all instances, or a list of instance names"
+        })
+    } else {
+        json!({ "type": "string", "description": "Optional: Name of the qBittorrent instance to target" })
+    };
+    if let Some(props) = definition["inputSchema"]["properties"].as_object_mut() {
+        props.insert("instance".to_string(), instance);
+        if tool.confirms() {
+            props.insert(
+                "confirm".to_string(),
+                json!({ "type": "string", "description": "Confirmation token returned by a previous call with the same arguments, when the server requires one" }),
+            );
+        }
+    }
+    definition
+}
+
+/// Generates the `inputSchema` for an argument struct: inlined, without the meta-schema
+/// and title, and with optional fields described by their plain type rather than
+/// `[T, "null"]`, which several clients do not understand.
+pub fn input_schema<T: JsonSchema>() -> Value {
+    let generator = SchemaSettings::draft2020_12()
+        .with(|s| {
+            s.inline_subschemas = true;
+            s.meta_schema = None;
+        })
+        .into_generator();
+    let mut schema = generator.into_root_schema_for::<T>().to_value();
+
+    if let Some(obj) = schema.as_object_mut() {
+        obj.remove("title");
+        obj.remove("description");
+        obj.entry("properties").or_insert_with(|| json!({}));
+        obj.entry("required").or_insert_with(|| json!([]));
+    }
+    if let Some(props) = schema["properties"].as_object_mut() {
+        props.values_mut().for_each(strip_null);
+    }
+    schema
+}
+
+fn strip_null(schema: &mut Value) {
+    let Some(obj) = schema.as_object_mut() else {
+        return;
+    };
+    if let Some(Value::Array(types)) = obj.get_mut("type") {
+        types.retain(|t| t != "null");
+        if types.len() == 1 {
+            let only = types.remove(0);
+            obj.insert("type".to_string(), only);
+        }
+    }
+    if let Some(Value::Array(values)) = obj.get_mut("enum") {
+        values.retain(|v| !v.is_null());
+    }
+}
+
+static SERDE_FIELD_ERROR: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(missing|unknown) field `([^`]+)`").unwrap());
+
+/// Deserializes tool arguments, turning serde's error into an invalid-params error that
+/// names the offending argument and what was expected of it.
+pub(crate) fn parse_args<T: DeserializeOwned + JsonSchema>(args: Value) -> Result<T> {
+    // Structs also deserialize from arrays; tool arguments are always named
+    if !args.is_object() {
+        return Err(invalid_argument(
+            "arguments",
+            "object",
+            "Invalid arguments: expected object".to_string(),
+        ));
+    }
+    serde_path_to_error::deserialize(args).map_err(|err| {
+        let schema = input_schema::<T>();
+        let path = err.path().to_string();
+        let inner = err.into_inner().to_string();
+
+        if let Some(caps) = SERDE_FIELD_ERROR.captures(&inner) {
+            let name = &caps[2];
+            return if &caps[1] == "missing" {
+                invalid_argument(
+                    name,
+                    &expected_type(&schema["properties"][name]),
+                    format!("Missing {}", name),
+                )
+            } else {
+                let known: Vec<&str> = schema["properties"]
+                    .as_object()
+                    .map(|p| p.keys().map(String::as_str).collect())
+                    .unwrap_or_default();
+                invalid_argument(
+                    name,
+                    &format!("one of {}", known.join(", ")),
+                    format!("Unknown argument: {}", name),
+                )
+            };
+        }
+
+        if path == "." {
+            return invalid_argument(
+                "arguments",
+                "object",
+                format!("Invalid arguments: {}", inner),
+            );
+        }
+        let name = path.split('.').next().unwrap_or(&path);
+        let expected = expected_type(&schema["properties"][name]);
+        invalid_argument(
+            name,
+            &expected,
+            format!("Invalid {}: expected {}", name, expected),
+        )
+    })
+}
+
+/// Describes a property schema the way the argument errors do: "integer", "string",
+/// or "one of a, b, c" for enums.
+fn expected_type(property: &Value) -> String {
+    if let Some(values) = property["enum"].as_array() {
+        let values: Vec<&str> = values.iter().filter_map(Value::as_str).collect();
+        return format!("one of {}", values.join(", "));
+    }
+    match &property["type"] {
+        Value::String(t) => t.clone(),
+        Value::Array(types) => types
+            .iter()
+            .filter_map(Value::as_str)
+            .collect::<Vec<_>>()
+            .join(" or "),
+        _ => "value".to_string(),
+    }
+}
+
+/// Shorthand for the usual single-text-block tool result.
+pub fn text_result(text: impl Into<String>) -> Value {
+    json!({ "content": [{ "type": "text", "text": text.into() }] })
+}
+
+/// A tool result holding `value` as pretty-printed JSON.
+pub fn json_result<T: Serialize + ?Sized>(value: &T) -> Result<Value> {
+    Ok(text_result(serde_json::to_string_pretty(value)?))
+}
+
+/// For arguments that are only required by some variants of a tool (e.g. `category` for
+/// `manage_torrents` with `set_category`).
+fn required<T>(value: Option<T>, name: &str, expected: &str) -> Result<T> {
+    value.ok_or_else(|| invalid_argument(name, expected, format!("Missing {}", name)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Deserialize, JsonSchema)]
+    #[serde(deny_unknown_fields)]
+    struct Example {
+        /// Torrent hash
+        hash: String,
+        /// Maximum number to return
+        limit: Option<i64>,
+    }
+
+    #[test]
+    fn test_input_schema() {
+        let schema = input_schema::<Example>();
+        assert_eq!(schema["type"], "object");
+        assert_eq!(schema["properties"]["hash"]["description"], "Torrent hash");
+        assert_eq!(schema["properties"]["limit"]["type"], "integer");
+        assert_eq!(schema["required"], json!(["hash"]));
+        assert_eq!(schema["additionalProperties"], false);
+        assert!(schema.get("title").is_none());
+        assert!(schema.get("$schema").is_none());
+    }
+
+    #[test]
+    fn test_registry_properties() {
+        let registry = ToolRegistry::builtin();
+        let delete = registry.definition("delete_torrent").unwrap();
+        let props = &delete["inputSchema"]["properties"];
+        assert!(props.get("instance").is_some());
+        assert_eq!(props["confirm"]["type"], "string");
+        let list = registry.definition("list_torrents").unwrap();
+        assert!(list["inputSchema"]["properties"].get("confirm").is_none());
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let example = parse_args::<Example>(json!({ "hash": "a", "limit": 5 })).unwrap();
+        assert_eq!((example.hash.as_str(), example.limit), ("a", Some(5)));
+    }
+
+    #[test]
+    fn test_parse_args_errors() {
+        let data = |err: anyhow::Error| {
+            let rpc = err.downcast::<crate::server::mcp::JsonRpcError>().unwrap();
+            (rpc.message, rpc.data.unwrap())
+        };
+
+        let (message, data_) = data(parse_args::<Example>(json!({})).err().unwrap());
+        assert_eq!(message, "Missing hash");
+        assert_eq!(data_, json!({ "argument": "hash", "expected": "string" }));
+
+        let (message, data_) = data(
+            parse_args::<Example>(json!({ "hash": "a", "limit": "ten" }))
+                .err()
+                .unwrap(),
+        );
+        assert_eq!(message, "Invalid limit: expected integer");
+        assert_eq!(data_["argument"], "limit");
+
+        let (message, data_) = data(
+            parse_args::<Example>(json!({ "hash": "a", "limt": 1 }))
+                .err()
+                .unwrap(),
+        );
+        assert_eq!(message, "Unknown argument: limt");
+        assert_eq!(data_["expected"], "one of hash, limit");
+
+        let (_, data_) = data(parse_args::<Example>(json!(["a", 1])).err().unwrap());
+        assert_eq!(data_["argument"], "arguments");
+    }
+}
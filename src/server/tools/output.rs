@@ -0,0 +1,327 @@
+//! Rendering for tools that return lists (torrents, files, log lines, ...): projection to
+//! a few fields, a choice of format, and a character budget with a continuation cursor,
+//! so that a large instance does not overwhelm the model's context.
+
+use super::text_result;
+use crate::server::mcp::invalid_argument;
+use anyhow::Result;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+#[derive(Deserialize, JsonSchema, Clone, Copy, Default, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputFormat {
+    /// Pretty-printed JSON array
+    #[default]
+    Json,
+    /// JSON array on a single line
+    Compact,
+    /// CSV with a header row
+    Csv,
+    /// Markdown table
+    Markdown,
+}
+
+/// The output arguments shared by the listing tools.
+#[derive(Default)]
+pub struct OutputOptions<'a> {
+    pub fields: Option<&'a [String]>,
+    pub format: Option<OutputFormat>,
+    pub max_output_chars: Option<usize>,
+    pub cursor: Option<&'a str>,
+}
+
+/// A tool result listing `items`, rendered according to `options`.
+///
+/// When the budget is hit the first content block holds as many whole items as fit (at
+/// least one, so that paging always makes progress), and a second block says how many
+/// were omitted and which cursor continues the listing.
+pub fn listing_result<T: Serialize>(items: &[T], options: &OutputOptions<'_>) -> Result<Value> {
+    let items = items
+        .iter()
+        .map(serde_json::to_value)
+        .collect::<Result<Vec<_>, _>>()?;
+    render_listing(items, options)
+}
+
+fn render_listing(items: Vec<Value>, options: &OutputOptions<'_>) -> Result<Value> {
+    let total = items.len();
+    let start = match options.cursor {
+        None => 0,
+        Some(cursor) => cursor
+            .parse::<usize>()
+            .ok()
+            .filter(|start| *start <= total)
+            .ok_or_else(|| {
+                invalid_argument(
+                    "cursor",
+                    "cursor from a previous truncated result",
+                    format!("Invalid cursor: {}", cursor),
+                )
+            })?,
+    };
+
+    let items = match options.fields {
+        Some(fields) => {
+            check_fields(&items, fields)?;
+            items.iter().map(|item| project(item, fields)).collect()
+        }
+        None => items,
+    };
+    let items = &items[start..];
+
+    let format = options.format.unwrap_or_default();
+    let columns = match options.fields {
+        Some(fields) => fields.to_vec(),
+        None => columns(items),
+    };
+    let (head, separator, tail) = frame(format, &columns, items.is_empty());
+    let rows: Vec<String> = items
+        .iter()
+        .map(|item| row(format, &columns, item))
+        .collect();
+
+    let mut shown = rows.len();
+    if let Some(budget) = options.max_output_chars {
+        let mut used = head.len() + tail.len();
+        for (i, row) in rows.iter().enumerate() {
+            used += row.len() + if i > 0 { separator.len() } else { 0 };
+            if used > budget && i > 0 {
+                shown = i;
+                break;
+            }
+        }
+    }
+
+    let mut result = text_result(format!("{}{}{}", head, rows[..shown].join(separator), tail));
+    let omitted = rows.len() - shown;
+    if omitted > 0 {
+        let next = start + shown;
+        let note = format!(
+            "Output truncated at max_output_chars: showing items {}-{} of {}, {} more omitted. Call again with the same arguments and cursor \"{}\" to continue.",
+            start + 1,
+            next,
+            total,
+            omitted,
+            next
+        );
+        result["content"]
+            .as_array_mut()
+            .expect("text_result has content")
+            .push(serde_json::json!({ "type": "text", "text": note }));
+    }
+    Ok(result)
+}
+
+fn check_fields(items: &[Value], fields: &[String]) -> Result<()> {
+    let available = columns(items);
+    if available.is_empty() {
+        return Ok(());
+    }
+    if let Some(unknown) = fields.iter().find(|f| !available.contains(f)) {
+        return Err(invalid_argument(
+            "fields",
+            &format!("fields from {}", available.join(", ")),
+            format!("Unknown field: {}", unknown),
+        ));
+    }
+    Ok(())
+}
+
+fn project(item: &Value, fields: &[String]) -> Value {
+    match item {
+        Value::Object(map) => Value::Object(
+            fields
+                .iter()
+                .filter_map(|f| map.get(f).map(|v| (f.clone(), v.clone())))
+                .collect::<Map<_, _>>(),
+        ),
+        other => other.clone(),
+    }
+}
+
+/// Keys of all items, in order of first appearance.
+fn columns(items: &[Value]) -> Vec<String> {
+    let mut columns: Vec<String> = Vec::new();
+    for key in items
+        .iter()
+        .filter_map(Value::as_object)
+        .flat_map(Map::keys)
+    {
+        if !columns.contains(key) {
+            columns.push(key.clone());
+        }
+    }
+    columns
+}
+
+/// What goes before the first row, between rows, and after the last one.
+fn frame(format: OutputFormat, columns: &[String], empty: bool) -> (String, &'static str, String) {
+    match format {
+        OutputFormat::Json if empty => ("[".into(), "", "]".into()),
+        OutputFormat::Json => ("[\n".into(), ",\n", "\n]".into()),
+        OutputFormat::Compact => ("[".into(), ",", "]".into()),
+        OutputFormat::Csv => {
+            let header: Vec<String> = columns.iter().map(|c| csv_cell(c)).collect();
+            (format!("{}\n", header.join(",")), "\n", String::new())
+        }
+        OutputFormat::Markdown => {
+            let header: Vec<String> = columns.iter().map(|c| markdown_cell(c)).collect();
+            (
+                format!(
+                    "| {} |\n|{}\n",
+                    header.join(" | "),
+                    "---|".repeat(columns.len())
+                ),
+                "\n",
+                String::new(),
+            )
+        }
+    }
+}
+
+fn row(format: OutputFormat, columns: &[String], item: &Value) -> String {
+    let cells = || {
+        columns
+            .iter()
+            .map(|c| cell_text(item.get(c).unwrap_or(&Value::Null)))
+    };
+    match format {
+        OutputFormat::Json => serde_json::to_string_pretty(item)
+            .unwrap_or_default()
+            .lines()
+            .map(|line| format!("  {}", line))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        OutputFormat::Compact => item.to_string(),
+        OutputFormat::Csv => cells().map(|c| csv_cell(&c)).collect::<Vec<_>>().join(","),
+        OutputFormat::Markdown => format!(
+            "| {} |",
+            cells()
+                .map(|c| markdown_cell(&c))
+                .collect::<Vec<_>>()
+                .join(" | ")
+        ),
+    }
+}
+
+fn cell_text(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn csv_cell(text: &str) -> String {
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+fn markdown_cell(text: &str) -> String {
+    text.replace('|', "\\|").replace(['\r', '\n'], " ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn text(result: &Value, block: usize) -> &str {
+        result["content"][block]["text"].as_str().unwrap()
+    }
+
+    #[test]
+    fn test_listing_matches_pretty_json() {
+        let items = vec![json!({ "a": 1, "b": [1, 2] }), json!({ "a": 2, "b": [] })];
+        let result = listing_result(&items, &OutputOptions::default()).unwrap();
+        assert_eq!(
+            text(&result, 0),
+            serde_json::to_string_pretty(&items).unwrap()
+        );
+        let empty: Vec<Value> = Vec::new();
+        let result = listing_result(&empty, &OutputOptions::default()).unwrap();
+        assert_eq!(text(&result, 0), "[]");
+    }
+
+    #[test]
+    fn test_listing_formats_and_projection() {
+        let items = vec![
+            json!({ "name": "a,b", "size": 1, "tags": "x|y" }),
+            json!({ "name": "c", "size": 2, "tags": "" }),
+        ];
+        let fields = ["name".to_string(), "size".to_string()];
+        let options = |format| OutputOptions {
+            fields: Some(&fields),
+            format: Some(format),
+            ..Default::default()
+        };
+        let result = listing_result(&items, &options(OutputFormat::Csv)).unwrap();
+        assert_eq!(text(&result, 0), "name,size\n\"a,b\",1\nc,2");
+        let result = listing_result(&items, &options(OutputFormat::Compact)).unwrap();
+        assert_eq!(
+            text(&result, 0),
+            r#"[{"name":"a,b","size":1},{"name":"c","size":2}]"#
+        );
+
+        let result = listing_result(
+            &items,
+            &OutputOptions {
+                format: Some(OutputFormat::Markdown),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            text(&result, 0),
+            "| name | size | tags |\n|---|---|---|\n| a,b | 1 | x\\|y |\n| c | 2 |  |"
+        );
+
+        let unknown = ["nmae".to_string()];
+        let options = OutputOptions {
+            fields: Some(&unknown),
+            ..Default::default()
+        };
+        assert!(listing_result(&items, &options).is_err());
+    }
+
+    #[test]
+    fn test_listing_budget_and_cursor() {
+        let items: Vec<Value> = (0..10).map(|i| json!({ "id": i })).collect();
+        let result = listing_result(
+            &items,
+            &OutputOptions {
+                format: Some(OutputFormat::Compact),
+                max_output_chars: Some(30),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(text(&result, 0), r#"[{"id":0},{"id":1},{"id":2}]"#);
+        assert!(text(&result, 1).contains("7 more omitted"));
+        assert!(text(&result, 1).contains("cursor \"3\""));
+
+        let result = listing_result(
+            &items,
+            &OutputOptions {
+                format: Some(OutputFormat::Compact),
+                max_output_chars: Some(30),
+                cursor: Some("9"),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(text(&result, 0), r#"[{"id":9}]"#);
+        assert!(result["content"].get(1).is_none());
+
+        let options = OutputOptions {
+            cursor: Some("11"),
+            ..Default::default()
+        };
+        assert!(listing_result(&items, &options).is_err());
+    }
+}
@@ -0,0 +1,221 @@
+use super::output::{OutputFormat, OutputOptions, listing_result};
+use super::{NoArgs, Tool, ToolAnnotations, ToolContext, ToolRegistry, json_result, text_result};
+use anyhow::Result;
+use async_trait::async_trait;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+pub(super) fn register(registry: &mut ToolRegistry) {
+    registry.register(AddRssFeed);
+    registry.register(GetRssFeeds);
+    registry.register(SetRssRule);
+    registry.register(GetRssRules);
+    registry.register(MoveRssItem);
+}
+
+pub struct AddRssFeed;
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct AddRssFeedArgs {
+    /// URL of the RSS feed
+    url: String,
+    /// Internal path/name for the feed
+    path: String,
+}
+
+#[async_trait]
+impl Tool for AddRssFeed {
+    type Args = AddRssFeedArgs;
+
+    fn name(&self) -> &str {
+        "add_rss_feed"
+    }
+
+    fn toolset(&self) -> &str {
+        "rss"
+    }
+
+    fn description(&self) -> &str {
+        "Add a new RSS feed"
+    }
+
+    fn annotations(&self) -> ToolAnnotations {
+        ToolAnnotations::additive().open_world()
+    }
+
+    async fn call(&self, ctx: &ToolContext<'_>, args: Self::Args) -> Result<Value> {
+        ctx.client.add_rss_feed(&args.url, &args.path).await?;
+        Ok(text_result("RSS feed added successfully"))
+    }
+}
+
+pub struct GetRssFeeds;
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct GetRssFeedsArgs {
+    /// Only return these fields of each item
+    fields: Option<Vec<String>>,
+    /// Output format (json, compact, csv, markdown; default: json)
+    format: Option<OutputFormat>,
+    /// Truncate the output to about this many characters and return a cursor for the rest
+    max_output_chars: Option<usize>,
+    /// Cursor from a truncated result, to continue where it stopped
+    cursor: Option<String>,
+}
+
+#[async_trait]
+impl Tool for GetRssFeeds {
+    type Args = GetRssFeedsArgs;
+
+    fn name(&self) -> &str {
+        "get_rss_feeds"
+    }
+
+    fn toolset(&self) -> &str {
+        "rss"
+    }
+
+    fn description(&self) -> &str {
+        "Get all RSS feeds and their items"
+    }
+
+    fn annotations(&self) -> ToolAnnotations {
+        ToolAnnotations::read_only()
+    }
+
+    async fn call(&self, ctx: &ToolContext<'_>, args: Self::Args) -> Result<Value> {
+        // One item per feed, keyed by its path, so that the list can be paged
+        let mut feeds: Vec<Value> = ctx
+            .client
+            .get_all_rss_feeds()
+            .await?
+            .into_iter()
+            .map(|(path, feed)| match feed {
+                Value::Object(mut map) => {
+                    map.insert("path".to_string(), Value::String(path));
+                    Value::Object(map)
+                }
+                other => json!({ "path": path, "value": other }),
+            })
+            .collect();
+        feeds.sort_by(|a, b| a["path"].as_str().cmp(&b["path"].as_str()));
+        listing_result(
+            &feeds,
+            &OutputOptions {
+                fields: args.fields.as_deref(),
+                format: args.format,
+                max_output_chars: args.max_output_chars,
+                cursor: args.cursor.as_deref(),
+            },
+        )
+    }
+}
+
+pub struct SetRssRule;
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct SetRssRuleArgs {
+    /// Name of the rule
+    name: String,
+    /// JSON string defining the rule
+    definition: String,
+}
+
+#[async_trait]
+impl Tool for SetRssRule {
+    type Args = SetRssRuleArgs;
+
+    fn name(&self) -> &str {
+        "set_rss_rule"
+    }
+
+    fn toolset(&self) -> &str {
+        "rss"
+    }
+
+    fn description(&self) -> &str {
+        "Create or update an RSS auto-download rule"
+    }
+
+    fn annotations(&self) -> ToolAnnotations {
+        ToolAnnotations::additive().idempotent()
+    }
+
+    async fn call(&self, ctx: &ToolContext<'_>, args: Self::Args) -> Result<Value> {
+        ctx.client
+            .set_rss_rule(&args.name, &args.definition)
+            .await?;
+        Ok(text_result("RSS rule set successfully"))
+    }
+}
+
+pub struct GetRssRules;
+
+#[async_trait]
+impl Tool for GetRssRules {
+    type Args = NoArgs;
+
+    fn name(&self) -> &str {
+        "get_rss_rules"
+    }
+
+    fn toolset(&self) -> &str {
+        "rss"
+    }
+
+    fn description(&self) -> &str {
+        "Get all RSS auto-download rules"
+    }
+
+    fn annotations(&self) -> ToolAnnotations {
+        ToolAnnotations::read_only()
+    }
+
+    async fn call(&self, ctx: &ToolContext<'_>, _args: Self::Args) -> Result<Value> {
+        let rules = ctx.client.get_all_rss_rules().await?;
+        json_result(&rules)
+    }
+}
+
+pub struct MoveRssItem;
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct MoveRssItemArgs {
+    /// Current path of the item
+    item_path: String,
+    /// Destination path
+    dest_path: String,
+}
+
+#[async_trait]
+impl Tool for MoveRssItem {
+    type Args = MoveRssItemArgs;
+
+    fn name(&self) -> &str {
+        "move_rss_item"
+    }
+
+    fn toolset(&self) -> &str {
+        "rss"
+    }
+
+    fn description(&self) -> &str {
+        "Move an RSS item (feed or folder)"
+    }
+
+    fn annotations(&self) -> ToolAnnotations {
+        ToolAnnotations::additive()
+    }
+
+    async fn call(&self, ctx: &ToolContext<'_>, args: Self::Args) -> Result<Value> {
+        ctx.client
+            .move_rss_item(&args.item_path, &args.dest_path)
+            .await?;
+        Ok(text_result("RSS item moved successfully"))
+    }
+}
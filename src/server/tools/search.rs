@@ -0,0 +1,339 @@
+use super::fan_out::tag;
+use super::output::{OutputFormat, OutputOptions, listing_result};
+use super::{NoArgs, Tool, ToolAnnotations, ToolContext, ToolRegistry, json_result, text_result};
+use crate::client::QBitClient;
+use crate::models::SearchResult;
+use anyhow::Result;
+use async_trait::async_trait;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::Value;
+use std::time::Duration;
+use tokio::time::sleep;
+use tracing::debug;
+
+pub(super) fn register(registry: &mut ToolRegistry) {
+    registry.register(SearchTorrents);
+    registry.register(InstallSearchPlugin);
+    registry.register(UninstallSearchPlugin);
+    registry.register(EnableSearchPlugin);
+    registry.register(UpdateSearchPlugins);
+    registry.register(GetSearchPlugins);
+}
+
+pub struct SearchTorrents;
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct SearchTorrentsArgs {
+    /// Search query
+    query: String,
+    /// Optional category
+    category: Option<String>,
+    /// Only return these fields of each item
+    fields: Option<Vec<String>>,
+    /// Output format (json, compact, csv, markdown; default: json)
+    format: Option<OutputFormat>,
+    /// Truncate the output to about this many characters and return a cursor for the rest
+    max_output_chars: Option<usize>,
+    /// Cursor from a truncated result, to continue where it stopped
+    cursor: Option<String>,
+}
+
+#[async_trait]
+impl Tool for SearchTorrents {
+    type Args = SearchTorrentsArgs;
+
+    fn name(&self) -> &str {
+        "search_torrents"
+    }
+
+    fn toolset(&self) -> &str {
+        "search"
+    }
+
+    fn description(&self) -> &str {
+        "Search for torrents. ASYNCHRONOUS: Results might be incomplete on the first call. Use get_search_results for polling if needed."
+    }
+
+    fn annotations(&self) -> ToolAnnotations {
+        ToolAnnotations::read_only().open_world()
+    }
+
+    fn fans_out(&self) -> bool {
+        true
+    }
+
+    async fn call(&self, ctx: &ToolContext<'_>, args: Self::Args) -> Result<Value> {
+        let output = OutputOptions {
+            fields: args.fields.as_deref(),
+            format: args.format,
+            max_output_chars: args.max_output_chars,
+            cursor: args.cursor.as_deref(),
+        };
+        if !ctx.is_fan_out() {
+            let results = search(ctx, ctx.instance, ctx.client, &args).await?;
+            return listing_result(&results, &output);
+        }
+
+        // Every instance searches at once; the merged results are sorted by seeders
+        let args = &args;
+        let fan_out = ctx
+            .fan_out(|instance, client| search(ctx, instance, client, args))
+            .await?;
+        let mut results: Vec<(&str, &SearchResult)> = fan_out
+            .results
+            .iter()
+            .flat_map(|(instance, results)| results.iter().map(move |r| (*instance, r)))
+            .collect();
+        results.sort_by_key(|(_, r)| std::cmp::Reverse(r.nb_seeders));
+        let results: Vec<Value> = results
+            .into_iter()
+            .map(|(instance, r)| tag(instance, serde_json::to_value(r).unwrap_or(Value::Null)))
+            .collect();
+        Ok(fan_out.report_failures(listing_result(&results, &output)?))
+    }
+}
+
+/// Runs a search job on `client` and polls it for a few seconds. Progress is reported for
+/// the call's first instance only, so a fan-out does not interleave several counts.
+async fn search(
+    ctx: &ToolContext<'_>,
+    instance: &str,
+    client: &QBitClient,
+    args: &SearchTorrentsArgs,
+) -> Result<Vec<SearchResult>> {
+    let id = client
+        .start_search(&args.query, args.category.as_deref())
+        .await?;
+    // Stops and deletes the job even if this future is dropped on cancellation
+    let mut job = SearchJobGuard::new(client, id);
+
+    const POLLS: usize = 5;
+    let mut final_results = Vec::new();
+    for attempt in 1..=POLLS {
+        sleep(Duration::from_secs(1)).await;
+        let resp = client.get_search_results(id, None, None).await;
+        if let Ok(r) = resp {
+            if r.status == "Stopped" {
+                final_results = r.results;
+                break;
+            }
+            final_results = r.results;
+        }
+        if instance == ctx.instance {
+            ctx.request.report_progress(
+                attempt as f64,
+                Some(POLLS as f64),
+                format!("{} results found so far", final_results.len()),
+            );
+        }
+    }
+    job.finish().await;
+    Ok(final_results)
+}
+
+/// Owns a qBittorrent search job for the lifetime of a `search_torrents` call.
+struct SearchJobGuard {
+    client: QBitClient,
+    id: i64,
+    active: bool,
+}
+
+impl SearchJobGuard {
+    fn new(client: &QBitClient, id: i64) -> Self {
+        Self {
+            client: client.clone(),
+            id,
+            active: true,
+        }
+    }
+
+    async fn finish(&mut self) {
+        self.active = false;
+        let _ = self.client.stop_search(self.id).await;
+        let _ = self.client.delete_search(self.id).await;
+    }
+}
+
+impl Drop for SearchJobGuard {
+    fn drop(&mut self) {
+        if !self.active {
+            return;
+        }
+        let client = self.client.clone();
+        let id = self.id;
+        if let Ok(handle) = tokio::runtime::Handle::try_current() {
+            handle.spawn(async move {
+                debug!("Cleaning up abandoned search job {}", id);
+                let _ = client.stop_search(id).await;
+                let _ = client.delete_search(id).await;
+            });
+        }
+    }
+}
+
+pub struct InstallSearchPlugin;
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct InstallSearchPluginArgs {
+    /// URL to the plugin file
+    url: String,
+}
+
+#[async_trait]
+impl Tool for InstallSearchPlugin {
+    type Args = InstallSearchPluginArgs;
+
+    fn name(&self) -> &str {
+        "install_search_plugin"
+    }
+
+    fn toolset(&self) -> &str {
+        "search"
+    }
+
+    fn description(&self) -> &str {
+        "Install a search plugin"
+    }
+
+    fn annotations(&self) -> ToolAnnotations {
+        ToolAnnotations::additive().idempotent().open_world()
+    }
+
+    async fn call(&self, ctx: &ToolContext<'_>, args: Self::Args) -> Result<Value> {
+        ctx.client.install_search_plugin(&args.url).await?;
+        Ok(text_result("Search plugin installed successfully"))
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct PluginNameArgs {
+    /// Name of the plugin
+    name: String,
+}
+
+pub struct UninstallSearchPlugin;
+
+#[async_trait]
+impl Tool for UninstallSearchPlugin {
+    type Args = PluginNameArgs;
+
+    fn name(&self) -> &str {
+        "uninstall_search_plugin"
+    }
+
+    fn toolset(&self) -> &str {
+        "search"
+    }
+
+    fn description(&self) -> &str {
+        "Uninstall a search plugin"
+    }
+
+    fn annotations(&self) -> ToolAnnotations {
+        ToolAnnotations::destructive().idempotent()
+    }
+
+    async fn call(&self, ctx: &ToolContext<'_>, args: Self::Args) -> Result<Value> {
+        ctx.client.uninstall_search_plugin(&args.name).await?;
+        Ok(text_result("Search plugin uninstalled successfully"))
+    }
+}
+
+pub struct EnableSearchPlugin;
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct EnableSearchPluginArgs {
+    /// Name of the plugin
+    name: String,
+    /// True to enable, False to disable
+    enable: bool,
+}
+
+#[async_trait]
+impl Tool for EnableSearchPlugin {
+    type Args = EnableSearchPluginArgs;
+
+    fn name(&self) -> &str {
+        "enable_search_plugin"
+    }
+
+    fn toolset(&self) -> &str {
+        "search"
+    }
+
+    fn description(&self) -> &str {
+        "Enable or disable a search plugin"
+    }
+
+    fn annotations(&self) -> ToolAnnotations {
+        ToolAnnotations::additive().idempotent()
+    }
+
+    async fn call(&self, ctx: &ToolContext<'_>, args: Self::Args) -> Result<Value> {
+        ctx.client
+            .enable_search_plugin(&args.name, args.enable)
+            .await?;
+        Ok(text_result("Search plugin status updated successfully"))
+    }
+}
+
+pub struct UpdateSearchPlugins;
+
+#[async_trait]
+impl Tool for UpdateSearchPlugins {
+    type Args = NoArgs;
+
+    fn name(&self) -> &str {
+        "update_search_plugins"
+    }
+
+    fn toolset(&self) -> &str {
+        "search"
+    }
+
+    fn description(&self) -> &str {
+        "Update all search plugins"
+    }
+
+    fn annotations(&self) -> ToolAnnotations {
+        ToolAnnotations::additive().idempotent().open_world()
+    }
+
+    async fn call(&self, ctx: &ToolContext<'_>, _args: Self::Args) -> Result<Value> {
+        ctx.client.update_search_plugins().await?;
+        Ok(text_result("Search plugins updated successfully"))
+    }
+}
+
+pub struct GetSearchPlugins;
+
+#[async_trait]
+impl Tool for GetSearchPlugins {
+    type Args = NoArgs;
+
+    fn name(&self) -> &str {
+        "get_search_plugins"
+    }
+
+    fn toolset(&self) -> &str {
+        "search"
+    }
+
+    fn description(&self) -> &str {
+        "List installed search plugins"
+    }
+
+    fn annotations(&self) -> ToolAnnotations {
+        ToolAnnotations::read_only()
+    }
+
+    async fn call(&self, ctx: &ToolContext<'_>, _args: Self::Args) -> Result<Value> {
+        let plugins = ctx.client.get_search_plugins().await?;
+        json_result(&plugins)
+    }
+}
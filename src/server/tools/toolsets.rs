@@ -0,0 +1,100 @@
+//! Named groups of tools that a session can switch on and off, so lazy clients only
+//! load the tools they need into context.
+
+use super::{input_schema, parse_args};
+use anyhow::Result;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+/// Built-in toolsets and what they are for, in `list_toolsets` order.
+pub const TOOLSETS: &[(&str, &str)] = &[
+    (
+        "torrents",
+        "List, add, delete and control torrents, their files and categories",
+    ),
+    ("trackers", "Add, edit and remove trackers"),
+    ("search", "Search for torrents and manage search plugins"),
+    ("rss", "RSS feeds and auto-download rules"),
+    ("transfer", "Peer bans and other transfer controls"),
+    (
+        "app",
+        "Application preferences, logs, system information and shutdown",
+    ),
+    (
+        "maintenance",
+        "Bulk cleanup of completed torrents, mass rename and duplicate detection",
+    ),
+];
+
+/// Toolset of tools that do not name one, e.g. tools added with `McpServer::with_tool`.
+pub const CUSTOM_TOOLSET: &str = "custom";
+
+/// Toolsets a lazy-mode session starts with unless configured otherwise.
+pub const LAZY_TOOLSETS: &[&str] = &["torrents"];
+
+/// Tools for managing toolsets. They are always listed and belong to no toolset.
+pub const META_TOOLS: &[&str] = &[
+    "list_toolsets",
+    "enable_toolset",
+    "disable_toolset",
+    "show_all_tools",
+];
+
+pub fn description(toolset: &str) -> &str {
+    TOOLSETS
+        .iter()
+        .find(|(name, _)| *name == toolset)
+        .map(|(_, description)| *description)
+        .unwrap_or("Additional tools provided by the embedding application")
+}
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+struct ToolsetArgs {
+    /// Name of the toolset, as returned by list_toolsets
+    toolset: String,
+}
+
+/// The `toolset` argument of `enable_toolset` and `disable_toolset`.
+pub fn toolset_arg(args: &Value) -> Result<String> {
+    let args = match args {
+        Value::Null => json!({}),
+        args => args.clone(),
+    };
+    Ok(parse_args::<ToolsetArgs>(args)?.toolset)
+}
+
+pub fn meta_tool_definitions() -> Vec<Value> {
+    let no_args = json!({
+        "type": "object",
+        "properties": {},
+        "required": []
+    });
+    vec![
+        json!({
+            "name": "list_toolsets",
+            "description": "List the available toolsets, what they contain and whether they are enabled",
+            "inputSchema": no_args,
+            "annotations": { "readOnlyHint": true, "openWorldHint": false }
+        }),
+        json!({
+            "name": "enable_toolset",
+            "description": "Make the tools of a toolset available in this session",
+            "inputSchema": input_schema::<ToolsetArgs>(),
+            "annotations": { "readOnlyHint": false, "destructiveHint": false, "idempotentHint": true, "openWorldHint": false }
+        }),
+        json!({
+            "name": "disable_toolset",
+            "description": "Hide the tools of a toolset in this session to save context",
+            "inputSchema": input_schema::<ToolsetArgs>(),
+            "annotations": { "readOnlyHint": false, "destructiveHint": false, "idempotentHint": true, "openWorldHint": false }
+        }),
+        json!({
+            "name": "show_all_tools",
+            "description": "Enable all available tools",
+            "inputSchema": no_args,
+            "annotations": { "readOnlyHint": false, "destructiveHint": false, "idempotentHint": true, "openWorldHint": false }
+        }),
+    ]
+}
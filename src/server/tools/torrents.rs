@@ -0,0 +1,1499 @@
+use super::fan_out::{compare_values, tag};
+use super::output::{OutputFormat, OutputOptions, listing_result};
+use super::{
+    NoArgs, Tool, ToolAnnotations, ToolContext, ToolRegistry, json_result, required, text_result,
+};
+use crate::client::QBitClient;
+use crate::humanize::{Humanizer, format_size, view};
+use crate::models::{Category, Torrent};
+use crate::server::journal::{UndoStep, group_by};
+use crate::server::mcp::invalid_argument;
+use anyhow::Result;
+use async_trait::async_trait;
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::time::Duration;
+use tokio::time::sleep;
+use tracing::warn;
+
+pub(super) fn register(registry: &mut ToolRegistry) {
+    registry.register(ListTorrents);
+    registry.register(ManageTorrents);
+    registry.register(AddTorrent);
+    registry.register(DeleteTorrent);
+    registry.register(GetTorrentFiles);
+    registry.register(InspectTorrent);
+    registry.register(GetCategories);
+    registry.register(WaitForTorrentStatus);
+    registry.register(CleanupCompleted);
+    registry.register(MassRename);
+    registry.register(FindDuplicates);
+    registry.register(AddTrackers);
+    registry.register(EditTracker);
+    registry.register(RemoveTrackers);
+    registry.register(RenameFolder);
+    registry.register(SetFilePriority);
+}
+
+/// Describes removing `torrents` for a confirmation prompt, e.g.
+/// "delete 14 torrents, 380.2 GiB of data on instance nas".
+fn describe_removal(torrents: &[Torrent], delete_files: bool, instance: &str) -> String {
+    let what = match torrents {
+        [t] => format!("torrent '{}'", t.name),
+        _ => format!("{} torrents", torrents.len()),
+    };
+    if delete_files {
+        let total: i64 = torrents.iter().map(|t| t.size_bytes).sum();
+        format!(
+            "delete {}, {} of data on instance {}",
+            what,
+            format_size(total),
+            instance
+        )
+    } else {
+        format!(
+            "remove {} from instance {} (files are kept)",
+            what, instance
+        )
+    }
+}
+
+/// The `dry_run` result for removing `torrents`: each torrent with its size, ratio and
+/// days since completion, plus the totals.
+fn removal_plan(torrents: &[Torrent], delete_files: bool, instance: &str) -> Result<Value> {
+    let now = unix_now()?;
+    let entries: Vec<Value> = torrents
+        .iter()
+        .map(|t| {
+            json!({
+                "hash": t.hash,
+                "name": t.name,
+                "size": format_size(t.size_bytes),
+                "ratio": (t.ratio * 100.0).round() / 100.0,
+                "age_days": (t.completion_on > 0).then(|| (now - t.completion_on) / 86400),
+                "state": t.state
+            })
+        })
+        .collect();
+    let total: i64 = torrents.iter().map(|t| t.size_bytes).sum();
+    json_result(&json!({
+        "dry_run": true,
+        "action": describe_removal(torrents, delete_files, instance),
+        "delete_files": delete_files,
+        "count": torrents.len(),
+        "total_size": format_size(total),
+        "torrents": entries
+    }))
+}
+
+fn unix_now() -> Result<i64> {
+    Ok(std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)?
+        .as_secs() as i64)
+}
+
+pub struct ListTorrents;
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ListTorrentsArgs {
+    /// Filter by status (all, downloading, completed, paused, active, inactive, resumed, stalled, stalled_uploading, stalled_downloading, errored)
+    filter: Option<String>,
+    /// Filter by category
+    category: Option<String>,
+    /// Filter by tag
+    tag: Option<String>,
+    /// Sort by column name (e.g., name, size, progress, added_on, dlspeed, upspeed, ratio, eta, state, category, tags)
+    sort: Option<String>,
+    /// True to reverse sort order
+    reverse: Option<bool>,
+    /// Maximum number of torrents to return
+    limit: Option<i64>,
+    /// Number of torrents to skip
+    offset: Option<i64>,
+    /// Include detailed properties for each torrent
+    #[serde(default)]
+    include_properties: bool,
+    /// Include file list for each torrent
+    #[serde(default)]
+    include_files: bool,
+    /// Show sizes, speeds, times, progress and states in human-friendly form (default: server setting)
+    humanize: Option<bool>,
+    /// Only return these fields of each item
+    fields: Option<Vec<String>>,
+    /// Output format (json, compact, csv, markdown; default: json)
+    format: Option<OutputFormat>,
+    /// Truncate the output to about this many characters and return a cursor for the rest
+    max_output_chars: Option<usize>,
+    /// Cursor from a truncated result, to continue where it stopped
+    cursor: Option<String>,
+}
+
+#[async_trait]
+impl Tool for ListTorrents {
+    type Args = ListTorrentsArgs;
+
+    fn name(&self) -> &str {
+        "list_torrents"
+    }
+
+    fn toolset(&self) -> &str {
+        "torrents"
+    }
+
+    fn description(&self) -> &str {
+        "List all torrents with optional filtering and sorting"
+    }
+
+    fn annotations(&self) -> ToolAnnotations {
+        ToolAnnotations::read_only()
+    }
+
+    fn fans_out(&self) -> bool {
+        true
+    }
+
+    async fn call(&self, ctx: &ToolContext<'_>, args: Self::Args) -> Result<Value> {
+        let output = OutputOptions {
+            fields: args.fields.as_deref(),
+            format: args.format,
+            max_output_chars: args.max_output_chars,
+            cursor: args.cursor.as_deref(),
+        };
+        let humanizer = ctx.server.humanizer_for(args.humanize);
+        if ctx.is_fan_out() {
+            return list_all_instances(ctx, &args, &output, humanizer).await;
+        }
+
+        let client = ctx.client;
+        let torrents = client
+            .get_torrent_list(
+                args.filter.as_deref(),
+                args.category.as_deref(),
+                args.tag.as_deref(),
+                args.sort.as_deref(),
+                args.reverse,
+                args.limit,
+                args.offset,
+            )
+            .await?;
+        let mut items = Vec::new();
+        for t in &torrents {
+            items.push(torrent_view(client, t, &args, humanizer).await);
+        }
+        listing_result(&items, &output)
+    }
+}
+
+/// `list_torrents` on several instances: each torrent is tagged with its instance, and
+/// `sort`, `limit` and `offset` apply to the merged list.
+async fn list_all_instances(
+    ctx: &ToolContext<'_>,
+    args: &ListTorrentsArgs,
+    output: &OutputOptions<'_>,
+    humanizer: Option<&Humanizer>,
+) -> Result<Value> {
+    // The first offset + limit torrents of each instance are all the merged page can hold
+    let per_instance = args
+        .limit
+        .map(|limit| limit + args.offset.unwrap_or(0).max(0));
+    let fan_out = ctx
+        .fan_out(|_, client| async move {
+            client
+                .get_torrent_list(
+                    args.filter.as_deref(),
+                    args.category.as_deref(),
+                    args.tag.as_deref(),
+                    args.sort.as_deref(),
+                    args.reverse,
+                    per_instance,
+                    None,
+                )
+                .await
+        })
+        .await?;
+
+    let mut torrents: Vec<(Value, &str, &Torrent)> = fan_out
+        .results
+        .iter()
+        .flat_map(|(instance, torrents)| torrents.iter().map(move |t| (*instance, t)))
+        .map(|(instance, t)| {
+            let key = args
+                .sort
+                .as_deref()
+                .and_then(|sort| serde_json::to_value(t).ok()?.get(sort).cloned())
+                .unwrap_or(Value::Null);
+            (key, instance, t)
+        })
+        .collect();
+    if args.sort.is_some() {
+        let reverse = args.reverse == Some(true);
+        torrents.sort_by(|(a, ..), (b, ..)| {
+            let order = compare_values(a, b);
+            if reverse { order.reverse() } else { order }
+        });
+    }
+
+    let offset = args.offset.unwrap_or(0).max(0) as usize;
+    let limit = args.limit.map_or(usize::MAX, |limit| limit.max(0) as usize);
+    let mut items = Vec::new();
+    for (_, instance, t) in torrents.into_iter().skip(offset).take(limit) {
+        let client = ctx.client_for(instance).unwrap_or(ctx.client);
+        items.push(tag(
+            instance,
+            torrent_view(client, t, args, humanizer).await,
+        ));
+    }
+    Ok(fan_out.report_failures(listing_result(&items, output)?))
+}
+
+/// A torrent in a `list_torrents` result, with its properties and files if requested.
+async fn torrent_view(
+    client: &QBitClient,
+    torrent: &Torrent,
+    args: &ListTorrentsArgs,
+    humanizer: Option<&Humanizer>,
+) -> Value {
+    let mut detailed = view(torrent, humanizer);
+    if args.include_properties
+        && let Ok(props) = client.get_torrent_properties(&torrent.hash).await
+    {
+        detailed["properties"] = view(&props, humanizer);
+    }
+    if args.include_files
+        && let Ok(files) = client.get_torrent_files(&torrent.hash).await
+    {
+        detailed["files"] = view(&files, humanizer);
+    }
+    detailed
+}
+
+pub struct ManageTorrents;
+
+#[derive(Deserialize, JsonSchema, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ManageAction {
+    Pause,
+    Resume,
+    Reannounce,
+    Recheck,
+    SetCategory,
+    AddTags,
+    RemoveTags,
+    SetShareLimits,
+    SetSpeedLimits,
+    ToggleSequential,
+    ToggleFirstLastPrio,
+    SetForceStart,
+    SetSuperSeeding,
+}
+
+impl ManageAction {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Pause => "pause",
+            Self::Resume => "resume",
+            Self::Reannounce => "reannounce",
+            Self::Recheck => "recheck",
+            Self::SetCategory => "set_category",
+            Self::AddTags => "add_tags",
+            Self::RemoveTags => "remove_tags",
+            Self::SetShareLimits => "set_share_limits",
+            Self::SetSpeedLimits => "set_speed_limits",
+            Self::ToggleSequential => "toggle_sequential",
+            Self::ToggleFirstLastPrio => "toggle_first_last_prio",
+            Self::SetForceStart => "set_force_start",
+            Self::SetSuperSeeding => "set_super_seeding",
+        }
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ManageTorrentsArgs {
+    /// Torrent hashes (pipe-separated)
+    hashes: String,
+    /// Action to perform
+    action: ManageAction,
+    /// For 'set_category'
+    category: Option<String>,
+    /// For 'add_tags' or 'remove_tags' (comma-separated)
+    tags: Option<String>,
+    /// For 'set_share_limits'
+    ratio_limit: Option<f64>,
+    /// For 'set_share_limits' (minutes)
+    seeding_time_limit: Option<i64>,
+    /// For 'set_share_limits' (minutes)
+    inactive_seeding_time_limit: Option<i64>,
+    /// For 'set_speed_limits' (bytes/s)
+    dl_limit: Option<i64>,
+    /// For 'set_speed_limits' (bytes/s)
+    up_limit: Option<i64>,
+    /// For 'set_force_start' or 'set_super_seeding'
+    value: Option<bool>,
+}
+
+#[async_trait]
+impl Tool for ManageTorrents {
+    type Args = ManageTorrentsArgs;
+
+    fn name(&self) -> &str {
+        "manage_torrents"
+    }
+
+    fn toolset(&self) -> &str {
+        "torrents"
+    }
+
+    fn description(&self) -> &str {
+        "Unified tool for multiple torrent actions (pause, resume, category, tags, limits, etc.)"
+    }
+
+    fn annotations(&self) -> ToolAnnotations {
+        ToolAnnotations::additive()
+    }
+
+    async fn call(&self, ctx: &ToolContext<'_>, args: Self::Args) -> Result<Value> {
+        let client = ctx.client;
+        let hashes = args.hashes.as_str();
+
+        // Snapshot the torrents first so that the change can be undone
+        let before = match client.get_torrents_info(hashes).await {
+            Ok(torrents) => Some(torrents),
+            Err(e) => {
+                warn!(
+                    "Could not snapshot torrents before {}: {}",
+                    args.action.as_str(),
+                    e
+                );
+                None
+            }
+        };
+        let undo = before
+            .as_deref()
+            .and_then(|before| undo_steps(&args, before));
+        let description = describe_manage_action(&args);
+
+        match args.action {
+            ManageAction::Pause => client.pause_torrents(hashes).await?,
+            ManageAction::Resume => client.resume_torrents(hashes).await?,
+            ManageAction::Reannounce => client.reannounce_torrents(hashes).await?,
+            ManageAction::Recheck => client.recheck_torrents(hashes).await?,
+            ManageAction::SetCategory => {
+                let category = required(args.category, "category", "string")?;
+                client.set_category(hashes, &category).await?;
+            }
+            ManageAction::AddTags => {
+                let tags = required(args.tags, "tags", "string")?;
+                client.add_tags(hashes, &tags).await?;
+            }
+            ManageAction::RemoveTags => {
+                let tags = required(args.tags, "tags", "string")?;
+                client.remove_tags(hashes, &tags).await?;
+            }
+            ManageAction::SetShareLimits => {
+                let ratio_limit = required(args.ratio_limit, "ratio_limit", "number")?;
+                let seeding_time_limit =
+                    required(args.seeding_time_limit, "seeding_time_limit", "integer")?;
+                client
+                    .set_torrent_share_limits(
+                        hashes,
+                        ratio_limit,
+                        seeding_time_limit,
+                        args.inactive_seeding_time_limit,
+                    )
+                    .await?;
+            }
+            ManageAction::SetSpeedLimits => {
+                if let Some(limit) = args.dl_limit {
+                    client.set_torrent_download_limit(hashes, limit).await?;
+                }
+                if let Some(limit) = args.up_limit {
+                    client.set_torrent_upload_limit(hashes, limit).await?;
+                }
+            }
+            ManageAction::ToggleSequential => client.toggle_sequential_download(hashes).await?,
+            ManageAction::ToggleFirstLastPrio => {
+                client.toggle_first_last_piece_priority(hashes).await?
+            }
+            ManageAction::SetForceStart => {
+                let value = required(args.value, "value", "boolean")?;
+                client.set_force_start(hashes, value).await?;
+            }
+            ManageAction::SetSuperSeeding => {
+                let value = required(args.value, "value", "boolean")?;
+                client.set_super_seeding(hashes, value).await?;
+            }
+        }
+
+        ctx.record_change(description, before.as_deref().unwrap_or_default(), undo);
+        Ok(text_result(format!(
+            "Action '{}' performed successfully on torrents.",
+            args.action.as_str()
+        )))
+    }
+}
+
+fn describe_manage_action(args: &ManageTorrentsArgs) -> String {
+    let action = args.action.as_str();
+    let detail = match args.action {
+        ManageAction::SetCategory => args.category.as_ref().map(|c| format!("'{}'", c)),
+        ManageAction::AddTags | ManageAction::RemoveTags => {
+            args.tags.as_ref().map(|t| format!("'{}'", t))
+        }
+        ManageAction::SetShareLimits => args.ratio_limit.map(|r| format!("ratio {}", r)),
+        ManageAction::SetSpeedLimits => {
+            Some(format!("dl {:?}, up {:?}", args.dl_limit, args.up_limit))
+        }
+        ManageAction::SetForceStart | ManageAction::SetSuperSeeding => {
+            args.value.map(|v| v.to_string())
+        }
+        _ => None,
+    };
+    match detail {
+        Some(detail) => format!("{} {}", action, detail),
+        None => action.to_string(),
+    }
+}
+
+pub(super) fn is_paused(state: &str) -> bool {
+    state.starts_with("paused") || state.starts_with("stopped")
+}
+
+fn split_tags(tags: &str) -> Vec<&str> {
+    tags.split(',')
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+        .collect()
+}
+
+/// The calls that put `before` back after `args` is applied, or `None` for actions that
+/// cannot be undone or when qBittorrent did not report the values to restore.
+fn undo_steps(args: &ManageTorrentsArgs, before: &[Torrent]) -> Option<Vec<UndoStep>> {
+    let all = || {
+        before
+            .iter()
+            .map(|t| t.hash.as_str())
+            .collect::<Vec<_>>()
+            .join("|")
+    };
+    let steps = match args.action {
+        ManageAction::Reannounce | ManageAction::Recheck => return None,
+        ManageAction::Pause | ManageAction::Resume => group_by(before, |t| is_paused(&t.state))
+            .into_iter()
+            .map(|(paused, hashes)| match paused {
+                true => UndoStep::Pause { hashes },
+                false => UndoStep::Resume { hashes },
+            })
+            .collect(),
+        ManageAction::SetCategory => group_by(before, |t| t.category.clone())
+            .into_iter()
+            .map(|(category, hashes)| UndoStep::SetCategory { hashes, category })
+            .collect(),
+        ManageAction::AddTags | ManageAction::RemoveTags => {
+            let requested = split_tags(args.tags.as_deref()?);
+            let adding = args.action == ManageAction::AddTags;
+            // Only touch the tags this call actually changes on each torrent
+            group_by(before, |t| {
+                let existing = split_tags(&t.tags);
+                requested
+                    .iter()
+                    .filter(|tag| existing.contains(tag) != adding)
+                    .copied()
+                    .collect::<Vec<_>>()
+                    .join(",")
+            })
+            .into_iter()
+            .filter(|(tags, _)| !tags.is_empty())
+            .map(|(tags, hashes)| match adding {
+                true => UndoStep::RemoveTags { hashes, tags },
+                false => UndoStep::AddTags { hashes, tags },
+            })
+            .collect()
+        }
+        ManageAction::SetShareLimits => group_by(before, |t| {
+            (
+                t.ratio_limit,
+                t.seeding_time_limit,
+                t.inactive_seeding_time_limit,
+            )
+        })
+        .into_iter()
+        .map(|((ratio, seeding, inactive), hashes)| {
+            Some(UndoStep::SetShareLimits {
+                hashes,
+                ratio_limit: ratio?,
+                seeding_time_limit: seeding?,
+                inactive_seeding_time_limit: inactive,
+            })
+        })
+        .collect::<Option<Vec<_>>>()?,
+        ManageAction::SetSpeedLimits => {
+            let mut steps = Vec::new();
+            if args.dl_limit.is_some() {
+                for (limit, hashes) in group_by(before, |t| t.dl_limit) {
+                    steps.push(UndoStep::SetDownloadLimit {
+                        hashes,
+                        limit: limit?,
+                    });
+                }
+            }
+            if args.up_limit.is_some() {
+                for (limit, hashes) in group_by(before, |t| t.up_limit) {
+                    steps.push(UndoStep::SetUploadLimit {
+                        hashes,
+                        limit: limit?,
+                    });
+                }
+            }
+            steps
+        }
+        ManageAction::ToggleSequential => vec![UndoStep::ToggleSequential { hashes: all() }],
+        ManageAction::ToggleFirstLastPrio => {
+            vec![UndoStep::ToggleFirstLastPrio { hashes: all() }]
+        }
+        ManageAction::SetForceStart => group_by(before, |t| t.force_start)
+            .into_iter()
+            .map(|(value, hashes)| UndoStep::SetForceStart { hashes, value })
+            .collect(),
+        ManageAction::SetSuperSeeding => group_by(before, |t| t.super_seeding)
+            .into_iter()
+            .map(|(value, hashes)| UndoStep::SetSuperSeeding { hashes, value })
+            .collect(),
+    };
+    Some(steps)
+}
+
+pub struct AddTorrent;
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct AddTorrentArgs {
+    /// Magnet URI or HTTP URL
+    url: String,
+    /// Optional save path
+    save_path: Option<String>,
+    /// Optional category
+    category: Option<String>,
+}
+
+#[async_trait]
+impl Tool for AddTorrent {
+    type Args = AddTorrentArgs;
+
+    fn name(&self) -> &str {
+        "add_torrent"
+    }
+
+    fn toolset(&self) -> &str {
+        "torrents"
+    }
+
+    fn description(&self) -> &str {
+        "Add a new torrent"
+    }
+
+    fn annotations(&self) -> ToolAnnotations {
+        ToolAnnotations::additive().open_world()
+    }
+
+    async fn call(&self, ctx: &ToolContext<'_>, args: Self::Args) -> Result<Value> {
+        ctx.client
+            .add_torrent(
+                &args.url,
+                args.save_path.as_deref(),
+                args.category.as_deref(),
+            )
+            .await?;
+        Ok(text_result("Torrent added successfully"))
+    }
+}
+
+pub struct DeleteTorrent;
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct DeleteTorrentArgs {
+    /// Torrent hash (pipe-separated for multiple)
+    hash: String,
+    /// Also delete files from disk
+    delete_files: bool,
+    /// Only return the torrents that would be deleted, without deleting anything
+    #[serde(default)]
+    dry_run: bool,
+}
+
+#[async_trait]
+impl Tool for DeleteTorrent {
+    type Args = DeleteTorrentArgs;
+
+    fn name(&self) -> &str {
+        "delete_torrent"
+    }
+
+    fn toolset(&self) -> &str {
+        "torrents"
+    }
+
+    fn description(&self) -> &str {
+        "Delete a torrent. DESTRUCTIVE: Inform the user and confirm before calling, especially if delete_files is true."
+    }
+
+    fn annotations(&self) -> ToolAnnotations {
+        ToolAnnotations::destructive().idempotent()
+    }
+
+    fn confirms(&self) -> bool {
+        true
+    }
+
+    async fn call(&self, ctx: &ToolContext<'_>, args: Self::Args) -> Result<Value> {
+        if args.dry_run {
+            let torrents = ctx.client.get_torrents_info(&args.hash).await?;
+            return removal_plan(&torrents, args.delete_files, ctx.instance);
+        }
+        // The names are only needed for the journal unless the user must confirm
+        let torrents = match ctx.client.get_torrents_info(&args.hash).await {
+            Ok(torrents) => torrents,
+            Err(e) if args.delete_files => return Err(e),
+            Err(_) => Vec::new(),
+        };
+        let action = describe_removal(&torrents, args.delete_files, ctx.instance);
+        if args.delete_files
+            && let Some(result) = ctx.confirm(&action).await?
+        {
+            return Ok(result);
+        }
+        ctx.client
+            .delete_torrents(&args.hash, args.delete_files)
+            .await?;
+        ctx.record_change(action, &torrents, None);
+        Ok(text_result("Torrent deleted successfully"))
+    }
+}
+
+pub struct GetTorrentFiles;
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct GetTorrentFilesArgs {
+    /// Torrent hash
+    hash: String,
+    /// Show sizes, speeds, times, progress and states in human-friendly form (default: server setting)
+    humanize: Option<bool>,
+    /// Only return these fields of each item
+    fields: Option<Vec<String>>,
+    /// Output format (json, compact, csv, markdown; default: json)
+    format: Option<OutputFormat>,
+    /// Truncate the output to about this many characters and return a cursor for the rest
+    max_output_chars: Option<usize>,
+    /// Cursor from a truncated result, to continue where it stopped
+    cursor: Option<String>,
+}
+
+#[async_trait]
+impl Tool for GetTorrentFiles {
+    type Args = GetTorrentFilesArgs;
+
+    fn name(&self) -> &str {
+        "get_torrent_files"
+    }
+
+    fn toolset(&self) -> &str {
+        "torrents"
+    }
+
+    fn description(&self) -> &str {
+        "Get file list of a torrent"
+    }
+
+    fn annotations(&self) -> ToolAnnotations {
+        ToolAnnotations::read_only()
+    }
+
+    async fn call(&self, ctx: &ToolContext<'_>, args: Self::Args) -> Result<Value> {
+        let files = ctx.client.get_torrent_files(&args.hash).await?;
+        let humanizer = ctx.server.humanizer_for(args.humanize);
+        let files: Vec<Value> = files.iter().map(|f| view(f, humanizer)).collect();
+        listing_result(
+            &files,
+            &OutputOptions {
+                fields: args.fields.as_deref(),
+                format: args.format,
+                max_output_chars: args.max_output_chars,
+                cursor: args.cursor.as_deref(),
+            },
+        )
+    }
+}
+
+pub struct InspectTorrent;
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct InspectTorrentArgs {
+    /// Torrent hash
+    hash: String,
+    /// Show sizes, speeds, times, progress and states in human-friendly form (default: server setting)
+    humanize: Option<bool>,
+}
+
+#[async_trait]
+impl Tool for InspectTorrent {
+    type Args = InspectTorrentArgs;
+
+    fn name(&self) -> &str {
+        "inspect_torrent"
+    }
+
+    fn toolset(&self) -> &str {
+        "torrents"
+    }
+
+    fn description(&self) -> &str {
+        "Comprehensive inspection of a torrent (properties, files, and trackers in one call)"
+    }
+
+    fn annotations(&self) -> ToolAnnotations {
+        ToolAnnotations::read_only()
+    }
+
+    async fn call(&self, ctx: &ToolContext<'_>, args: Self::Args) -> Result<Value> {
+        let client = ctx.client;
+        let properties = client.get_torrent_properties(&args.hash).await?;
+        let files = client.get_torrent_files(&args.hash).await?;
+        let trackers = client.get_torrent_trackers(&args.hash).await?;
+
+        let humanizer = ctx.server.humanizer_for(args.humanize);
+
+        json_result(&json!({
+            "properties": view(&properties, humanizer),
+            "files": view(&files, humanizer),
+            "trackers": trackers
+        }))
+    }
+}
+
+pub struct GetCategories;
+
+#[async_trait]
+impl Tool for GetCategories {
+    type Args = NoArgs;
+
+    fn name(&self) -> &str {
+        "get_categories"
+    }
+
+    fn toolset(&self) -> &str {
+        "torrents"
+    }
+
+    fn description(&self) -> &str {
+        "Get all categories"
+    }
+
+    fn annotations(&self) -> ToolAnnotations {
+        ToolAnnotations::read_only()
+    }
+
+    fn fans_out(&self) -> bool {
+        true
+    }
+
+    async fn call(&self, ctx: &ToolContext<'_>, _args: Self::Args) -> Result<Value> {
+        if !ctx.is_fan_out() {
+            let categories = ctx.client.get_categories().await?;
+            return json_result(&categories);
+        }
+
+        // A list sorted by name, since the same category usually exists on several instances
+        let fan_out = ctx.fan_out(|_, client| client.get_categories()).await?;
+        let mut categories: Vec<(&str, &Category)> = fan_out
+            .results
+            .iter()
+            .flat_map(|(instance, categories)| categories.values().map(move |c| (*instance, c)))
+            .collect();
+        categories.sort_by(|(a_instance, a), (b_instance, b)| {
+            a.name.cmp(&b.name).then(a_instance.cmp(b_instance))
+        });
+        let categories: Vec<Value> = categories
+            .into_iter()
+            .map(|(instance, c)| tag(instance, json!(c)))
+            .collect();
+        Ok(fan_out.report_failures(json_result(&categories)?))
+    }
+}
+
+pub struct WaitForTorrentStatus;
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct WaitForTorrentStatusArgs {
+    /// Torrent hash
+    hash: String,
+    /// Status to wait for (e.g., uploading, stalledUP)
+    target_status: String,
+    /// Max wait time (default 60, max 300)
+    timeout_seconds: Option<i64>,
+}
+
+#[async_trait]
+impl Tool for WaitForTorrentStatus {
+    type Args = WaitForTorrentStatusArgs;
+
+    fn name(&self) -> &str {
+        "wait_for_torrent_status"
+    }
+
+    fn toolset(&self) -> &str {
+        "torrents"
+    }
+
+    fn description(&self) -> &str {
+        "Poll a torrent until it reaches a desired state or timeout"
+    }
+
+    fn annotations(&self) -> ToolAnnotations {
+        ToolAnnotations::read_only()
+    }
+
+    async fn call(&self, ctx: &ToolContext<'_>, args: Self::Args) -> Result<Value> {
+        let target_status = args.target_status.as_str();
+        let timeout = args.timeout_seconds.unwrap_or(60).clamp(1, 300);
+
+        let start_time = std::time::Instant::now();
+        while start_time.elapsed().as_secs() < timeout as u64 {
+            let torrents = ctx.client.get_torrents_info(&args.hash).await?;
+            if let Some(t) = torrents.first() {
+                if t.state == target_status {
+                    return Ok(text_result(format!(
+                        "Torrent reached target status: {}",
+                        target_status
+                    )));
+                }
+                ctx.request.report_progress(
+                    start_time.elapsed().as_secs_f64(),
+                    Some(timeout as f64),
+                    format!("{}: {:.1}% ({})", t.name, t.progress * 100.0, t.state),
+                );
+            } else {
+                anyhow::bail!("Torrent not found: {}", args.hash);
+            }
+            sleep(Duration::from_secs(2)).await;
+        }
+        Ok(
+            json!({ "content": [{ "type": "text", "text": format!("Timed out waiting for status {}", target_status) }], "isError": true }),
+        )
+    }
+}
+
+pub struct CleanupCompleted;
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct CleanupCompletedArgs {
+    /// Minimum ratio to trigger removal
+    min_ratio: Option<f64>,
+    /// Maximum age in days since completion to trigger removal
+    max_age_days: Option<i64>,
+    /// Also delete downloaded files from disk
+    delete_files: bool,
+    /// Only return the torrents that would be removed, without removing anything
+    #[serde(default)]
+    dry_run: bool,
+}
+
+#[async_trait]
+impl Tool for CleanupCompleted {
+    type Args = CleanupCompletedArgs;
+
+    fn name(&self) -> &str {
+        "cleanup_completed"
+    }
+
+    fn toolset(&self) -> &str {
+        "maintenance"
+    }
+
+    fn description(&self) -> &str {
+        "Bulk remove completed torrents based on ratio or age. DESTRUCTIVE: Inform the user and confirm before calling."
+    }
+
+    fn annotations(&self) -> ToolAnnotations {
+        ToolAnnotations::destructive()
+    }
+
+    fn confirms(&self) -> bool {
+        true
+    }
+
+    async fn call(&self, ctx: &ToolContext<'_>, args: Self::Args) -> Result<Value> {
+        let client = ctx.client;
+        let (min_ratio, max_age_days, delete_files) =
+            (args.min_ratio, args.max_age_days, args.delete_files);
+
+        let torrents = client
+            .get_torrent_list(Some("completed"), None, None, None, None, None, None)
+            .await?;
+
+        let now = unix_now()?;
+
+        let to_delete: Vec<Torrent> = torrents
+            .into_iter()
+            .filter(|t| {
+                let mut should_delete = false;
+                if let Some(r) = min_ratio {
+                    should_delete = t.ratio >= r;
+                }
+                if let Some(age_days) = max_age_days {
+                    let age_secs = age_days * 24 * 3600;
+                    if t.completion_on > 0 && (now - t.completion_on) >= age_secs {
+                        should_delete = true;
+                    }
+                }
+                // If neither is specified, we don't delete anything automatically
+                // to avoid accidental wipes of all completed torrents.
+                // UNLESS the user explicitly wants to delete all completed.
+                // But let's require at least one condition for safety in this macro.
+                if min_ratio.is_none() && max_age_days.is_none() {
+                    return false;
+                }
+                should_delete
+            })
+            .collect();
+
+        ctx.record_torrents(&to_delete);
+        if args.dry_run {
+            return removal_plan(&to_delete, delete_files, ctx.instance);
+        }
+        if to_delete.is_empty() {
+            return Ok(text_result("No torrents matched the cleanup criteria."));
+        }
+
+        let action = describe_removal(&to_delete, delete_files, ctx.instance);
+        if let Some(result) = ctx.confirm(&action).await? {
+            return Ok(result);
+        }
+
+        let count = to_delete.len();
+        if ctx.request.progress_token().is_some() {
+            // Delete one at a time so the client can follow along.
+            for (i, t) in to_delete.iter().enumerate() {
+                if let Err(e) = client.delete_torrents(&t.hash, delete_files).await {
+                    // The ones already gone still belong in the journal
+                    let removed = &to_delete[..i];
+                    if !removed.is_empty() {
+                        ctx.record_change(
+                            describe_removal(removed, delete_files, ctx.instance),
+                            removed,
+                            None,
+                        );
+                    }
+                    return Err(e.context(format!(
+                        "Removed {} of {} torrents before '{}' failed",
+                        i, count, t.name
+                    )));
+                }
+                ctx.request.report_progress(
+                    (i + 1) as f64,
+                    Some(count as f64),
+                    format!("Removed '{}'", t.name),
+                );
+            }
+        } else {
+            let hashes: Vec<&str> = to_delete.iter().map(|t| t.hash.as_str()).collect();
+            client
+                .delete_torrents(&hashes.join("|"), delete_files)
+                .await?;
+        }
+
+        ctx.record_change(
+            describe_removal(&to_delete, delete_files, ctx.instance),
+            &to_delete,
+            None,
+        );
+        Ok(text_result(format!(
+            "Successfully cleaned up {} torrents.",
+            count
+        )))
+    }
+}
+
+pub struct MassRename;
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct MassRenameArgs {
+    /// Torrent hash
+    hash: String,
+    /// Regex pattern to match
+    pattern: String,
+    /// Replacement string (supports $1, $2, etc.)
+    replacement: String,
+    /// Only return the old and new paths, without renaming anything
+    #[serde(default)]
+    dry_run: bool,
+}
+
+#[async_trait]
+impl Tool for MassRename {
+    type Args = MassRenameArgs;
+
+    fn name(&self) -> &str {
+        "mass_rename"
+    }
+
+    fn toolset(&self) -> &str {
+        "maintenance"
+    }
+
+    fn description(&self) -> &str {
+        "Rename files in a torrent using a regex pattern"
+    }
+
+    fn annotations(&self) -> ToolAnnotations {
+        ToolAnnotations::additive()
+    }
+
+    async fn call(&self, ctx: &ToolContext<'_>, args: Self::Args) -> Result<Value> {
+        let hash = args.hash.as_str();
+        let re = Regex::new(&args.pattern).map_err(|e| {
+            invalid_argument(
+                "pattern",
+                "regular expression",
+                format!("Invalid pattern: {}", e),
+            )
+        })?;
+        let files: Vec<String> = ctx
+            .client
+            .get_torrent_files(hash)
+            .await?
+            .into_iter()
+            .map(|file| file.name)
+            .collect();
+
+        let renames: Vec<Rename> = files
+            .iter()
+            .filter(|name| re.is_match(name))
+            .filter_map(|name| {
+                let new_name = re.replace_all(name, &args.replacement).to_string();
+                (new_name != *name).then(|| (name.clone(), new_name))
+            })
+            .collect();
+
+        // Reject the whole plan up front rather than stopping halfway through it
+        let collisions = rename_collisions(&files, &renames);
+        if !collisions.is_empty() {
+            anyhow::bail!(
+                "Rename plan rejected, nothing was renamed. Several files would end up with the same path: {}",
+                collisions.join("; ")
+            );
+        }
+
+        // A file can only take a path once the file there has moved on
+        let (renames, stuck) = order_renames(renames);
+        if !stuck.is_empty() {
+            let cycle: Vec<String> = stuck
+                .iter()
+                .map(|(old, new)| format!("{} -> {}", old, new))
+                .collect();
+            anyhow::bail!(
+                "Rename plan rejected, nothing was renamed. These files would take each other's paths: {}",
+                cycle.join("; ")
+            );
+        }
+
+        if args.dry_run {
+            let plan: Vec<Value> = renames
+                .iter()
+                .map(|(old, new)| json!({ "old_path": old, "new_path": new }))
+                .collect();
+            return json_result(&json!({
+                "dry_run": true,
+                "count": plan.len(),
+                "renames": plan
+            }));
+        }
+
+        let total = renames.len();
+        let mut rename_count = 0;
+        for (old_name, new_name) in &renames {
+            ctx.client
+                .rename_file(hash, old_name, new_name)
+                .await
+                .map_err(|e| {
+                    e.context(format!(
+                        "Renamed {} of {} files before '{}' failed",
+                        rename_count, total, old_name
+                    ))
+                })?;
+            rename_count += 1;
+            ctx.request.report_progress(
+                rename_count as f64,
+                Some(total as f64),
+                format!("Renamed '{}' -> '{}'", old_name, new_name),
+            );
+        }
+
+        Ok(text_result(format!(
+            "Successfully renamed {} files.",
+            rename_count
+        )))
+    }
+}
+
+/// Describes every path that more than one file would have after `renames`, e.g.
+/// "a.mkv, b.mkv -> c.mkv". Files that are not renamed keep their path.
+fn rename_collisions(files: &[String], renames: &[Rename]) -> Vec<String> {
+    let renamed: HashMap<&str, &str> = renames
+        .iter()
+        .map(|(old, new)| (old.as_str(), new.as_str()))
+        .collect();
+    let mut targets: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    for file in files {
+        let target = renamed.get(file.as_str()).copied().unwrap_or(file);
+        targets.entry(target).or_default().push(file);
+    }
+    targets
+        .into_iter()
+        .filter(|(_, sources)| sources.len() > 1)
+        .map(|(target, sources)| format!("{} -> {}", sources.join(", "), target))
+        .collect()
+}
+
+/// An old and a new file path.
+type Rename = (String, String);
+
+/// Orders `renames` so that each one runs after the rename that frees its target path,
+/// e.g. "a.mkv -> a" before "a.mkv.mkv -> a.mkv". Returns the ordered renames and those
+/// that wait on each other in a cycle and cannot run one at a time.
+fn order_renames(renames: Vec<Rename>) -> (Vec<Rename>, Vec<Rename>) {
+    let mut ordered = Vec::with_capacity(renames.len());
+    let mut pending = renames;
+    loop {
+        let sources: HashSet<String> = pending.iter().map(|(old, _)| old.clone()).collect();
+        let (ready, blocked): (Vec<_>, Vec<_>) = pending
+            .into_iter()
+            .partition(|(_, new)| !sources.contains(new));
+        if ready.is_empty() {
+            return (ordered, blocked);
+        }
+        ordered.extend(ready);
+        pending = blocked;
+    }
+}
+
+pub struct FindDuplicates;
+
+#[async_trait]
+impl Tool for FindDuplicates {
+    type Args = NoArgs;
+
+    fn name(&self) -> &str {
+        "find_duplicates"
+    }
+
+    fn toolset(&self) -> &str {
+        "maintenance"
+    }
+
+    fn description(&self) -> &str {
+        "Find duplicate torrents by name"
+    }
+
+    fn annotations(&self) -> ToolAnnotations {
+        ToolAnnotations::read_only()
+    }
+
+    fn fans_out(&self) -> bool {
+        true
+    }
+
+    async fn call(&self, ctx: &ToolContext<'_>, _args: Self::Args) -> Result<Value> {
+        // Across instances, each torrent is tagged with the instance it is on
+        let (torrents, fan_out) = if ctx.is_fan_out() {
+            let fan_out = ctx.fan_out(|_, client| all_torrents(client)).await?;
+            let torrents: Vec<(Option<&str>, Torrent)> = fan_out
+                .results
+                .iter()
+                .flat_map(|(instance, torrents)| {
+                    torrents.iter().map(move |t| (Some(*instance), t.clone()))
+                })
+                .collect();
+            (torrents, Some(fan_out))
+        } else {
+            let torrents = all_torrents(ctx.client).await?;
+            (torrents.into_iter().map(|t| (None, t)).collect(), None)
+        };
+
+        let mut names: BTreeMap<String, Vec<(Option<&str>, Torrent)>> = BTreeMap::new();
+        for (instance, t) in torrents {
+            names.entry(t.name.clone()).or_default().push((instance, t));
+        }
+
+        let duplicates: Vec<Value> = names
+            .into_iter()
+            .filter(|(_, v)| v.len() > 1)
+            .map(|(name, v)| {
+                json!({
+                    "name": name,
+                    "count": v.len(),
+                    "torrents": v.into_iter().map(|(instance, t)| {
+                        let entry = json!({
+                            "hash": t.hash,
+                            "size": t.size_bytes,
+                            "progress": t.progress,
+                            "state": t.state
+                        });
+                        match instance {
+                            Some(instance) => tag(instance, entry),
+                            None => entry,
+                        }
+                    }).collect::<Vec<Value>>()
+                })
+            })
+            .collect();
+
+        let result = if duplicates.is_empty() {
+            text_result("No duplicate torrents found.")
+        } else {
+            json_result(&duplicates)?
+        };
+        Ok(match fan_out {
+            Some(fan_out) => fan_out.report_failures(result),
+            None => result,
+        })
+    }
+}
+
+async fn all_torrents(client: &QBitClient) -> Result<Vec<Torrent>> {
+    client
+        .get_torrent_list(None, None, None, None, None, None, None)
+        .await
+}
+
+pub struct AddTrackers;
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct AddTrackersArgs {
+    /// Torrent hashes (pipe-separated)
+    hashes: String,
+    /// URLs of the trackers (newline-separated)
+    urls: String,
+}
+
+#[async_trait]
+impl Tool for AddTrackers {
+    type Args = AddTrackersArgs;
+
+    fn name(&self) -> &str {
+        "add_trackers"
+    }
+
+    fn toolset(&self) -> &str {
+        "trackers"
+    }
+
+    fn description(&self) -> &str {
+        "Add trackers to torrents"
+    }
+
+    fn annotations(&self) -> ToolAnnotations {
+        ToolAnnotations::additive().idempotent()
+    }
+
+    async fn call(&self, ctx: &ToolContext<'_>, args: Self::Args) -> Result<Value> {
+        ctx.client.add_trackers(&args.hashes, &args.urls).await?;
+        Ok(text_result("Trackers added successfully"))
+    }
+}
+
+pub struct EditTracker;
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct EditTrackerArgs {
+    /// Torrent hash
+    hash: String,
+    /// Original tracker URL
+    orig_url: String,
+    /// New tracker URL
+    new_url: String,
+}
+
+#[async_trait]
+impl Tool for EditTracker {
+    type Args = EditTrackerArgs;
+
+    fn name(&self) -> &str {
+        "edit_tracker"
+    }
+
+    fn toolset(&self) -> &str {
+        "trackers"
+    }
+
+    fn description(&self) -> &str {
+        "Edit a tracker URL for a torrent"
+    }
+
+    fn annotations(&self) -> ToolAnnotations {
+        ToolAnnotations::additive()
+    }
+
+    async fn call(&self, ctx: &ToolContext<'_>, args: Self::Args) -> Result<Value> {
+        ctx.client
+            .edit_tracker(&args.hash, &args.orig_url, &args.new_url)
+            .await?;
+        Ok(text_result("Tracker edited successfully"))
+    }
+}
+
+pub struct RemoveTrackers;
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct RemoveTrackersArgs {
+    /// Torrent hashes (pipe-separated)
+    hashes: String,
+    /// URLs of the trackers to remove (newline-separated)
+    urls: String,
+}
+
+#[async_trait]
+impl Tool for RemoveTrackers {
+    type Args = RemoveTrackersArgs;
+
+    fn name(&self) -> &str {
+        "remove_trackers"
+    }
+
+    fn toolset(&self) -> &str {
+        "trackers"
+    }
+
+    fn description(&self) -> &str {
+        "Remove trackers from torrents"
+    }
+
+    fn annotations(&self) -> ToolAnnotations {
+        ToolAnnotations::destructive().idempotent()
+    }
+
+    async fn call(&self, ctx: &ToolContext<'_>, args: Self::Args) -> Result<Value> {
+        ctx.client.remove_trackers(&args.hashes, &args.urls).await?;
+        Ok(text_result("Trackers removed successfully"))
+    }
+}
+
+pub struct RenameFolder;
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct RenameFolderArgs {
+    /// Torrent hash
+    hash: String,
+    /// Current folder path
+    old_path: String,
+    /// New folder path
+    new_path: String,
+}
+
+#[async_trait]
+impl Tool for RenameFolder {
+    type Args = RenameFolderArgs;
+
+    fn name(&self) -> &str {
+        "rename_folder"
+    }
+
+    fn toolset(&self) -> &str {
+        "torrents"
+    }
+
+    fn description(&self) -> &str {
+        "Rename a folder in a torrent"
+    }
+
+    fn annotations(&self) -> ToolAnnotations {
+        ToolAnnotations::additive()
+    }
+
+    async fn call(&self, ctx: &ToolContext<'_>, args: Self::Args) -> Result<Value> {
+        ctx.client
+            .rename_folder(&args.hash, &args.old_path, &args.new_path)
+            .await?;
+        Ok(text_result("Folder renamed successfully"))
+    }
+}
+
+pub struct SetFilePriority;
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct SetFilePriorityArgs {
+    /// Torrent hash
+    hash: String,
+    /// File IDs (pipe-separated)
+    id: String,
+    /// Priority (0: Do not download, 1: Normal, 6: High, 7: Maximal)
+    priority: i32,
+}
+
+#[async_trait]
+impl Tool for SetFilePriority {
+    type Args = SetFilePriorityArgs;
+
+    fn name(&self) -> &str {
+        "set_file_priority"
+    }
+
+    fn toolset(&self) -> &str {
+        "torrents"
+    }
+
+    fn description(&self) -> &str {
+        "Set priority for files in a torrent"
+    }
+
+    fn annotations(&self) -> ToolAnnotations {
+        ToolAnnotations::additive().idempotent()
+    }
+
+    async fn call(&self, ctx: &ToolContext<'_>, args: Self::Args) -> Result<Value> {
+        ctx.client
+            .set_file_priority(&args.hash, &args.id, args.priority)
+            .await?;
+        Ok(text_result("File priority updated successfully"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rename(old: &str, new: &str) -> Rename {
+        (old.to_string(), new.to_string())
+    }
+
+    #[test]
+    fn test_order_renames() {
+        // a.mkv.mkv can only become a.mkv once a.mkv has become a
+        let (ordered, stuck) =
+            order_renames(vec![rename("a.mkv.mkv", "a.mkv"), rename("a.mkv", "a")]);
+        assert_eq!(
+            ordered,
+            vec![rename("a.mkv", "a"), rename("a.mkv.mkv", "a.mkv")]
+        );
+        assert!(stuck.is_empty());
+
+        let (ordered, stuck) =
+            order_renames(vec![rename("a", "b"), rename("b", "a"), rename("c", "d")]);
+        assert_eq!(ordered, vec![rename("c", "d")]);
+        assert_eq!(stuck, vec![rename("a", "b"), rename("b", "a")]);
+    }
+}
@@ -0,0 +1,45 @@
+use super::{Tool, ToolAnnotations, ToolContext, ToolRegistry, text_result};
+use anyhow::Result;
+use async_trait::async_trait;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::Value;
+
+pub(super) fn register(registry: &mut ToolRegistry) {
+    registry.register(BanPeers);
+}
+
+pub struct BanPeers;
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct BanPeersArgs {
+    /// Peers to ban (host:port, pipe-separated)
+    peers: String,
+}
+
+#[async_trait]
+impl Tool for BanPeers {
+    type Args = BanPeersArgs;
+
+    fn name(&self) -> &str {
+        "ban_peers"
+    }
+
+    fn toolset(&self) -> &str {
+        "transfer"
+    }
+
+    fn description(&self) -> &str {
+        "Ban a list of peers"
+    }
+
+    fn annotations(&self) -> ToolAnnotations {
+        ToolAnnotations::additive().idempotent()
+    }
+
+    async fn call(&self, ctx: &ToolContext<'_>, args: Self::Args) -> Result<Value> {
+        ctx.client.ban_peers(&args.peers).await?;
+        Ok(text_result("Peers banned successfully"))
+    }
+}
@@ -83,6 +83,7 @@ async fn test_run_app_no_instances_error() {
         log_rotate: "d".into(),
         http_auth_token: None,
         polling_interval_ms: 100,
+        ..Default::default()
     };
 
     let result = run_app(config, None).await;
@@ -104,6 +105,7 @@ async fn test_run_app_https_and_port() {
                 username: None,
                 password: None,
                 no_verify_ssl: Some(true),
+                ..Default::default()
             },
             qbittorrent_mcp_rs::config::QBitInstance {
                 name: "https_no_port".into(),
@@ -112,6 +114,7 @@ async fn test_run_app_https_and_port() {
                 username: None,
                 password: None,
                 no_verify_ssl: None,
+                ..Default::default()
             },
         ]),
         qbittorrent_host: "localhost".into(),
@@ -128,6 +131,7 @@ async fn test_run_app_https_and_port() {
         log_rotate: "d".into(),
         http_auth_token: None,
         polling_interval_ms: 100,
+        ..Default::default()
     };
 
     let (tx, rx) = oneshot::channel();
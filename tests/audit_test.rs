@@ -0,0 +1,121 @@
+mod common;
+
+use common::{result_json, tool_call};
+use qbittorrent_mcp_rs::client::QBitClient;
+use qbittorrent_mcp_rs::server::audit::AuditLog;
+use qbittorrent_mcp_rs::server::mcp::{McpServer, Peer, RequestContext, SessionInfo};
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use tracing_appender::rolling::Rotation;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+#[tokio::test]
+async fn test_audit_log_records_mutating_calls() {
+    let mock_server = MockServer::start().await;
+    let dir = tempfile::tempdir().unwrap();
+    let mut clients = HashMap::new();
+    clients.insert(
+        "default".to_string(),
+        QBitClient::new_no_auth(mock_server.uri(), false),
+    );
+    let audit = AuditLog::new(dir.path(), "audit.jsonl", Rotation::NEVER).unwrap();
+    let server = McpServer::new(clients, false).with_audit_log(audit);
+
+    let torrent = r#"[{"hash": "h1", "name": "Ubuntu ISO", "size": 1024, "progress": 1.0, "dlspeed": 0, "upspeed": 0, "priority": 0, "num_seeds": 1, "num_leechs": 0, "num_incomplete": 0, "num_complete": 1, "ratio": 2.5, "eta": 0, "state": "uploading", "added_on": 100, "completion_on": 200, "seq_dl": false, "f_l_piece_prio": false, "category": "", "tags": "", "super_seeding": false, "force_start": false}]"#;
+    Mock::given(method("GET"))
+        .and(path("/api/v2/torrents/info"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(torrent))
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/api/v2/torrents/delete"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/api/v2/app/setPreferences"))
+        .respond_with(ResponseTemplate::new(500))
+        .mount(&mock_server)
+        .await;
+
+    let (peer, _rx) = Peer::channel();
+    let peer = peer.with_session(SessionInfo {
+        transport: "http",
+        session_id: Some("session-1".to_string()),
+        identity: Some("alice".to_string()),
+        grant: None,
+    });
+    let call = |name: &str, args: Value| {
+        server.handle_request_with_context(tool_call(name, args), RequestContext::new(peer.clone()))
+    };
+
+    call(
+        "delete_torrent",
+        json!({ "hash": "h1", "delete_files": false }),
+    )
+    .await
+    .unwrap();
+    // Read-only tools are not audited
+    call("list_torrents", json!({})).await.unwrap();
+    let prefs = json!({ "web_ui_password": "hunter2" }).to_string();
+    let resp = call("set_app_preferences", json!({ "preferences": prefs }))
+        .await
+        .unwrap();
+    assert_eq!(resp["isError"], true);
+
+    let resp = call("get_audit_log", json!({})).await.unwrap();
+    let entries = result_json(&resp);
+    let entries = entries.as_array().unwrap();
+    assert_eq!(entries.len(), 2);
+
+    let delete = &entries[0];
+    assert_eq!(delete["tool"], "delete_torrent");
+    assert_eq!(delete["transport"], "http");
+    assert_eq!(delete["session_id"], "session-1");
+    assert_eq!(delete["identity"], "alice");
+    assert_eq!(delete["instance"], "default");
+    assert_eq!(delete["outcome"], "success");
+    assert_eq!(
+        delete["torrents"],
+        json!([{ "hash": "h1", "name": "Ubuntu ISO" }])
+    );
+
+    let prefs = &entries[1];
+    assert_eq!(prefs["outcome"], "error");
+    assert!(
+        !prefs["arguments"]["preferences"]
+            .as_str()
+            .unwrap()
+            .contains("hunter2")
+    );
+
+    // Filters
+    let resp = call("get_audit_log", json!({ "tool": "set_app_preferences" }))
+        .await
+        .unwrap();
+    assert_eq!(result_json(&resp).as_array().unwrap().len(), 1);
+    let resp = call("get_audit_log", json!({ "since": "2999-01-01T00:00:00Z" }))
+        .await
+        .unwrap();
+    assert_eq!(result_json(&resp).as_array().unwrap().len(), 0);
+
+    // The log itself is plain JSONL
+    let raw = std::fs::read_to_string(dir.path().join("audit.jsonl")).unwrap();
+    assert_eq!(raw.lines().count(), 2);
+}
+
+#[tokio::test]
+async fn test_get_audit_log_when_disabled() {
+    let mut clients = HashMap::new();
+    clients.insert(
+        "default".to_string(),
+        QBitClient::new_no_auth("http://localhost:1", false),
+    );
+    let server = McpServer::new(clients, false);
+    let resp = server
+        .handle_request(tool_call("get_audit_log", json!({})))
+        .await
+        .unwrap();
+    assert_eq!(resp["isError"], true);
+}
@@ -51,7 +51,14 @@ async fn test_tags_operations() -> Result<()> {
         .mount(&mock_server)
         .await;
 
+    Mock::given(method("GET"))
+        .and(path("/api/v2/torrents/tags"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!(["tag1", "tag2"])))
+        .mount(&mock_server)
+        .await;
+
     client.add_tags("hash1", "tag1,tag2").await?;
+    assert_eq!(client.get_tags().await?, vec!["tag1", "tag2"]);
 
     Ok(())
 }
@@ -1,6 +1,6 @@
 use anyhow::Result;
-use qbittorrent_mcp_rs::client::QBitClient;
-use wiremock::matchers::{method, path};
+use qbittorrent_mcp_rs::client::{AddTorrentOptions, QBitClient};
+use wiremock::matchers::{body_string_contains, method, path};
 use wiremock::{Mock, MockServer, ResponseTemplate};
 
 #[tokio::test]
@@ -60,3 +60,48 @@ async fn test_manage_torrents() -> Result<()> {
 
     Ok(())
 }
+
+#[tokio::test]
+async fn test_export_and_add_torrent_file() -> Result<()> {
+    let mock_server = MockServer::start().await;
+    let client = QBitClient::new_no_auth(mock_server.uri(), false);
+
+    Mock::given(method("POST"))
+        .and(path("/api/v2/torrents/export"))
+        .and(body_string_contains("hash=abc"))
+        .respond_with(ResponseTemplate::new(200).set_body_bytes(b"d4:infode".to_vec()))
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/api/v2/torrents/add"))
+        .and(body_string_contains("skip_checking"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("Ok."))
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/api/v2/torrents/add"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("Fails."))
+        .mount(&mock_server)
+        .await;
+
+    let file = client.export_torrent("abc").await?;
+    assert_eq!(file, b"d4:infode");
+
+    let options = AddTorrentOptions {
+        save_path: Some("/data".to_string()),
+        skip_checking: true,
+        ..Default::default()
+    };
+    client
+        .add_torrent_file(file.clone(), "abc.torrent", &options)
+        .await?;
+
+    // qBittorrent reports a rejected torrent in the body, not the status
+    let err = client
+        .add_torrent_file(file, "abc.torrent", &AddTorrentOptions::default())
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("rejected"));
+
+    Ok(())
+}
@@ -0,0 +1,19 @@
+//! Helpers shared by the integration tests. Each test crate uses only some of them.
+#![allow(dead_code)]
+
+use qbittorrent_mcp_rs::server::mcp::JsonRpcRequest;
+use serde_json::{Value, json};
+
+pub fn tool_call(name: &str, arguments: Value) -> JsonRpcRequest {
+    JsonRpcRequest {
+        jsonrpc: "2.0".to_string(),
+        method: "tools/call".to_string(),
+        params: Some(json!({ "name": name, "arguments": arguments })),
+        id: Some(json!(1)),
+    }
+}
+
+/// The JSON a tool returned as its text content.
+pub fn result_json(resp: &Value) -> Value {
+    serde_json::from_str(resp["content"][0]["text"].as_str().unwrap()).unwrap()
+}
@@ -0,0 +1,188 @@
+use qbittorrent_mcp_rs::app::build_instances;
+use qbittorrent_mcp_rs::config::AppConfig;
+use qbittorrent_mcp_rs::reload::Reloader;
+use qbittorrent_mcp_rs::server::mcp::{JsonRpcRequest, McpServer, Peer};
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::Duration;
+use tempfile::tempdir;
+use tokio::sync::mpsc::UnboundedReceiver;
+use wiremock::matchers::{body_string_contains, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+fn instance(name: &str, host: &str, extra: &str) -> String {
+    format!(
+        "[[instances]]\nname = \"{}\"\nhost = \"{}\"\n{}\n",
+        name, host, extra
+    )
+}
+
+fn write_config(path: &Path, top: &str, instances: &[String]) {
+    std::fs::write(path, format!("{}\n{}", top, instances.concat())).unwrap();
+}
+
+fn start(path: &Path) -> (McpServer, Reloader) {
+    let config = AppConfig::load(Some(path.to_str().unwrap().to_string()), vec![]).unwrap();
+    let server =
+        McpServer::new(HashMap::new(), false).with_instances(build_instances(&config, None));
+    let reloader = Reloader::new(server.clone(), config, Box::new(|_| {}));
+    (server, reloader)
+}
+
+async fn request(server: &McpServer, method: &str) -> Value {
+    server
+        .handle_request(JsonRpcRequest {
+            jsonrpc: "2.0".to_string(),
+            method: method.to_string(),
+            params: None,
+            id: Some(json!(1)),
+        })
+        .await
+        .unwrap()
+}
+
+fn notifications(mut rx: UnboundedReceiver<Value>) -> Vec<String> {
+    std::iter::from_fn(|| rx.try_recv().ok())
+        .map(|message| message["method"].as_str().unwrap().to_string())
+        .collect()
+}
+
+async fn resource_uris(server: &McpServer) -> Vec<String> {
+    request(server, "resources/list").await["resources"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|r| r["uri"].as_str().unwrap().to_string())
+        .collect()
+}
+
+#[tokio::test]
+async fn test_reload_adds_removes_and_reconnects_instances() {
+    let nas = MockServer::start().await;
+    let seedbox = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/api/v2/auth/login"))
+        .and(body_string_contains("password=rotated"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("Ok."))
+        .expect(1)
+        .mount(&nas)
+        .await;
+
+    let dir = tempdir().unwrap();
+    let file = dir.path().join("config.toml");
+    write_config(&file, "", &[instance("nas", &nas.uri(), "")]);
+    let (server, mut reloader) = start(&file);
+    // Every connected session hears about the reload
+    let sessions: Vec<_> = (0..2).map(|_| Peer::channel()).collect();
+    for (peer, _) in &sessions {
+        server.connect(peer);
+    }
+
+    // A new instance and read-only mode
+    write_config(
+        &file,
+        "read_only = true\npolling_interval_ms = 500",
+        &[
+            instance("nas", &nas.uri(), ""),
+            instance("seedbox", &seedbox.uri(), ""),
+        ],
+    );
+    let changes = reloader.reload().await.unwrap();
+    assert_eq!(changes.added, vec!["seedbox"]);
+    assert!(changes.removed.is_empty() && changes.changed.is_empty());
+    assert!(changes.policies_changed);
+    assert_eq!(reloader.config().polling_interval_ms, 500);
+    assert!(
+        resource_uris(&server)
+            .await
+            .contains(&"qbittorrent://seedbox/torrents".to_string())
+    );
+    for (_, rx) in sessions {
+        assert_eq!(
+            notifications(rx),
+            [
+                "notifications/resources/list_changed",
+                "notifications/tools/list_changed"
+            ]
+        );
+    }
+    let tools = request(&server, "tools/list").await;
+    let names: Vec<&str> = tools["tools"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|t| t["name"].as_str().unwrap())
+        .collect();
+    assert!(names.contains(&"list_torrents"));
+    assert!(!names.contains(&"delete_torrent"));
+
+    // New credentials for nas, seedbox gone
+    write_config(
+        &file,
+        "read_only = true\npolling_interval_ms = 500",
+        &[instance(
+            "nas",
+            &nas.uri(),
+            "username = \"admin\"\npassword = \"rotated\"",
+        )],
+    );
+    let changes = reloader.reload().await.unwrap();
+    assert_eq!(changes.changed, vec!["nas"]);
+    assert_eq!(changes.removed, vec!["seedbox"]);
+    assert!(!changes.policies_changed);
+    assert_eq!(server.instances().names(), vec!["nas"]);
+    assert!(
+        !resource_uris(&server)
+            .await
+            .iter()
+            .any(|uri| uri.contains("seedbox"))
+    );
+
+    // Nothing changed
+    let changes = reloader.reload().await.unwrap();
+    assert!(changes.is_empty());
+
+    // A configuration without instances is rejected and the current one kept
+    write_config(&file, "qbittorrent_host = \"\"", &[]);
+    assert!(reloader.reload().await.is_err());
+    assert_eq!(server.instances().names(), vec!["nas"]);
+}
+
+#[tokio::test]
+async fn test_config_file_is_watched() {
+    let nas = MockServer::start().await;
+    let dir = tempdir().unwrap();
+    let file = dir.path().join("config.toml");
+    write_config(&file, "", &[instance("nas", &nas.uri(), "")]);
+    let (server, reloader) = start(&file);
+    let watcher = tokio::spawn(
+        reloader
+            .with_watch_interval(Duration::from_millis(20))
+            .run(),
+    );
+    // Let the watcher take note of the file as it is
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    write_config(
+        &file,
+        "",
+        &[
+            instance("nas", &nas.uri(), ""),
+            instance("seedbox", &nas.uri(), ""),
+        ],
+    );
+    for _ in 0..100 {
+        if server.instances().names().len() == 2 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    assert_eq!(server.instances().names(), vec!["nas", "seedbox"]);
+
+    server.shutdown();
+    tokio::time::timeout(Duration::from_secs(1), watcher)
+        .await
+        .unwrap()
+        .unwrap();
+}
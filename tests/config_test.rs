@@ -83,8 +83,8 @@ fn test_cli_overrides_extended() {
     let config = AppConfig::load(None, args).expect("Failed to load config with extended CLI args");
 
     assert_eq!(config.qbittorrent_username, Some("admin".to_string()));
-    assert_eq!(config.qbittorrent_password, Some("password".to_string()));
-    assert_eq!(config.http_auth_token, Some("token".to_string()));
+    assert_eq!(config.qbittorrent_password, Some("password".into()));
+    assert_eq!(config.http_auth_token, Some("token".into()));
     assert_eq!(config.log_dir, "/tmp");
     assert_eq!(config.log_filename, "qbit.log");
     assert_eq!(config.log_rotate, "hourly");
@@ -0,0 +1,189 @@
+mod common;
+
+use common::{result_json, tool_call};
+use qbittorrent_mcp_rs::client::QBitClient;
+use qbittorrent_mcp_rs::server::mcp::{JsonRpcError, JsonRpcRequest, McpServer};
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+fn torrent(hash: &str, name: &str, size: i64) -> Value {
+    json!({
+        "hash": hash, "name": name, "size": size, "progress": 1.0, "dlspeed": 0, "upspeed": 0,
+        "priority": 0, "num_seeds": 1, "num_leechs": 0, "num_incomplete": 0, "num_complete": 1,
+        "ratio": 1.0, "eta": 0, "state": "uploading", "added_on": 100, "completion_on": 200,
+        "seq_dl": false, "f_l_piece_prio": false, "category": "", "tags": "",
+        "super_seeding": false, "force_start": false
+    })
+}
+
+async fn instance(torrents: Value, categories: Value) -> MockServer {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/api/v2/torrents/info"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(torrents))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/api/v2/torrents/categories"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(categories))
+        .mount(&server)
+        .await;
+    server
+}
+
+/// Instances "a" and "b" answer; "down" has nothing listening.
+async fn setup() -> (MockServer, MockServer, McpServer) {
+    let a = instance(
+        json!([torrent("a1", "Shared", 300), torrent("a2", "Only A", 100)]),
+        json!({ "movies": { "name": "movies", "savePath": "/a/movies" } }),
+    )
+    .await;
+    let b = instance(
+        json!([torrent("b1", "Shared", 200)]),
+        json!({
+            "movies": { "name": "movies", "savePath": "/b/movies" },
+            "music": { "name": "music", "savePath": "/b/music" }
+        }),
+    )
+    .await;
+    let mut clients = HashMap::new();
+    clients.insert("a".to_string(), QBitClient::new_no_auth(a.uri(), false));
+    clients.insert("b".to_string(), QBitClient::new_no_auth(b.uri(), false));
+    clients.insert(
+        "down".to_string(),
+        QBitClient::new_no_auth("http://127.0.0.1:1", false),
+    );
+    (a, b, McpServer::new(clients, false))
+}
+
+#[tokio::test]
+async fn test_list_torrents_across_all_instances() {
+    let (_a, _b, server) = setup().await;
+    let resp = server
+        .handle_request(tool_call(
+            "list_torrents",
+            json!({ "instance": "*", "sort": "size", "reverse": true, "fields": ["hash", "instance"] }),
+        ))
+        .await
+        .unwrap();
+    assert!(resp.get("isError").is_none());
+    let torrents: Value = result_json(&resp);
+    assert_eq!(
+        torrents,
+        json!([
+            { "hash": "a1", "instance": "a" },
+            { "hash": "b1", "instance": "b" },
+            { "hash": "a2", "instance": "a" }
+        ])
+    );
+    let note = resp["content"][1]["text"].as_str().unwrap();
+    assert!(note.contains("- down:"), "{}", note);
+
+    // limit and offset apply to the merged list
+    let resp = server
+        .handle_request(tool_call(
+            "list_torrents",
+            json!({ "instance": ["b", "a"], "sort": "size", "limit": 1, "offset": 1, "fields": ["hash"] }),
+        ))
+        .await
+        .unwrap();
+    let torrents: Value = result_json(&resp);
+    assert_eq!(torrents, json!([{ "hash": "b1" }]));
+    assert_eq!(resp["content"].as_array().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn test_find_duplicates_and_categories_across_instances() {
+    let (_a, _b, server) = setup().await;
+    let resp = server
+        .handle_request(tool_call(
+            "find_duplicates",
+            json!({ "instance": ["a", "b"] }),
+        ))
+        .await
+        .unwrap();
+    let duplicates: Value = result_json(&resp);
+    assert_eq!(duplicates[0]["name"], "Shared");
+    assert_eq!(duplicates[0]["count"], 2);
+    assert_eq!(duplicates[0]["torrents"][0]["instance"], "a");
+    assert_eq!(duplicates[0]["torrents"][1]["instance"], "b");
+
+    let resp = server
+        .handle_request(tool_call(
+            "get_categories",
+            json!({ "instance": ["a", "b"] }),
+        ))
+        .await
+        .unwrap();
+    let categories: Value = result_json(&resp);
+    assert_eq!(
+        categories,
+        json!([
+            { "instance": "a", "name": "movies", "savePath": "/a/movies" },
+            { "instance": "b", "name": "movies", "savePath": "/b/movies" },
+            { "instance": "b", "name": "music", "savePath": "/b/music" }
+        ])
+    );
+}
+
+#[tokio::test]
+async fn test_fan_out_errors() {
+    let (_a, _b, server) = setup().await;
+
+    // Only read tools that merge results accept several instances
+    let err = server
+        .handle_request(tool_call(
+            "delete_torrent",
+            json!({ "instance": "*", "hash": "a1", "delete_files": false }),
+        ))
+        .await
+        .unwrap_err();
+    let err = err.downcast::<JsonRpcError>().unwrap();
+    assert!(err.message.contains("list_torrents"), "{}", err.message);
+
+    let err = server
+        .handle_request(tool_call(
+            "list_torrents",
+            json!({ "instance": ["a", "nope"] }),
+        ))
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("Instance not found: nope"));
+
+    // Every instance failing fails the call
+    let resp = server
+        .handle_request(tool_call("list_torrents", json!({ "instance": ["down"] })))
+        .await
+        .unwrap();
+    assert_eq!(resp["isError"], true);
+    assert!(
+        resp["content"][0]["text"]
+            .as_str()
+            .unwrap()
+            .contains("No instance could be queried")
+    );
+
+    // The schema advertises the list form only where it works
+    let tools = server
+        .handle_request(JsonRpcRequest {
+            jsonrpc: "2.0".to_string(),
+            method: "tools/list".to_string(),
+            params: None,
+            id: Some(json!(2)),
+        })
+        .await
+        .unwrap();
+    let schema = |name: &str| {
+        tools["tools"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|t| t["name"] == name)
+            .unwrap()["inputSchema"]["properties"]["instance"]
+            .clone()
+    };
+    assert!(schema("list_torrents").get("anyOf").is_some());
+    assert_eq!(schema("delete_torrent")["type"], "string");
+}
@@ -1,12 +1,15 @@
 use anyhow::Result;
 use futures::StreamExt;
 use qbittorrent_mcp_rs::client::QBitClient;
-use qbittorrent_mcp_rs::server::http::{create_router, run_http_server};
+use qbittorrent_mcp_rs::server::auth::HttpAuth;
+use qbittorrent_mcp_rs::server::http::{create_router, create_router_with_auth, run_http_server};
 use qbittorrent_mcp_rs::server::mcp::McpServer;
 use serde_json::json;
 use std::collections::HashMap;
 use std::time::Duration;
 use tokio::time::timeout;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
 
 async fn setup_test_server(auth_token: Option<String>) -> (String, tokio::task::JoinHandle<()>) {
     let client = QBitClient::new("http://localhost:8080", "admin", "adminadmin", false);
@@ -83,6 +86,17 @@ async fn test_http_sse_session_and_message() -> Result<()> {
     assert!(second_chunk_str.contains("\"id\":1"));
     assert!(second_chunk_str.contains("\"jsonrpc\":\"2.0\""));
 
+    // 5. Closing the stream ends the session
+    let session_id = session_id.to_string();
+    drop(source);
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    let resp = client
+        .post(format!("{}/message?session_id={}", base_url, session_id))
+        .json(&req_body)
+        .send()
+        .await?;
+    assert_eq!(resp.status(), reqwest::StatusCode::NOT_FOUND);
+
     Ok(())
 }
 
@@ -113,6 +127,23 @@ async fn test_http_auth_query_param() -> Result<()> {
     let (base_url, _handle) = setup_test_server(Some(token.clone())).await;
     let client = reqwest::Client::new();
 
+    // The query parameter is not accepted unless enabled
+    let resp = client
+        .get(format!("{}/sse?token={}", base_url, token))
+        .send()
+        .await?;
+    assert_eq!(resp.status(), reqwest::StatusCode::UNAUTHORIZED);
+
+    let server = McpServer::new(HashMap::new(), false);
+    let app = create_router_with_auth(
+        server,
+        HttpAuth::shared(token.as_str()).with_query_token(true),
+    )
+    .await;
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+    let base_url = format!("http://{}", listener.local_addr()?);
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
     // Authorized via query param
     let resp = client
         .get(format!("{}/sse?token={}", base_url, token))
@@ -179,17 +210,16 @@ async fn test_http_malformed_json() -> Result<()> {
     let (base_url, _handle) = setup_test_server(None).await;
     let client = reqwest::Client::new();
 
-    let first_chunk_str = {
-        let mut source = client
-            .get(format!("{}/sse", base_url))
-            .send()
-            .await?
-            .bytes_stream();
-        let chunk = timeout(Duration::from_secs(2), source.next())
-            .await?
-            .ok_or_else(|| anyhow::anyhow!("Expected endpoint event"))??;
-        String::from_utf8_lossy(&chunk).to_string()
-    };
+    // The session lives as long as its stream
+    let mut source = client
+        .get(format!("{}/sse", base_url))
+        .send()
+        .await?
+        .bytes_stream();
+    let chunk = timeout(Duration::from_secs(2), source.next())
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("Expected endpoint event"))??;
+    let first_chunk_str = String::from_utf8_lossy(&chunk).to_string();
     let session_id = first_chunk_str.split("session_id=").last().unwrap().trim();
 
     let resp = client
@@ -199,7 +229,61 @@ async fn test_http_malformed_json() -> Result<()> {
         .send()
         .await?;
 
-    assert_ne!(resp.status(), reqwest::StatusCode::ACCEPTED);
+    assert_eq!(resp.status(), reqwest::StatusCode::BAD_REQUEST);
+    let body: serde_json::Value = resp.json().await?;
+    assert_eq!(body["error"]["code"], -32700);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_http_batch_request() -> Result<()> {
+    let (base_url, _handle) = setup_test_server(None).await;
+    let client = reqwest::Client::new();
+
+    let mut source = client
+        .get(format!("{}/sse", base_url))
+        .send()
+        .await?
+        .bytes_stream();
+    let first_chunk = timeout(Duration::from_secs(2), source.next())
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("Expected endpoint event"))??;
+    let first_chunk_str = String::from_utf8_lossy(&first_chunk);
+    let session_id = first_chunk_str.split("session_id=").last().unwrap().trim();
+
+    let batch = json!([
+        { "jsonrpc": "2.0", "id": 1, "method": "ping" },
+        { "jsonrpc": "2.0", "id": 2, "method": "invalid_method" }
+    ]);
+    let resp = client
+        .post(format!("{}/message?session_id={}", base_url, session_id))
+        .json(&batch)
+        .send()
+        .await?;
+    assert_eq!(resp.status(), reqwest::StatusCode::ACCEPTED);
+
+    let chunk = timeout(Duration::from_secs(2), source.next())
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("Expected message event"))??;
+    let chunk_str = String::from_utf8_lossy(&chunk);
+    let data = chunk_str
+        .lines()
+        .find_map(|l| l.strip_prefix("data: "))
+        .unwrap();
+    let responses: serde_json::Value = serde_json::from_str(data)?;
+    let responses = responses.as_array().unwrap();
+    assert_eq!(responses.len(), 2);
+    assert!(
+        responses
+            .iter()
+            .any(|r| r["id"] == 1 && r.get("result").is_some())
+    );
+    assert!(
+        responses
+            .iter()
+            .any(|r| r["id"] == 2 && r["error"]["code"] == -32601)
+    );
 
     Ok(())
 }
@@ -241,7 +325,7 @@ async fn test_http_mcp_error_response() -> Result<()> {
 
     assert!(second_chunk_str.contains("event: message"));
     assert!(second_chunk_str.contains("\"error\":"));
-    assert!(second_chunk_str.contains("\"code\":-32603"));
+    assert!(second_chunk_str.contains("\"code\":-32602"));
 
     Ok(())
 }
@@ -311,7 +395,7 @@ async fn test_http_sse_notification() -> Result<()> {
     let first_chunk_str = String::from_utf8_lossy(&first_chunk);
     let session_id = first_chunk_str.split("session_id=").last().unwrap().trim();
 
-    // 3. Call tool 'show_all_tools' which sets should_notify = true
+    // 3. Call tool 'show_all_tools', which changes this session's tool list
     let req_body = json!({
         "jsonrpc": "2.0",
         "id": 1,
@@ -331,7 +415,7 @@ async fn test_http_sse_notification() -> Result<()> {
         .ok_or_else(|| anyhow::anyhow!("Expected message event"))??;
 
     // 5. Expect 'message' event (notification)
-    // The server loop checks should_notify every 100ms
+    // Sent after the response that changed the tool list
     let third_chunk = timeout(Duration::from_secs(2), source.next())
         .await?
         .ok_or_else(|| anyhow::anyhow!("Expected notification event"))??;
@@ -342,3 +426,44 @@ async fn test_http_sse_notification() -> Result<()> {
 
     Ok(())
 }
+
+#[tokio::test]
+async fn test_http_sse_instance_status_notification() -> Result<()> {
+    let mock_server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/api/v2/auth/login"))
+        .respond_with(ResponseTemplate::new(503))
+        .mount(&mock_server)
+        .await;
+    let mut clients = HashMap::new();
+    clients.insert(
+        "nas".to_string(),
+        QBitClient::new(mock_server.uri(), "admin", "secret", false),
+    );
+    let server = McpServer::new(clients, false);
+    let app = create_router(server.clone(), None).await;
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+    let base_url = format!("http://{}", listener.local_addr()?);
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+    let mut source = reqwest::Client::new()
+        .get(format!("{}/sse", base_url))
+        .send()
+        .await?
+        .bytes_stream();
+    let _endpoint = timeout(Duration::from_secs(2), source.next()).await?;
+
+    // Not a reply to anything the session sent
+    server.login_all().await;
+    let chunk = timeout(Duration::from_secs(2), source.next())
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("Expected notification event"))??;
+    let chunk_str = String::from_utf8_lossy(&chunk);
+    assert!(
+        chunk_str.contains("notifications/instance_status"),
+        "{}",
+        chunk_str
+    );
+    assert!(chunk_str.contains("\"status\":\"down\""), "{}", chunk_str);
+    Ok(())
+}
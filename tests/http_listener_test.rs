@@ -0,0 +1,161 @@
+use anyhow::Result;
+use qbittorrent_mcp_rs::app::http_address;
+use qbittorrent_mcp_rs::config::AppConfig;
+use qbittorrent_mcp_rs::server::auth::HttpAuth;
+use qbittorrent_mcp_rs::server::http::{HttpAddress, HttpListener, serve_http};
+use qbittorrent_mcp_rs::server::mcp::McpServer;
+use qbittorrent_mcp_rs::server::tls::{TlsFiles, TlsListener};
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::Duration;
+use tempfile::tempdir;
+use tokio_rustls::rustls::pki_types::CertificateDer;
+use tokio_rustls::rustls::pki_types::pem::PemObject;
+
+const TLS_DIR: &str = "tests/resources/tls";
+
+fn copy_pair(name: &str, dir: &Path) {
+    std::fs::copy(format!("{}/{}.crt", TLS_DIR, name), dir.join("server.crt")).unwrap();
+    std::fs::copy(format!("{}/{}.key", TLS_DIR, name), dir.join("server.key")).unwrap();
+}
+
+/// The certificate the server at `url` presents, DER encoded.
+async fn peer_certificate(url: &str) -> Result<Vec<u8>> {
+    // A new client each time, so that no pooled connection hides a reload
+    let client = reqwest::Client::builder()
+        .tls_danger_accept_invalid_certs(true)
+        .tls_info(true)
+        .build()?;
+    let resp = client.get(url).send().await?;
+    assert_eq!(resp.status(), reqwest::StatusCode::OK);
+    let info = resp
+        .extensions()
+        .get::<reqwest::tls::TlsInfo>()
+        .expect("TLS info");
+    Ok(info.peer_certificate().unwrap().to_vec())
+}
+
+fn der(name: &str) -> Vec<u8> {
+    CertificateDer::from_pem_file(format!("{}/{}.crt", TLS_DIR, name))
+        .unwrap()
+        .to_vec()
+}
+
+#[tokio::test]
+async fn test_https_and_certificate_reload() -> Result<()> {
+    let dir = tempdir()?;
+    copy_pair("first", dir.path());
+    let files = TlsFiles {
+        cert: dir.path().join("server.crt"),
+        key: dir.path().join("server.key"),
+    };
+
+    let tcp = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+    let listener = TlsListener::with_watch_interval(tcp, files, Duration::from_millis(20))?;
+    let port = axum::serve::Listener::local_addr(&listener)?.port();
+    let server = McpServer::new(HashMap::new(), false);
+    tokio::spawn(serve_http(
+        server,
+        HttpListener::Tls(listener),
+        HttpAuth::default(),
+    ));
+
+    let url = format!("https://127.0.0.1:{}/sse", port);
+    assert_eq!(peer_certificate(&url).await?, der("first"));
+
+    // A renewed certificate is served without a restart
+    copy_pair("second", dir.path());
+    let mut served = Vec::new();
+    for _ in 0..100 {
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        served = peer_certificate(&url).await?;
+        if served == der("second") {
+            break;
+        }
+    }
+    assert_eq!(served, der("second"));
+
+    // A broken certificate is rejected and the last good one kept
+    std::fs::write(dir.path().join("server.crt"), "not a certificate")?;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(peer_certificate(&url).await?, der("second"));
+
+    // Plain HTTP is not served on the TLS port
+    let plain = reqwest::get(format!("http://127.0.0.1:{}/sse", port)).await;
+    assert!(plain.is_err());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_invalid_tls_files_fail_at_bind() {
+    let address = HttpAddress::Tcp {
+        host: "127.0.0.1".into(),
+        port: 0,
+        tls: Some(TlsFiles {
+            cert: "/nonexistent/server.crt".into(),
+            key: "/nonexistent/server.key".into(),
+        }),
+    };
+    let err = HttpListener::bind(&address).await.err().unwrap();
+    assert!(format!("{:#}", err).contains("Failed to read certificate"));
+}
+
+#[test]
+fn test_http_address_from_config() {
+    let config = AppConfig::load(None, vec![]).unwrap();
+    assert_eq!(
+        http_address(&config).unwrap(),
+        HttpAddress::Tcp {
+            host: "0.0.0.0".into(),
+            port: 3000,
+            tls: None
+        }
+    );
+    assert_eq!(
+        http_address(&config).unwrap().to_string(),
+        "http://0.0.0.0:3000"
+    );
+
+    let config = AppConfig {
+        http_tls_cert: Some("cert.pem".into()),
+        ..config
+    };
+    assert!(http_address(&config).is_err());
+
+    let config = AppConfig {
+        http_unix_socket: Some("/run/mcp.sock".into()),
+        ..config
+    };
+    assert_eq!(
+        http_address(&config).unwrap(),
+        HttpAddress::Unix("/run/mcp.sock".into())
+    );
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_unix_socket() -> Result<()> {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let dir = tempdir()?;
+    let path = dir.path().join("mcp.sock");
+    // A stale socket from an earlier run is replaced
+    drop(std::os::unix::net::UnixListener::bind(&path)?);
+    let listener = HttpListener::bind(&HttpAddress::Unix(path.clone())).await?;
+    let server = McpServer::new(HashMap::new(), false);
+    tokio::spawn(serve_http(server, listener, HttpAuth::default()));
+
+    let mut stream = tokio::net::UnixStream::connect(&path).await?;
+    stream
+        .write_all(b"GET /sse HTTP/1.1\r\nHost: localhost\r\n\r\n")
+        .await?;
+    let mut buf = vec![0; 4096];
+    let mut received = String::new();
+    while !received.contains("event: endpoint") {
+        let n = tokio::time::timeout(Duration::from_secs(2), stream.read(&mut buf)).await??;
+        assert!(n > 0, "connection closed: {}", received);
+        received.push_str(&String::from_utf8_lossy(&buf[..n]));
+    }
+    assert!(received.starts_with("HTTP/1.1 200"), "{}", received);
+    Ok(())
+}
@@ -0,0 +1,471 @@
+use anyhow::Result;
+use futures::StreamExt;
+use qbittorrent_mcp_rs::app::http_auth;
+use qbittorrent_mcp_rs::client::QBitClient;
+use qbittorrent_mcp_rs::config::AppConfig;
+use qbittorrent_mcp_rs::server::audit::{AuditEntry, AuditLog, now_rfc3339};
+use qbittorrent_mcp_rs::server::auth::{Grant, HttpAuth, Scope};
+use qbittorrent_mcp_rs::server::http::create_router_with_auth;
+use qbittorrent_mcp_rs::server::journal::Journal;
+use qbittorrent_mcp_rs::server::mcp::{McpServer, Peer, SessionInfo};
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tempfile::tempdir;
+use time::OffsetDateTime;
+use tokio::time::timeout;
+use tracing_appender::rolling::Rotation;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+fn grant(name: &str, scopes: &[Scope], instances: Option<&[&str]>) -> Grant {
+    Grant {
+        name: name.into(),
+        scopes: scopes.iter().copied().collect(),
+        instances: instances.map(|i| i.iter().map(|s| s.to_string()).collect()),
+        expires: None,
+    }
+}
+
+fn peer_with(grant: Grant) -> Peer {
+    let (peer, _rx) = Peer::channel();
+    peer.with_session(SessionInfo {
+        transport: "http",
+        session_id: Some("test".into()),
+        identity: Some(grant.name.clone()),
+        grant: Some(Arc::new(grant)),
+    })
+}
+
+async fn request(server: &McpServer, peer: &Peer, method: &str, params: Value) -> Value {
+    let message = json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params });
+    let resp = server.handle_message(message, peer).await.unwrap();
+    resp.get("result")
+        .cloned()
+        .unwrap_or_else(|| resp["error"].clone())
+}
+
+/// Two instances, `nas` and `seedbox`, that both answer torrent listings.
+async fn two_instances() -> (McpServer, MockServer) {
+    let mock_server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/api/v2/torrents/info"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("[]"))
+        .mount(&mock_server)
+        .await;
+    let mut clients = HashMap::new();
+    for name in ["nas", "seedbox"] {
+        clients.insert(
+            name.to_string(),
+            QBitClient::new_no_auth(mock_server.uri(), false),
+        );
+    }
+    (McpServer::new(clients, false), mock_server)
+}
+
+#[tokio::test]
+async fn test_scopes_limit_tools() {
+    let (server, _mock) = two_instances().await;
+    let reader = peer_with(grant("dashboard", &[Scope::Read], None));
+
+    let tools = request(&server, &reader, "tools/list", json!({})).await;
+    let names: Vec<&str> = tools["tools"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|t| t["name"].as_str().unwrap())
+        .collect();
+    assert!(names.contains(&"list_torrents"));
+    assert!(!names.contains(&"delete_torrent"));
+    assert!(!names.contains(&"shutdown_app"));
+
+    let res = request(
+        &server,
+        &reader,
+        "tools/call",
+        json!({ "name": "list_torrents", "arguments": { "instance": "nas" } }),
+    )
+    .await;
+    assert!(res.get("isError").is_none(), "{}", res);
+
+    let res = request(
+        &server,
+        &reader,
+        "tools/call",
+        json!({ "name": "delete_torrent", "arguments": { "instance": "nas", "hashes": "abc" } }),
+    )
+    .await;
+    assert_eq!(res["isError"], true);
+    let text = res["content"][0]["text"].as_str().unwrap();
+    assert!(text.contains("lacks the write scope"), "{}", text);
+
+    // Write does not include admin
+    let writer = peer_with(grant("automation", &[Scope::Read, Scope::Write], None));
+    let res = request(
+        &server,
+        &writer,
+        "tools/call",
+        json!({ "name": "shutdown_app", "arguments": { "instance": "nas" } }),
+    )
+    .await;
+    assert_eq!(res["isError"], true);
+    let text = res["content"][0]["text"].as_str().unwrap();
+    assert!(text.contains("lacks the admin scope"), "{}", text);
+}
+
+#[tokio::test]
+async fn test_instance_list_limits_tools_and_resources() {
+    let (server, _mock) = two_instances().await;
+    let peer = peer_with(grant("nas-only", &[Scope::Read], Some(&["nas"])));
+
+    let res = request(
+        &server,
+        &peer,
+        "tools/call",
+        json!({ "name": "list_torrents", "arguments": { "instance": "seedbox" } }),
+    )
+    .await;
+    assert_eq!(res["isError"], true);
+    let text = res["content"][0]["text"].as_str().unwrap();
+    assert!(text.contains("not allowed on this instance"), "{}", text);
+
+    let resources = request(&server, &peer, "resources/list", json!({})).await;
+    let uris: Vec<&str> = resources["resources"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|r| r["uri"].as_str().unwrap())
+        .collect();
+    assert!(uris.contains(&"qbittorrent://nas/torrents"));
+    assert!(!uris.iter().any(|uri| uri.contains("seedbox")));
+
+    let res = request(
+        &server,
+        &peer,
+        "resources/read",
+        json!({ "uri": "qbittorrent://nas/torrents" }),
+    )
+    .await;
+    assert!(res.get("contents").is_some(), "{}", res);
+    let res = request(
+        &server,
+        &peer,
+        "resources/read",
+        json!({ "uri": "qbittorrent://seedbox/torrents" }),
+    )
+    .await;
+    assert!(
+        res["message"]
+            .as_str()
+            .unwrap()
+            .contains("not allowed on this instance"),
+        "{}",
+        res
+    );
+
+    // Prompts embed the instance's data too
+    let res = request(
+        &server,
+        &peer,
+        "prompts/get",
+        json!({ "name": "analyze_disk_space", "arguments": { "instance": "seedbox" } }),
+    )
+    .await;
+    assert!(
+        res["message"]
+            .as_str()
+            .unwrap()
+            .contains("not allowed on this instance"),
+        "{}",
+        res
+    );
+
+    let status = request(
+        &server,
+        &peer,
+        "resources/read",
+        json!({ "uri": "qbittorrent://instances/status" }),
+    )
+    .await;
+    let status: Value =
+        serde_json::from_str(status["contents"][0]["text"].as_str().unwrap()).unwrap();
+    let instances: Vec<&str> = status
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|s| s["instance"].as_str().unwrap())
+        .collect();
+    assert_eq!(instances, vec!["nas"]);
+}
+
+#[tokio::test]
+async fn test_completion_respects_instances() {
+    let mock_server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/api/v2/torrents/info"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([{
+            "hash": "a".repeat(40), "name": "Secret ISO", "size": 1, "progress": 1.0,
+            "dlspeed": 0, "upspeed": 0, "priority": 0, "num_seeds": 0, "num_leechs": 0,
+            "num_incomplete": 0, "num_complete": 0, "ratio": 0.0, "eta": 0,
+            "state": "uploading", "added_on": 0, "completion_on": 0, "seq_dl": false,
+            "f_l_piece_prio": false, "category": "", "tags": "", "super_seeding": false,
+            "force_start": false
+        }])))
+        .mount(&mock_server)
+        .await;
+    let mut clients = HashMap::new();
+    for name in ["nas", "seedbox"] {
+        clients.insert(
+            name.to_string(),
+            QBitClient::new_no_auth(mock_server.uri(), false),
+        );
+    }
+    let server = McpServer::new(clients, false);
+    let peer = peer_with(grant("nas-only", &[Scope::Read], Some(&["nas"])));
+    let complete = |name: &str, instance: &str| {
+        json!({
+            "ref": { "type": "ref/prompt", "name": "troubleshoot_torrent" },
+            "argument": { "name": name, "value": "" },
+            "context": { "arguments": { "instance": instance } }
+        })
+    };
+
+    let res = request(
+        &server,
+        &peer,
+        "completion/complete",
+        complete("hash", "nas"),
+    )
+    .await;
+    assert_eq!(res["completion"]["values"], json!(["a".repeat(40)]));
+
+    // Nothing from instances the token may not read, not even their names
+    let res = request(
+        &server,
+        &peer,
+        "completion/complete",
+        complete("hash", "seedbox"),
+    )
+    .await;
+    assert_eq!(res["completion"]["values"], json!([]));
+    let res = request(
+        &server,
+        &peer,
+        "completion/complete",
+        complete("instance", ""),
+    )
+    .await;
+    assert_eq!(res["completion"]["values"], json!(["nas"]));
+}
+
+#[tokio::test]
+async fn test_recent_actions_need_admin_and_respect_instances() {
+    let mut clients = HashMap::new();
+    for name in ["default", "seedbox"] {
+        clients.insert(
+            name.to_string(),
+            QBitClient::new_no_auth("http://localhost:1", false),
+        );
+    }
+    let journal = Journal::in_memory(10);
+    journal.record("default", "manage_torrents", "pause".into(), &[], None);
+    journal.record("seedbox", "manage_torrents", "resume".into(), &[], None);
+    let server = McpServer::new(clients, false).with_journal(journal);
+    let call = json!({ "name": "list_recent_actions", "arguments": {} });
+
+    let writer = peer_with(grant("automation", &[Scope::Read, Scope::Write], None));
+    let res = request(&server, &writer, "tools/call", call.clone()).await;
+    assert_eq!(res["isError"], true);
+    let text = res["content"][0]["text"].as_str().unwrap();
+    assert!(text.contains("lacks the admin scope"), "{}", text);
+
+    // Without an instance argument, entries of other instances are left out
+    let admin = peer_with(grant(
+        "default-admin",
+        &[Scope::Read, Scope::Admin],
+        Some(&["default"]),
+    ));
+    let res = request(&server, &admin, "tools/call", call).await;
+    let entries: Value = serde_json::from_str(res["content"][0]["text"].as_str().unwrap()).unwrap();
+    let instances: Vec<&str> = entries
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|e| e["instance"].as_str().unwrap())
+        .collect();
+    assert_eq!(instances, vec!["default"]);
+}
+
+#[tokio::test]
+async fn test_audit_log_respects_instances() {
+    let dir = tempdir().unwrap();
+    let audit = AuditLog::new(dir.path(), "audit.jsonl", Rotation::NEVER).unwrap();
+    for instance in ["default", "seedbox"] {
+        audit.record(&AuditEntry {
+            timestamp: now_rfc3339(),
+            transport: "http".into(),
+            session_id: Some("other".into()),
+            identity: Some("someone".into()),
+            instance: instance.into(),
+            tool: "delete_torrent".into(),
+            arguments: json!({ "hash": "abc" }),
+            torrents: Vec::new(),
+            outcome: "success".into(),
+            error: None,
+        });
+    }
+    let mut clients = HashMap::new();
+    for name in ["default", "seedbox"] {
+        clients.insert(
+            name.to_string(),
+            QBitClient::new_no_auth("http://localhost:1", false),
+        );
+    }
+    let server = McpServer::new(clients, false).with_audit_log(audit);
+
+    // Without an instance argument, entries of other instances are left out
+    let admin = peer_with(grant(
+        "default-admin",
+        &[Scope::Read, Scope::Admin],
+        Some(&["default"]),
+    ));
+    let call = json!({ "name": "get_audit_log", "arguments": {} });
+    let res = request(&server, &admin, "tools/call", call).await;
+    let entries: Value = serde_json::from_str(res["content"][0]["text"].as_str().unwrap()).unwrap();
+    let instances: Vec<&str> = entries
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|e| e["instance"].as_str().unwrap())
+        .collect();
+    assert_eq!(instances, vec!["default"]);
+}
+
+/// Opens an SSE session with `token` and returns its message URL.
+async fn open_session(client: &reqwest::Client, base_url: &str, token: &str) -> Result<String> {
+    let resp = client
+        .get(format!("{}/sse", base_url))
+        .bearer_auth(token)
+        .send()
+        .await?;
+    assert_eq!(resp.status(), reqwest::StatusCode::OK);
+    let mut events = resp.bytes_stream();
+    let chunk = timeout(Duration::from_secs(2), events.next())
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("Expected endpoint event"))??;
+    let chunk = String::from_utf8_lossy(&chunk).to_string();
+    let endpoint = chunk.split("data: ").last().unwrap().trim();
+    // Keep the stream open for the rest of the test
+    tokio::spawn(async move { while events.next().await.is_some() {} });
+    Ok(format!("{}{}", base_url, endpoint))
+}
+
+#[tokio::test]
+async fn test_http_tokens_and_sessions() -> Result<()> {
+    let (server, _mock) = two_instances().await;
+    let expired = Grant {
+        expires: Some(OffsetDateTime::now_utc() - time::Duration::hours(1)),
+        ..grant("old", &[Scope::Read], None)
+    };
+    let auth = HttpAuth::default()
+        .with_token("reader-token", grant("reader", &[Scope::Read], None))
+        .with_token("writer-token", grant("writer", &[Scope::Write], None))
+        .with_token("old-token", expired);
+    let app = create_router_with_auth(server, auth).await;
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+    let base_url = format!("http://{}", listener.local_addr()?);
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    let client = reqwest::Client::new();
+
+    for token in ["old-token", "unknown"] {
+        let resp = client
+            .get(format!("{}/sse", base_url))
+            .bearer_auth(token)
+            .send()
+            .await?;
+        assert_eq!(resp.status(), reqwest::StatusCode::UNAUTHORIZED);
+    }
+
+    // A session only accepts messages from the token that opened it
+    let message_url = open_session(&client, &base_url, "reader-token").await?;
+    let ping = json!({ "jsonrpc": "2.0", "id": 1, "method": "ping" });
+    let resp = client
+        .post(&message_url)
+        .bearer_auth("writer-token")
+        .json(&ping)
+        .send()
+        .await?;
+    assert_eq!(resp.status(), reqwest::StatusCode::FORBIDDEN);
+    let resp = client
+        .post(&message_url)
+        .bearer_auth("reader-token")
+        .json(&ping)
+        .send()
+        .await?;
+    assert_eq!(resp.status(), reqwest::StatusCode::ACCEPTED);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_http_tokens_from_config() {
+    let dir = tempdir().unwrap();
+    let token_file = dir.path().join("token");
+    std::fs::write(&token_file, "file-token\n").unwrap();
+    let file = dir.path().join("config.toml");
+    std::fs::write(
+        &file,
+        format!(
+            r#"
+http_auth_token = "shared-token"
+
+[[http_tokens]]
+name = "dashboard"
+token = "dashboard-token"
+scopes = ["read"]
+instances = ["default"]
+
+[[http_tokens]]
+name = "automation"
+token_file = "{}"
+scopes = ["read", "write"]
+expires = "2999-01-01T00:00:00Z"
+"#,
+            token_file.display()
+        ),
+    )
+    .unwrap();
+    let config = AppConfig::load(Some(file.to_str().unwrap().to_string()), vec![]).unwrap();
+    let auth = http_auth(&config).unwrap();
+    assert!(!auth.allows_query_token());
+
+    let dashboard = auth.authenticate(Some("dashboard-token")).await.unwrap();
+    assert_eq!(dashboard.scopes, [Scope::Read].into());
+    assert_eq!(dashboard.instances, Some(["default".to_string()].into()));
+    let automation = auth.authenticate(Some("file-token")).await.unwrap();
+    assert_eq!(automation.name, "automation");
+    assert!(automation.expires.is_some());
+    let shared = auth.authenticate(Some("shared-token")).await.unwrap();
+    assert_eq!(shared.scopes.len(), 3);
+
+    // Names must be unique and expiries valid
+    let config = AppConfig {
+        http_tokens: config.http_tokens.clone().map(|mut tokens| {
+            tokens[1].name = "dashboard".into();
+            tokens
+        }),
+        ..config.clone()
+    };
+    let err = http_auth(&config).err().unwrap();
+    assert!(err.to_string().contains("more than once"), "{}", err);
+    let config = AppConfig {
+        http_tokens: config.http_tokens.clone().map(|mut tokens| {
+            tokens[1].name = "automation".into();
+            tokens[1].expires = Some("next year".into());
+            tokens
+        }),
+        ..config
+    };
+    let err = http_auth(&config).err().unwrap();
+    assert!(err.to_string().contains("RFC 3339"), "{}", err);
+}
@@ -0,0 +1,127 @@
+use qbittorrent_mcp_rs::client::QBitClient;
+use qbittorrent_mcp_rs::server::mcp::{JsonRpcRequest, McpServer};
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::time::Duration;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+async fn read_status(server: &McpServer) -> Vec<Value> {
+    let res = server
+        .handle_request(JsonRpcRequest {
+            jsonrpc: "2.0".to_string(),
+            method: "resources/read".to_string(),
+            params: Some(json!({ "uri": "qbittorrent://instances/status" })),
+            id: Some(json!(1)),
+        })
+        .await
+        .unwrap();
+    serde_json::from_str(res["contents"][0]["text"].as_str().unwrap()).unwrap()
+}
+
+#[tokio::test]
+async fn test_login_is_retried_for_instances_down_at_startup() {
+    let mock_server = MockServer::start().await;
+    // Down at startup, fine afterwards
+    Mock::given(method("POST"))
+        .and(path("/api/v2/auth/login"))
+        .respond_with(ResponseTemplate::new(503))
+        .up_to_n_times(1)
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/api/v2/auth/login"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("Ok."))
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/api/v2/sync/maindata"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_json(json!({ "rid": 1, "full_update": true })),
+        )
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/api/v2/app/webapiVersion"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("2.11.2"))
+        .mount(&mock_server)
+        .await;
+
+    let mut clients = HashMap::new();
+    clients.insert(
+        "nas".to_string(),
+        QBitClient::new(mock_server.uri(), "admin", "secret", false),
+    );
+    let server = McpServer::new(clients, false);
+
+    server.login_all().await;
+    let status = read_status(&server).await;
+    assert_eq!(status[0]["instance"], "nas");
+    assert_eq!(status[0]["status"], "down");
+    assert_eq!(status[0]["login"], "failed");
+    assert_eq!(status[0]["consecutive_failures"], 1);
+    assert_eq!(status[0]["default"], true);
+    assert!(
+        status[0]["last_error"]
+            .as_str()
+            .unwrap()
+            .contains("Failed to login")
+    );
+
+    // The first retry comes after a second
+    server.start_event_loop(50);
+    tokio::time::sleep(Duration::from_millis(1500)).await;
+    server.shutdown();
+
+    let res = server
+        .call_tool("list_instances", &json!({}))
+        .await
+        .unwrap();
+    let status: Vec<Value> =
+        serde_json::from_str(res["content"][0]["text"].as_str().unwrap()).unwrap();
+    assert_eq!(status[0]["status"], "up");
+    assert_eq!(status[0]["login"], "logged_in");
+    assert_eq!(status[0]["api_version"], "2.11.2");
+    assert_eq!(status[0]["consecutive_failures"], 0);
+    assert!(status[0]["latency_ms"].is_u64());
+    assert!(status[0]["last_success"].is_string());
+}
+
+#[tokio::test]
+async fn test_unreachable_instance_is_reported() {
+    let mut clients = HashMap::new();
+    clients.insert(
+        "offline".to_string(),
+        QBitClient::new_no_auth("http://127.0.0.1:1", false),
+    );
+    let server = McpServer::new(clients, false);
+
+    let status = read_status(&server).await;
+    assert_eq!(status[0]["status"], "unknown");
+    assert_eq!(status[0]["login"], "not_required");
+
+    let err = server
+        .call_tool("list_torrents", &json!({}))
+        .await
+        .unwrap_err();
+    assert!(
+        format!("{:#}", err).contains("qBittorrent instance 'offline' is unreachable"),
+        "{:#}",
+        err
+    );
+
+    let status = read_status(&server).await;
+    assert_eq!(status[0]["status"], "down");
+    assert_eq!(status[0]["consecutive_failures"], 1);
+
+    let res = server
+        .handle_request(JsonRpcRequest {
+            jsonrpc: "2.0".to_string(),
+            method: "resources/list".to_string(),
+            params: None,
+            id: Some(json!(2)),
+        })
+        .await
+        .unwrap();
+    assert_eq!(res["resources"][0]["uri"], "qbittorrent://instances/status");
+}
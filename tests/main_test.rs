@@ -7,7 +7,7 @@ fn test_config_instances_mapping() {
         qbittorrent_host: "localhost".to_string(),
         qbittorrent_port: Some(8080),
         qbittorrent_username: Some("admin".to_string()),
-        qbittorrent_password: Some("password".to_string()),
+        qbittorrent_password: Some("password".into()),
         server_mode: "stdio".to_string(),
         lazy_mode: false,
         no_verify_ssl: false,
@@ -18,6 +18,7 @@ fn test_config_instances_mapping() {
         log_rotate: "hourly".to_string(), // Trigger branch
         http_auth_token: None,
         polling_interval_ms: 2000,
+        ..Default::default()
     };
 
     let instances = config.get_instances();
@@ -31,6 +32,7 @@ fn test_config_instances_mapping() {
         username: None,
         password: None,
         no_verify_ssl: None,
+        ..Default::default()
     }]);
     let instances2 = config.get_instances();
     assert_eq!(instances2.len(), 1);
@@ -48,6 +50,7 @@ fn test_config_instances_url_parsing() {
                 username: None,
                 password: None,
                 no_verify_ssl: None,
+                ..Default::default()
             },
             qbittorrent_mcp_rs::config::QBitInstance {
                 name: "test2".to_string(),
@@ -56,6 +59,7 @@ fn test_config_instances_url_parsing() {
                 username: None,
                 password: None,
                 no_verify_ssl: None,
+                ..Default::default()
             },
         ]),
         qbittorrent_host: "localhost".to_string(),
@@ -72,6 +76,7 @@ fn test_config_instances_url_parsing() {
         log_rotate: "daily".to_string(),
         http_auth_token: None,
         polling_interval_ms: 2000,
+        ..Default::default()
     };
 
     let instances = config.get_instances();
@@ -96,6 +101,7 @@ fn test_config_various_log_rotations() {
         log_rotate: "never".to_string(), // Test 'never'
         http_auth_token: None,
         polling_interval_ms: 2000,
+        ..Default::default()
     };
 
     // We can't easily test init_logging directly as it calls .init() which can only be called once.
@@ -119,6 +125,7 @@ fn test_config_instances_complex_urls() {
                 username: None,
                 password: None,
                 no_verify_ssl: Some(true),
+                ..Default::default()
             },
             qbittorrent_mcp_rs::config::QBitInstance {
                 name: "test2".into(),
@@ -127,6 +134,7 @@ fn test_config_instances_complex_urls() {
                 username: Some("u".into()),
                 password: Some("p".into()),
                 no_verify_ssl: None,
+                ..Default::default()
             },
         ]),
         qbittorrent_host: "localhost".to_string(),
@@ -143,6 +151,7 @@ fn test_config_instances_complex_urls() {
         log_rotate: "daily".to_string(),
         http_auth_token: Some("secret".into()),
         polling_interval_ms: 500,
+        ..Default::default()
     };
 
     let instances = config.get_instances();
@@ -161,6 +170,7 @@ fn test_config_instances_mapping_various() {
                 username: Some("u1".into()),
                 password: Some("p1".into()),
                 no_verify_ssl: None,
+                ..Default::default()
             },
             qbittorrent_mcp_rs::config::QBitInstance {
                 name: "t2".into(),
@@ -169,6 +179,7 @@ fn test_config_instances_mapping_various() {
                 username: None,
                 password: None,
                 no_verify_ssl: Some(false),
+                ..Default::default()
             },
         ]),
         qbittorrent_host: "h".into(),
@@ -185,6 +196,7 @@ fn test_config_instances_mapping_various() {
         log_rotate: "d".into(),
         http_auth_token: None,
         polling_interval_ms: 100,
+        ..Default::default()
     };
 
     let instances = config.get_instances();
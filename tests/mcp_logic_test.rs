@@ -1,8 +1,15 @@
+mod common;
+
+use common::{result_json, tool_call};
 use qbittorrent_mcp_rs::client::QBitClient;
-use qbittorrent_mcp_rs::server::mcp::{JsonRpcRequest, McpServer};
+use qbittorrent_mcp_rs::server::auth::{Grant, Scope};
+use qbittorrent_mcp_rs::server::mcp::{
+    JsonRpcError, JsonRpcRequest, McpServer, Peer, RequestContext, SessionInfo, is_cancelled,
+};
 use serde_json::json;
 use std::collections::HashMap;
-use wiremock::matchers::{method, path, query_param};
+use std::sync::Arc;
+use wiremock::matchers::{body_string_contains, method, path, query_param};
 use wiremock::{Mock, MockServer, ResponseTemplate};
 
 async fn setup_mock_server() -> (MockServer, McpServer) {
@@ -94,6 +101,92 @@ async fn test_handle_wait_for_torrent_status_timeout() {
     assert_eq!(resp["isError"], true);
 }
 
+#[tokio::test]
+async fn test_handle_wait_for_torrent_status_progress() {
+    let (mock_server, server) = setup_mock_server().await;
+
+    let mock_torrents = r#"[
+        {"hash": "abc", "name": "Ubuntu", "size": 2048, "progress": 0.5, "dlspeed": 500, "upspeed": 10, "priority": 1, "num_seeds": 5, "num_leechs": 2, "num_incomplete": 2, "num_complete": 5, "ratio": 0.1, "eta": 60, "state": "downloading", "added_on": 1500000000, "completion_on": 0, "seq_dl": true, "f_l_piece_prio": false, "category": "linux", "tags": "os", "super_seeding": false, "force_start": false}
+    ]"#;
+
+    Mock::given(method("GET"))
+        .and(path("/api/v2/torrents/info"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(mock_torrents))
+        .mount(&mock_server)
+        .await;
+
+    let (peer, mut rx) = Peer::channel();
+    let req = JsonRpcRequest {
+        jsonrpc: "2.0".to_string(),
+        method: "tools/call".to_string(),
+        params: Some(json!({
+            "name": "wait_for_torrent_status",
+            "arguments": {
+                "hash": "abc",
+                "target_status": "uploading",
+                "timeout_seconds": 1
+            },
+            "_meta": { "progressToken": "wait-1" }
+        })),
+        id: Some(json!(1)),
+    };
+
+    server
+        .handle_request_with_context(req, RequestContext::new(peer))
+        .await
+        .unwrap();
+
+    let notification = rx.try_recv().expect("Expected a progress notification");
+    assert_eq!(notification["method"], "notifications/progress");
+    assert_eq!(notification["params"]["progressToken"], "wait-1");
+    assert_eq!(notification["params"]["total"], 1.0);
+    assert!(
+        notification["params"]["message"]
+            .as_str()
+            .unwrap()
+            .contains("Ubuntu: 50.0% (downloading)")
+    );
+}
+
+#[tokio::test]
+async fn test_handle_mass_rename_progress_without_token() {
+    let (mock_server, server) = setup_mock_server().await;
+
+    let mock_files = r#"[
+        {"index": 0, "name": "movie.mp4", "size": 1000, "progress": 1.0, "priority": 1, "availability": 1.0}
+    ]"#;
+
+    Mock::given(method("GET"))
+        .and(path("/api/v2/torrents/files"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(mock_files))
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path("/api/v2/torrents/renameFile"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&mock_server)
+        .await;
+
+    // No progressToken in _meta: nothing should be emitted
+    let (peer, mut rx) = Peer::channel();
+    let req = JsonRpcRequest {
+        jsonrpc: "2.0".to_string(),
+        method: "tools/call".to_string(),
+        params: Some(json!({
+            "name": "mass_rename",
+            "arguments": { "hash": "abc", "pattern": "movie", "replacement": "film" }
+        })),
+        id: Some(json!(1)),
+    };
+
+    server
+        .handle_request_with_context(req, RequestContext::new(peer))
+        .await
+        .unwrap();
+    assert!(rx.try_recv().is_err());
+}
+
 #[tokio::test]
 async fn test_handle_cleanup_completed_ratio() {
     let (mock_server, server) = setup_mock_server().await;
@@ -134,6 +227,71 @@ async fn test_handle_cleanup_completed_ratio() {
     assert!(text.contains("Successfully cleaned up 1 torrents"));
 }
 
+#[tokio::test]
+async fn test_cleanup_completed_partial_failure_is_journaled() {
+    let (mock_server, server) = setup_mock_server().await;
+
+    let mock_torrents = r#"[
+        {"hash": "h1", "name": "T1", "size": 1024, "progress": 1.0, "dlspeed": 0, "upspeed": 0, "priority": 0, "num_seeds": 1, "num_leechs": 0, "num_incomplete": 0, "num_complete": 1, "ratio": 2.5, "eta": 0, "state": "uploading", "added_on": 100, "completion_on": 200, "seq_dl": false, "f_l_piece_prio": false, "category": "", "tags": "", "super_seeding": false, "force_start": false},
+        {"hash": "h2", "name": "T2", "size": 1024, "progress": 1.0, "dlspeed": 0, "upspeed": 0, "priority": 0, "num_seeds": 1, "num_leechs": 0, "num_incomplete": 0, "num_complete": 1, "ratio": 2.5, "eta": 0, "state": "uploading", "added_on": 100, "completion_on": 200, "seq_dl": false, "f_l_piece_prio": false, "category": "", "tags": "", "super_seeding": false, "force_start": false}
+    ]"#;
+
+    Mock::given(method("GET"))
+        .and(path("/api/v2/torrents/info"))
+        .and(query_param("filter", "completed"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(mock_torrents))
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/api/v2/torrents/delete"))
+        .and(body_string_contains("hashes=h1"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/api/v2/torrents/delete"))
+        .and(body_string_contains("hashes=h2"))
+        .respond_with(ResponseTemplate::new(500))
+        .mount(&mock_server)
+        .await;
+
+    // With a progress token, torrents are removed one at a time
+    let (peer, _rx) = Peer::channel();
+    let req = JsonRpcRequest {
+        jsonrpc: "2.0".to_string(),
+        method: "tools/call".to_string(),
+        params: Some(json!({
+            "name": "cleanup_completed",
+            "arguments": { "min_ratio": 2.0, "delete_files": true },
+            "_meta": { "progressToken": "cleanup-1" }
+        })),
+        id: Some(json!(1)),
+    };
+    let resp = server
+        .handle_request_with_context(req, RequestContext::new(peer))
+        .await
+        .unwrap();
+    assert_eq!(resp["isError"], true);
+    let text = resp["content"][0]["text"].as_str().unwrap();
+    assert!(
+        text.contains("Removed 1 of 2 torrents before 'T2' failed"),
+        "{}",
+        text
+    );
+
+    // The torrent that is already gone is in the journal
+    let resp = server
+        .handle_request(tool_call("list_recent_actions", json!({})))
+        .await
+        .unwrap();
+    let entries = result_json(&resp);
+    let entries = entries.as_array().unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0]["tool"], "cleanup_completed");
+    assert_eq!(entries[0]["torrents"][0]["hash"], "h1");
+    assert_eq!(entries[0]["torrents"].as_array().unwrap().len(), 1);
+}
+
 #[tokio::test]
 async fn test_handle_cleanup_completed_none() {
     let (mock_server, server) = setup_mock_server().await;
@@ -404,3 +562,1037 @@ async fn test_handle_search_torrents_lifecycle() {
     let resp = server.handle_request(req).await.unwrap();
     assert!(resp["content"][0]["text"].as_str().unwrap().contains("f1"));
 }
+
+#[tokio::test]
+async fn test_cancel_search_torrents_cleans_up_job() {
+    let (mock_server, server) = setup_mock_server().await;
+
+    Mock::given(method("POST"))
+        .and(path("/api/v2/search/start"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({"id": 7})))
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path("/api/v2/search/results"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "results": [],
+            "status": "Running",
+            "total": 0
+        })))
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path("/api/v2/search/stop"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path("/api/v2/search/delete"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let (peer, _rx) = Peer::channel();
+    let search = {
+        let server = server.clone();
+        let ctx = RequestContext::new(peer.clone());
+        tokio::spawn(async move {
+            let req = JsonRpcRequest {
+                jsonrpc: "2.0".to_string(),
+                method: "tools/call".to_string(),
+                params: Some(json!({
+                    "name": "search_torrents",
+                    "arguments": { "query": "linux" }
+                })),
+                id: Some(json!("search-1")),
+            };
+            server.handle_request_with_context(req, ctx).await
+        })
+    };
+
+    tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+
+    let cancel = JsonRpcRequest {
+        jsonrpc: "2.0".to_string(),
+        method: "notifications/cancelled".to_string(),
+        params: Some(json!({ "requestId": "search-1", "reason": "user aborted" })),
+        id: None,
+    };
+    server
+        .handle_request_with_context(cancel, RequestContext::new(peer))
+        .await
+        .unwrap();
+
+    let result = tokio::time::timeout(std::time::Duration::from_secs(1), search)
+        .await
+        .expect("search should stop promptly after cancellation")
+        .unwrap();
+    assert!(result.is_err_and(|e| is_cancelled(&e)));
+
+    // Give the cleanup task a moment to stop and delete the job
+    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+}
+
+#[tokio::test]
+async fn test_cancel_request_waiting_for_a_slot() {
+    let (mock_server, server) = setup_mock_server().await;
+    Mock::given(method("GET"))
+        .and(path("/api/v2/torrents/info"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_string("[]")
+                .set_delay(std::time::Duration::from_millis(500)),
+        )
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let (peer, _rx) = Peer::channel();
+    let peer = peer.with_limit(1);
+    let list = |id: &str| {
+        let server = server.clone();
+        let ctx = RequestContext::new(peer.clone());
+        let req = JsonRpcRequest {
+            jsonrpc: "2.0".to_string(),
+            method: "tools/call".to_string(),
+            params: Some(json!({ "name": "list_torrents", "arguments": {} })),
+            id: Some(json!(id)),
+        };
+        tokio::spawn(async move { server.handle_request_with_context(req, ctx).await })
+    };
+    let running = list("running");
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    let queued = list("queued");
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+    // The queued request is known, so it can be cancelled before it gets the slot
+    let cancel = JsonRpcRequest {
+        jsonrpc: "2.0".to_string(),
+        method: "notifications/cancelled".to_string(),
+        params: Some(json!({ "requestId": "queued" })),
+        id: None,
+    };
+    server
+        .handle_request_with_context(cancel, RequestContext::new(peer.clone()))
+        .await
+        .unwrap();
+    let result = tokio::time::timeout(std::time::Duration::from_millis(200), queued)
+        .await
+        .expect("a queued request should stop at once")
+        .unwrap();
+    assert!(result.is_err_and(|e| is_cancelled(&e)));
+    assert!(running.await.unwrap().is_ok());
+}
+
+#[tokio::test]
+async fn test_tool_errors_and_invalid_params() {
+    let (mock_server, server) = setup_mock_server().await;
+
+    Mock::given(method("GET"))
+        .and(path("/api/v2/torrents/info"))
+        .respond_with(ResponseTemplate::new(500))
+        .mount(&mock_server)
+        .await;
+
+    let call = |name: &str, arguments: serde_json::Value| JsonRpcRequest {
+        jsonrpc: "2.0".to_string(),
+        method: "tools/call".to_string(),
+        params: Some(json!({ "name": name, "arguments": arguments })),
+        id: Some(json!(1)),
+    };
+
+    // Upstream failures are tool results the model can read, not protocol errors
+    let resp = server
+        .handle_request(call("list_torrents", json!({})))
+        .await
+        .unwrap();
+    assert_eq!(resp["isError"], true);
+    assert!(resp["content"][0]["text"].as_str().unwrap().contains("500"));
+
+    // Missing and mistyped arguments say which argument is wrong
+    let err = server
+        .handle_request(call("delete_torrent", json!({})))
+        .await
+        .unwrap_err();
+    let rpc = err.downcast_ref::<JsonRpcError>().unwrap();
+    assert_eq!(rpc.code, -32602);
+    assert_eq!(rpc.message, "Missing hash");
+    assert_eq!(
+        rpc.data,
+        Some(json!({ "argument": "hash", "expected": "string" }))
+    );
+
+    let err = server
+        .handle_request(call("list_torrents", json!({ "limit": "ten" })))
+        .await
+        .unwrap_err();
+    let rpc = err.downcast_ref::<JsonRpcError>().unwrap();
+    assert_eq!(rpc.code, -32602);
+    assert_eq!(rpc.data.as_ref().unwrap()["argument"], "limit");
+    assert_eq!(rpc.data.as_ref().unwrap()["expected"], "integer");
+
+    let err = server
+        .handle_request(call("no_such_tool", json!({})))
+        .await
+        .unwrap_err();
+    assert_eq!(err.downcast_ref::<JsonRpcError>().unwrap().code, -32602);
+
+    let err = server
+        .handle_request(JsonRpcRequest {
+            jsonrpc: "2.0".to_string(),
+            method: "no/such/method".to_string(),
+            params: None,
+            id: Some(json!(2)),
+        })
+        .await
+        .unwrap_err();
+    assert_eq!(err.downcast_ref::<JsonRpcError>().unwrap().code, -32601);
+}
+
+#[tokio::test]
+async fn test_delete_torrent_elicitation() {
+    let (mock_server, server) = setup_mock_server().await;
+    let torrent = r#"[
+        {"hash": "abc", "name": "Ubuntu", "size": 3221225472, "progress": 1.0, "dlspeed": 0, "upspeed": 0, "priority": 1, "num_seeds": 5, "num_leechs": 2, "num_incomplete": 2, "num_complete": 5, "ratio": 2.0, "eta": 0, "state": "uploading", "added_on": 1500000000, "completion_on": 1500001000, "seq_dl": false, "f_l_piece_prio": false, "category": "linux", "tags": "", "super_seeding": false, "force_start": false}
+    ]"#;
+    Mock::given(method("GET"))
+        .and(path("/api/v2/torrents/info"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(torrent))
+        .mount(&mock_server)
+        .await;
+    // Only the accepted attempt may reach qBittorrent
+    Mock::given(method("POST"))
+        .and(path("/api/v2/torrents/delete"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let (peer, mut rx) = Peer::channel();
+    peer.set_client_capabilities(json!({ "elicitation": {} }));
+
+    for (answer, deleted) in [
+        (json!({ "action": "decline" }), false),
+        // Accepting without ticking confirm is no go-ahead either
+        (json!({ "action": "accept", "content": {} }), false),
+        (
+            json!({ "action": "accept", "content": { "confirm": true } }),
+            true,
+        ),
+    ] {
+        let call = server.handle_request_with_context(
+            tool_call(
+                "delete_torrent",
+                json!({ "hash": "abc", "delete_files": true }),
+            ),
+            RequestContext::new(peer.clone()),
+        );
+        let respond = async {
+            let request = rx.recv().await.unwrap();
+            assert_eq!(request["method"], "elicitation/create");
+            assert_eq!(
+                request["params"]["message"],
+                "The assistant wants to delete torrent 'Ubuntu', 3.0 GiB of data on instance default. Proceed?"
+            );
+            assert!(peer.resolve(&json!({
+                "jsonrpc": "2.0",
+                "id": request["id"],
+                "result": answer
+            })));
+        };
+        let (resp, _) = tokio::join!(call, respond);
+        let text = resp.unwrap()["content"][0]["text"]
+            .as_str()
+            .unwrap()
+            .to_string();
+        assert_eq!(text.starts_with("Aborted"), !deleted, "{}", text);
+    }
+
+    // A connection that goes away before answering declines
+    let call = server.handle_request_with_context(
+        tool_call(
+            "delete_torrent",
+            json!({ "hash": "abc", "delete_files": true }),
+        ),
+        RequestContext::new(peer.clone()),
+    );
+    let disconnect = async {
+        rx.recv().await.unwrap();
+        peer.fail_pending();
+    };
+    let (resp, _) = tokio::join!(call, disconnect);
+    let text = resp.unwrap()["content"][0]["text"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    assert!(text.starts_with("Aborted"), "{}", text);
+}
+
+#[tokio::test]
+async fn test_shutdown_requires_confirm_token() {
+    let (mock_server, server) = setup_mock_server().await;
+    let server = server.with_require_confirmation(true);
+    Mock::given(method("POST"))
+        .and(path("/api/v2/app/shutdown"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    // First call only hands out a token
+    let resp = server
+        .handle_request(tool_call("shutdown_app", json!({})))
+        .await
+        .unwrap();
+    let token = resp["confirm"].as_str().unwrap().to_string();
+    assert!(
+        resp["content"][0]["text"]
+            .as_str()
+            .unwrap()
+            .contains("shut down qBittorrent on instance default")
+    );
+
+    let resp = server
+        .handle_request(tool_call("shutdown_app", json!({ "confirm": token })))
+        .await
+        .unwrap();
+    assert_eq!(resp["content"][0]["text"], "Shutdown command sent");
+
+    // Tokens are single use
+    let err = server
+        .handle_request(tool_call("shutdown_app", json!({ "confirm": token })))
+        .await
+        .unwrap_err();
+    assert_eq!(err.downcast_ref::<JsonRpcError>().unwrap().code, -32602);
+}
+
+#[tokio::test]
+async fn test_initialize_negotiates_protocol_version() {
+    let (_mock_server, server) = setup_mock_server().await;
+    let initialize = |version: &str| JsonRpcRequest {
+        jsonrpc: "2.0".to_string(),
+        method: "initialize".to_string(),
+        params: Some(json!({ "protocolVersion": version, "capabilities": {} })),
+        id: Some(json!(1)),
+    };
+
+    let resp = server
+        .handle_request(initialize("2024-11-05"))
+        .await
+        .unwrap();
+    assert_eq!(resp["protocolVersion"], "2024-11-05");
+
+    let resp = server
+        .handle_request(initialize("1999-01-01"))
+        .await
+        .unwrap();
+    assert_eq!(resp["protocolVersion"], "2025-06-18");
+}
+
+#[tokio::test]
+async fn test_completion_complete() {
+    let (mock_server, server) = setup_mock_server().await;
+    let torrents: Vec<serde_json::Value> = (0..150)
+        .map(|i| {
+            json!({"hash": format!("{:040x}", i), "name": format!("Linux ISO {}", i), "size": 1, "progress": 1.0, "dlspeed": 0, "upspeed": 0, "priority": 0, "num_seeds": 0, "num_leechs": 0, "num_incomplete": 0, "num_complete": 0, "ratio": 0.0, "eta": 0, "state": "uploading", "added_on": 0, "completion_on": 0, "seq_dl": false, "f_l_piece_prio": false, "category": "", "tags": "", "super_seeding": false, "force_start": false})
+        })
+        .chain(std::iter::once(
+            json!({"hash": "f".repeat(40), "name": "Ubuntu 24.04", "size": 1, "progress": 1.0, "dlspeed": 0, "upspeed": 0, "priority": 0, "num_seeds": 0, "num_leechs": 0, "num_incomplete": 0, "num_complete": 0, "ratio": 0.0, "eta": 0, "state": "uploading", "added_on": 0, "completion_on": 0, "seq_dl": false, "f_l_piece_prio": false, "category": "", "tags": "", "super_seeding": false, "force_start": false}),
+        ))
+        .collect();
+    Mock::given(method("GET"))
+        .and(path("/api/v2/torrents/info"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(torrents))
+        .mount(&mock_server)
+        .await;
+
+    let complete = |reference: serde_json::Value, name: &str, value: &str| JsonRpcRequest {
+        jsonrpc: "2.0".to_string(),
+        method: "completion/complete".to_string(),
+        params: Some(json!({
+            "ref": reference,
+            "argument": { "name": name, "value": value },
+            "context": { "arguments": { "instance": "default" } }
+        })),
+        id: Some(json!(1)),
+    };
+    let prompt = json!({ "type": "ref/prompt", "name": "troubleshoot_torrent" });
+    let template = json!({
+        "type": "ref/resource",
+        "uri": "qbittorrent://{instance}/torrent/{hash}/files"
+    });
+
+    // Torrent names map to hashes
+    let resp = server
+        .handle_request(complete(prompt.clone(), "hash", "ubuntu"))
+        .await
+        .unwrap();
+    assert_eq!(resp["completion"]["values"], json!(["f".repeat(40)]));
+
+    // Capped at 100 values
+    let resp = server
+        .handle_request(complete(template.clone(), "hash", "linux"))
+        .await
+        .unwrap();
+    assert_eq!(resp["completion"]["values"].as_array().unwrap().len(), 100);
+    assert_eq!(resp["completion"]["total"], 150);
+    assert_eq!(resp["completion"]["hasMore"], true);
+
+    let resp = server
+        .handle_request(complete(template, "instance", "def"))
+        .await
+        .unwrap();
+    assert_eq!(resp["completion"]["values"], json!(["default"]));
+
+    // Arguments the prompt does not have are rejected
+    let err = server
+        .handle_request(complete(prompt, "nope", ""))
+        .await
+        .unwrap_err();
+    assert_eq!(err.downcast_ref::<JsonRpcError>().unwrap().code, -32602);
+}
+
+#[tokio::test]
+async fn test_logging_set_level_forwards_messages() {
+    use qbittorrent_mcp_rs::server::logging::{McpLogLayer, register_secret};
+    use tracing_subscriber::layer::SubscriberExt;
+
+    let (_mock_server, server) = setup_mock_server().await;
+    let (peer, mut rx) = Peer::channel();
+    let set_level = JsonRpcRequest {
+        jsonrpc: "2.0".to_string(),
+        method: "logging/setLevel".to_string(),
+        params: Some(json!({ "level": "warning" })),
+        id: Some(json!(1)),
+    };
+    server
+        .handle_request_with_context(set_level, RequestContext::new(peer))
+        .await
+        .unwrap();
+    register_secret("s3cr3t-pass");
+
+    let subscriber = tracing_subscriber::registry().with(McpLogLayer::filtered());
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::info!(target: "qbittorrent_mcp_rs::server::mcp", "Below the selected level");
+        tracing::error!(
+            target: "qbittorrent_mcp_rs::server::mcp",
+            "Polling error for instance nas: login with s3cr3t-pass rejected"
+        );
+        tracing::error!(target: "hyper::proto", "Not one of ours");
+    });
+
+    let message = rx.try_recv().expect("Expected a log notification");
+    assert_eq!(message["method"], "notifications/message");
+    assert_eq!(message["params"]["level"], "error");
+    assert_eq!(
+        message["params"]["logger"],
+        "qbittorrent_mcp_rs::server::mcp"
+    );
+    assert_eq!(
+        message["params"]["data"],
+        "Polling error for instance nas: login with [REDACTED] rejected"
+    );
+    assert!(rx.try_recv().is_err());
+
+    // Without the admin scope, a session only gets the logs of its own requests
+    let session = |id: &str, scopes: &[Scope]| {
+        let (peer, rx) = Peer::channel();
+        let grant = Grant {
+            scopes: scopes.iter().copied().collect(),
+            ..Grant::unrestricted(id)
+        };
+        let peer = peer.with_session(SessionInfo {
+            transport: "http",
+            session_id: Some(id.into()),
+            identity: Some(id.into()),
+            grant: Some(Arc::new(grant)),
+        });
+        (peer, rx)
+    };
+    let (reader, mut reader_rx) = session("reader", &[Scope::Read]);
+    let (admin, mut admin_rx) = session("admin", &[Scope::Read, Scope::Admin]);
+    for peer in [reader, admin] {
+        let set_level = JsonRpcRequest {
+            jsonrpc: "2.0".to_string(),
+            method: "logging/setLevel".to_string(),
+            params: Some(json!({ "level": "debug" })),
+            id: Some(json!(1)),
+        };
+        server
+            .handle_request_with_context(set_level, RequestContext::new(peer))
+            .await
+            .unwrap();
+    }
+    let subscriber = tracing_subscriber::registry().with(McpLogLayer::filtered());
+    tracing::subscriber::with_default(subscriber, || {
+        for session in ["reader", "admin"] {
+            let span = tracing::info_span!(target: "qbittorrent_mcp_rs::server::http", "http", session = %session);
+            span.in_scope(|| {
+                tracing::debug!(target: "qbittorrent_mcp_rs::server::http", "Request of {}", session)
+            });
+        }
+        tracing::debug!(target: "qbittorrent_mcp_rs::server::mcp", "Polling");
+    });
+    let received = |rx: &mut tokio::sync::mpsc::UnboundedReceiver<serde_json::Value>| {
+        std::iter::from_fn(|| rx.try_recv().ok())
+            .map(|message| message["params"]["data"].as_str().unwrap().to_string())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(received(&mut reader_rx), ["Request of reader"]);
+    assert_eq!(
+        received(&mut admin_rx),
+        ["Request of reader", "Request of admin", "Polling"]
+    );
+
+    // Unknown levels are rejected
+    let err = server
+        .handle_request(JsonRpcRequest {
+            jsonrpc: "2.0".to_string(),
+            method: "logging/setLevel".to_string(),
+            params: Some(json!({ "level": "loud" })),
+            id: Some(json!(2)),
+        })
+        .await
+        .unwrap_err();
+    assert_eq!(err.downcast_ref::<JsonRpcError>().unwrap().code, -32602);
+}
+
+#[tokio::test]
+async fn test_tool_schemas_and_unknown_arguments() {
+    let (_mock_server, server) = setup_mock_server().await;
+
+    let list = server
+        .handle_request(JsonRpcRequest {
+            jsonrpc: "2.0".to_string(),
+            method: "tools/list".to_string(),
+            params: None,
+            id: Some(json!(1)),
+        })
+        .await
+        .unwrap();
+    let tools = list["tools"].as_array().unwrap();
+    let tool = |name: &str| tools.iter().find(|t| t["name"] == name).unwrap().clone();
+
+    let manage = tool("manage_torrents");
+    let schema = &manage["inputSchema"];
+    assert_eq!(schema["required"], json!(["hashes", "action"]));
+    assert_eq!(schema["properties"]["action"]["type"], "string");
+    assert!(
+        schema["properties"]["action"]["enum"]
+            .as_array()
+            .unwrap()
+            .contains(&json!("set_force_start"))
+    );
+    assert_eq!(schema["properties"]["category"]["type"], "string");
+    assert_eq!(schema["properties"]["instance"]["type"], "string");
+    assert_eq!(manage["annotations"]["readOnlyHint"], false);
+    assert_eq!(
+        tool("delete_torrent")["annotations"]["destructiveHint"],
+        true
+    );
+    assert_eq!(tool("list_torrents")["annotations"]["readOnlyHint"], true);
+
+    // Misspelled arguments are rejected rather than ignored
+    let err = server
+        .handle_request(tool_call("list_torrents", json!({ "limt": 5 })))
+        .await
+        .unwrap_err();
+    let rpc = err.downcast_ref::<JsonRpcError>().unwrap();
+    assert_eq!(rpc.code, -32602);
+    assert_eq!(rpc.message, "Unknown argument: limt");
+    assert_eq!(rpc.data.as_ref().unwrap()["argument"], "limt");
+
+    let err = server
+        .handle_request(tool_call(
+            "manage_torrents",
+            json!({ "hashes": "abc", "action": "explode" }),
+        ))
+        .await
+        .unwrap_err();
+    let rpc = err.downcast_ref::<JsonRpcError>().unwrap();
+    assert_eq!(rpc.data.as_ref().unwrap()["argument"], "action");
+    assert!(
+        rpc.data.as_ref().unwrap()["expected"]
+            .as_str()
+            .unwrap()
+            .starts_with("one of pause, resume")
+    );
+
+    // Arguments only some actions need are still checked
+    let err = server
+        .handle_request(tool_call(
+            "manage_torrents",
+            json!({ "hashes": "abc", "action": "set_category" }),
+        ))
+        .await
+        .unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<JsonRpcError>().unwrap().message,
+        "Missing category"
+    );
+}
+
+#[tokio::test]
+async fn test_lazy_mode_uses_registered_schema() {
+    let client = QBitClient::new_no_auth("http://localhost:1", false);
+    let mut clients = HashMap::new();
+    clients.insert("default".to_string(), client);
+    let lazy = McpServer::new(clients.clone(), true);
+    let full = McpServer::new(clients, false);
+
+    let list = |server: McpServer| async move {
+        server
+            .handle_request(JsonRpcRequest {
+                jsonrpc: "2.0".to_string(),
+                method: "tools/list".to_string(),
+                params: None,
+                id: Some(json!(1)),
+            })
+            .await
+            .unwrap()["tools"]
+            .as_array()
+            .unwrap()
+            .clone()
+    };
+    let lazy_tools = list(lazy).await;
+    let full_tools = list(full).await;
+    let names: Vec<&str> = lazy_tools
+        .iter()
+        .map(|t| t["name"].as_str().unwrap())
+        .collect();
+    assert!(names.contains(&"list_torrents"));
+    assert!(names.contains(&"enable_toolset"));
+    assert!(!names.contains(&"search_torrents"));
+    assert!(lazy_tools.len() < full_tools.len());
+    let find = |tools: &[serde_json::Value]| {
+        tools
+            .iter()
+            .find(|t| t["name"] == "list_torrents")
+            .unwrap()
+            .clone()
+    };
+    assert_eq!(find(&lazy_tools), find(&full_tools));
+}
+
+#[tokio::test]
+async fn test_toolsets_are_tracked_per_session() {
+    let client = QBitClient::new_no_auth("http://localhost:1", false);
+    let mut clients = HashMap::new();
+    clients.insert("default".to_string(), client);
+    let server = McpServer::new(clients, true);
+    let (peer_a, _rx_a) = Peer::channel();
+    let (peer_b, _rx_b) = Peer::channel();
+
+    let call = |peer: &Peer, req: JsonRpcRequest| {
+        server.handle_request_with_context(req, RequestContext::new(peer.clone()))
+    };
+    let tool_names = |result: serde_json::Value| -> Vec<String> {
+        result["tools"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|t| t["name"].as_str().unwrap().to_string())
+            .collect()
+    };
+    let list_tools = || JsonRpcRequest {
+        jsonrpc: "2.0".to_string(),
+        method: "tools/list".to_string(),
+        params: None,
+        id: Some(json!(2)),
+    };
+
+    // Tools outside the enabled toolsets are neither listed nor callable
+    let err = call(&peer_a, tool_call("get_search_plugins", json!({})))
+        .await
+        .unwrap_err();
+    let rpc = err.downcast_ref::<JsonRpcError>().unwrap();
+    assert_eq!(rpc.code, -32602);
+    assert!(rpc.message.contains("enable_toolset"));
+
+    let res = call(&peer_a, tool_call("list_toolsets", json!({})))
+        .await
+        .unwrap();
+    let toolsets = result_json(&res);
+    let search = toolsets
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|t| t["name"] == "search")
+        .unwrap();
+    assert_eq!(search["enabled"], false);
+    assert!(
+        search["tools"]
+            .as_array()
+            .unwrap()
+            .contains(&json!("search_torrents"))
+    );
+
+    call(
+        &peer_a,
+        tool_call("enable_toolset", json!({ "toolset": "search" })),
+    )
+    .await
+    .unwrap();
+    assert!(peer_a.take_tools_changed());
+    assert!(!peer_b.take_tools_changed());
+
+    let names_a = tool_names(call(&peer_a, list_tools()).await.unwrap());
+    let names_b = tool_names(call(&peer_b, list_tools()).await.unwrap());
+    assert!(names_a.contains(&"search_torrents".to_string()));
+    assert!(!names_b.contains(&"search_torrents".to_string()));
+
+    // Enabling twice is a no-op and does not notify again
+    call(
+        &peer_a,
+        tool_call("enable_toolset", json!({ "toolset": "search" })),
+    )
+    .await
+    .unwrap();
+    assert!(!peer_a.take_tools_changed());
+
+    call(
+        &peer_a,
+        tool_call("disable_toolset", json!({ "toolset": "torrents" })),
+    )
+    .await
+    .unwrap();
+    assert!(peer_a.take_tools_changed());
+    let names_a = tool_names(call(&peer_a, list_tools()).await.unwrap());
+    assert!(!names_a.contains(&"list_torrents".to_string()));
+    assert!(names_a.contains(&"enable_toolset".to_string()));
+
+    let err = call(
+        &peer_a,
+        tool_call("enable_toolset", json!({ "toolset": "bogus" })),
+    )
+    .await
+    .unwrap_err();
+    let rpc = err.downcast_ref::<JsonRpcError>().unwrap();
+    assert_eq!(rpc.data.as_ref().unwrap()["argument"], "toolset");
+}
+
+mod custom_tool {
+    use qbittorrent_mcp_rs::server::tools::{Tool, ToolAnnotations, ToolContext, text_result};
+    use schemars::JsonSchema;
+    use serde::Deserialize;
+    use serde_json::Value;
+
+    pub struct Greet;
+
+    #[derive(Deserialize, JsonSchema)]
+    #[serde(deny_unknown_fields)]
+    pub struct GreetArgs {
+        /// Who to greet
+        name: String,
+    }
+
+    #[async_trait::async_trait]
+    impl Tool for Greet {
+        type Args = GreetArgs;
+
+        fn name(&self) -> &str {
+            "greet"
+        }
+
+        fn description(&self) -> &str {
+            "Say hello"
+        }
+
+        fn annotations(&self) -> ToolAnnotations {
+            ToolAnnotations::read_only()
+        }
+
+        async fn call(&self, ctx: &ToolContext<'_>, args: Self::Args) -> anyhow::Result<Value> {
+            Ok(text_result(format!(
+                "Hello {} from {}",
+                args.name, ctx.instance
+            )))
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_register_custom_tool() {
+    let (_mock_server, server) = setup_mock_server().await;
+    let server = server.with_tool(custom_tool::Greet);
+
+    let list = server
+        .handle_request(JsonRpcRequest {
+            jsonrpc: "2.0".to_string(),
+            method: "tools/list".to_string(),
+            params: None,
+            id: Some(json!(1)),
+        })
+        .await
+        .unwrap();
+    let greet = list["tools"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|t| t["name"] == "greet")
+        .unwrap();
+    assert_eq!(
+        greet["inputSchema"]["properties"]["name"]["description"],
+        "Who to greet"
+    );
+
+    let resp = server
+        .handle_request(tool_call("greet", json!({ "name": "Ann" })))
+        .await
+        .unwrap();
+    assert_eq!(resp["content"][0]["text"], "Hello Ann from default");
+}
+
+#[tokio::test]
+async fn test_cleanup_completed_dry_run() {
+    let (mock_server, server) = setup_mock_server().await;
+
+    let mock_torrents = r#"[
+        {"hash": "h1", "name": "T1", "size": 1024, "progress": 1.0, "dlspeed": 0, "upspeed": 0, "priority": 0, "num_seeds": 1, "num_leechs": 0, "num_incomplete": 0, "num_complete": 1, "ratio": 2.5, "eta": 0, "state": "uploading", "added_on": 100, "completion_on": 200, "seq_dl": false, "f_l_piece_prio": false, "category": "", "tags": "", "super_seeding": false, "force_start": false},
+        {"hash": "h2", "name": "T2", "size": 1024, "progress": 1.0, "dlspeed": 0, "upspeed": 0, "priority": 0, "num_seeds": 1, "num_leechs": 0, "num_incomplete": 0, "num_complete": 1, "ratio": 0.5, "eta": 0, "state": "uploading", "added_on": 100, "completion_on": 200, "seq_dl": false, "f_l_piece_prio": false, "category": "", "tags": "", "super_seeding": false, "force_start": false}
+    ]"#;
+
+    Mock::given(method("GET"))
+        .and(path("/api/v2/torrents/info"))
+        .and(query_param("filter", "completed"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(mock_torrents))
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path("/api/v2/torrents/delete"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&mock_server)
+        .await;
+
+    let resp = server
+        .handle_request(tool_call(
+            "cleanup_completed",
+            json!({ "min_ratio": 2.0, "delete_files": true, "dry_run": true }),
+        ))
+        .await
+        .unwrap();
+    let plan = result_json(&resp);
+    assert_eq!(plan["dry_run"], true);
+    assert_eq!(plan["count"], 1);
+    assert_eq!(plan["total_size"], "1.0 KiB");
+    let torrent = &plan["torrents"][0];
+    assert_eq!(torrent["name"], "T1");
+    assert_eq!(torrent["size"], "1.0 KiB");
+    assert_eq!(torrent["ratio"], 2.5);
+    assert!(torrent["age_days"].as_i64().unwrap() > 0);
+}
+
+#[tokio::test]
+async fn test_mass_rename_dry_run_and_collisions() {
+    let (mock_server, server) = setup_mock_server().await;
+
+    let mock_files = r#"[
+        {"index": 0, "name": "show/a.mkv", "size": 1000, "progress": 1.0, "priority": 1, "availability": 1.0},
+        {"index": 1, "name": "show/b.mkv", "size": 1000, "progress": 1.0, "priority": 1, "availability": 1.0},
+        {"index": 2, "name": "show/c.mkv", "size": 1000, "progress": 1.0, "priority": 1, "availability": 1.0}
+    ]"#;
+
+    Mock::given(method("GET"))
+        .and(path("/api/v2/torrents/files"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(mock_files))
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path("/api/v2/torrents/renameFile"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&mock_server)
+        .await;
+
+    let resp = server
+        .handle_request(tool_call(
+            "mass_rename",
+            json!({ "hash": "abc", "pattern": "(\\w)\\.mkv$", "replacement": "${1}1.mkv", "dry_run": true }),
+        ))
+        .await
+        .unwrap();
+    let plan = result_json(&resp);
+    assert_eq!(plan["count"], 3);
+    assert_eq!(plan["renames"][0]["old_path"], "show/a.mkv");
+    assert_eq!(plan["renames"][0]["new_path"], "show/a1.mkv");
+
+    // a.mkv would be renamed onto the untouched c.mkv, b.mkv too
+    let resp = server
+        .handle_request(tool_call(
+            "mass_rename",
+            json!({ "hash": "abc", "pattern": "[ab]\\.mkv$", "replacement": "c.mkv" }),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp["isError"], true);
+    let text = resp["content"][0]["text"].as_str().unwrap();
+    assert!(text.contains("nothing was renamed"));
+    assert!(text.contains("show/a.mkv, show/b.mkv, show/c.mkv -> show/c.mkv"));
+}
+
+#[tokio::test]
+async fn test_set_app_preferences_dry_run() {
+    let (mock_server, server) = setup_mock_server().await;
+
+    Mock::given(method("GET"))
+        .and(path("/api/v2/app/preferences"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_json(json!({ "dl_limit": 0, "up_limit": 100 })),
+        )
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path("/api/v2/app/setPreferences"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&mock_server)
+        .await;
+
+    let prefs = json!({ "dl_limit": 500, "up_limit": 100, "bogus": 1 }).to_string();
+    let resp = server
+        .handle_request(tool_call(
+            "set_app_preferences",
+            json!({ "preferences": prefs, "dry_run": true }),
+        ))
+        .await
+        .unwrap();
+    let diff = result_json(&resp);
+    assert_eq!(
+        diff["changes"],
+        json!({ "dl_limit": { "before": 0, "after": 500 } })
+    );
+    assert_eq!(diff["unchanged"], json!(["up_limit"]));
+    assert_eq!(diff["unknown"], json!(["bogus"]));
+}
+
+#[tokio::test]
+async fn test_list_torrents_fields_format_and_budget() {
+    let (mock_server, server) = setup_mock_server().await;
+
+    let torrents: Vec<serde_json::Value> = (0..20)
+        .map(|i| {
+            json!({
+                "hash": format!("h{}", i), "name": format!("Torrent {}", i), "size": 1024,
+                "progress": 1.0, "dlspeed": 0, "upspeed": 0, "priority": 0, "num_seeds": 0,
+                "num_leechs": 0, "num_incomplete": 0, "num_complete": 0, "ratio": 1.0, "eta": 0,
+                "state": "uploading", "added_on": 0, "completion_on": 0, "seq_dl": false,
+                "f_l_piece_prio": false, "category": "", "tags": "", "super_seeding": false,
+                "force_start": false
+            })
+        })
+        .collect();
+    Mock::given(method("GET"))
+        .and(path("/api/v2/torrents/info"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(&torrents))
+        .mount(&mock_server)
+        .await;
+
+    let resp = server
+        .handle_request(tool_call(
+            "list_torrents",
+            json!({ "fields": ["name", "state"], "format": "csv", "max_output_chars": 100 }),
+        ))
+        .await
+        .unwrap();
+    let content = resp["content"].as_array().unwrap();
+    let csv = content[0]["text"].as_str().unwrap();
+    assert!(csv.starts_with("name,state\nTorrent 0,uploading\n"));
+    assert!(csv.len() <= 100);
+    let note = content[1]["text"].as_str().unwrap();
+    let shown = csv.lines().count() - 1;
+    assert!(note.contains(&format!("{} more omitted", 20 - shown)));
+
+    // The cursor continues where the first page stopped
+    let resp = server
+        .handle_request(tool_call(
+            "list_torrents",
+            json!({ "fields": ["name"], "format": "compact", "cursor": shown.to_string() }),
+        ))
+        .await
+        .unwrap();
+    let page = result_json(&resp);
+    assert_eq!(page.as_array().unwrap().len(), 20 - shown);
+    assert_eq!(page[0], json!({ "name": format!("Torrent {}", shown) }));
+
+    let err = server
+        .handle_request(tool_call("list_torrents", json!({ "fields": ["nmae"] })))
+        .await
+        .unwrap_err();
+    let rpc = err.downcast_ref::<JsonRpcError>().unwrap();
+    assert_eq!(rpc.code, -32602);
+    assert_eq!(rpc.data.as_ref().unwrap()["argument"], "fields");
+}
+
+#[tokio::test]
+async fn test_humanized_views() {
+    let (mock_server, server) = setup_mock_server().await;
+    let server = server.with_humanize(
+        false,
+        qbittorrent_mcp_rs::humanize::Humanizer::new("+01:00").unwrap(),
+    );
+
+    let torrents = r#"[{"hash": "abc", "name": "Ubuntu", "size": 2000000000, "progress": 0.5, "dlspeed": 1048576, "upspeed": 0, "priority": 1, "num_seeds": 5, "num_leechs": 2, "num_incomplete": 2, "num_complete": 5, "ratio": 0.1, "eta": 8640000, "state": "stalledDL", "added_on": 1700000000, "completion_on": -1, "seq_dl": false, "f_l_piece_prio": false, "category": "", "tags": "", "super_seeding": false, "force_start": false}]"#;
+    Mock::given(method("GET"))
+        .and(path("/api/v2/torrents/info"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(torrents))
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/api/v2/transfer/info"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "dl_info_speed": 2048, "dl_info_data": 1073741824, "up_info_speed": 0,
+            "up_info_data": 0, "dl_rate_limit": 0, "up_rate_limit": 512000,
+            "dht_nodes": 10, "connection_status": "connected"
+        })))
+        .mount(&mock_server)
+        .await;
+
+    let resp = server
+        .handle_request(tool_call("list_torrents", json!({ "humanize": true })))
+        .await
+        .unwrap();
+    let list = result_json(&resp);
+    let t = &list[0];
+    assert_eq!(t["size"], "1.9 GiB");
+    assert_eq!(t["dlspeed"], "1.0 MiB/s");
+    assert_eq!(t["progress"], "50.0%");
+    assert_eq!(t["eta"], "∞");
+    assert_eq!(t["added_on"], "2023-11-14T23:13:20+01:00");
+    assert_eq!(t["completion_on"], "never");
+    assert_eq!(
+        t["state"],
+        "Downloading, but no peers are sending data (stalledDL)"
+    );
+
+    // Raw values stay the default
+    let resp = server
+        .handle_request(tool_call("list_torrents", json!({})))
+        .await
+        .unwrap();
+    let list = result_json(&resp);
+    assert_eq!(list[0]["size"], 2000000000);
+
+    let resp = server
+        .handle_request(JsonRpcRequest {
+            jsonrpc: "2.0".to_string(),
+            method: "resources/read".to_string(),
+            params: Some(json!({ "uri": "qbittorrent://default/transfer?humanize=true" })),
+            id: Some(json!(1)),
+        })
+        .await
+        .unwrap();
+    let info: serde_json::Value =
+        serde_json::from_str(resp["contents"][0]["text"].as_str().unwrap()).unwrap();
+    assert_eq!(info["dl_info_data"], "1.0 GiB");
+    assert_eq!(info["dl_rate_limit"], "unlimited");
+    assert_eq!(info["up_rate_limit"], "500.0 KiB/s");
+}
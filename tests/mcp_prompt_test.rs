@@ -0,0 +1,191 @@
+use qbittorrent_mcp_rs::client::QBitClient;
+use qbittorrent_mcp_rs::server::mcp::{JsonRpcRequest, McpServer};
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+const TORRENTS: &str = r#"[
+    {"hash": "small", "name": "Small Done", "size": 1073741824, "progress": 1.0, "dlspeed": 0, "upspeed": 0, "priority": 0, "num_seeds": 1, "num_leechs": 0, "num_incomplete": 0, "num_complete": 1, "ratio": 2.5, "eta": 0, "state": "uploading", "added_on": 100, "completion_on": 200, "seq_dl": false, "f_l_piece_prio": false, "category": "", "tags": "", "super_seeding": false, "force_start": false},
+    {"hash": "big", "name": "Big Done", "size": 10737418240, "progress": 1.0, "dlspeed": 0, "upspeed": 0, "priority": 0, "num_seeds": 1, "num_leechs": 0, "num_incomplete": 0, "num_complete": 1, "ratio": 0.3, "eta": 0, "state": "stalledUP", "added_on": 100, "completion_on": 200, "seq_dl": false, "f_l_piece_prio": false, "category": "movies", "tags": "", "super_seeding": false, "force_start": false},
+    {"hash": "stuck", "name": "Stuck", "size": 2048, "progress": 0.5, "dlspeed": 0, "upspeed": 0, "priority": 1, "num_seeds": 0, "num_leechs": 0, "num_incomplete": 0, "num_complete": 0, "ratio": 0.0, "eta": 8640000, "state": "stalledDL", "added_on": 100, "completion_on": 0, "seq_dl": false, "f_l_piece_prio": false, "category": "", "tags": "", "super_seeding": false, "force_start": false}
+]"#;
+
+fn prompt_get(name: &str, arguments: Value) -> JsonRpcRequest {
+    JsonRpcRequest {
+        jsonrpc: "2.0".to_string(),
+        method: "prompts/get".to_string(),
+        params: Some(json!({ "name": name, "arguments": arguments })),
+        id: Some(json!(1)),
+    }
+}
+
+fn embedded_uris(prompt: &Value) -> Vec<String> {
+    prompt["messages"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|m| m["content"]["type"] == "resource")
+        .map(|m| {
+            m["content"]["resource"]["uri"]
+                .as_str()
+                .unwrap()
+                .to_string()
+        })
+        .collect()
+}
+
+fn texts(prompt: &Value) -> String {
+    prompt["messages"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter_map(|m| m["content"]["text"].as_str())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+async fn server_with_torrents() -> (MockServer, McpServer) {
+    let mock_server = MockServer::start().await;
+    let mut clients = HashMap::new();
+    clients.insert(
+        "nas".to_string(),
+        QBitClient::new_no_auth(mock_server.uri(), false),
+    );
+    Mock::given(method("GET"))
+        .and(path("/api/v2/torrents/info"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(TORRENTS))
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/api/v2/sync/maindata"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "rid": 1,
+            "full_update": true,
+            "server_state": { "free_space_on_disk": 5368709120i64, "connection_status": "connected" }
+        })))
+        .mount(&mock_server)
+        .await;
+    (mock_server, McpServer::new(clients, false))
+}
+
+#[tokio::test]
+async fn test_troubleshoot_prompt_embeds_torrent_data() {
+    let (mock_server, server) = server_with_torrents().await;
+    Mock::given(method("GET"))
+        .and(path("/api/v2/torrents/trackers"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/api/v2/sync/torrentPeers"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "rid": 1,
+            "full_update": true,
+            "peers": { "1.2.3.4:5000": { "client": "qBittorrent/5.0" } }
+        })))
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/api/v2/log/main"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+            { "id": 1, "message": "Tracker unreachable", "timestamp": 1700000000, "type": 4 }
+        ])))
+        .mount(&mock_server)
+        .await;
+    // Properties are not mocked, so that part is noted rather than failing the prompt
+
+    let prompt = server
+        .handle_request(prompt_get(
+            "troubleshoot_torrent",
+            json!({ "issue_type": "stalled", "hash": "stuck", "instance": "nas" }),
+        ))
+        .await
+        .unwrap();
+
+    assert_eq!(
+        embedded_uris(&prompt),
+        vec![
+            "qbittorrent://nas/torrent/stuck/trackers",
+            "qbittorrent://nas/torrent/stuck/peers"
+        ]
+    );
+    let text = texts(&prompt);
+    assert!(text.contains("Could not fetch qbittorrent://nas/torrent/stuck/properties"));
+    assert!(text.contains("Tracker unreachable"));
+    assert!(text.contains("2023-11-14T22:13:20Z"));
+    assert!(text.contains("Downloading, but no peers are sending data (stalledDL)"));
+    let peers = prompt["messages"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|m| m["content"]["resource"]["uri"] == "qbittorrent://nas/torrent/stuck/peers")
+        .unwrap();
+    assert!(
+        peers["content"]["resource"]["text"]
+            .as_str()
+            .unwrap()
+            .contains("1.2.3.4:5000")
+    );
+}
+
+#[tokio::test]
+async fn test_free_up_space_prompt() {
+    let (_mock_server, server) = server_with_torrents().await;
+
+    let prompt = server
+        .handle_request(prompt_get("free_up_space", json!({ "target_gb": "8" })))
+        .await
+        .unwrap();
+    assert_eq!(
+        embedded_uris(&prompt),
+        vec!["qbittorrent://nas/server-state"]
+    );
+    let text = texts(&prompt);
+    assert!(text.contains("free up 8 GB"));
+    assert!(text.contains("dry_run=true"));
+    assert!(text.contains("\"free_space_on_disk\": \"5.0 GiB\""));
+    assert!(text.contains("\"reclaimable_by_removing_all_with_files\": \"11.0 GiB\""));
+    // Only completed torrents, largest first
+    let big = text.find("Big Done").unwrap();
+    let small = text.find("Small Done").unwrap();
+    assert!(big < small);
+    assert!(!text.contains("\"Stuck\""));
+
+    let err = server
+        .handle_request(prompt_get("free_up_space", json!({ "target_gb": "lots" })))
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("target_gb"));
+}
+
+#[tokio::test]
+async fn test_organize_library_defaults_to_uncategorized() {
+    let (mock_server, server) = server_with_torrents().await;
+    Mock::given(method("GET"))
+        .and(path("/api/v2/torrents/categories"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "movies": { "name": "movies", "savePath": "/data/movies" }
+        })))
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/api/v2/torrents/tags"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!(["hd"])))
+        .mount(&mock_server)
+        .await;
+
+    let prompt = server
+        .handle_request(prompt_get("organize_library", json!({})))
+        .await
+        .unwrap();
+    assert_eq!(
+        embedded_uris(&prompt),
+        vec!["qbittorrent://nas/categories", "qbittorrent://nas/tags"]
+    );
+    let text = texts(&prompt);
+    assert!(text.contains("uncategorized torrents"));
+    assert!(text.contains("Small Done"));
+    assert!(text.contains("Stuck"));
+    assert!(!text.contains("Big Done"));
+}
@@ -2,7 +2,7 @@ use qbittorrent_mcp_rs::client::QBitClient;
 use qbittorrent_mcp_rs::server::mcp::{JsonRpcRequest, McpServer};
 use serde_json::json;
 use std::collections::HashMap;
-use wiremock::matchers::{method, path};
+use wiremock::matchers::{method, path, query_param};
 use wiremock::{Mock, MockServer, ResponseTemplate};
 
 #[tokio::test]
@@ -174,7 +174,7 @@ async fn test_mcp_resource_templates_list() {
         .await
         .unwrap();
     let templates = res.get("resourceTemplates").unwrap().as_array().unwrap();
-    assert_eq!(templates.len(), 3);
+    assert_eq!(templates.len(), 6);
     assert!(templates[0].get("uriTemplate").is_some());
 }
 
@@ -244,3 +244,119 @@ async fn test_mcp_errors() {
         .unwrap();
     assert!(res5.is_object());
 }
+
+#[tokio::test]
+async fn test_mcp_resource_catalog() {
+    let mock_server = MockServer::start().await;
+    let mut clients = HashMap::new();
+    clients.insert(
+        "nas".to_string(),
+        QBitClient::new_no_auth(mock_server.uri(), false),
+    );
+    clients.insert(
+        "seedbox".to_string(),
+        QBitClient::new_no_auth(mock_server.uri(), false),
+    );
+    let server = McpServer::new(clients, false);
+
+    Mock::given(method("GET"))
+        .and(path("/api/v2/sync/maindata"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "rid": 1,
+            "server_state": { "free_space_on_disk": 1024, "alltime_dl": 2048 }
+        })))
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/api/v2/app/preferences"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "save_path": "/downloads",
+            "proxy_password": "hunter2"
+        })))
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/api/v2/sync/torrentPeers"))
+        .and(query_param("hash", "abc"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "rid": 1,
+            "peers": { "1.2.3.4:6881": { "client": "qBittorrent", "progress": 0.5 } }
+        })))
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/api/v2/torrents/info"))
+        .and(query_param("category", "My Movies"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("[]"))
+        .mount(&mock_server)
+        .await;
+
+    let res = server
+        .handle_request(JsonRpcRequest {
+            jsonrpc: "2.0".to_string(),
+            method: "resources/list".to_string(),
+            params: None,
+            id: Some(json!(1)),
+        })
+        .await
+        .unwrap();
+    let uris: Vec<&str> = res["resources"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|r| r["uri"].as_str().unwrap())
+        .collect();
+    for instance in ["nas", "seedbox"] {
+        for resource in [
+            "main-log",
+            "peer-log",
+            "rss/feeds",
+            "rss/rules",
+            "tags",
+            "preferences",
+            "search-plugins",
+            "server-state",
+        ] {
+            let uri = format!("qbittorrent://{}/{}", instance, resource);
+            assert!(uris.contains(&uri.as_str()), "missing {}", uri);
+        }
+    }
+    assert!(
+        res["resources"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .all(|r| r["mimeType"] == "application/json" && r["description"].is_string())
+    );
+
+    let read = |uri: &str| {
+        server.handle_request(JsonRpcRequest {
+            jsonrpc: "2.0".to_string(),
+            method: "resources/read".to_string(),
+            params: Some(json!({ "uri": uri })),
+            id: Some(json!(1)),
+        })
+    };
+    let text = |res: serde_json::Value| -> serde_json::Value {
+        serde_json::from_str(res["contents"][0]["text"].as_str().unwrap()).unwrap()
+    };
+
+    let state = text(read("qbittorrent://nas/server-state").await.unwrap());
+    assert_eq!(state["free_space_on_disk"], 1024);
+
+    let prefs = text(read("qbittorrent://seedbox/preferences").await.unwrap());
+    assert_eq!(prefs["save_path"], "/downloads");
+    assert_eq!(prefs["proxy_password"], "[REDACTED]");
+
+    let peers = text(read("qbittorrent://nas/torrent/abc/peers").await.unwrap());
+    assert_eq!(peers["1.2.3.4:6881"]["client"], "qBittorrent");
+
+    let torrents = text(
+        read("qbittorrent://nas/category/My%20Movies/torrents")
+            .await
+            .unwrap(),
+    );
+    assert_eq!(torrents, json!([]));
+
+    assert!(read("qbittorrent://nas/no-such-resource").await.is_err());
+}
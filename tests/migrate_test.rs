@@ -0,0 +1,264 @@
+mod common;
+
+use common::{result_json, tool_call};
+use qbittorrent_mcp_rs::client::QBitClient;
+use qbittorrent_mcp_rs::server::mcp::McpServer;
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use wiremock::matchers::{body_string_contains, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+fn torrent(state: &str, progress: f64) -> Value {
+    json!({
+        "hash": "abc", "name": "Movie", "size": 1000, "progress": progress, "dlspeed": 0,
+        "upspeed": 0, "priority": 0, "num_seeds": 1, "num_leechs": 0, "num_incomplete": 0,
+        "num_complete": 1, "ratio": 1.0, "eta": 0, "state": state, "added_on": 100,
+        "completion_on": 200, "seq_dl": false, "f_l_piece_prio": false, "category": "movies",
+        "tags": "hd", "super_seeding": false, "force_start": false, "ratio_limit": 2.0,
+        "seeding_time_limit": -2
+    })
+}
+
+fn properties() -> Value {
+    json!({
+        "save_path": "/downloads/movies", "creation_date": 0, "piece_size": 0, "comment": "",
+        "total_wasted": 0, "total_uploaded": 0, "total_downloaded": 0, "up_limit": 0,
+        "dl_limit": 0, "time_elapsed": 0, "seeding_time": 0, "nb_connections": 0,
+        "nb_connections_limit": 0, "share_ratio": 0.0, "addition_date": 0, "completion_date": 0,
+        "created_by": "", "dl_speed_avg": 0, "dl_speed": 0, "eta": 0, "last_seen": 0, "peers": 0,
+        "peers_total": 0, "pieces_have": 0, "pieces_num": 0, "reannounce": 0, "seeds": 0,
+        "seeds_total": 0, "total_size": 0, "up_speed_avg": 0, "up_speed": 0
+    })
+}
+
+/// A source seeding "abc" and a target that does not have it until it is added, after
+/// which it reports `target_state`.
+async fn setup(target_state: &str, target_progress: f64) -> (MockServer, MockServer, McpServer) {
+    let source = MockServer::start().await;
+    let target = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/api/v2/torrents/info"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([torrent("uploading", 1.0)])))
+        .mount(&source)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/api/v2/torrents/properties"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(properties()))
+        .mount(&source)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/api/v2/torrents/export"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_bytes(b"d4:infod4:name5:Movieee".to_vec()),
+        )
+        .mount(&source)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/api/v2/torrents/stop"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&source)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/api/v2/torrents/info"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+        .up_to_n_times(1)
+        .mount(&target)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/api/v2/torrents/info"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_json(json!([torrent(target_state, target_progress)])),
+        )
+        .mount(&target)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/api/v2/torrents/add"))
+        .and(body_string_contains("/data/movies"))
+        .and(body_string_contains("movies"))
+        .and(body_string_contains("ratioLimit"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("Ok."))
+        .expect(1)
+        .mount(&target)
+        .await;
+
+    let mut clients = HashMap::new();
+    clients.insert(
+        "old".to_string(),
+        QBitClient::new_no_auth(source.uri(), false),
+    );
+    clients.insert(
+        "new".to_string(),
+        QBitClient::new_no_auth(target.uri(), false),
+    );
+    (source, target, McpServer::new(clients, false))
+}
+
+fn arguments() -> Value {
+    json!({
+        "instance": "old",
+        "hash": "abc",
+        "target_instance": "new",
+        "path_map": { "/downloads": "/data" }
+    })
+}
+
+#[tokio::test]
+async fn test_migrate_torrent() {
+    let (source, target, server) = setup("stalledUP", 1.0).await;
+    Mock::given(method("POST"))
+        .and(path("/api/v2/torrents/delete"))
+        .and(body_string_contains("deleteFiles=false"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&source)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/api/v2/torrents/delete"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&target)
+        .await;
+
+    let resp = server
+        .handle_request(tool_call("migrate_torrent", arguments()))
+        .await
+        .unwrap();
+    assert!(resp.get("isError").is_none(), "{}", resp);
+    let result = result_json(&resp);
+    assert_eq!(result["from"], "old");
+    assert_eq!(result["to"], "new");
+    assert_eq!(result["save_path"], "/data/movies");
+    assert_eq!(
+        result["state"],
+        "Seeding, but no peers are downloading (stalledUP)"
+    );
+}
+
+#[tokio::test]
+async fn test_migrate_torrent_rolls_back_failed_check() {
+    let (source, target, server) = setup("missingFiles", 0.0).await;
+    Mock::given(method("POST"))
+        .and(path("/api/v2/torrents/delete"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&source)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/api/v2/torrents/start"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&source)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/api/v2/torrents/delete"))
+        .and(body_string_contains("deleteFiles=false"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&target)
+        .await;
+
+    let resp = server
+        .handle_request(tool_call("migrate_torrent", arguments()))
+        .await
+        .unwrap();
+    assert_eq!(resp["isError"], true);
+    let text = resp["content"][0]["text"].as_str().unwrap();
+    assert!(text.contains("Data files are missing"), "{}", text);
+    assert!(text.contains("Rolled back"), "{}", text);
+}
+
+#[tokio::test]
+async fn test_migrate_torrent_reports_incomplete_rollback() {
+    let (source, target, server) = setup("missingFiles", 0.0).await;
+    Mock::given(method("POST"))
+        .and(path("/api/v2/torrents/start"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&source)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/api/v2/torrents/delete"))
+        .respond_with(ResponseTemplate::new(500))
+        .mount(&target)
+        .await;
+
+    let resp = server
+        .handle_request(tool_call("migrate_torrent", arguments()))
+        .await
+        .unwrap();
+    assert_eq!(resp["isError"], true);
+    let text = resp["content"][0]["text"].as_str().unwrap();
+    assert!(!text.contains("Rolled back"), "{}", text);
+    assert!(
+        text.contains("Rolling back did not complete: the torrent is still on 'new'; remove it there without deleting its files"),
+        "{}",
+        text
+    );
+    assert!(!text.contains("resume it"), "{}", text);
+}
+
+#[tokio::test]
+async fn test_migrate_torrent_dry_run_and_errors() {
+    let source = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/api/v2/torrents/info"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([torrent("pausedUP", 1.0)])))
+        .mount(&source)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/api/v2/torrents/properties"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(properties()))
+        .mount(&source)
+        .await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&source)
+        .await;
+    let target = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/api/v2/torrents/info"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+        .mount(&target)
+        .await;
+    let mut clients = HashMap::new();
+    clients.insert(
+        "old".to_string(),
+        QBitClient::new_no_auth(source.uri(), false),
+    );
+    clients.insert(
+        "new".to_string(),
+        QBitClient::new_no_auth(target.uri(), false),
+    );
+    let server = McpServer::new(clients, false);
+
+    let mut args = arguments();
+    args["dry_run"] = json!(true);
+    args["save_path"] = json!("/mnt/elsewhere");
+    let resp = server
+        .handle_request(tool_call("migrate_torrent", args))
+        .await
+        .unwrap();
+    let result = result_json(&resp);
+    assert_eq!(result["dry_run"], true);
+    assert_eq!(result["plan"]["save_path"], "/mnt/elsewhere");
+    assert_eq!(result["plan"]["source_save_path"], "/downloads/movies");
+
+    let mut args = arguments();
+    args["target_instance"] = json!("old");
+    let err = server
+        .handle_request(tool_call("migrate_torrent", args))
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("already on instance 'old'"));
+
+    let mut args = arguments();
+    args["target_instance"] = json!("nope");
+    let err = server
+        .handle_request(tool_call("migrate_torrent", args))
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("Instance not found: nope"));
+}
@@ -0,0 +1,278 @@
+use anyhow::Result;
+use jsonwebtoken::{Algorithm, EncodingKey, Header, encode};
+use qbittorrent_mcp_rs::app::http_auth;
+use qbittorrent_mcp_rs::config::AppConfig;
+use qbittorrent_mcp_rs::server::auth::{AuthFailure, HttpAuth, Scope};
+use qbittorrent_mcp_rs::server::http::create_router_with_auth;
+use qbittorrent_mcp_rs::server::mcp::McpServer;
+use qbittorrent_mcp_rs::server::oauth::{JwksSource, OAuthSettings, OAuthVerifier};
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::path::Path;
+use tempfile::tempdir;
+use time::OffsetDateTime;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+const OAUTH_DIR: &str = "tests/resources/oauth";
+const ISSUER: &str = "https://auth.example.com";
+const RESOURCE: &str = "https://mcp.example.com";
+
+fn settings(jwks: JwksSource) -> OAuthSettings {
+    OAuthSettings {
+        issuer: ISSUER.into(),
+        resource: RESOURCE.into(),
+        audience: RESOURCE.into(),
+        jwks,
+        scopes: vec![
+            ("qbittorrent:read".into(), Scope::Read),
+            ("qbittorrent:write".into(), Scope::Write),
+            ("qbittorrent:admin".into(), Scope::Admin),
+        ],
+    }
+}
+
+fn jwks(name: &str) -> Value {
+    let content = std::fs::read_to_string(format!("{}/{}.jwks.json", OAUTH_DIR, name)).unwrap();
+    serde_json::from_str(&content).unwrap()
+}
+
+/// A token signed with the key `kid`, with these claims on top of valid defaults.
+fn token(kid: &str, claims: Value) -> String {
+    let pem = std::fs::read(format!("{}/{}.key", OAUTH_DIR, kid)).unwrap();
+    let key = EncodingKey::from_ec_pem(&pem).unwrap();
+    let mut header = Header::new(Algorithm::ES256);
+    header.kid = Some(kid.into());
+    let mut payload = json!({
+        "iss": ISSUER,
+        "aud": RESOURCE,
+        "sub": "alice",
+        "exp": OffsetDateTime::now_utc().unix_timestamp() + 600,
+        "scope": "qbittorrent:read",
+    });
+    for (name, value) in claims.as_object().unwrap() {
+        payload[name] = value.clone();
+    }
+    encode(&header, &payload, &key).unwrap()
+}
+
+fn copy_jwks(name: &str, dir: &Path) -> std::path::PathBuf {
+    let file = dir.join("jwks.json");
+    std::fs::copy(format!("{}/{}.jwks.json", OAUTH_DIR, name), &file).unwrap();
+    file
+}
+
+#[tokio::test]
+async fn test_verify_claims_and_scopes() {
+    let dir = tempdir().unwrap();
+    let file = copy_jwks("first", dir.path());
+    let verifier = OAuthVerifier::new(settings(JwksSource::File(file))).unwrap();
+
+    let grant = verifier
+        .verify(&token(
+            "first",
+            json!({ "scope": "openid qbittorrent:read qbittorrent:write" }),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(grant.name, "oauth:alice");
+    assert_eq!(grant.scopes, [Scope::Read, Scope::Write].into());
+    assert!(grant.expires.is_some());
+
+    // Scopes as a list, as some issuers send them
+    let grant = verifier
+        .verify(&token(
+            "first",
+            json!({ "scope": null, "scp": ["qbittorrent:admin"] }),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(grant.scopes, [Scope::Admin].into());
+
+    let rejected = [
+        json!({ "aud": "https://other.example.com" }),
+        json!({ "iss": "https://evil.example.com" }),
+        json!({ "exp": OffsetDateTime::now_utc().unix_timestamp() - 3600 }),
+    ];
+    for claims in rejected {
+        let result = verifier.verify(&token("first", claims.clone())).await;
+        assert!(
+            matches!(result, Err(AuthFailure::Invalid(_))),
+            "{}: {:?}",
+            claims,
+            result
+        );
+    }
+    // Signed by a key the issuer does not publish
+    let result = verifier.verify(&token("second", json!({}))).await;
+    assert!(
+        matches!(result, Err(AuthFailure::Invalid(_))),
+        "{:?}",
+        result
+    );
+
+    let result = verifier
+        .verify(&token("first", json!({ "scope": "openid" })))
+        .await;
+    assert_eq!(
+        result,
+        Err(AuthFailure::InsufficientScope("oauth:alice".into()))
+    );
+
+    // A rotated key in the file is picked up
+    copy_jwks("second", dir.path());
+    let grant = verifier.verify(&token("second", json!({}))).await.unwrap();
+    assert_eq!(grant.scopes, [Scope::Read].into());
+}
+
+#[tokio::test]
+async fn test_jwks_from_url() {
+    let issuer = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/jwks.json"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(jwks("first")))
+        .expect(1)
+        .mount(&issuer)
+        .await;
+    let url = format!("{}/jwks.json", issuer.uri());
+    let verifier = OAuthVerifier::new(settings(JwksSource::Url(url))).unwrap();
+
+    // The keys are fetched once and cached
+    for _ in 0..3 {
+        verifier.verify(&token("first", json!({}))).await.unwrap();
+    }
+}
+
+#[tokio::test]
+async fn test_jwks_failures_keep_cached_keys_and_back_off() {
+    // Keys that fail to load again stay in use
+    let dir = tempdir().unwrap();
+    let file = copy_jwks("first", dir.path());
+    let verifier = OAuthVerifier::new(settings(JwksSource::File(file.clone()))).unwrap();
+    std::fs::write(&file, "{ truncated").unwrap();
+    verifier.verify(&token("first", json!({}))).await.unwrap();
+    verifier.verify(&token("first", json!({}))).await.unwrap();
+
+    // An issuer that is down is not asked again on every request
+    let issuer = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/jwks.json"))
+        .respond_with(ResponseTemplate::new(503))
+        .expect(1)
+        .mount(&issuer)
+        .await;
+    let url = format!("{}/jwks.json", issuer.uri());
+    let verifier = OAuthVerifier::new(settings(JwksSource::Url(url))).unwrap();
+    for _ in 0..3 {
+        let result = verifier.verify(&token("first", json!({}))).await;
+        assert!(
+            matches!(&result, Err(AuthFailure::Invalid(reason)) if reason.contains("JWKS")),
+            "{:?}",
+            result
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_http_oauth() -> Result<()> {
+    let dir = tempdir()?;
+    let file = copy_jwks("first", dir.path());
+    let auth = HttpAuth::shared("static-token")
+        .with_oauth(OAuthVerifier::new(settings(JwksSource::File(file)))?);
+    let app = create_router_with_auth(McpServer::new(HashMap::new(), false), auth).await;
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+    let base_url = format!("http://{}", listener.local_addr()?);
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    let client = reqwest::Client::new();
+
+    // The metadata is public
+    let metadata: Value = client
+        .get(format!("{}/.well-known/oauth-protected-resource", base_url))
+        .send()
+        .await?
+        .json()
+        .await?;
+    assert_eq!(metadata["resource"], RESOURCE);
+    assert_eq!(metadata["authorization_servers"], json!([ISSUER]));
+    assert_eq!(
+        metadata["scopes_supported"],
+        json!(["qbittorrent:read", "qbittorrent:write", "qbittorrent:admin"])
+    );
+
+    let sse = |token: Option<String>| {
+        let request = client.get(format!("{}/sse", base_url));
+        match token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        }
+        .send()
+    };
+    let challenge = |resp: &reqwest::Response| {
+        resp.headers()
+            .get("www-authenticate")
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_string()
+    };
+
+    let resp = sse(None).await?;
+    assert_eq!(resp.status(), reqwest::StatusCode::UNAUTHORIZED);
+    assert_eq!(
+        challenge(&resp),
+        "Bearer resource_metadata=\"https://mcp.example.com/.well-known/oauth-protected-resource\""
+    );
+
+    let resp = sse(Some(token(
+        "first",
+        json!({ "aud": "https://other.example.com" }),
+    )))
+    .await?;
+    assert_eq!(resp.status(), reqwest::StatusCode::UNAUTHORIZED);
+    assert!(challenge(&resp).contains("error=\"invalid_token\""));
+
+    let resp = sse(Some(token("first", json!({ "scope": "openid" })))).await?;
+    assert_eq!(resp.status(), reqwest::StatusCode::FORBIDDEN);
+    assert!(challenge(&resp).contains("error=\"insufficient_scope\""));
+
+    // OAuth and static tokens both work
+    let resp = sse(Some(token("first", json!({})))).await?;
+    assert_eq!(resp.status(), reqwest::StatusCode::OK);
+    let resp = sse(Some("static-token".into())).await?;
+    assert_eq!(resp.status(), reqwest::StatusCode::OK);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_oauth_from_config() {
+    let dir = tempdir().unwrap();
+    let file = copy_jwks("first", dir.path());
+    let base = AppConfig::load(None, vec![]).unwrap();
+
+    let config = AppConfig {
+        oauth_issuer: Some(ISSUER.into()),
+        oauth_resource: Some(RESOURCE.into()),
+        oauth_jwks_file: Some(file.to_str().unwrap().into()),
+        oauth_read_scope: "mcp:read".into(),
+        ..base.clone()
+    };
+    let auth = http_auth(&config).unwrap();
+    assert!(auth.is_enabled());
+    let grant = auth
+        .authenticate(Some(&token("first", json!({ "scope": "mcp:read" }))))
+        .await
+        .unwrap();
+    assert_eq!(grant.scopes, [Scope::Read].into());
+
+    let without_keys = AppConfig {
+        oauth_jwks_file: None,
+        ..config.clone()
+    };
+    assert!(http_auth(&without_keys).is_err());
+    let without_resource = AppConfig {
+        oauth_resource: None,
+        ..config
+    };
+    assert!(http_auth(&without_resource).is_err());
+    assert!(http_auth(&base).unwrap().oauth().is_none());
+}
@@ -0,0 +1,171 @@
+mod common;
+
+use common::tool_call;
+use qbittorrent_mcp_rs::app::build_instances;
+use qbittorrent_mcp_rs::client::QBitClient;
+use qbittorrent_mcp_rs::config::AppConfig;
+use qbittorrent_mcp_rs::server::mcp::{JsonRpcRequest, McpServer};
+use qbittorrent_mcp_rs::server::policy::ToolPolicy;
+use serde_json::json;
+use std::collections::HashMap;
+use tempfile::tempdir;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+async fn tool_names(server: &McpServer) -> Vec<String> {
+    let res = server
+        .handle_request(JsonRpcRequest {
+            jsonrpc: "2.0".to_string(),
+            method: "tools/list".to_string(),
+            params: None,
+            id: Some(json!(1)),
+        })
+        .await
+        .unwrap();
+    res["tools"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|t| t["name"].as_str().unwrap().to_string())
+        .collect()
+}
+
+#[tokio::test]
+async fn test_read_only_mode_hides_and_rejects_mutating_tools() {
+    let mock_server = MockServer::start().await;
+    let mut clients = HashMap::new();
+    clients.insert(
+        "default".to_string(),
+        QBitClient::new_no_auth(mock_server.uri(), false),
+    );
+    let server = McpServer::new(clients, false).with_policy(ToolPolicy::read_only());
+
+    Mock::given(method("GET"))
+        .and(path("/api/v2/torrents/info"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("[]"))
+        .mount(&mock_server)
+        .await;
+
+    let names = tool_names(&server).await;
+    assert!(names.contains(&"list_torrents".to_string()));
+    assert!(!names.contains(&"add_torrent".to_string()));
+    assert!(!names.contains(&"shutdown_app".to_string()));
+
+    let res = server
+        .handle_request(tool_call("list_torrents", json!({})))
+        .await
+        .unwrap();
+    assert!(res.get("isError").is_none());
+
+    // Hidden tools are also rejected when called directly
+    let res = server
+        .handle_request(tool_call(
+            "add_torrent",
+            json!({ "url": "magnet:?xt=urn:btih:abc" }),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(res["isError"], true);
+    let text = res["content"][0]["text"].as_str().unwrap();
+    assert!(text.contains("Policy denied"));
+    assert!(text.contains("read-only"));
+}
+
+#[tokio::test]
+async fn test_instance_policy_overrides_server_policy() {
+    let scratch = MockServer::start().await;
+    let archive = MockServer::start().await;
+    let mut clients = HashMap::new();
+    clients.insert(
+        "scratch".to_string(),
+        QBitClient::new_no_auth(scratch.uri(), false),
+    );
+    clients.insert(
+        "archive".to_string(),
+        QBitClient::new_no_auth(archive.uri(), false),
+    );
+    let server = McpServer::new(clients, false)
+        .with_policy(ToolPolicy::default().with_denied_tools(["delete_torrent"]))
+        .with_instance_policy("scratch", ToolPolicy::default());
+
+    Mock::given(method("POST"))
+        .and(path("/api/v2/torrents/delete"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&scratch)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/api/v2/torrents/delete"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&archive)
+        .await;
+
+    // Still listed, since one instance allows it
+    assert!(
+        tool_names(&server)
+            .await
+            .contains(&"delete_torrent".to_string())
+    );
+
+    let res = server
+        .handle_request(tool_call(
+            "delete_torrent",
+            json!({ "hash": "abc", "delete_files": false, "instance": "archive" }),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(res["isError"], true);
+    assert!(
+        res["content"][0]["text"]
+            .as_str()
+            .unwrap()
+            .contains("not allowed on instance 'archive'")
+    );
+
+    let res = server
+        .handle_request(tool_call(
+            "delete_torrent",
+            json!({ "hash": "abc", "delete_files": false, "instance": "scratch" }),
+        ))
+        .await
+        .unwrap();
+    assert!(res.get("isError").is_none());
+}
+
+#[test]
+fn test_instance_cannot_lift_read_only() {
+    let dir = tempdir().unwrap();
+    let file = dir.path().join("config.toml");
+    let config = |read_only: bool| {
+        std::fs::write(
+            &file,
+            format!(
+                r#"
+read_only = {}
+
+[[instances]]
+name = "archive"
+host = "archive.local"
+read_only = true
+
+[[instances]]
+name = "scratch"
+host = "scratch.local"
+read_only = false
+"#,
+                read_only
+            ),
+        )
+        .unwrap();
+        AppConfig::load(Some(file.to_str().unwrap().to_string()), vec![]).unwrap()
+    };
+
+    let instances = build_instances(&config(true), None);
+    assert!(instances.policy_for("archive").read_only);
+    assert!(instances.policy_for("scratch").read_only);
+
+    let instances = build_instances(&config(false), None);
+    assert!(instances.policy_for("archive").read_only);
+    assert!(!instances.policy_for("scratch").read_only);
+}
@@ -0,0 +1,30 @@
+use qbittorrent_mcp_rs::config::AppConfig;
+use tempfile::tempdir;
+
+#[test]
+fn test_file_variables() {
+    let dir = tempdir().unwrap();
+    let token_file = dir.path().join("token");
+    std::fs::write(&token_file, "docker-secret").unwrap();
+    let password_file = dir.path().join("password");
+    std::fs::write(&password_file, "systemd-credential").unwrap();
+    // SAFETY: the only test in this binary, since every load reads these
+    unsafe {
+        std::env::set_var("QBITTORRENT_HTTP_AUTH_TOKEN_FILE", &token_file);
+        std::env::set_var("QBITTORRENT_PASSWORD_FILE", &password_file);
+    }
+
+    let config = AppConfig::load(None, vec![]).unwrap();
+    assert_eq!(
+        config.http_auth_token.as_ref().unwrap().expose(),
+        "docker-secret"
+    );
+    assert_eq!(
+        config.get_instances()[0]
+            .password
+            .as_ref()
+            .unwrap()
+            .expose(),
+        "systemd-credential"
+    );
+}
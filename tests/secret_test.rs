@@ -0,0 +1,102 @@
+use qbittorrent_mcp_rs::config::AppConfig;
+use tempfile::tempdir;
+
+#[test]
+fn test_instance_passwords_from_file_command_and_env() {
+    let dir = tempdir().unwrap();
+    let password_file = dir.path().join("nas_password");
+    std::fs::write(&password_file, "from-file\n").unwrap();
+    // SAFETY: only this test reads this variable
+    unsafe { std::env::set_var("QBT_TEST_SEEDBOX_PASSWORD", "from-env") };
+
+    let file = dir.path().join("config.toml");
+    std::fs::write(
+        &file,
+        format!(
+            r#"
+[[instances]]
+name = "nas"
+host = "nas"
+username = "admin"
+password_file = "{}"
+
+[[instances]]
+name = "seedbox"
+host = "seedbox"
+username = "admin"
+password = "${{QBT_TEST_SEEDBOX_PASSWORD}}"
+
+[[instances]]
+name = "vps"
+host = "vps"
+username = "admin"
+password_command = "echo from-command | tr a-z A-Z"
+"#,
+            password_file.display()
+        ),
+    )
+    .unwrap();
+
+    let config = AppConfig::load(Some(file.to_str().unwrap().to_string()), vec![]).unwrap();
+    let passwords: Vec<String> = config
+        .get_instances()
+        .iter()
+        .map(|i| i.password.as_ref().unwrap().expose().to_string())
+        .collect();
+    assert_eq!(passwords, vec!["from-file", "from-env", "FROM-COMMAND"]);
+
+    let debug = format!("{:?}", config);
+    assert!(debug.contains("[REDACTED]"));
+    for password in &passwords {
+        assert!(!debug.contains(password.as_str()), "{}", debug);
+    }
+}
+
+#[test]
+fn test_secret_errors() {
+    let dir = tempdir().unwrap();
+    let file = dir.path().join("config.toml");
+
+    std::fs::write(
+        &file,
+        "[[instances]]\nname = \"nas\"\nhost = \"nas\"\npassword = \"a\"\npassword_command = \"echo b\"\n",
+    )
+    .unwrap();
+    let err = AppConfig::load(Some(file.to_str().unwrap().to_string()), vec![]).unwrap_err();
+    assert!(err.to_string().contains("Instance 'nas'"), "{}", err);
+    assert!(err.to_string().contains("Only one of"), "{}", err);
+
+    std::fs::write(
+        &file,
+        "[[instances]]\nname = \"nas\"\nhost = \"nas\"\npassword_file = \"/nonexistent/secret\"\n",
+    )
+    .unwrap();
+    assert!(AppConfig::load(Some(file.to_str().unwrap().to_string()), vec![]).is_err());
+}
+
+#[test]
+fn test_cli_secret_replaces_config_file() {
+    let dir = tempdir().unwrap();
+    let password_file = dir.path().join("password");
+    std::fs::write(&password_file, "from-cli-file\n").unwrap();
+    let file = dir.path().join("config.toml");
+    std::fs::write(
+        &file,
+        "qbittorrent_password = \"from-config\"\nhttp_auth_token_command = \"echo from-command\"\n",
+    )
+    .unwrap();
+
+    let args = vec![
+        "app".to_string(),
+        "--qbittorrent-password-file".to_string(),
+        password_file.to_str().unwrap().to_string(),
+        "--http-auth-token".to_string(),
+        "from-cli".to_string(),
+    ];
+    let config = AppConfig::load(Some(file.to_str().unwrap().to_string()), args).unwrap();
+    assert_eq!(
+        config.qbittorrent_password.unwrap().expose(),
+        "from-cli-file"
+    );
+    assert_eq!(config.http_auth_token.unwrap().expose(), "from-cli");
+}
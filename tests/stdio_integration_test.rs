@@ -102,11 +102,17 @@ fn test_stdio_interaction() {
     let call_req = r#"{"jsonrpc":"2.0","id":3,"method":"tools/call","params":{"name":"list_torrents","arguments":{}}}"#;
     writeln!(stdin, "{}", call_req).expect("Failed to write call req");
 
-    line.clear();
-    reader
-        .read_line(&mut line)
-        .expect("Failed to read call response");
-    let resp: Value = serde_json::from_str(&line).expect("Failed to parse call response");
+    // Notifications, e.g. that the instance is down, may come before the response
+    let resp = loop {
+        line.clear();
+        reader
+            .read_line(&mut line)
+            .expect("Failed to read call response");
+        let message: Value = serde_json::from_str(&line).expect("Failed to parse call response");
+        if message.get("id").is_some() {
+            break message;
+        }
+    };
 
     // Either success (result) or error (error)
     assert!(
@@ -119,3 +125,120 @@ fn test_stdio_interaction() {
     drop(stdin); // Close stdin to signal exit
     let _ = child.wait().expect("Failed to wait on child");
 }
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_stdio_concurrent_requests() {
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    // A slow qBittorrent so that list_torrents is still running when ping arrives
+    let mock_server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/api/v2/torrents/info"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_string("[]")
+                .set_delay(std::time::Duration::from_secs(2)),
+        )
+        .mount(&mock_server)
+        .await;
+
+    let bin_path = env!("CARGO_BIN_EXE_qbittorrent-mcp-rs");
+    let mut child = Command::new(bin_path)
+        .arg("--server-mode")
+        .arg("stdio")
+        .arg("--qbittorrent-host")
+        .arg(mock_server.uri())
+        .arg("--polling-interval-ms")
+        .arg("60000")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .expect("Failed to spawn process");
+
+    let mut stdin = child.stdin.take().expect("Failed to open stdin");
+    let stdout = child.stdout.take().expect("Failed to open stdout");
+
+    let reader = std::thread::spawn(move || {
+        let mut reader = BufReader::new(stdout);
+        let mut ids = Vec::new();
+        let mut line = String::new();
+        while ids.len() < 2 && reader.read_line(&mut line).unwrap_or(0) > 0 {
+            let resp: Value = serde_json::from_str(&line).expect("Failed to parse response");
+            ids.push(resp["id"].clone());
+            line.clear();
+        }
+        ids
+    });
+
+    let slow = r#"{"jsonrpc":"2.0","id":1,"method":"tools/call","params":{"name":"list_torrents","arguments":{}}}"#;
+    let ping = r#"{"jsonrpc":"2.0","id":2,"method":"ping"}"#;
+    writeln!(stdin, "{}", slow).expect("Failed to write slow request");
+    writeln!(stdin, "{}", ping).expect("Failed to write ping");
+
+    // Closing stdin must still let the slow request finish and respond
+    drop(stdin);
+    let ids = tokio::task::spawn_blocking(move || reader.join().unwrap())
+        .await
+        .unwrap();
+    let _ = child.wait();
+
+    assert_eq!(ids, vec![serde_json::json!(2), serde_json::json!(1)]);
+}
+
+#[test]
+fn test_stdio_parse_errors_and_batches() {
+    let bin_path = env!("CARGO_BIN_EXE_qbittorrent-mcp-rs");
+    let mut child = Command::new(bin_path)
+        .arg("--server-mode")
+        .arg("stdio")
+        .arg("--qbittorrent-host")
+        .arg("localhost")
+        .arg("--polling-interval-ms")
+        .arg("60000")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .expect("Failed to spawn process");
+
+    let mut stdin = child.stdin.take().expect("Failed to open stdin");
+    let stdout = child.stdout.take().expect("Failed to open stdout");
+    let mut reader = BufReader::new(stdout);
+    let mut read_response = || {
+        let mut line = String::new();
+        reader
+            .read_line(&mut line)
+            .expect("Failed to read response");
+        serde_json::from_str::<Value>(&line).expect("Failed to parse response")
+    };
+
+    writeln!(stdin, "{{not json").expect("Failed to write garbage");
+    let resp = read_response();
+    assert_eq!(resp["error"]["code"], -32700);
+    assert!(resp["id"].is_null());
+
+    writeln!(stdin, r#"{{"jsonrpc":"1.0","id":7,"method":"ping"}}"#)
+        .expect("Failed to write invalid request");
+    let resp = read_response();
+    assert_eq!(resp["error"]["code"], -32600);
+    assert_eq!(resp["id"], 7);
+
+    // The notification in the batch gets no entry in the response array
+    let batch = r#"[{"jsonrpc":"2.0","id":1,"method":"ping"},{"jsonrpc":"2.0","method":"notifications/initialized"},{"jsonrpc":"2.0","id":2,"method":"nope"}]"#;
+    writeln!(stdin, "{}", batch).expect("Failed to write batch");
+    let resp = read_response();
+    let responses = resp.as_array().expect("Batch response should be an array");
+    assert_eq!(responses.len(), 2);
+    let by_id = |id: i64| responses.iter().find(|r| r["id"] == id).unwrap();
+    assert!(by_id(1).get("result").is_some());
+    assert_eq!(by_id(2)["error"]["code"], -32601);
+
+    writeln!(stdin, "[]").expect("Failed to write empty batch");
+    let resp = read_response();
+    assert_eq!(resp["error"]["code"], -32600);
+
+    drop(stdin);
+    let _ = child.wait();
+}
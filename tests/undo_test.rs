@@ -0,0 +1,123 @@
+mod common;
+
+use common::{result_json, tool_call};
+use qbittorrent_mcp_rs::client::QBitClient;
+use qbittorrent_mcp_rs::server::journal::Journal;
+use qbittorrent_mcp_rs::server::mcp::McpServer;
+use serde_json::json;
+use std::collections::HashMap;
+use wiremock::matchers::{body_string_contains, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+#[tokio::test]
+async fn test_undo_restores_previous_category() {
+    let mock_server = MockServer::start().await;
+    let dir = tempfile::tempdir().unwrap();
+    let journal_path = dir.path().join("journal.json");
+    let mut clients = HashMap::new();
+    clients.insert(
+        "default".to_string(),
+        QBitClient::new_no_auth(mock_server.uri(), false),
+    );
+    let server =
+        McpServer::new(clients, false).with_journal(Journal::open(&journal_path, 10).unwrap());
+
+    let torrents = r#"[
+        {"hash": "h1", "name": "One", "size": 1, "progress": 1.0, "dlspeed": 0, "upspeed": 0, "priority": 0, "num_seeds": 0, "num_leechs": 0, "num_incomplete": 0, "num_complete": 0, "ratio": 0.0, "eta": 0, "state": "uploading", "added_on": 0, "completion_on": 0, "seq_dl": false, "f_l_piece_prio": false, "category": "old", "tags": "a", "super_seeding": false, "force_start": false},
+        {"hash": "h2", "name": "Two", "size": 1, "progress": 1.0, "dlspeed": 0, "upspeed": 0, "priority": 0, "num_seeds": 0, "num_leechs": 0, "num_incomplete": 0, "num_complete": 0, "ratio": 0.0, "eta": 0, "state": "uploading", "added_on": 0, "completion_on": 0, "seq_dl": false, "f_l_piece_prio": false, "category": "", "tags": "", "super_seeding": false, "force_start": false}
+    ]"#;
+    Mock::given(method("GET"))
+        .and(path("/api/v2/torrents/info"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(torrents))
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/api/v2/torrents/setCategory"))
+        .and(body_string_contains("category=new"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+    // Each torrent gets its own category back
+    Mock::given(method("POST"))
+        .and(path("/api/v2/torrents/setCategory"))
+        .and(body_string_contains("hashes=h1&category=old"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/api/v2/torrents/setCategory"))
+        .and(body_string_contains("hashes=h2&category="))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/api/v2/torrents/delete"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&mock_server)
+        .await;
+
+    let resp = server
+        .handle_request(tool_call(
+            "manage_torrents",
+            json!({ "hashes": "h1|h2", "action": "set_category", "category": "new" }),
+        ))
+        .await
+        .unwrap();
+    assert!(resp.get("isError").is_none(), "{}", resp);
+    server
+        .handle_request(tool_call(
+            "delete_torrent",
+            json!({ "hash": "h2", "delete_files": true }),
+        ))
+        .await
+        .unwrap();
+
+    let resp = server
+        .handle_request(tool_call("list_recent_actions", json!({})))
+        .await
+        .unwrap();
+    let actions = result_json(&resp);
+    assert_eq!(actions[0]["tool"], "delete_torrent");
+    assert_eq!(actions[0]["undoable"], false);
+    assert_eq!(actions[1]["action"], "set_category 'new'");
+    assert_eq!(actions[1]["undoable"], true);
+
+    // The deletion cannot be undone, so the category change is picked
+    let resp = server
+        .handle_request(tool_call("undo_last_action", json!({})))
+        .await
+        .unwrap();
+    assert!(resp.get("isError").is_none(), "{}", resp);
+    assert!(
+        resp["content"][0]["text"]
+            .as_str()
+            .unwrap()
+            .contains("on 2 torrents")
+    );
+
+    let resp = server
+        .handle_request(tool_call("undo_last_action", json!({})))
+        .await
+        .unwrap();
+    assert_eq!(resp["isError"], true);
+    let resp = server
+        .handle_request(tool_call("undo_last_action", json!({ "id": 2 })))
+        .await
+        .unwrap();
+    assert_eq!(resp["isError"], true);
+    assert!(
+        resp["content"][0]["text"]
+            .as_str()
+            .unwrap()
+            .contains("cannot be undone")
+    );
+
+    // Persisted across restarts
+    let reopened = Journal::open(&journal_path, 10).unwrap();
+    let recent = reopened.recent(None, 10);
+    assert_eq!(recent.len(), 2);
+    assert!(recent[1].undone);
+}